@@ -0,0 +1,223 @@
+use std::{collections::HashMap, fmt::Display};
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::{anyhow, Result};
+use cred_x::{
+    CollateralConfig, InsuranceFund, LoanAccount, PriceQuote, ProtocolState, SimplePriceOracle,
+    StabilityPool,
+};
+use cred_x_client::{
+    collateral_config_pda, collateral_price, collateral_vault_pda, decode_collateral_config,
+    decode_collateral_vault, decode_price_quote, loan_health, CollateralContext, LoanHealth,
+};
+
+use crate::rpc::Rpc;
+
+fn field(name: &str, value: impl Display) {
+    println!("  {name:<26} {value}");
+}
+
+pub fn format_bps(bps: Option<u128>) -> String {
+    match bps {
+        Some(bps) => format!("{}.{:02}%", bps / 100, bps % 100),
+        None => "-".to_string(),
+    }
+}
+
+pub fn print_protocol(address: &Pubkey, state: &ProtocolState) {
+    println!("ProtocolState {address}");
+    field("admin", state.admin);
+    field("seed_admin", state.seed_admin());
+    field("credit_mint", state.credit_mint);
+    field("credit_decimals", state.credit_decimals);
+    field("ltv", format_bps(Some(state.ltv_ratio_bps.into())));
+    field(
+        "liquidation_threshold",
+        format_bps(Some(state.liquidation_threshold_bps.into())),
+    );
+    field("borrow_fee", format_bps(Some(state.borrow_fee_bps.into())));
+    field("flash_fee", format_bps(Some(state.flash_fee_bps.into())));
+    field("swap_program", state.swap_program);
+    field(
+        "redemption_fee",
+        format_bps(Some(state.redemption_fee_bps.into())),
+    );
+    field("total_redeemed", state.total_redeemed);
+    field("max_price_age_secs", state.max_price_age_secs);
+    field("debt_ceiling", state.debt_ceiling);
+    field("total_debt", state.total_debt);
+    field("paused", state.is_locked);
+    field("governance_delay_secs", state.governance_delay_secs);
+    field("param_changes_queued", state.param_changes_queued);
+    field("unbound_legacy_loans", state.unbound_legacy_loans);
+    field("unbound_legacy_debt", state.unbound_legacy_debt);
+    field(
+        "credit_transfer_mode",
+        format!("{:?}", state.credit_transfer_mode),
+    );
+    field("stats.total_minted", state.stats.total_minted);
+    field(
+        "stats.total_repaid_via_yield",
+        state.stats.total_repaid_via_yield,
+    );
+    field(
+        "stats.total_repaid_via_user",
+        state.stats.total_repaid_via_user,
+    );
+    field(
+        "stats.total_yield_harvested",
+        state.stats.total_yield_harvested,
+    );
+    field("stats.open_loans", state.stats.open_loans);
+}
+
+pub fn print_collateral(address: &Pubkey, config: &CollateralConfig) {
+    println!("CollateralConfig {address}");
+    field("protocol", config.protocol);
+    field("mint", config.mint);
+    field("supply_cap", config.supply_cap);
+    field("total_collateral", config.total_collateral);
+    field("pricing", format!("{:?}", config.pricing));
+    field("oracle.primary", config.oracle.primary);
+    if config.oracle.has_secondary() {
+        field("oracle.secondary", config.oracle.secondary);
+        field(
+            "oracle.max_deviation",
+            format_bps(Some(config.oracle.max_deviation_bps.into())),
+        );
+        field(
+            "oracle.fallback_max_age_secs",
+            config.oracle.fallback_max_age_secs,
+        );
+    }
+    field("staleness", format!("{:?}", config.staleness));
+    field("circuit_breaker", format!("{:?}", config.circuit_breaker));
+    field("redemption_mark", format!("{:?}", config.redemption_mark));
+}
+
+pub fn print_insurance_fund(address: &Pubkey, fund: &InsuranceFund) {
+    println!("InsuranceFund {address}");
+    field("protocol", fund.protocol);
+    field("total_funded", fund.total_funded);
+    field("total_covered", fund.total_covered);
+    field("total_socialized", fund.total_socialized);
+    field("bad_debts_resolved", fund.bad_debts_resolved);
+    field("total_recovered", fund.total_recovered);
+}
+
+pub fn print_stability_pool(address: &Pubkey, pool: &StabilityPool) {
+    println!("StabilityPool {address}");
+    field("protocol", pool.protocol);
+    field("collateral_mint", pool.collateral_mint);
+    field("total_deposits", pool.total_deposits);
+    field("total_collateral", pool.total_collateral);
+    field("total_debt_offset", pool.total_debt_offset);
+    field("product", pool.product);
+    field("scale", pool.scale);
+}
+
+pub fn print_oracle(address: &Pubkey, quote: &PriceQuote, simple: Option<&SimplePriceOracle>) {
+    println!("Price account {address}");
+    if let Some(oracle) = simple {
+        field("kind", "SimplePriceOracle");
+        field("authority", oracle.authority);
+    } else {
+        field("kind", "Pyth");
+    }
+    field("price", quote.price);
+    field("ema_price", quote.ema_price);
+    field("conf", quote.conf);
+    field("publish_time", quote.publish_time);
+}
+
+pub fn print_loan(address: &Pubkey, loan: &LoanAccount, position: Option<&Position>) {
+    println!("LoanAccount {address}");
+    field("user", loan.user);
+    field("collateral_amount", loan.collateral_amount);
+    field("remaining_debt", loan.remaining_debt);
+    field("yield_earned", loan.yield_earned);
+    field("oracle_price_account", loan.oracle_price_account);
+    if let Some(position) = position {
+        field("collateral_mint", position.mint);
+        field("collateral_price", position.price);
+        field("collateral_value", position.health.collateral_value);
+        field("borrow_limit", position.health.borrow_limit);
+        field("available_to_borrow", position.health.available_to_borrow);
+        field("ltv", format_bps(position.health.ltv_bps));
+        field(
+            "health_factor",
+            format_bps(position.health.health_factor_bps),
+        );
+    }
+}
+
+/// A loan valued at its collateral's current primary oracle price.
+pub struct Position {
+    pub mint: Pubkey,
+    pub price: u64,
+    pub health: LoanHealth,
+}
+
+/// Loads collateral configs and prices once per mint.
+pub struct Valuer<'a> {
+    rpc: &'a Rpc,
+    protocol: &'a ProtocolState,
+    protocol_key: Pubkey,
+    collaterals: HashMap<Pubkey, (CollateralContext, u64)>,
+}
+
+impl<'a> Valuer<'a> {
+    pub fn new(rpc: &'a Rpc, protocol_key: Pubkey, protocol: &'a ProtocolState) -> Self {
+        Self {
+            rpc,
+            protocol,
+            protocol_key,
+            collaterals: HashMap::new(),
+        }
+    }
+
+    /// Mint, token program and oracles of `user`'s collateral.
+    pub fn collateral_of(&mut self, user: &Pubkey) -> Result<CollateralContext> {
+        let mint = self.vault_mint(user)?;
+        Ok(self.collateral(&mint)?.0)
+    }
+
+    pub fn position(&mut self, loan: &LoanAccount) -> Result<Position> {
+        let mint = self.vault_mint(&loan.user)?;
+        let (_, price) = self.collateral(&mint)?;
+        Ok(Position {
+            mint,
+            price,
+            health: loan_health(self.protocol, loan, price)?,
+        })
+    }
+
+    fn vault_mint(&self, user: &Pubkey) -> Result<Pubkey> {
+        let vault_key = collateral_vault_pda(user).0;
+        let vault = self.rpc.require_account(&vault_key, "collateral vault")?;
+        Ok(decode_collateral_vault(&vault.data)?.mint)
+    }
+
+    fn collateral(&mut self, mint: &Pubkey) -> Result<(CollateralContext, u64)> {
+        if let Some(entry) = self.collaterals.get(mint) {
+            return Ok(*entry);
+        }
+
+        let config_key = collateral_config_pda(&self.protocol_key, mint).0;
+        let config = self.rpc.require_account(&config_key, "collateral config")?;
+        let config = decode_collateral_config(&config.data)?;
+        let mint_account = self.rpc.require_account(mint, "collateral mint")?;
+        let oracle = self
+            .rpc
+            .require_account(&config.oracle.primary, "price account")?;
+        let quote = decode_price_quote(&config.oracle.primary, &oracle.owner, &oracle.data)
+            .map_err(|err| anyhow!("reading price for {mint}: {err}"))?;
+
+        let entry = (
+            CollateralContext::from_config(&config, mint_account.owner),
+            collateral_price(&config, &quote)?,
+        );
+        self.collaterals.insert(*mint, entry);
+        Ok(entry)
+    }
+}
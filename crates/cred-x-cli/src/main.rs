@@ -0,0 +1,424 @@
+mod inspect;
+mod rpc;
+
+use std::path::{Path, PathBuf};
+
+use anchor_lang::{prelude::Pubkey, Discriminator};
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use cred_x::{
+    CreditMetadata, CreditTransferMode, InitializeProtocolParams, LoanAccount,
+    MigrateProtocolParams, PriceQuote, ProtocolState,
+};
+use cred_x_client::{
+    bind_loan, collateral_config_pda, create_simple_oracle, cron_repayment,
+    decode_collateral_config, decode_insurance_fund, decode_loan_account, decode_price_quote,
+    decode_protocol_state, decode_protocol_state_v0, decode_simple_price_oracle,
+    decode_stability_pool, initialize_protocol, insurance_fund_pda, loan_pda, migrate_loan,
+    migrate_protocol, migrate_simple_oracle, protocol_pda, set_protocol_locked, stability_pool_pda,
+    update_simple_oracle, ProtocolContext,
+};
+use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
+
+use crate::{
+    inspect::{format_bps, Valuer},
+    rpc::Rpc,
+};
+
+/// Administers and inspects a cred-x deployment.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    #[arg(long, global = true, default_value = "http://127.0.0.1:8899")]
+    rpc_url: String,
+
+    /// Keypair paying for and signing transactions [default: ~/.config/solana/id.json]
+    #[arg(long, global = true)]
+    keypair: Option<PathBuf>,
+
+    /// Admin of the protocol to inspect [default: the keypair's address]
+    #[arg(long, global = true)]
+    admin: Option<Pubkey>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Create the protocol and its credit mint with the keypair as admin
+    InitProtocol {
+        #[arg(long, default_value_t = 6)]
+        credit_decimals: u8,
+        #[arg(long, default_value_t = 6000)]
+        ltv_bps: u16,
+        #[arg(long, default_value_t = 8000)]
+        liquidation_threshold_bps: u16,
+        #[arg(long, default_value_t = 300)]
+        max_price_age_secs: i64,
+        #[arg(long, default_value_t = 0)]
+        borrow_fee_bps: u16,
+        #[arg(long, default_value_t = u64::MAX)]
+        debt_ceiling: u64,
+        /// Create the credit mint under Token-2022
+        #[arg(long)]
+        token_2022: bool,
+        /// Transfer rules for the credit token, anything but unrestricted needs --token-2022
+        #[arg(long, value_enum, default_value_t = TransferMode::Unrestricted)]
+        transfer_mode: TransferMode,
+        /// Credit token metadata, needs --token-2022
+        #[arg(long, requires_all = ["symbol", "uri"])]
+        name: Option<String>,
+        #[arg(long, requires = "name")]
+        symbol: Option<String>,
+        #[arg(long, requires = "name")]
+        uri: Option<String>,
+    },
+    /// Create a SimplePriceOracle owned by the keypair
+    CreateOracle {
+        #[arg(long)]
+        price: u64,
+        #[arg(long, default_value_t = 0)]
+        conf: u64,
+        /// Keypair of the new price account, generated when omitted
+        #[arg(long)]
+        account: Option<PathBuf>,
+    },
+    /// Publish a new price to a SimplePriceOracle
+    UpdateOracle {
+        address: Pubkey,
+        #[arg(long)]
+        price: u64,
+        #[arg(long, default_value_t = 0)]
+        conf: u64,
+    },
+    /// Show the protocol state
+    Protocol,
+    /// Show a user's loan, vault and health
+    Loan { user: Pubkey },
+    /// Show a collateral's configuration
+    Collateral { mint: Pubkey },
+    /// Show the insurance fund's bad-debt accounting
+    InsuranceFund,
+    /// Show a collateral's stability pool
+    StabilityPool { mint: Pubkey },
+    /// Show a Pyth or SimplePriceOracle price account
+    Oracle { address: Pubkey },
+    /// List every loan with its LTV and health factor
+    Loans,
+    /// Stop deposits, borrowing, repayments and withdrawals
+    Pause,
+    /// Resume a paused protocol
+    Unpause,
+    /// Run cron_repayment for one user's loan
+    Crank { user: Pubkey },
+    /// Rewrite a protocol created before account versioning in the current
+    /// layout, with the settings and debt totals it never stored
+    MigrateProtocol {
+        #[arg(long, default_value_t = 8000)]
+        liquidation_threshold_bps: u16,
+        #[arg(long, default_value_t = 300)]
+        max_price_age_secs: i64,
+        #[arg(long, default_value_t = u64::MAX)]
+        debt_ceiling: u64,
+        /// Sum of the outstanding debt of every loan
+        #[arg(long)]
+        total_debt: u64,
+        /// Number of loans with outstanding debt
+        #[arg(long)]
+        open_loans: u64,
+        /// Number of loan accounts, with or without debt, that `bind-loan`
+        /// may claim for this protocol
+        #[arg(long)]
+        legacy_loans: u64,
+    },
+    /// Grow a user's loan and vault to the current account version
+    MigrateLoan { user: Pubkey },
+    /// Record the protocol of a loan opened before loans carried one
+    BindLoan { user: Pubkey },
+    /// Grow a SimplePriceOracle to the current account version
+    MigrateOracle {
+        address: Pubkey,
+        /// Collateral mint whose config reads the oracle
+        collateral: Pubkey,
+        /// Signer allowed to publish prices afterwards, the keypair when omitted
+        #[arg(long)]
+        authority: Option<Pubkey>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum TransferMode {
+    Unrestricted,
+    DebtFloor,
+    Allowlist,
+}
+
+impl From<TransferMode> for CreditTransferMode {
+    fn from(mode: TransferMode) -> Self {
+        match mode {
+            TransferMode::Unrestricted => Self::Unrestricted,
+            TransferMode::DebtFloor => Self::DebtFloor,
+            TransferMode::Allowlist => Self::Allowlist,
+        }
+    }
+}
+
+fn read_keypair(path: &Path) -> Result<Keypair> {
+    read_keypair_file(path).map_err(|err| anyhow!("reading {}: {err}", path.display()))
+}
+
+fn default_keypair_path() -> Result<PathBuf> {
+    let home =
+        std::env::var_os("HOME").ok_or_else(|| anyhow!("HOME is not set, pass --keypair"))?;
+    Ok(PathBuf::from(home).join(".config/solana/id.json"))
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let keypair_path = match args.keypair {
+        Some(path) => path,
+        None => default_keypair_path()?,
+    };
+    let rpc = Rpc::new(args.rpc_url, read_keypair(&keypair_path)?);
+    let admin = args.admin.unwrap_or_else(|| rpc.payer.pubkey());
+
+    match args.command {
+        Command::InitProtocol {
+            credit_decimals,
+            ltv_bps,
+            liquidation_threshold_bps,
+            max_price_age_secs,
+            borrow_fee_bps,
+            debt_ceiling,
+            token_2022,
+            transfer_mode,
+            name,
+            symbol,
+            uri,
+        } => {
+            let credit_metadata = match (name, symbol, uri) {
+                (Some(name), Some(symbol), Some(uri)) => Some(CreditMetadata { name, symbol, uri }),
+                _ => None,
+            };
+            let token_program = if token_2022 {
+                anchor_spl::token_2022::ID
+            } else {
+                anchor_spl::token::ID
+            };
+            let params = InitializeProtocolParams {
+                credit_decimals,
+                ltv_ratio_bps: ltv_bps,
+                liquidation_threshold_bps,
+                max_price_age_secs,
+                borrow_fee_bps,
+                debt_ceiling,
+                credit_transfer_mode: transfer_mode.into(),
+                credit_metadata,
+            };
+            let signature = rpc.send(
+                &[initialize_protocol(
+                    rpc.payer.pubkey(),
+                    token_program,
+                    params,
+                )],
+                &[],
+            )?;
+            let protocol = ProtocolContext::new(rpc.payer.pubkey(), token_program);
+            println!("protocol    {}", protocol.protocol());
+            println!("credit mint {}", protocol.credit_mint);
+            println!("signature   {signature}");
+        }
+        Command::CreateOracle {
+            price,
+            conf,
+            account,
+        } => {
+            let price_account = match account {
+                Some(path) => read_keypair(&path)?,
+                None => Keypair::new(),
+            };
+            let signature = rpc.send(
+                &[create_simple_oracle(
+                    rpc.payer.pubkey(),
+                    price_account.pubkey(),
+                    price,
+                    conf,
+                )],
+                &[&price_account],
+            )?;
+            println!("oracle    {}", price_account.pubkey());
+            println!("signature {signature}");
+        }
+        Command::UpdateOracle {
+            address,
+            price,
+            conf,
+        } => {
+            let signature = rpc.send(
+                &[update_simple_oracle(
+                    rpc.payer.pubkey(),
+                    address,
+                    price,
+                    conf,
+                )],
+                &[],
+            )?;
+            println!("signature {signature}");
+        }
+        Command::Protocol => {
+            let (address, protocol) = load_protocol(&rpc, &admin)?;
+            inspect::print_protocol(&address, &protocol);
+        }
+        Command::Loan { user } => {
+            let (protocol_key, protocol) = load_protocol(&rpc, &admin)?;
+            let address = loan_pda(&user).0;
+            let loan = decode_loan_account(&rpc.require_account(&address, "loan")?.data)?;
+            let mut valuer = Valuer::new(&rpc, protocol_key, &protocol);
+            let position = match valuer.position(&loan) {
+                Ok(position) => Some(position),
+                Err(err) => {
+                    eprintln!("cannot value loan: {err:#}");
+                    None
+                }
+            };
+            inspect::print_loan(&address, &loan, position.as_ref());
+        }
+        Command::Collateral { mint } => {
+            let address = collateral_config_pda(&protocol_pda(&admin).0, &mint).0;
+            let account = rpc.require_account(&address, "collateral config")?;
+            inspect::print_collateral(&address, &decode_collateral_config(&account.data)?);
+        }
+        Command::InsuranceFund => {
+            let address = insurance_fund_pda(&protocol_pda(&admin).0).0;
+            let account = rpc.require_account(&address, "insurance fund")?;
+            inspect::print_insurance_fund(&address, &decode_insurance_fund(&account.data)?);
+        }
+        Command::StabilityPool { mint } => {
+            let address = stability_pool_pda(&protocol_pda(&admin).0, &mint).0;
+            let account = rpc.require_account(&address, "stability pool")?;
+            inspect::print_stability_pool(&address, &decode_stability_pool(&account.data)?);
+        }
+        Command::Oracle { address } => {
+            let account = rpc.require_account(&address, "price account")?;
+            let simple = if account.owner == cred_x::ID {
+                Some(decode_simple_price_oracle(&account.data)?)
+            } else {
+                None
+            };
+            let quote = match &simple {
+                Some(oracle) => PriceQuote::from_simple_oracle(oracle),
+                None => decode_price_quote(&address, &account.owner, &account.data)?,
+            };
+            inspect::print_oracle(&address, &quote, simple.as_ref());
+        }
+        Command::Loans => {
+            let (protocol_key, protocol) = load_protocol(&rpc, &admin)?;
+            let mut valuer = Valuer::new(&rpc, protocol_key, &protocol);
+
+            println!(
+                "{:<44} {:>20} {:>20} {:>9} {:>9}  status",
+                "user", "collateral", "debt", "ltv", "health"
+            );
+            for (_, account) in rpc.program_accounts(LoanAccount::DISCRIMINATOR)? {
+                let loan = decode_loan_account(&account.data)?;
+                let (ltv, health, status) = match valuer.position(&loan) {
+                    Ok(position) => (
+                        format_bps(position.health.ltv_bps),
+                        format_bps(position.health.health_factor_bps),
+                        if position.health.is_healthy() {
+                            "healthy"
+                        } else {
+                            "unhealthy"
+                        },
+                    ),
+                    Err(_) => ("-".to_string(), "-".to_string(), "unpriced"),
+                };
+                println!(
+                    "{:<44} {:>20} {:>20} {:>9} {:>9}  {status}",
+                    loan.user.to_string(),
+                    loan.collateral_amount,
+                    loan.remaining_debt,
+                    ltv,
+                    health
+                );
+            }
+        }
+        Command::Pause | Command::Unpause => {
+            let locked = matches!(args.command, Command::Pause);
+            let protocol = protocol_context(&rpc, &load_protocol(&rpc, &admin)?.1)?;
+            let signature = rpc.send(&[set_protocol_locked(&protocol, locked)], &[])?;
+            println!("signature {signature}");
+        }
+        Command::Crank { user } => {
+            let (protocol_key, state) = load_protocol(&rpc, &admin)?;
+            let protocol = protocol_context(&rpc, &state)?;
+            let collateral = Valuer::new(&rpc, protocol_key, &state).collateral_of(&user)?;
+            let signature = rpc.send(&[cron_repayment(&protocol, user, &collateral)], &[])?;
+            println!("signature {signature}");
+        }
+        Command::MigrateProtocol {
+            liquidation_threshold_bps,
+            max_price_age_secs,
+            debt_ceiling,
+            total_debt,
+            open_loans,
+            legacy_loans,
+        } => {
+            let account = rpc.require_account(&protocol_pda(&admin).0, "protocol")?;
+            let legacy = decode_protocol_state_v0(&account.data)?;
+            let credit_mint = rpc.require_account(&legacy.credit_mint, "credit mint")?;
+            let protocol = ProtocolContext {
+                credit_mint: legacy.credit_mint,
+                ..ProtocolContext::new(admin, credit_mint.owner)
+            };
+            let params = MigrateProtocolParams {
+                liquidation_threshold_bps,
+                max_price_age_secs,
+                debt_ceiling,
+                total_debt,
+                open_loans,
+                legacy_loans,
+            };
+            let signature = rpc.send(&[migrate_protocol(&protocol, params)], &[])?;
+            println!("signature {signature}");
+        }
+        Command::MigrateLoan { user } => {
+            let signature = rpc.send(&[migrate_loan(rpc.payer.pubkey(), user)], &[])?;
+            println!("signature {signature}");
+        }
+        Command::BindLoan { user } => {
+            let (protocol_key, state) = load_protocol(&rpc, &admin)?;
+            let protocol = protocol_context(&rpc, &state)?;
+            let collateral = Valuer::new(&rpc, protocol_key, &state).collateral_of(&user)?;
+            let signature = rpc.send(&[bind_loan(&protocol, user, &collateral)], &[])?;
+            println!("signature {signature}");
+        }
+        Command::MigrateOracle {
+            address,
+            collateral,
+            authority,
+        } => {
+            let (_, state) = load_protocol(&rpc, &admin)?;
+            let protocol = protocol_context(&rpc, &state)?;
+            let authority = authority.unwrap_or_else(|| rpc.payer.pubkey());
+            let ix = migrate_simple_oracle(&protocol, collateral, address, authority);
+            let signature = rpc.send(&[ix], &[])?;
+            println!("signature {signature}");
+        }
+    }
+
+    Ok(())
+}
+
+fn load_protocol(rpc: &Rpc, admin: &Pubkey) -> Result<(Pubkey, ProtocolState)> {
+    let address = protocol_pda(admin).0;
+    let account = rpc.require_account(&address, "protocol")?;
+    Ok((address, decode_protocol_state(&account.data)?))
+}
+
+fn protocol_context(rpc: &Rpc, state: &ProtocolState) -> Result<ProtocolContext> {
+    let credit_mint = rpc.require_account(&state.credit_mint, "credit mint")?;
+    Ok(ProtocolContext::from_state(state, credit_mint.owner))
+}
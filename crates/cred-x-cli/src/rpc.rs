@@ -0,0 +1,73 @@
+use anchor_lang::{prelude::Pubkey, solana_program::instruction::Instruction};
+use anyhow::{Context, Result};
+use solana_account_decoder_client_types::UiAccountEncoding;
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_rpc_client_api::{
+    config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{
+    account::Account,
+    commitment_config::CommitmentConfig,
+    signature::{Keypair, Signature, Signer},
+    transaction::Transaction,
+};
+
+/// RPC connection plus the keypair that pays for and signs transactions.
+pub struct Rpc {
+    client: RpcClient,
+    pub payer: Keypair,
+}
+
+impl Rpc {
+    pub fn new(rpc_url: String, payer: Keypair) -> Self {
+        Self {
+            client: RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed()),
+            payer,
+        }
+    }
+
+    pub fn account(&self, address: &Pubkey) -> Result<Option<Account>> {
+        Ok(self
+            .client
+            .get_account_with_commitment(address, self.client.commitment())?
+            .value)
+    }
+
+    pub fn require_account(&self, address: &Pubkey, what: &str) -> Result<Account> {
+        self.account(address)?
+            .with_context(|| format!("{what} {address} not found"))
+    }
+
+    /// cred-x accounts whose data starts with `discriminator`.
+    pub fn program_accounts(&self, discriminator: &[u8]) -> Result<Vec<(Pubkey, Account)>> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                0,
+                discriminator,
+            ))]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+        Ok(self
+            .client
+            .get_program_accounts_with_config(&cred_x::ID, config)?)
+    }
+
+    /// Sends `instructions` paid by the payer and signed by it and `signers`.
+    pub fn send(&self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<Signature> {
+        let mut keypairs = vec![&self.payer];
+        keypairs.extend_from_slice(signers);
+        let blockhash = self.client.get_latest_blockhash()?;
+        let tx = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.payer.pubkey()),
+            &keypairs,
+            blockhash,
+        );
+        Ok(self.client.send_and_confirm_transaction(&tx)?)
+    }
+}
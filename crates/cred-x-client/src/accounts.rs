@@ -0,0 +1,86 @@
+use anchor_lang::{
+    error::ErrorCode,
+    prelude::{AccountInfo, Pubkey},
+    AccountDeserialize, AnchorDeserialize, Discriminator, Result,
+};
+use cred_x::{
+    CollateralConfig, CollateralVault, CreditAllowlistEntry, InsuranceFund, LoanAccount,
+    PendingParamChange, PositionDelegate, PriceQuote, ProtocolState, ProtocolStateV0,
+    SimplePriceOracle, StabilityDeposit, StabilityPool,
+};
+
+/// Decodes a cred-x account, checking its discriminator.
+pub fn decode_account<T: AccountDeserialize>(data: &[u8]) -> Result<T> {
+    T::try_deserialize(&mut &data[..])
+}
+
+pub fn decode_protocol_state(data: &[u8]) -> Result<ProtocolState> {
+    decode_account(data)
+}
+
+/// Reads a protocol still in the layout from before account versioning.
+pub fn decode_protocol_state_v0(data: &[u8]) -> Result<ProtocolStateV0> {
+    if !data.starts_with(ProtocolState::DISCRIMINATOR) {
+        return Err(ErrorCode::AccountDiscriminatorMismatch.into());
+    }
+    ProtocolStateV0::deserialize(&mut &data[8..])
+        .map_err(|_| ErrorCode::AccountDidNotDeserialize.into())
+}
+
+pub fn decode_loan_account(data: &[u8]) -> Result<LoanAccount> {
+    decode_account(data)
+}
+
+pub fn decode_collateral_vault(data: &[u8]) -> Result<CollateralVault> {
+    decode_account(data)
+}
+
+pub fn decode_collateral_config(data: &[u8]) -> Result<CollateralConfig> {
+    decode_account(data)
+}
+
+pub fn decode_credit_allowlist_entry(data: &[u8]) -> Result<CreditAllowlistEntry> {
+    decode_account(data)
+}
+
+pub fn decode_position_delegate(data: &[u8]) -> Result<PositionDelegate> {
+    decode_account(data)
+}
+
+pub fn decode_insurance_fund(data: &[u8]) -> Result<InsuranceFund> {
+    decode_account(data)
+}
+
+pub fn decode_stability_pool(data: &[u8]) -> Result<StabilityPool> {
+    decode_account(data)
+}
+
+pub fn decode_stability_deposit(data: &[u8]) -> Result<StabilityDeposit> {
+    decode_account(data)
+}
+
+pub fn decode_pending_param_change(data: &[u8]) -> Result<PendingParamChange> {
+    decode_account(data)
+}
+
+pub fn decode_simple_price_oracle(data: &[u8]) -> Result<SimplePriceOracle> {
+    decode_account(data)
+}
+
+/// Reads a price account the way the program does: a `SimplePriceOracle` when
+/// `owner` is cred-x, a Pyth price feed otherwise.
+pub fn decode_price_quote(address: &Pubkey, owner: &Pubkey, data: &[u8]) -> Result<PriceQuote> {
+    let mut lamports = 0;
+    let mut data = data.to_vec();
+    let account = AccountInfo::new(
+        address,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        owner,
+        false,
+        0,
+    );
+    PriceQuote::load(&account)
+}
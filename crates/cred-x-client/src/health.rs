@@ -0,0 +1,139 @@
+use anchor_lang::{require, Result};
+use cred_x::{error::CredXError, math, CollateralConfig, LoanAccount, PriceQuote, ProtocolState};
+
+pub use cred_x::BorrowQuote;
+
+/// Snapshot of a loan valued at a given collateral price, using the same
+/// integer math as `lend_credit_token` and `withdraw_collateral`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoanHealth {
+    pub collateral_value: u128,
+    // Most the loan may owe at origination (`collateral_value * ltv`)
+    pub borrow_limit: u128,
+    pub available_to_borrow: u64,
+    // Debt over collateral value, `None` when there is no collateral value
+    pub ltv_bps: Option<u128>,
+    // Collateral value at the liquidation threshold over debt, 10_000 = 1.0,
+    // `None` when there is no debt
+    pub health_factor_bps: Option<u128>,
+}
+
+impl LoanHealth {
+    pub fn is_healthy(&self) -> bool {
+        self.health_factor_bps.is_none_or(|factor| factor >= 10_000)
+    }
+}
+
+/// What a `cron_repayment` call would burn for a loan.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct YieldRepayment {
+    pub yield_amount: u64,
+    pub repayment: u64,
+}
+
+/// Collateral price used on-chain for a quote under the collateral's pricing
+/// policy.
+pub fn collateral_price(config: &CollateralConfig, quote: &PriceQuote) -> Result<u64> {
+    quote.collateral_price(&config.pricing)
+}
+
+pub fn collateral_value(collateral_amount: u64, collateral_price: u64) -> u128 {
+    math::collateral_value(collateral_amount, collateral_price)
+}
+
+pub fn loan_health(
+    protocol: &ProtocolState,
+    loan: &LoanAccount,
+    collateral_price: u64,
+) -> Result<LoanHealth> {
+    let collateral_value = collateral_value(loan.collateral_amount, collateral_price);
+    let borrow_limit = math::borrow_limit(collateral_value, protocol.ltv_ratio_bps)?;
+    let available_to_borrow = borrow_limit.saturating_sub(loan.remaining_debt as u128);
+    let debt = loan.remaining_debt as u128;
+
+    let ltv_bps = debt
+        .checked_mul(10_000)
+        .ok_or(CredXError::MathOverflow)?
+        .checked_div(collateral_value);
+    let health_factor_bps = collateral_value
+        .checked_mul(protocol.liquidation_threshold_bps as u128)
+        .ok_or(CredXError::MathOverflow)?
+        .checked_div(debt);
+
+    Ok(LoanHealth {
+        collateral_value,
+        borrow_limit,
+        available_to_borrow: u64::try_from(available_to_borrow)
+            .map_err(|_| CredXError::MathOverflow)?,
+        ltv_bps,
+        health_factor_bps,
+    })
+}
+
+/// Mirrors `lend_credit_token`: the loan borrows up to its limit and the
+/// protocol's borrow fee is carved out of that amount.
+pub fn borrow_quote(
+    protocol: &ProtocolState,
+    loan: &LoanAccount,
+    collateral_price: u64,
+) -> Result<BorrowQuote> {
+    require!(
+        loan.collateral_amount > 0,
+        CredXError::NoCollateralDeposited
+    );
+    math::borrow_quote(
+        loan.collateral_amount,
+        collateral_price,
+        protocol.ltv_ratio_bps,
+        loan.remaining_debt,
+        protocol.borrow_fee_bps,
+    )
+}
+
+/// Mirrors `cron_repayment`: yield is the vault balance above the deposited
+/// collateral, and its value repays debt up to what is outstanding.
+pub fn yield_repayment(
+    loan: &LoanAccount,
+    vault_balance: u64,
+    collateral_price: u64,
+) -> Result<YieldRepayment> {
+    let yield_amount = math::harvestable_yield(vault_balance, loan.collateral_amount)?;
+    let repayment = match yield_amount {
+        0 => 0,
+        _ => math::yield_repayment(yield_amount, collateral_price, loan.remaining_debt)?,
+    };
+
+    Ok(YieldRepayment {
+        yield_amount,
+        repayment,
+    })
+}
+
+/// Mirrors the `withdraw_collateral` check that the vault still covers the
+/// outstanding debt.
+pub fn can_withdraw(loan: &LoanAccount, vault_balance: u64, collateral_price: u64) -> bool {
+    math::covers_debt(vault_balance, collateral_price, loan.remaining_debt)
+}
+
+/// Loans `redeem_credit` accepts at `collateral_price`, riskiest first: loans
+/// with debt whose collateral still covers it, ordered by LTV descending.
+pub fn redemption_order<K: Copy>(loans: &[(K, LoanAccount)], collateral_price: u64) -> Vec<K> {
+    let mut candidates: Vec<_> = loans
+        .iter()
+        .filter(|(_, loan)| {
+            loan.remaining_debt > 0
+                && math::covers_debt(
+                    loan.collateral_amount,
+                    collateral_price,
+                    loan.remaining_debt,
+                )
+        })
+        .collect();
+    // Compare debt / collateral by cross-multiplying, as the program does
+    candidates.sort_by(|(_, a), (_, b)| {
+        let a_ratio = a.remaining_debt as u128 * b.collateral_amount as u128;
+        let b_ratio = b.remaining_debt as u128 * a.collateral_amount as u128;
+        b_ratio.cmp(&a_ratio)
+    });
+    candidates.into_iter().map(|(key, _)| *key).collect()
+}
@@ -0,0 +1,1136 @@
+use anchor_lang::{
+    prelude::{AccountMeta, Pubkey},
+    solana_program::{instruction::Instruction, sysvar},
+    system_program, InstructionData, ToAccountMetas,
+};
+use anchor_spl::associated_token;
+use cred_x::{
+    accounts, instruction, CollateralConfig, CollateralConfigParams, CreditMetadata,
+    CreditTransferMode, DelegatePermissions, InitializeProtocolParams, MigrateProtocolParams,
+    OracleSources, ParamChange, ProtocolState,
+};
+
+use crate::{pda::*, PROGRAM_ID};
+
+/// Keys shared by every instruction against one protocol deployment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProtocolContext {
+    // Current admin, signing admin instructions
+    pub admin: Pubkey,
+    // Admin the protocol PDA was derived from
+    pub original_admin: Pubkey,
+    pub credit_mint: Pubkey,
+    pub credit_token_program: Pubkey,
+}
+
+impl ProtocolContext {
+    /// Protocol whose credit mint was created by `initialize_protocol`.
+    pub fn new(admin: Pubkey, credit_token_program: Pubkey) -> Self {
+        Self {
+            admin,
+            original_admin: admin,
+            credit_mint: credit_mint_pda(&admin).0,
+            credit_token_program,
+        }
+    }
+
+    pub fn from_state(protocol: &ProtocolState, credit_token_program: Pubkey) -> Self {
+        Self {
+            admin: protocol.admin,
+            original_admin: protocol.seed_admin(),
+            credit_mint: protocol.credit_mint,
+            credit_token_program,
+        }
+    }
+
+    /// The same protocol after its admin has been rotated to `admin`.
+    pub fn with_admin(self, admin: Pubkey) -> Self {
+        Self { admin, ..self }
+    }
+
+    pub fn protocol(&self) -> Pubkey {
+        protocol_pda(&self.original_admin).0
+    }
+
+    pub fn credit_ata(&self, owner: &Pubkey) -> Pubkey {
+        associated_token_address(owner, &self.credit_mint, &self.credit_token_program)
+    }
+
+    pub fn protocol_credit_ata(&self) -> Pubkey {
+        self.credit_ata(&program_authority_pda().0)
+    }
+}
+
+/// Keys of a configured collateral mint and its oracle sources.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CollateralContext {
+    pub mint: Pubkey,
+    pub token_program: Pubkey,
+    pub oracle: OracleSources,
+}
+
+impl CollateralContext {
+    pub fn from_config(config: &CollateralConfig, token_program: Pubkey) -> Self {
+        Self {
+            mint: config.mint,
+            token_program,
+            oracle: config.oracle,
+        }
+    }
+
+    pub fn config(&self, protocol: &ProtocolContext) -> Pubkey {
+        collateral_config_pda(&protocol.protocol(), &self.mint).0
+    }
+
+    pub fn vault_ata(&self, user: &Pubkey) -> Pubkey {
+        associated_token_address(
+            &collateral_vault_pda(user).0,
+            &self.mint,
+            &self.token_program,
+        )
+    }
+
+    pub fn user_ata(&self, user: &Pubkey) -> Pubkey {
+        associated_token_address(user, &self.mint, &self.token_program)
+    }
+
+    fn secondary_oracle(&self) -> Option<Pubkey> {
+        self.oracle.has_secondary().then_some(self.oracle.secondary)
+    }
+}
+
+fn build(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+pub fn initialize_protocol(
+    admin: Pubkey,
+    credit_token_program: Pubkey,
+    params: InitializeProtocolParams,
+) -> Instruction {
+    let protocol = ProtocolContext::new(admin, credit_token_program);
+    build(
+        accounts::InitializeProtocol {
+            admin,
+            program_authority: program_authority_pda().0,
+            credit_mint: protocol.credit_mint,
+            protocol: protocol.protocol(),
+            protocol_credit_ata: protocol.protocol_credit_ata(),
+            associated_token_program: associated_token::ID,
+            token_program: credit_token_program,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeProtocol { params },
+    )
+}
+
+pub fn initialize_protocol_with_mint(
+    protocol: &ProtocolContext,
+    current_mint_authority: Pubkey,
+    params: InitializeProtocolParams,
+) -> Instruction {
+    build(
+        accounts::InitializeProtocolWithMint {
+            admin: protocol.admin,
+            current_mint_authority,
+            program_authority: program_authority_pda().0,
+            credit_mint: protocol.credit_mint,
+            protocol: protocol.protocol(),
+            protocol_credit_ata: protocol.protocol_credit_ata(),
+            associated_token_program: associated_token::ID,
+            token_program: protocol.credit_token_program,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeProtocolWithMint { params },
+    )
+}
+
+pub fn initialize_loan(
+    protocol: &ProtocolContext,
+    user: Pubkey,
+    collateral: &CollateralContext,
+) -> Instruction {
+    build(
+        accounts::InitializeLoan {
+            user,
+            protocol: protocol.protocol(),
+            program_authority: program_authority_pda().0,
+            credit_mint: protocol.credit_mint,
+            user_credit_ata: protocol.credit_ata(&user),
+            collateral_vault: collateral_vault_pda(&user).0,
+            loan_account: loan_pda(&user).0,
+            oracle_price_account: collateral.oracle.primary,
+            associated_token_program: associated_token::ID,
+            token_program: protocol.credit_token_program,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeLoan {
+            collateral_mint: collateral.mint,
+        },
+    )
+}
+
+/// The delegate grant `authority` acts under on `user`'s position, if it is
+/// not the owner.
+fn delegate_grant(authority: Pubkey, user: Pubkey) -> Option<Pubkey> {
+    (authority != user).then(|| position_delegate_pda(&user, &authority).0)
+}
+
+pub fn deposit_collateral(
+    protocol: &ProtocolContext,
+    user: Pubkey,
+    collateral: &CollateralContext,
+    amount: u64,
+) -> Instruction {
+    deposit_collateral_by(protocol, user, user, collateral, amount)
+}
+
+/// `deposit_collateral` into `user`'s position from `authority`'s collateral,
+/// signed by the owner or one of their delegates.
+pub fn deposit_collateral_by(
+    protocol: &ProtocolContext,
+    authority: Pubkey,
+    user: Pubkey,
+    collateral: &CollateralContext,
+    amount: u64,
+) -> Instruction {
+    build(
+        accounts::DepositCollateral {
+            authority,
+            user,
+            protocol: protocol.protocol(),
+            collateral_mint: collateral.mint,
+            collateral_config: collateral.config(protocol),
+            authority_collateral_ata: collateral.user_ata(&authority),
+            collateral_vault: collateral_vault_pda(&user).0,
+            collateral_vault_ata: collateral.vault_ata(&user),
+            program_authority: program_authority_pda().0,
+            loan_account: loan_pda(&user).0,
+            position_delegate: delegate_grant(authority, user),
+            associated_token_program: associated_token::ID,
+            token_program: collateral.token_program,
+            system_program: system_program::ID,
+        },
+        instruction::DepositCollateral { amount },
+    )
+}
+
+pub fn lend_credit_token(
+    protocol: &ProtocolContext,
+    user: Pubkey,
+    collateral: &CollateralContext,
+) -> Instruction {
+    lend_credit_token_by(protocol, user, user, collateral)
+}
+
+/// `lend_credit_token` against `user`'s position, signed by the owner or one
+/// of their delegates. Credit is always minted to the owner.
+pub fn lend_credit_token_by(
+    protocol: &ProtocolContext,
+    authority: Pubkey,
+    user: Pubkey,
+    collateral: &CollateralContext,
+) -> Instruction {
+    build(
+        accounts::LendCreditToken {
+            authority,
+            user,
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+            program_authority: program_authority_pda().0,
+            credit_mint: protocol.credit_mint,
+            user_credit_ata: protocol.credit_ata(&user),
+            protocol_credit_ata: protocol.protocol_credit_ata(),
+            collateral_vault: collateral_vault_pda(&user).0,
+            collateral_config: collateral.config(protocol),
+            loan_account: loan_pda(&user).0,
+            position_delegate: delegate_grant(authority, user),
+            oracle_price_account: collateral.oracle.primary,
+            secondary_oracle_account: collateral.secondary_oracle(),
+            associated_token_program: associated_token::ID,
+            token_program: protocol.credit_token_program,
+            system_program: system_program::ID,
+        },
+        instruction::LendCreditToken {},
+    )
+}
+
+pub fn cron_repayment(
+    protocol: &ProtocolContext,
+    user: Pubkey,
+    collateral: &CollateralContext,
+) -> Instruction {
+    build(
+        accounts::CronRepayment {
+            protocol: protocol.protocol(),
+            collateral_vault: collateral_vault_pda(&user).0,
+            collateral_config: collateral.config(protocol),
+            collateral_vault_ata: collateral.vault_ata(&user),
+            loan_account: loan_pda(&user).0,
+            credit_mint: protocol.credit_mint,
+            program_authority: program_authority_pda().0,
+            protocol_credit_ata: protocol.protocol_credit_ata(),
+            user_credit_ata: protocol.credit_ata(&user),
+            oracle_price_account: collateral.oracle.primary,
+            secondary_oracle_account: collateral.secondary_oracle(),
+            credit_token_program: protocol.credit_token_program,
+            collateral_token_program: collateral.token_program,
+        },
+        instruction::CronRepayment {},
+    )
+}
+
+pub fn withdraw_collateral(
+    protocol: &ProtocolContext,
+    user: Pubkey,
+    collateral: &CollateralContext,
+) -> Instruction {
+    withdraw_collateral_by(protocol, user, user, collateral)
+}
+
+/// `withdraw_collateral` from `user`'s position, signed by the owner or one
+/// of their delegates. Collateral is always returned to the owner.
+pub fn withdraw_collateral_by(
+    protocol: &ProtocolContext,
+    authority: Pubkey,
+    user: Pubkey,
+    collateral: &CollateralContext,
+) -> Instruction {
+    build(
+        accounts::WithdrawCollateral {
+            authority,
+            user,
+            protocol: protocol.protocol(),
+            credit_mint: protocol.credit_mint,
+            program_authority: program_authority_pda().0,
+            collateral_vault: collateral_vault_pda(&user).0,
+            collateral_mint: collateral.mint,
+            collateral_config: collateral.config(protocol),
+            collateral_vault_ata: collateral.vault_ata(&user),
+            user_collateral_ata: collateral.user_ata(&user),
+            loan_account: loan_pda(&user).0,
+            position_delegate: delegate_grant(authority, user),
+            user_credit_ata: protocol.credit_ata(&user),
+            oracle_price_account: collateral.oracle.primary,
+            secondary_oracle_account: collateral.secondary_oracle(),
+            associated_token_program: associated_token::ID,
+            credit_token_program: protocol.credit_token_program,
+            collateral_token_program: collateral.token_program,
+            system_program: system_program::ID,
+        },
+        instruction::WithdrawCollateral {},
+    )
+}
+
+/// Burns up to `amount` of `authority`'s credit against `user`'s debt.
+pub fn repay_debt(
+    protocol: &ProtocolContext,
+    authority: Pubkey,
+    user: Pubkey,
+    collateral: &CollateralContext,
+    amount: u64,
+) -> Instruction {
+    build(
+        accounts::RepayDebt {
+            authority,
+            user,
+            protocol: protocol.protocol(),
+            credit_mint: protocol.credit_mint,
+            authority_credit_ata: protocol.credit_ata(&authority),
+            collateral_vault: collateral_vault_pda(&user).0,
+            collateral_config: collateral.config(protocol),
+            loan_account: loan_pda(&user).0,
+            position_delegate: delegate_grant(authority, user),
+            credit_token_program: protocol.credit_token_program,
+        },
+        instruction::RepayDebt { amount },
+    )
+}
+
+pub fn set_position_delegate(
+    owner: Pubkey,
+    delegate: Pubkey,
+    permissions: DelegatePermissions,
+    borrow_limit: u64,
+) -> Instruction {
+    build(
+        accounts::SetPositionDelegate {
+            owner,
+            position_delegate: position_delegate_pda(&owner, &delegate).0,
+            system_program: system_program::ID,
+        },
+        instruction::SetPositionDelegate {
+            delegate,
+            permissions,
+            borrow_limit,
+        },
+    )
+}
+
+pub fn revoke_position_delegate(owner: Pubkey, delegate: Pubkey) -> Instruction {
+    build(
+        accounts::RevokePositionDelegate {
+            owner,
+            position_delegate: position_delegate_pda(&owner, &delegate).0,
+        },
+        instruction::RevokePositionDelegate {},
+    )
+}
+
+/// Swaps `user`'s whole vault from `from` into `to` collateral through
+/// `swap_program`. `swap_accounts` are the swap program's own accounts,
+/// appended after the ones cred-x passes to every swap adapter.
+pub fn swap_collateral(
+    protocol: &ProtocolContext,
+    user: Pubkey,
+    from: &CollateralContext,
+    to: &CollateralContext,
+    swap_program: Pubkey,
+    swap_accounts: Vec<AccountMeta>,
+    min_amount_out: u64,
+) -> Instruction {
+    let mut ix = build(
+        accounts::SwapCollateral {
+            user,
+            protocol: protocol.protocol(),
+            collateral_vault: collateral_vault_pda(&user).0,
+            loan_account: loan_pda(&user).0,
+            source_mint: from.mint,
+            source_config: from.config(protocol),
+            source_vault_ata: from.vault_ata(&user),
+            target_mint: to.mint,
+            target_config: to.config(protocol),
+            target_vault_ata: to.vault_ata(&user),
+            program_authority: program_authority_pda().0,
+            source_oracle_account: from.oracle.primary,
+            source_secondary_oracle_account: from.secondary_oracle(),
+            target_oracle_account: to.oracle.primary,
+            target_secondary_oracle_account: to.secondary_oracle(),
+            swap_program,
+            source_token_program: from.token_program,
+            target_token_program: to.token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::SwapCollateral { min_amount_out },
+    );
+    ix.accounts.extend(swap_accounts);
+    ix
+}
+
+/// Borrows against `user`'s loan and swaps the credit into more collateral
+/// until the loan sits at `target_ltv_bps`. Co-signed by the admin like
+/// `lend_credit_token`.
+pub fn leverage(
+    protocol: &ProtocolContext,
+    user: Pubkey,
+    collateral: &CollateralContext,
+    swap_program: Pubkey,
+    swap_accounts: Vec<AccountMeta>,
+    target_ltv_bps: u16,
+    min_collateral_out: u64,
+) -> Instruction {
+    let collateral_vault = collateral_vault_pda(&user).0;
+    let mut ix = build(
+        accounts::Leverage {
+            user,
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+            program_authority: program_authority_pda().0,
+            credit_mint: protocol.credit_mint,
+            protocol_credit_ata: protocol.protocol_credit_ata(),
+            collateral_vault,
+            collateral_mint: collateral.mint,
+            collateral_config: collateral.config(protocol),
+            collateral_vault_ata: collateral.vault_ata(&user),
+            vault_credit_ata: protocol.credit_ata(&collateral_vault),
+            loan_account: loan_pda(&user).0,
+            oracle_price_account: collateral.oracle.primary,
+            secondary_oracle_account: collateral.secondary_oracle(),
+            swap_program,
+            credit_token_program: protocol.credit_token_program,
+            collateral_token_program: collateral.token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::Leverage {
+            target_ltv_bps,
+            min_collateral_out,
+        },
+    );
+    ix.accounts.extend(swap_accounts);
+    ix
+}
+
+/// Sells enough of `user`'s collateral for credit to repay the loan down to
+/// `target_ltv_bps`.
+pub fn deleverage(
+    protocol: &ProtocolContext,
+    user: Pubkey,
+    collateral: &CollateralContext,
+    swap_program: Pubkey,
+    swap_accounts: Vec<AccountMeta>,
+    target_ltv_bps: u16,
+    min_credit_out: u64,
+) -> Instruction {
+    let collateral_vault = collateral_vault_pda(&user).0;
+    let mut ix = build(
+        accounts::Deleverage {
+            user,
+            protocol: protocol.protocol(),
+            program_authority: program_authority_pda().0,
+            credit_mint: protocol.credit_mint,
+            user_credit_ata: protocol.credit_ata(&user),
+            collateral_vault,
+            collateral_mint: collateral.mint,
+            collateral_config: collateral.config(protocol),
+            collateral_vault_ata: collateral.vault_ata(&user),
+            vault_credit_ata: protocol.credit_ata(&collateral_vault),
+            loan_account: loan_pda(&user).0,
+            oracle_price_account: collateral.oracle.primary,
+            secondary_oracle_account: collateral.secondary_oracle(),
+            swap_program,
+            credit_token_program: protocol.credit_token_program,
+            collateral_token_program: collateral.token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::Deleverage {
+            target_ltv_bps,
+            min_credit_out,
+        },
+    );
+    ix.accounts.extend(swap_accounts);
+    ix
+}
+
+/// Burns up to `amount` of `redeemer`'s credit for `collateral` taken from
+/// the loans of `borrowers`, which must be ordered riskiest first (see
+/// `redemption_order`).
+pub fn redeem_credit(
+    protocol: &ProtocolContext,
+    redeemer: Pubkey,
+    collateral: &CollateralContext,
+    borrowers: &[Pubkey],
+    amount: u64,
+    min_collateral_out: u64,
+) -> Instruction {
+    let mut ix = build(
+        accounts::RedeemCredit {
+            redeemer,
+            protocol: protocol.protocol(),
+            credit_mint: protocol.credit_mint,
+            redeemer_credit_ata: protocol.credit_ata(&redeemer),
+            collateral_mint: collateral.mint,
+            collateral_config: collateral.config(protocol),
+            redeemer_collateral_ata: collateral.user_ata(&redeemer),
+            oracle_price_account: collateral.oracle.primary,
+            secondary_oracle_account: collateral.secondary_oracle(),
+            credit_token_program: protocol.credit_token_program,
+            collateral_token_program: collateral.token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::RedeemCredit {
+            amount,
+            min_collateral_out,
+        },
+    );
+    for borrower in borrowers {
+        ix.accounts.extend([
+            AccountMeta::new(loan_pda(borrower).0, false),
+            AccountMeta::new_readonly(collateral_vault_pda(borrower).0, false),
+            AccountMeta::new(collateral.vault_ata(borrower), false),
+        ]);
+    }
+    ix
+}
+
+/// Moves `amount` of collected fees into the insurance fund, creating it on
+/// first use.
+pub fn fund_insurance(protocol: &ProtocolContext, amount: u64) -> Instruction {
+    let insurance_fund = insurance_fund_pda(&protocol.protocol()).0;
+    build(
+        accounts::FundInsurance {
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+            program_authority: program_authority_pda().0,
+            credit_mint: protocol.credit_mint,
+            protocol_credit_ata: protocol.protocol_credit_ata(),
+            insurance_fund,
+            fund_credit_ata: protocol.credit_ata(&insurance_fund),
+            credit_token_program: protocol.credit_token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::FundInsurance { amount },
+    )
+}
+
+/// Writes off `user`'s underwater loan against the insurance fund. `payer`
+/// funds the fund's collateral account if it does not exist yet.
+pub fn resolve_bad_debt(
+    protocol: &ProtocolContext,
+    payer: Pubkey,
+    user: Pubkey,
+    collateral: &CollateralContext,
+) -> Instruction {
+    let insurance_fund = insurance_fund_pda(&protocol.protocol()).0;
+    build(
+        accounts::ResolveBadDebt {
+            payer,
+            protocol: protocol.protocol(),
+            insurance_fund,
+            credit_mint: protocol.credit_mint,
+            fund_credit_ata: protocol.credit_ata(&insurance_fund),
+            collateral_vault: collateral_vault_pda(&user).0,
+            collateral_mint: collateral.mint,
+            collateral_config: collateral.config(protocol),
+            collateral_vault_ata: collateral.vault_ata(&user),
+            fund_collateral_ata: collateral.user_ata(&insurance_fund),
+            loan_account: loan_pda(&user).0,
+            oracle_price_account: collateral.oracle.primary,
+            secondary_oracle_account: collateral.secondary_oracle(),
+            credit_token_program: protocol.credit_token_program,
+            collateral_token_program: collateral.token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::ResolveBadDebt {},
+    )
+}
+
+/// Sells `amount` of the collateral the insurance fund seized to the
+/// collateral's stability pool for credit.
+pub fn sell_fund_collateral(
+    protocol: &ProtocolContext,
+    collateral: &CollateralContext,
+    amount: u64,
+) -> Instruction {
+    let insurance_fund = insurance_fund_pda(&protocol.protocol()).0;
+    let stability_pool = stability_pool_pda(&protocol.protocol(), &collateral.mint).0;
+    build(
+        accounts::SellFundCollateral {
+            protocol: protocol.protocol(),
+            insurance_fund,
+            program_authority: program_authority_pda().0,
+            credit_mint: protocol.credit_mint,
+            fund_credit_ata: protocol.credit_ata(&insurance_fund),
+            fund_collateral_ata: collateral.user_ata(&insurance_fund),
+            stability_pool,
+            pool_credit_ata: protocol.credit_ata(&stability_pool),
+            pool_collateral_ata: collateral.user_ata(&stability_pool),
+            collateral_mint: collateral.mint,
+            collateral_config: collateral.config(protocol),
+            oracle_price_account: collateral.oracle.primary,
+            secondary_oracle_account: collateral.secondary_oracle(),
+            credit_token_program: protocol.credit_token_program,
+            collateral_token_program: collateral.token_program,
+        },
+        instruction::SellFundCollateral { amount },
+    )
+}
+
+pub fn initialize_stability_pool(
+    protocol: &ProtocolContext,
+    collateral: &CollateralContext,
+) -> Instruction {
+    let stability_pool = stability_pool_pda(&protocol.protocol(), &collateral.mint).0;
+    build(
+        accounts::InitializeStabilityPool {
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+            credit_mint: protocol.credit_mint,
+            collateral_mint: collateral.mint,
+            collateral_config: collateral.config(protocol),
+            stability_pool,
+            pool_credit_ata: protocol.credit_ata(&stability_pool),
+            pool_collateral_ata: collateral.user_ata(&stability_pool),
+            credit_token_program: protocol.credit_token_program,
+            collateral_token_program: collateral.token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeStabilityPool {},
+    )
+}
+
+/// Adds `amount` of `depositor`'s credit to the collateral's stability pool,
+/// paying out any collateral the existing deposit has gained.
+pub fn deposit_to_stability_pool(
+    protocol: &ProtocolContext,
+    depositor: Pubkey,
+    collateral: &CollateralContext,
+    amount: u64,
+) -> Instruction {
+    let stability_pool = stability_pool_pda(&protocol.protocol(), &collateral.mint).0;
+    build(
+        accounts::DepositToStabilityPool {
+            depositor,
+            protocol: protocol.protocol(),
+            stability_pool,
+            stability_deposit: stability_deposit_pda(&stability_pool, &depositor).0,
+            program_authority: program_authority_pda().0,
+            credit_mint: protocol.credit_mint,
+            depositor_credit_ata: protocol.credit_ata(&depositor),
+            pool_credit_ata: protocol.credit_ata(&stability_pool),
+            collateral_mint: collateral.mint,
+            pool_collateral_ata: collateral.user_ata(&stability_pool),
+            depositor_collateral_ata: collateral.user_ata(&depositor),
+            credit_token_program: protocol.credit_token_program,
+            collateral_token_program: collateral.token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::DepositToStabilityPool { amount },
+    )
+}
+
+/// Withdraws up to `amount` of `depositor`'s remaining stability deposit;
+/// `u64::MAX` withdraws all of it.
+pub fn withdraw_from_stability_pool(
+    protocol: &ProtocolContext,
+    depositor: Pubkey,
+    collateral: &CollateralContext,
+    amount: u64,
+) -> Instruction {
+    let stability_pool = stability_pool_pda(&protocol.protocol(), &collateral.mint).0;
+    build(
+        accounts::WithdrawFromStabilityPool {
+            depositor,
+            protocol: protocol.protocol(),
+            stability_pool,
+            stability_deposit: stability_deposit_pda(&stability_pool, &depositor).0,
+            program_authority: program_authority_pda().0,
+            credit_mint: protocol.credit_mint,
+            depositor_credit_ata: protocol.credit_ata(&depositor),
+            pool_credit_ata: protocol.credit_ata(&stability_pool),
+            collateral_mint: collateral.mint,
+            pool_collateral_ata: collateral.user_ata(&stability_pool),
+            depositor_collateral_ata: collateral.user_ata(&depositor),
+            credit_token_program: protocol.credit_token_program,
+            collateral_token_program: collateral.token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::WithdrawFromStabilityPool { amount },
+    )
+}
+
+pub fn claim_stability_gains(
+    protocol: &ProtocolContext,
+    depositor: Pubkey,
+    collateral: &CollateralContext,
+) -> Instruction {
+    let stability_pool = stability_pool_pda(&protocol.protocol(), &collateral.mint).0;
+    build(
+        accounts::ClaimStabilityGains {
+            depositor,
+            protocol: protocol.protocol(),
+            stability_pool,
+            stability_deposit: stability_deposit_pda(&stability_pool, &depositor).0,
+            collateral_mint: collateral.mint,
+            pool_collateral_ata: collateral.user_ata(&stability_pool),
+            depositor_collateral_ata: collateral.user_ata(&depositor),
+            collateral_token_program: collateral.token_program,
+            associated_token_program: associated_token::ID,
+            system_program: system_program::ID,
+        },
+        instruction::ClaimStabilityGains {},
+    )
+}
+
+/// Cancels as much of `user`'s unhealthy debt as the stability pool can
+/// absorb.
+pub fn liquidate(
+    protocol: &ProtocolContext,
+    user: Pubkey,
+    collateral: &CollateralContext,
+) -> Instruction {
+    let stability_pool = stability_pool_pda(&protocol.protocol(), &collateral.mint).0;
+    build(
+        accounts::Liquidate {
+            protocol: protocol.protocol(),
+            credit_mint: protocol.credit_mint,
+            stability_pool,
+            pool_credit_ata: protocol.credit_ata(&stability_pool),
+            pool_collateral_ata: collateral.user_ata(&stability_pool),
+            collateral_vault: collateral_vault_pda(&user).0,
+            collateral_mint: collateral.mint,
+            collateral_config: collateral.config(protocol),
+            collateral_vault_ata: collateral.vault_ata(&user),
+            loan_account: loan_pda(&user).0,
+            oracle_price_account: collateral.oracle.primary,
+            secondary_oracle_account: collateral.secondary_oracle(),
+            credit_token_program: protocol.credit_token_program,
+            collateral_token_program: collateral.token_program,
+        },
+        instruction::Liquidate {},
+    )
+}
+
+/// Mints `amount` credit into the `receiver` token account. Must be followed
+/// in the same transaction by a `flash_repay` of the same amount.
+pub fn flash_mint(protocol: &ProtocolContext, receiver: Pubkey, amount: u64) -> Instruction {
+    build(
+        accounts::FlashMint {
+            protocol: protocol.protocol(),
+            program_authority: program_authority_pda().0,
+            credit_mint: protocol.credit_mint,
+            receiver,
+            instructions: sysvar::instructions::ID,
+            token_program: protocol.credit_token_program,
+        },
+        instruction::FlashMint { amount },
+    )
+}
+
+/// Burns `amount` plus the flash fee from `payer`'s credit account.
+pub fn flash_repay(protocol: &ProtocolContext, payer: Pubkey, amount: u64) -> Instruction {
+    build(
+        accounts::FlashRepay {
+            protocol: protocol.protocol(),
+            program_authority: program_authority_pda().0,
+            credit_mint: protocol.credit_mint,
+            payer,
+            payer_credit_ata: protocol.credit_ata(&payer),
+            protocol_credit_ata: protocol.protocol_credit_ata(),
+            instructions: sysvar::instructions::ID,
+            token_program: protocol.credit_token_program,
+        },
+        instruction::FlashRepay { amount },
+    )
+}
+
+pub fn configure_collateral(
+    protocol: &ProtocolContext,
+    collateral_mint: Pubkey,
+    params: CollateralConfigParams,
+) -> Instruction {
+    build(
+        accounts::ConfigureCollateral {
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+            collateral_mint,
+            collateral_config: collateral_config_pda(&protocol.protocol(), &collateral_mint).0,
+            system_program: system_program::ID,
+        },
+        instruction::ConfigureCollateral { params },
+    )
+}
+
+/// Queues `change` under the next id, `ProtocolState::param_changes_queued`.
+pub fn queue_param_change(protocol: &ProtocolContext, id: u64, change: ParamChange) -> Instruction {
+    build(
+        accounts::QueueParamChange {
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+            pending_change: param_change_pda(&protocol.protocol(), id).0,
+            system_program: system_program::ID,
+        },
+        instruction::QueueParamChange { change },
+    )
+}
+
+/// Executes queued change `id`. A collateral change needs `collateral_mint`.
+/// Accounts beyond the protocol's own are derived from the queued `change`.
+pub fn execute_param_change(
+    protocol: &ProtocolContext,
+    id: u64,
+    change: &ParamChange,
+) -> Instruction {
+    let credit = change.needs_credit_mint();
+    build(
+        accounts::ExecuteParamChange {
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+            pending_change: param_change_pda(&protocol.protocol(), id).0,
+            collateral_config: change
+                .collateral_mint()
+                .map(|mint| collateral_config_pda(&protocol.protocol(), &mint).0),
+            program_authority: credit.then(|| program_authority_pda().0),
+            credit_mint: credit.then_some(protocol.credit_mint),
+            credit_token_program: credit.then_some(protocol.credit_token_program),
+            system_program: credit.then_some(system_program::ID),
+        },
+        instruction::ExecuteParamChange {},
+    )
+}
+
+pub fn cancel_param_change(protocol: &ProtocolContext, id: u64) -> Instruction {
+    build(
+        accounts::CancelParamChange {
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+            pending_change: param_change_pda(&protocol.protocol(), id).0,
+        },
+        instruction::CancelParamChange {},
+    )
+}
+
+pub fn set_debt_ceiling(protocol: &ProtocolContext, debt_ceiling: u64) -> Instruction {
+    build(
+        accounts::SetDebtCeiling {
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+        },
+        instruction::SetDebtCeiling { debt_ceiling },
+    )
+}
+
+pub fn set_flash_fee(protocol: &ProtocolContext, flash_fee_bps: u16) -> Instruction {
+    build(
+        accounts::SetFlashFee {
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+        },
+        instruction::SetFlashFee { flash_fee_bps },
+    )
+}
+
+pub fn set_redemption_fee(protocol: &ProtocolContext, redemption_fee_bps: u16) -> Instruction {
+    build(
+        accounts::SetRedemptionFee {
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+        },
+        instruction::SetRedemptionFee { redemption_fee_bps },
+    )
+}
+
+/// Sets the program `swap_collateral` routes through, `Pubkey::default()`
+/// to disable swaps.
+pub fn set_swap_program(protocol: &ProtocolContext, swap_program: Pubkey) -> Instruction {
+    build(
+        accounts::SetSwapProgram {
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+        },
+        instruction::SetSwapProgram { swap_program },
+    )
+}
+
+pub fn set_protocol_locked(protocol: &ProtocolContext, locked: bool) -> Instruction {
+    build(
+        accounts::SetProtocolLocked {
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+        },
+        instruction::SetProtocolLocked { locked },
+    )
+}
+
+pub fn reset_circuit_breaker(
+    protocol: &ProtocolContext,
+    collateral: &CollateralContext,
+) -> Instruction {
+    build(
+        accounts::ResetCircuitBreaker {
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+            collateral_config: collateral.config(protocol),
+            oracle_price_account: collateral.oracle.primary,
+            secondary_oracle_account: collateral.secondary_oracle(),
+        },
+        instruction::ResetCircuitBreaker {},
+    )
+}
+
+pub fn trip_circuit_breaker(
+    protocol: &ProtocolContext,
+    collateral: &CollateralContext,
+) -> Instruction {
+    build(
+        accounts::TripCircuitBreaker {
+            protocol: protocol.protocol(),
+            collateral_config: collateral.config(protocol),
+            oracle_price_account: collateral.oracle.primary,
+            secondary_oracle_account: collateral.secondary_oracle(),
+        },
+        instruction::TripCircuitBreaker {},
+    )
+}
+
+pub fn update_credit_metadata(protocol: &ProtocolContext, metadata: CreditMetadata) -> Instruction {
+    build(
+        accounts::UpdateCreditMetadata {
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+            program_authority: program_authority_pda().0,
+            credit_mint: protocol.credit_mint,
+            token_program: protocol.credit_token_program,
+            system_program: system_program::ID,
+        },
+        instruction::UpdateCreditMetadata { metadata },
+    )
+}
+
+pub fn initialize_credit_transfer_hook(protocol: &ProtocolContext) -> Instruction {
+    build(
+        accounts::InitializeCreditTransferHook {
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+            credit_mint: protocol.credit_mint,
+            extra_account_meta_list: extra_account_metas_pda(&protocol.credit_mint).0,
+            token_program: protocol.credit_token_program,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeCreditTransferHook {},
+    )
+}
+
+/// The `Execute` call the token program makes into the hook. Transfers
+/// normally resolve these accounts from the extra-account-metas PDA; this is
+/// for building the call directly.
+pub fn credit_transfer_hook(
+    protocol: &ProtocolContext,
+    source_owner: Pubkey,
+    destination_owner: Pubkey,
+    owner: Pubkey,
+    amount: u64,
+) -> Instruction {
+    let protocol_key = protocol.protocol();
+    let source_collateral_vault = collateral_vault_pda(&source_owner).0;
+    build(
+        accounts::CreditTransferHook {
+            source_token: protocol.credit_ata(&source_owner),
+            credit_mint: protocol.credit_mint,
+            destination_token: protocol.credit_ata(&destination_owner),
+            owner,
+            extra_account_meta_list: extra_account_metas_pda(&protocol.credit_mint).0,
+            protocol: protocol_key,
+            source_collateral_vault,
+            source_loan: loan_pda(&source_owner).0,
+            destination_allowlist: credit_allowlist_pda(&protocol_key, &destination_owner).0,
+        },
+        instruction::CreditTransferHook { amount },
+    )
+}
+
+pub fn set_credit_transfer_mode(
+    protocol: &ProtocolContext,
+    mode: CreditTransferMode,
+) -> Instruction {
+    build(
+        accounts::SetCreditTransferMode {
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+            credit_mint: protocol.credit_mint,
+        },
+        instruction::SetCreditTransferMode { mode },
+    )
+}
+
+pub fn set_credit_allowlist(
+    protocol: &ProtocolContext,
+    owner: Pubkey,
+    allowed: bool,
+) -> Instruction {
+    build(
+        accounts::SetCreditAllowlist {
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+            allowlist_entry: credit_allowlist_pda(&protocol.protocol(), &owner).0,
+            system_program: system_program::ID,
+        },
+        instruction::SetCreditAllowlist { owner, allowed },
+    )
+}
+
+pub fn migrate_protocol(protocol: &ProtocolContext, params: MigrateProtocolParams) -> Instruction {
+    build(
+        accounts::MigrateProtocol {
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+            credit_mint: protocol.credit_mint,
+            system_program: system_program::ID,
+        },
+        instruction::MigrateProtocol { params },
+    )
+}
+
+pub fn migrate_loan(payer: Pubkey, user: Pubkey) -> Instruction {
+    build(
+        accounts::MigrateLoan {
+            payer,
+            user,
+            collateral_vault: collateral_vault_pda(&user).0,
+            loan_account: loan_pda(&user).0,
+            system_program: system_program::ID,
+        },
+        instruction::MigrateLoan {},
+    )
+}
+
+/// Migrates a `SimplePriceOracle`, handing price updates to `authority`.
+/// Migrates `price_account`, which the config of `collateral_mint` must read.
+pub fn migrate_simple_oracle(
+    protocol: &ProtocolContext,
+    collateral_mint: Pubkey,
+    price_account: Pubkey,
+    authority: Pubkey,
+) -> Instruction {
+    build(
+        accounts::MigrateSimpleOracle {
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+            collateral_config: collateral_config_pda(&protocol.protocol(), &collateral_mint).0,
+            price_account,
+            system_program: system_program::ID,
+        },
+        instruction::MigrateSimpleOracle { authority },
+    )
+}
+
+pub fn bind_loan(
+    protocol: &ProtocolContext,
+    user: Pubkey,
+    collateral: &CollateralContext,
+) -> Instruction {
+    build(
+        accounts::BindLoan {
+            admin: protocol.admin,
+            protocol: protocol.protocol(),
+            user,
+            collateral_vault: collateral_vault_pda(&user).0,
+            collateral_config: collateral.config(protocol),
+            loan_account: loan_pda(&user).0,
+        },
+        instruction::BindLoan {},
+    )
+}
+
+pub fn create_simple_oracle(
+    authority: Pubkey,
+    price_account: Pubkey,
+    price: u64,
+    conf: u64,
+) -> Instruction {
+    build(
+        accounts::CreateSimpleOracle {
+            authority,
+            price_account,
+            system_program: system_program::ID,
+        },
+        instruction::CreateSimpleOracle { price, conf },
+    )
+}
+
+pub fn update_simple_oracle(
+    authority: Pubkey,
+    price_account: Pubkey,
+    price: u64,
+    conf: u64,
+) -> Instruction {
+    build(
+        accounts::UpdateSimpleOracle {
+            authority,
+            price_account,
+        },
+        instruction::UpdateSimpleOracle { price, conf },
+    )
+}
@@ -0,0 +1,17 @@
+//! Rust client for the cred-x program: PDA derivation, instruction builders,
+//! account decoding, the loan health math used on-chain and reading outcomes
+//! from transaction logs.
+
+pub mod accounts;
+pub mod health;
+pub mod instructions;
+pub mod logs;
+pub mod pda;
+
+pub use accounts::*;
+pub use health::*;
+pub use instructions::*;
+pub use logs::*;
+pub use pda::*;
+
+pub use cred_x::ID as PROGRAM_ID;
@@ -0,0 +1,18 @@
+use anchor_lang::prelude::Pubkey;
+use cred_x::CIRCUIT_BREAKER_TRIPPED_LOG;
+
+/// Collateral whose circuit breaker the transaction with `logs` found
+/// tripped, if any.
+///
+/// Only `tripCircuitBreaker` and `cronRepayment` save a trip, and each logs
+/// it once, from the reading that tripped the breaker. Instructions that
+/// pause on a tripped breaker fail with `CircuitBreakerTripPending` instead.
+pub fn tripped_circuit_breaker(logs: &[String]) -> Option<Pubkey> {
+    logs.iter().find_map(|line| {
+        let rest = line
+            .strip_prefix("Program log: ")?
+            .strip_prefix(CIRCUIT_BREAKER_TRIPPED_LOG)?;
+        let (mint, _) = rest.split_once(':')?;
+        mint.parse().ok()
+    })
+}
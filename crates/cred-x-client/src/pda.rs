@@ -0,0 +1,92 @@
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::associated_token::get_associated_token_address_with_program_id;
+
+use crate::PROGRAM_ID;
+
+pub fn protocol_pda(admin: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"protocol", admin.as_ref()], &PROGRAM_ID)
+}
+
+/// Credit mint created by `initialize_protocol`. Protocols set up with
+/// `initialize_protocol_with_mint` use the adopted mint instead.
+pub fn credit_mint_pda(admin: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"credit", admin.as_ref()], &PROGRAM_ID)
+}
+
+pub fn program_authority_pda() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"program_authority"], &PROGRAM_ID)
+}
+
+pub fn collateral_vault_pda(user: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"collateral_vault", user.as_ref()], &PROGRAM_ID)
+}
+
+pub fn loan_pda(user: &Pubkey) -> (Pubkey, u8) {
+    let (collateral_vault, _) = collateral_vault_pda(user);
+    Pubkey::find_program_address(
+        &[b"loan", user.as_ref(), collateral_vault.as_ref()],
+        &PROGRAM_ID,
+    )
+}
+
+pub fn collateral_config_pda(protocol: &Pubkey, collateral_mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"collateral_config",
+            protocol.as_ref(),
+            collateral_mint.as_ref(),
+        ],
+        &PROGRAM_ID,
+    )
+}
+
+pub fn credit_allowlist_pda(protocol: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"credit_allowlist", protocol.as_ref(), owner.as_ref()],
+        &PROGRAM_ID,
+    )
+}
+
+pub fn position_delegate_pda(owner: &Pubkey, delegate: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"position_delegate", owner.as_ref(), delegate.as_ref()],
+        &PROGRAM_ID,
+    )
+}
+
+pub fn insurance_fund_pda(protocol: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"insurance_fund", protocol.as_ref()], &PROGRAM_ID)
+}
+
+pub fn stability_pool_pda(protocol: &Pubkey, collateral_mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"stability_pool",
+            protocol.as_ref(),
+            collateral_mint.as_ref(),
+        ],
+        &PROGRAM_ID,
+    )
+}
+
+pub fn stability_deposit_pda(pool: &Pubkey, owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"stability_deposit", pool.as_ref(), owner.as_ref()],
+        &PROGRAM_ID,
+    )
+}
+
+pub fn param_change_pda(protocol: &Pubkey, id: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"param_change", protocol.as_ref(), &id.to_le_bytes()],
+        &PROGRAM_ID,
+    )
+}
+
+pub fn extra_account_metas_pda(credit_mint: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"extra-account-metas", credit_mint.as_ref()], &PROGRAM_ID)
+}
+
+pub fn associated_token_address(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    get_associated_token_address_with_program_id(owner, mint, token_program)
+}
@@ -0,0 +1,193 @@
+use std::str::FromStr;
+
+use anchor_lang::prelude::Pubkey;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RepaymentSource {
+    // Burned from harvested yield by `cron_repayment`
+    Yield,
+    // Paid off by the user in `withdraw_collateral`
+    Withdrawal,
+    // Burned by the owner or a delegate in `repay_debt`
+    Direct,
+}
+
+impl RepaymentSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Yield => "yield",
+            Self::Withdrawal => "withdrawal",
+            Self::Direct => "direct",
+        }
+    }
+}
+
+/// A state change decoded from one cred-x instruction's logs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CredXEvent {
+    Deposit {
+        user: Pubkey,
+        amount: u64,
+    },
+    Borrow {
+        user: Pubkey,
+        amount: u64,
+        fee: u64,
+        total_debt: u64,
+    },
+    Repayment {
+        user: Pubkey,
+        amount: u64,
+        remaining_debt: u64,
+        source: RepaymentSource,
+    },
+    Withdrawal {
+        user: Pubkey,
+        collateral_returned: u64,
+        yield_earned: u64,
+        total_yield: u64,
+    },
+    OracleUpdate {
+        oracle: Pubkey,
+        price: u64,
+        conf: u64,
+    },
+}
+
+/// Events of one cred-x invocation, numbered in the order the invocations
+/// appear in the transaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InstructionEvents {
+    pub index: u32,
+    pub events: Vec<CredXEvent>,
+}
+
+/// Splits a transaction's logs by cred-x invocation, including CPIs into
+/// cred-x, and decodes each invocation's `Program log:` lines.
+pub fn decode_logs(logs: &[String]) -> Vec<InstructionEvents> {
+    let program_id = cred_x::ID.to_string();
+    // Program of each open invocation, with its index when it is cred-x
+    let mut stack: Vec<Option<usize>> = vec![];
+    let mut invocations: Vec<Vec<&str>> = vec![];
+
+    for line in logs {
+        if let Some(rest) = line.strip_prefix("Program ") {
+            if let Some(message) = rest.strip_prefix("log: ") {
+                if let Some(Some(index)) = stack.last() {
+                    invocations[*index].push(message);
+                }
+            } else if let Some((program, depth)) = rest.split_once(" invoke [") {
+                if program == program_id && depth.ends_with(']') {
+                    invocations.push(vec![]);
+                    stack.push(Some(invocations.len() - 1));
+                } else {
+                    stack.push(None);
+                }
+            } else if rest.ends_with(" success") || rest.contains(" failed: ") {
+                stack.pop();
+            }
+        }
+    }
+
+    invocations
+        .iter()
+        .enumerate()
+        .map(|(index, messages)| InstructionEvents {
+            index: index as u32,
+            events: decode_messages(messages),
+        })
+        .filter(|instruction| !instruction.events.is_empty())
+        .collect()
+}
+
+fn decode_messages(messages: &[&str]) -> Vec<CredXEvent> {
+    let mut events = vec![];
+    // `withdraw_collateral` logs the debt it burns before naming the user
+    let mut repaid_on_withdrawal = None;
+
+    for message in messages {
+        if let Some(event) = decode_message(message) {
+            events.push(event);
+        } else if let Some(amount) = message
+            .strip_prefix("Repaid full debt: ")
+            .and_then(|amount| amount.parse().ok())
+        {
+            repaid_on_withdrawal = Some(amount);
+        } else if let Some(withdrawal) = decode_withdrawal(message) {
+            if let (Some(amount), CredXEvent::Withdrawal { user, .. }) =
+                (repaid_on_withdrawal.take(), &withdrawal)
+            {
+                if amount > 0 {
+                    events.push(CredXEvent::Repayment {
+                        user: *user,
+                        amount,
+                        remaining_debt: 0,
+                        source: RepaymentSource::Withdrawal,
+                    });
+                }
+            }
+            events.push(withdrawal);
+        }
+    }
+
+    events
+}
+
+fn decode_message(message: &str) -> Option<CredXEvent> {
+    if let Some(rest) = message.strip_prefix("Deposited ") {
+        let (amount, user) = rest.split_once(" collateral tokens for user: ")?;
+        return Some(CredXEvent::Deposit {
+            user: Pubkey::from_str(user).ok()?,
+            amount: amount.parse().ok()?,
+        });
+    }
+    if let Some(rest) = message.strip_prefix("Admin minted ") {
+        let (amount, rest) = rest.split_once(" credit tokens to user: ")?;
+        let (user, rest) = rest.split_once(", Fee: ")?;
+        let (fee, total_debt) = rest.split_once(", Total debt: ")?;
+        return Some(CredXEvent::Borrow {
+            user: Pubkey::from_str(user).ok()?,
+            amount: amount.parse().ok()?,
+            fee: fee.parse().ok()?,
+            total_debt: total_debt.parse().ok()?,
+        });
+    }
+    if let Some(rest) = message.strip_prefix("Repaid ") {
+        let (amount, rest) = rest.split_once(" credit tokens for user: ")?;
+        let (user, rest) = rest.split_once(", remaining debt: ")?;
+        // Only `repay_debt` names who paid
+        let (remaining_debt, source) = match rest.split_once(", paid by: ") {
+            Some((remaining_debt, _)) => (remaining_debt, RepaymentSource::Direct),
+            None => (rest, RepaymentSource::Yield),
+        };
+        return Some(CredXEvent::Repayment {
+            user: Pubkey::from_str(user).ok()?,
+            amount: amount.parse().ok()?,
+            remaining_debt: remaining_debt.parse().ok()?,
+            source,
+        });
+    }
+    if let Some(rest) = message.strip_prefix("Oracle ") {
+        let (oracle, rest) = rest.split_once(" price set to ")?;
+        let (price, conf) = rest.split_once(", conf: ")?;
+        return Some(CredXEvent::OracleUpdate {
+            oracle: Pubkey::from_str(oracle).ok()?,
+            price: price.parse().ok()?,
+            conf: conf.parse().ok()?,
+        });
+    }
+    None
+}
+
+fn decode_withdrawal(message: &str) -> Option<CredXEvent> {
+    let rest = message.strip_prefix("Loan fully closed for user ")?;
+    let (user, rest) = rest.split_once(". Collateral returned: ")?;
+    let (collateral_returned, rest) = rest.split_once(", Yield earned this withdrawal: ")?;
+    let (yield_earned, total_yield) = rest.split_once(", Total yield: ")?;
+    Some(CredXEvent::Withdrawal {
+        user: Pubkey::from_str(user).ok()?,
+        collateral_returned: collateral_returned.parse().ok()?,
+        yield_earned: yield_earned.parse().ok()?,
+        total_yield: total_yield.parse().ok()?,
+    })
+}
@@ -0,0 +1,10 @@
+//! Follows cred-x transactions over RPC, decodes the program's logs into
+//! events and stores them in SQLite for per-user loan statements.
+
+pub mod events;
+pub mod source;
+pub mod store;
+
+pub use events::*;
+pub use source::*;
+pub use store::*;
@@ -0,0 +1,122 @@
+use std::{path::PathBuf, thread, time::Duration};
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use cred_x_indexer::{CredXEvent, RpcSource, Store};
+use log::{error, info};
+
+/// Indexes cred-x deposits, borrows, repayments, withdrawals and oracle
+/// updates into SQLite.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// SQLite database, created if missing
+    #[arg(long, global = true, default_value = "cred-x.sqlite")]
+    db: PathBuf,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Follow the program and index new transactions
+    Run {
+        #[arg(long, default_value = "http://127.0.0.1:8899")]
+        rpc_url: String,
+
+        /// Seconds between polls
+        #[arg(long, default_value_t = 10)]
+        poll_secs: u64,
+
+        /// Index what is available, then exit
+        #[arg(long)]
+        once: bool,
+    },
+    /// Print a user's loan and its history from the database
+    Statement { user: Pubkey },
+}
+
+fn main() -> Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let args = Args::parse();
+    let mut store = Store::open(&args.db)?;
+
+    match args.command {
+        Command::Run {
+            rpc_url,
+            poll_secs,
+            once,
+        } => {
+            let source = RpcSource::new(rpc_url);
+            loop {
+                match index_new(&source, &mut store) {
+                    Ok(indexed) if indexed > 0 => info!("indexed {indexed} transactions"),
+                    Ok(_) => {}
+                    Err(err) if !once => error!("poll failed: {err:#}"),
+                    Err(err) => return Err(err),
+                }
+                if once {
+                    return Ok(());
+                }
+                thread::sleep(Duration::from_secs(poll_secs));
+            }
+        }
+        Command::Statement { user } => print_statement(&store, &user),
+    }
+}
+
+fn index_new(source: &RpcSource, store: &mut Store) -> Result<usize> {
+    let cursor = store.latest_signature()?;
+    let mut indexed = 0;
+    for tx in source.transactions_after(cursor.as_deref())? {
+        if store.record(&tx)? {
+            indexed += 1;
+        }
+    }
+    Ok(indexed)
+}
+
+fn print_statement(store: &Store, user: &Pubkey) -> Result<()> {
+    match store.loan(user)? {
+        Some(loan) => println!(
+            "loan of {user}: {}, remaining debt {}",
+            if loan.open { "open" } else { "closed" },
+            loan.remaining_debt
+        ),
+        None => {
+            println!("no indexed loan for {user}");
+            return Ok(());
+        }
+    }
+
+    for line in store.statement(user)? {
+        let description = match line.event {
+            CredXEvent::Deposit { amount, .. } => format!("deposit {amount} collateral"),
+            CredXEvent::Borrow {
+                amount,
+                fee,
+                total_debt,
+                ..
+            } => format!("borrow {amount} credit, fee {fee}, debt {total_debt}"),
+            CredXEvent::Repayment {
+                amount,
+                remaining_debt,
+                source,
+                ..
+            } => format!(
+                "repay {amount} credit from {}, debt {remaining_debt}",
+                source.as_str()
+            ),
+            CredXEvent::Withdrawal {
+                collateral_returned,
+                yield_earned,
+                ..
+            } => format!("withdraw {collateral_returned} collateral, yield {yield_earned}"),
+            CredXEvent::OracleUpdate { .. } => continue,
+        };
+        println!("{:>12}  {description}  {}", line.slot, line.signature);
+    }
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use log::debug;
+use solana_rpc_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use solana_rpc_client_api::config::RpcTransactionConfig;
+use solana_sdk::{commitment_config::CommitmentConfig, signature::Signature};
+use solana_transaction_status_client_types::UiTransactionEncoding;
+
+use crate::IndexedTransaction;
+
+// Most signatures `getSignaturesForAddress` returns per call
+const PAGE_SIZE: usize = 1_000;
+
+/// Reads cred-x transactions from an RPC node.
+pub struct RpcSource {
+    client: RpcClient,
+}
+
+impl RpcSource {
+    pub fn new(rpc_url: String) -> Self {
+        Self {
+            client: RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed()),
+        }
+    }
+
+    /// Transactions mentioning the program after `after`, oldest first. With
+    /// no `after` the whole history the node retains is returned.
+    pub fn transactions_after(&self, after: Option<&str>) -> Result<Vec<IndexedTransaction>> {
+        let until = after
+            .map(Signature::from_str)
+            .transpose()
+            .context("invalid cursor signature")?;
+
+        // Signatures come newest first, so page backwards down to `until`
+        let mut statuses = vec![];
+        let mut before = None;
+        loop {
+            let page = self.client.get_signatures_for_address_with_config(
+                &cred_x::ID,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until,
+                    limit: Some(PAGE_SIZE),
+                    commitment: Some(self.client.commitment()),
+                },
+            )?;
+            let Some(last) = page.last() else {
+                break;
+            };
+            before = Some(Signature::from_str(&last.signature)?);
+            let full = page.len() == PAGE_SIZE;
+            statuses.extend(page);
+            if !full {
+                break;
+            }
+        }
+        debug!("{} new signatures", statuses.len());
+
+        statuses
+            .into_iter()
+            .rev()
+            .map(|status| {
+                if status.err.is_some() {
+                    return Ok(IndexedTransaction {
+                        signature: status.signature,
+                        slot: status.slot,
+                        block_time: status.block_time,
+                        failed: true,
+                        logs: vec![],
+                    });
+                }
+                self.transaction(&status.signature)
+            })
+            .collect()
+    }
+
+    fn transaction(&self, signature: &str) -> Result<IndexedTransaction> {
+        let tx = self.client.get_transaction_with_config(
+            &Signature::from_str(signature)?,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(self.client.commitment()),
+                max_supported_transaction_version: Some(0),
+            },
+        )?;
+        let meta = tx
+            .transaction
+            .meta
+            .with_context(|| format!("transaction {signature} has no status meta"))?;
+
+        Ok(IndexedTransaction {
+            signature: signature.to_string(),
+            slot: tx.slot,
+            block_time: tx.block_time,
+            failed: meta.err.is_some(),
+            logs: Option::from(meta.log_messages).unwrap_or_default(),
+        })
+    }
+}
@@ -0,0 +1,387 @@
+use std::{path::Path, str::FromStr};
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::{anyhow, Result};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+
+use crate::{decode_logs, CredXEvent, RepaymentSource};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS transactions (
+    signature TEXT PRIMARY KEY,
+    slot INTEGER NOT NULL,
+    block_time INTEGER,
+    failed INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS loans (
+    user TEXT PRIMARY KEY,
+    status TEXT NOT NULL CHECK (status IN ('open', 'closed')),
+    remaining_debt INTEGER NOT NULL,
+    opened_signature TEXT NOT NULL REFERENCES transactions (signature),
+    last_signature TEXT NOT NULL REFERENCES transactions (signature)
+);
+CREATE TABLE IF NOT EXISTS deposits (
+    signature TEXT NOT NULL REFERENCES transactions (signature),
+    ix INTEGER NOT NULL,
+    user TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    PRIMARY KEY (signature, ix)
+);
+CREATE TABLE IF NOT EXISTS borrows (
+    signature TEXT NOT NULL REFERENCES transactions (signature),
+    ix INTEGER NOT NULL,
+    user TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    fee INTEGER NOT NULL,
+    total_debt INTEGER NOT NULL,
+    PRIMARY KEY (signature, ix)
+);
+CREATE TABLE IF NOT EXISTS repayments (
+    signature TEXT NOT NULL REFERENCES transactions (signature),
+    ix INTEGER NOT NULL,
+    user TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    remaining_debt INTEGER NOT NULL,
+    source TEXT NOT NULL CHECK (source IN ('yield', 'withdrawal', 'direct')),
+    PRIMARY KEY (signature, ix)
+);
+CREATE TABLE IF NOT EXISTS withdrawals (
+    signature TEXT NOT NULL REFERENCES transactions (signature),
+    ix INTEGER NOT NULL,
+    user TEXT NOT NULL,
+    collateral_returned INTEGER NOT NULL,
+    yield_earned INTEGER NOT NULL,
+    total_yield INTEGER NOT NULL,
+    PRIMARY KEY (signature, ix)
+);
+CREATE TABLE IF NOT EXISTS oracle_updates (
+    signature TEXT NOT NULL REFERENCES transactions (signature),
+    ix INTEGER NOT NULL,
+    oracle TEXT NOT NULL,
+    price INTEGER NOT NULL,
+    conf INTEGER NOT NULL,
+    PRIMARY KEY (signature, ix)
+);
+CREATE INDEX IF NOT EXISTS deposits_user ON deposits (user);
+CREATE INDEX IF NOT EXISTS borrows_user ON borrows (user);
+CREATE INDEX IF NOT EXISTS repayments_user ON repayments (user);
+CREATE INDEX IF NOT EXISTS withdrawals_user ON withdrawals (user);
+CREATE INDEX IF NOT EXISTS oracle_updates_oracle ON oracle_updates (oracle);
+";
+
+// Every event of a user's loan, in chain order
+const STATEMENT: &str = "
+SELECT t.rowid, e.ix, 0, t.signature, t.slot, t.block_time, 'deposit', e.amount, 0, 0, NULL
+    FROM deposits e JOIN transactions t USING (signature) WHERE e.user = ?1
+UNION ALL
+SELECT t.rowid, e.ix, 1, t.signature, t.slot, t.block_time, 'borrow', e.amount, e.fee, e.total_debt, NULL
+    FROM borrows e JOIN transactions t USING (signature) WHERE e.user = ?1
+UNION ALL
+SELECT t.rowid, e.ix, 2, t.signature, t.slot, t.block_time, 'repayment', e.amount, e.remaining_debt, 0, e.source
+    FROM repayments e JOIN transactions t USING (signature) WHERE e.user = ?1
+UNION ALL
+SELECT t.rowid, e.ix, 3, t.signature, t.slot, t.block_time, 'withdrawal', e.collateral_returned, e.yield_earned, e.total_yield, NULL
+    FROM withdrawals e JOIN transactions t USING (signature) WHERE e.user = ?1
+ORDER BY 1, 2, 3
+";
+
+/// A cred-x transaction as fetched from the cluster.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexedTransaction {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub failed: bool,
+    pub logs: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LoanSummary {
+    pub user: Pubkey,
+    pub open: bool,
+    pub remaining_debt: u64,
+    // Transaction of the deposit that opened the current loan
+    pub opened_signature: String,
+    pub last_signature: String,
+}
+
+/// One event of a user's loan statement.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatementLine {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub event: CredXEvent,
+}
+
+pub struct Store {
+    conn: Connection,
+}
+
+impl Store {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self { conn })
+    }
+
+    /// Newest indexed transaction, where the next poll resumes from.
+    pub fn latest_signature(&self) -> Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT signature FROM transactions ORDER BY slot DESC, rowid DESC LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Stores a transaction and its events atomically. Returns false when it
+    /// was already indexed.
+    pub fn record(&mut self, tx: &IndexedTransaction) -> Result<bool> {
+        let db = self.conn.transaction()?;
+        let inserted = db.execute(
+            "INSERT OR IGNORE INTO transactions (signature, slot, block_time, failed)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![tx.signature, tx.slot, tx.block_time, tx.failed],
+        )?;
+        if inserted == 0 {
+            return Ok(false);
+        }
+
+        if !tx.failed {
+            for instruction in decode_logs(&tx.logs) {
+                for event in &instruction.events {
+                    insert_event(&db, &tx.signature, instruction.index, event)?;
+                }
+            }
+        }
+
+        db.commit()?;
+        Ok(true)
+    }
+
+    pub fn loan(&self, user: &Pubkey) -> Result<Option<LoanSummary>> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT status, remaining_debt, opened_signature, last_signature
+                 FROM loans WHERE user = ?1",
+                [user.to_string()],
+                |row| {
+                    Ok(LoanSummary {
+                        user: *user,
+                        open: row.get::<_, String>(0)? == "open",
+                        remaining_debt: row.get(1)?,
+                        opened_signature: row.get(2)?,
+                        last_signature: row.get(3)?,
+                    })
+                },
+            )
+            .optional()?)
+    }
+
+    /// Deposits, borrows, repayments and withdrawals of `user`, oldest first.
+    pub fn statement(&self, user: &Pubkey) -> Result<Vec<StatementLine>> {
+        let mut query = self.conn.prepare(STATEMENT)?;
+        let rows = query.query_map([user.to_string()], |row| {
+            Ok((
+                row.get::<_, String>(3)?,
+                row.get::<_, u64>(4)?,
+                row.get::<_, Option<i64>>(5)?,
+                row.get::<_, String>(6)?,
+                row.get::<_, u64>(7)?,
+                row.get::<_, u64>(8)?,
+                row.get::<_, u64>(9)?,
+                row.get::<_, Option<String>>(10)?,
+            ))
+        })?;
+
+        let mut lines = vec![];
+        for row in rows {
+            let (signature, slot, block_time, kind, a, b, c, source) = row?;
+            let user = *user;
+            let event = match kind.as_str() {
+                "deposit" => CredXEvent::Deposit { user, amount: a },
+                "borrow" => CredXEvent::Borrow {
+                    user,
+                    amount: a,
+                    fee: b,
+                    total_debt: c,
+                },
+                "repayment" => CredXEvent::Repayment {
+                    user,
+                    amount: a,
+                    remaining_debt: b,
+                    source: match source.as_deref() {
+                        Some("withdrawal") => RepaymentSource::Withdrawal,
+                        Some("direct") => RepaymentSource::Direct,
+                        _ => RepaymentSource::Yield,
+                    },
+                },
+                _ => CredXEvent::Withdrawal {
+                    user,
+                    collateral_returned: a,
+                    yield_earned: b,
+                    total_yield: c,
+                },
+            };
+            lines.push(StatementLine {
+                signature,
+                slot,
+                block_time,
+                event,
+            });
+        }
+        Ok(lines)
+    }
+
+    /// Price updates published to `oracle`, oldest first, as
+    /// `(signature, slot, price, conf)`.
+    pub fn oracle_updates(&self, oracle: &Pubkey) -> Result<Vec<(String, u64, u64, u64)>> {
+        let mut query = self.conn.prepare(
+            "SELECT t.signature, t.slot, e.price, e.conf
+             FROM oracle_updates e JOIN transactions t USING (signature)
+             WHERE e.oracle = ?1 ORDER BY t.rowid, e.ix",
+        )?;
+        let rows = query.query_map([oracle.to_string()], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Users with an open loan according to the indexed events.
+    pub fn open_loans(&self) -> Result<Vec<LoanSummary>> {
+        let mut query = self
+            .conn
+            .prepare("SELECT user FROM loans WHERE status = 'open' ORDER BY user")?;
+        let users = query
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let mut loans = vec![];
+        for user in users {
+            let user =
+                Pubkey::from_str(&user).map_err(|err| anyhow!("invalid user {user}: {err}"))?;
+            loans.extend(self.loan(&user)?);
+        }
+        Ok(loans)
+    }
+}
+
+fn insert_event(db: &Transaction, signature: &str, ix: u32, event: &CredXEvent) -> Result<()> {
+    match event {
+        CredXEvent::Deposit { user, amount } => {
+            db.execute(
+                "INSERT INTO deposits (signature, ix, user, amount) VALUES (?1, ?2, ?3, ?4)",
+                params![signature, ix, user.to_string(), amount],
+            )?;
+            // A deposit after a withdrawal opens a new loan
+            db.execute(
+                "INSERT INTO loans (user, status, remaining_debt, opened_signature, last_signature)
+                 VALUES (?1, 'open', 0, ?2, ?2)
+                 ON CONFLICT (user) DO UPDATE SET
+                     opened_signature = CASE WHEN status = 'closed'
+                         THEN excluded.opened_signature ELSE opened_signature END,
+                     status = 'open',
+                     last_signature = excluded.last_signature",
+                params![user.to_string(), signature],
+            )?;
+        }
+        CredXEvent::Borrow {
+            user,
+            amount,
+            fee,
+            total_debt,
+        } => {
+            db.execute(
+                "INSERT INTO borrows (signature, ix, user, amount, fee, total_debt)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![signature, ix, user.to_string(), amount, fee, total_debt],
+            )?;
+            update_loan(db, user, signature, *total_debt, "open")?;
+        }
+        CredXEvent::Repayment {
+            user,
+            amount,
+            remaining_debt,
+            source,
+        } => {
+            db.execute(
+                "INSERT INTO repayments (signature, ix, user, amount, remaining_debt, source)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    signature,
+                    ix,
+                    user.to_string(),
+                    amount,
+                    remaining_debt,
+                    source.as_str()
+                ],
+            )?;
+            update_loan(db, user, signature, *remaining_debt, "open")?;
+        }
+        CredXEvent::Withdrawal {
+            user,
+            collateral_returned,
+            yield_earned,
+            total_yield,
+        } => {
+            db.execute(
+                "INSERT INTO withdrawals
+                     (signature, ix, user, collateral_returned, yield_earned, total_yield)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    signature,
+                    ix,
+                    user.to_string(),
+                    collateral_returned,
+                    yield_earned,
+                    total_yield
+                ],
+            )?;
+            update_loan(db, user, signature, 0, "closed")?;
+        }
+        CredXEvent::OracleUpdate {
+            oracle,
+            price,
+            conf,
+        } => {
+            db.execute(
+                "INSERT INTO oracle_updates (signature, ix, oracle, price, conf)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![signature, ix, oracle.to_string(), price, conf],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Sets a loan's debt and status. Loans first seen mid-life, e.g. when
+/// indexing starts after their deposit, are opened by this event.
+fn update_loan(
+    db: &Transaction,
+    user: &Pubkey,
+    signature: &str,
+    remaining_debt: u64,
+    status: &str,
+) -> Result<()> {
+    db.execute(
+        "INSERT INTO loans (user, status, remaining_debt, opened_signature, last_signature)
+         VALUES (?1, ?2, ?3, ?4, ?4)
+         ON CONFLICT (user) DO UPDATE SET
+             status = excluded.status,
+             remaining_debt = excluded.remaining_debt,
+             last_signature = excluded.last_signature",
+        params![user.to_string(), status, remaining_debt, signature],
+    )?;
+    Ok(())
+}
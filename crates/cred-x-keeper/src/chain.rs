@@ -0,0 +1,120 @@
+use anchor_lang::{prelude::Pubkey, solana_program::instruction::Instruction};
+use anyhow::{anyhow, Result};
+use solana_account_decoder_client_types::UiAccountEncoding;
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_rpc_client_api::{
+    config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+
+/// Owner and data of an on-chain account.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChainAccount {
+    pub owner: Pubkey,
+    pub data: Vec<u8>,
+}
+
+/// What the keeper needs from a cluster. Implemented over RPC here, and over
+/// an in-process bank in the integration tests.
+pub trait Chain {
+    /// cred-x accounts whose data starts with `discriminator`.
+    fn program_accounts(&self, discriminator: &[u8]) -> Result<Vec<(Pubkey, ChainAccount)>>;
+
+    fn account(&self, address: &Pubkey) -> Result<Option<ChainAccount>>;
+
+    /// Simulates `instruction` and returns its program logs.
+    fn simulate(&self, instruction: &Instruction) -> Result<Vec<String>>;
+
+    /// Sends and confirms `instruction`, returning the transaction signature.
+    fn send(&self, instruction: &Instruction) -> Result<String>;
+}
+
+pub struct RpcChain {
+    client: RpcClient,
+    payer: Keypair,
+}
+
+impl RpcChain {
+    pub fn new(rpc_url: String, payer: Keypair) -> Self {
+        Self {
+            client: RpcClient::new_with_commitment(rpc_url, CommitmentConfig::confirmed()),
+            payer,
+        }
+    }
+
+    fn transaction(&self, instruction: &Instruction) -> Result<Transaction> {
+        let blockhash = self.client.get_latest_blockhash()?;
+        Ok(Transaction::new_signed_with_payer(
+            std::slice::from_ref(instruction),
+            Some(&self.payer.pubkey()),
+            &[&self.payer],
+            blockhash,
+        ))
+    }
+}
+
+impl Chain for RpcChain {
+    fn program_accounts(&self, discriminator: &[u8]) -> Result<Vec<(Pubkey, ChainAccount)>> {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+                0,
+                discriminator,
+            ))]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+        let accounts = self
+            .client
+            .get_program_accounts_with_config(&cred_x::ID, config)?;
+        Ok(accounts
+            .into_iter()
+            .map(|(address, account)| {
+                (
+                    address,
+                    ChainAccount {
+                        owner: account.owner,
+                        data: account.data,
+                    },
+                )
+            })
+            .collect())
+    }
+
+    fn account(&self, address: &Pubkey) -> Result<Option<ChainAccount>> {
+        let account = self
+            .client
+            .get_account_with_commitment(address, self.client.commitment())?
+            .value;
+        Ok(account.map(|account| ChainAccount {
+            owner: account.owner,
+            data: account.data,
+        }))
+    }
+
+    fn simulate(&self, instruction: &Instruction) -> Result<Vec<String>> {
+        let result = self
+            .client
+            .simulate_transaction(&self.transaction(instruction)?)?
+            .value;
+        let logs = result.logs.unwrap_or_default();
+        match result.err {
+            Some(err) => Err(anyhow!("simulation failed: {err} ({})", logs.join("; "))),
+            None => Ok(logs),
+        }
+    }
+
+    fn send(&self, instruction: &Instruction) -> Result<String> {
+        let signature = self
+            .client
+            .send_and_confirm_transaction(&self.transaction(instruction)?)?;
+        Ok(signature.to_string())
+    }
+}
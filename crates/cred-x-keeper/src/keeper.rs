@@ -0,0 +1,263 @@
+use std::{cell::RefCell, collections::HashMap, thread, time::Duration};
+
+use anchor_lang::{prelude::Pubkey, Discriminator};
+use anchor_spl::token_2022::spl_token_2022::{
+    extension::StateWithExtensions, state::Account as TokenAccount,
+};
+use anyhow::{anyhow, Context, Result};
+use cred_x::{CollateralConfig, LoanAccount, PriceQuote};
+use cred_x_client::{
+    collateral_config_pda, collateral_price, collateral_vault_pda, cron_repayment,
+    decode_collateral_config, decode_collateral_vault, decode_loan_account, decode_protocol_state,
+    decode_simple_price_oracle, protocol_pda, tripped_circuit_breaker, yield_repayment,
+    CollateralContext, ProtocolContext,
+};
+use log::{debug, info, warn};
+
+use crate::{Chain, ChainAccount};
+
+#[derive(Clone, Debug)]
+pub struct KeeperConfig {
+    // Simulate and log instead of sending transactions
+    pub dry_run: bool,
+    pub max_retries: u32,
+    // Delay before the first retry, doubled on each further attempt
+    pub retry_delay: Duration,
+}
+
+/// A loan that `cron_repayment` would currently repay.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RepaymentJob {
+    pub loan: Pubkey,
+    pub user: Pubkey,
+    pub collateral: CollateralContext,
+    pub yield_amount: u64,
+    pub remaining_debt: u64,
+    // Only known when the primary oracle is a `SimplePriceOracle`
+    pub estimated_repayment: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RunSummary {
+    pub loans_scanned: usize,
+    pub jobs: usize,
+    pub repaid: usize,
+    pub failed: usize,
+}
+
+pub struct Keeper<C> {
+    chain: C,
+    protocol: ProtocolContext,
+    config: KeeperConfig,
+    collaterals: RefCell<HashMap<Pubkey, Option<(CollateralConfig, CollateralContext)>>>,
+}
+
+impl<C: Chain> Keeper<C> {
+    /// Loads the protocol created by `admin` and the credit mint's token program.
+    pub fn new(chain: C, admin: Pubkey, config: KeeperConfig) -> Result<Self> {
+        let protocol_key = protocol_pda(&admin).0;
+        let protocol = chain
+            .account(&protocol_key)?
+            .ok_or_else(|| anyhow!("protocol {protocol_key} not found"))?;
+        let protocol = decode_protocol_state(&protocol.data)?;
+        let credit_mint = chain
+            .account(&protocol.credit_mint)?
+            .ok_or_else(|| anyhow!("credit mint {} not found", protocol.credit_mint))?;
+
+        Ok(Self {
+            protocol: ProtocolContext::from_state(&protocol, credit_mint.owner),
+            chain,
+            config,
+            collaterals: RefCell::default(),
+        })
+    }
+
+    pub fn chain(&self) -> &C {
+        &self.chain
+    }
+
+    /// Finds every loan with outstanding debt and yield sitting in its vault.
+    /// Loans whose collateral is not configured for this protocol are skipped.
+    pub fn scan(&self) -> Result<(usize, Vec<RepaymentJob>)> {
+        let loans = self.chain.program_accounts(LoanAccount::DISCRIMINATOR)?;
+        let mut jobs = vec![];
+
+        for (address, account) in &loans {
+            let loan = match decode_loan_account(&account.data) {
+                Ok(loan) => loan,
+                Err(err) => {
+                    warn!("skipping undecodable loan {address}: {err}");
+                    continue;
+                }
+            };
+            match self.job_for(*address, &loan) {
+                Ok(Some(job)) => jobs.push(job),
+                Ok(None) => {}
+                Err(err) => warn!("skipping loan {address}: {err:#}"),
+            }
+        }
+
+        Ok((loans.len(), jobs))
+    }
+
+    fn job_for(&self, address: Pubkey, loan: &LoanAccount) -> Result<Option<RepaymentJob>> {
+        if loan.remaining_debt == 0 {
+            debug!("loan {address} has no outstanding debt");
+            return Ok(None);
+        }
+
+        let vault_key = collateral_vault_pda(&loan.user).0;
+        let Some(vault) = self.chain.account(&vault_key)? else {
+            debug!("loan {address} has no collateral vault");
+            return Ok(None);
+        };
+        let vault = decode_collateral_vault(&vault.data)?;
+        let Some((config, collateral)) = self.collateral(&vault.mint)? else {
+            debug!(
+                "collateral {} of loan {address} is not configured",
+                vault.mint
+            );
+            return Ok(None);
+        };
+
+        let vault_balance = self
+            .token_balance(&collateral.vault_ata(&loan.user))?
+            .context("collateral vault token account missing")?;
+        if vault_balance <= loan.collateral_amount {
+            debug!("loan {address} has no yield to harvest");
+            return Ok(None);
+        }
+        let credit_balance = self
+            .token_balance(&self.protocol.credit_ata(&loan.user))?
+            .unwrap_or_default();
+        if credit_balance == 0 {
+            info!("loan {address} has yield but its owner holds no credit tokens to burn");
+            return Ok(None);
+        }
+
+        let estimated_repayment = match self.simple_oracle_price(&config)? {
+            Some(price) => Some(yield_repayment(loan, vault_balance, price)?.repayment),
+            None => None,
+        };
+
+        Ok(Some(RepaymentJob {
+            loan: address,
+            user: loan.user,
+            collateral,
+            yield_amount: vault_balance - loan.collateral_amount,
+            remaining_debt: loan.remaining_debt,
+            estimated_repayment,
+        }))
+    }
+
+    fn collateral(&self, mint: &Pubkey) -> Result<Option<(CollateralConfig, CollateralContext)>> {
+        if let Some(cached) = self.collaterals.borrow().get(mint) {
+            return Ok(cached.clone());
+        }
+
+        let config_key = collateral_config_pda(&self.protocol.protocol(), mint).0;
+        let entry = match self.chain.account(&config_key)? {
+            Some(account) => {
+                let config = decode_collateral_config(&account.data)?;
+                let mint_account = self
+                    .chain
+                    .account(mint)?
+                    .ok_or_else(|| anyhow!("collateral mint {mint} not found"))?;
+                let context = CollateralContext::from_config(&config, mint_account.owner);
+                Some((config, context))
+            }
+            None => None,
+        };
+        self.collaterals.borrow_mut().insert(*mint, entry.clone());
+        Ok(entry)
+    }
+
+    fn token_balance(&self, address: &Pubkey) -> Result<Option<u64>> {
+        self.chain
+            .account(address)?
+            .map(|ChainAccount { data, .. }| {
+                let account = StateWithExtensions::<TokenAccount>::unpack(&data)
+                    .map_err(|err| anyhow!("invalid token account {address}: {err}"))?;
+                Ok(account.base.amount)
+            })
+            .transpose()
+    }
+
+    fn simple_oracle_price(&self, config: &CollateralConfig) -> Result<Option<u64>> {
+        let Some(account) = self.chain.account(&config.oracle.primary)? else {
+            return Ok(None);
+        };
+        if account.owner != cred_x::ID {
+            return Ok(None);
+        }
+        let oracle = decode_simple_price_oracle(&account.data)?;
+        Ok(Some(collateral_price(
+            config,
+            &PriceQuote::from_simple_oracle(&oracle),
+        )?))
+    }
+
+    /// Scans once and repays every job found, or only simulates in dry-run mode.
+    pub fn run_once(&self) -> Result<RunSummary> {
+        let (loans_scanned, jobs) = self.scan()?;
+        let mut summary = RunSummary {
+            loans_scanned,
+            jobs: jobs.len(),
+            ..RunSummary::default()
+        };
+        info!("scanned {loans_scanned} loans, {} to repay", jobs.len());
+
+        for job in &jobs {
+            info!(
+                "loan {}: yield {}, debt {}, estimated repayment {:?}",
+                job.loan, job.yield_amount, job.remaining_debt, job.estimated_repayment
+            );
+            match self.repay(job) {
+                Ok(()) => summary.repaid += 1,
+                Err(err) => {
+                    warn!("repayment of loan {} failed: {err:#}", job.loan);
+                    summary.failed += 1;
+                }
+            }
+        }
+
+        Ok(summary)
+    }
+
+    fn repay(&self, job: &RepaymentJob) -> Result<()> {
+        let instruction = cron_repayment(&self.protocol, job.user, &job.collateral);
+
+        if self.config.dry_run {
+            let logs = self.chain.simulate(&instruction)?;
+            for line in &logs {
+                debug!("  {line}");
+            }
+            if let Some(mint) = tripped_circuit_breaker(&logs) {
+                warn!("circuit breaker tripped for collateral {mint}");
+            }
+            info!("dry run: cron_repayment for loan {} simulated", job.loan);
+            return Ok(());
+        }
+
+        let mut delay = self.config.retry_delay;
+        let mut attempt = 0;
+        loop {
+            match self.chain.send(&instruction) {
+                Ok(signature) => {
+                    info!("repaid loan {} in {signature}", job.loan);
+                    return Ok(());
+                }
+                Err(err) if attempt < self.config.max_retries => {
+                    attempt += 1;
+                    warn!(
+                        "cron_repayment for loan {} failed (attempt {attempt}): {err:#}, retrying in {delay:?}",
+                        job.loan
+                    );
+                    thread::sleep(delay);
+                    delay *= 2;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
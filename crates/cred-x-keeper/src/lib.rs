@@ -0,0 +1,8 @@
+//! Keeper that finds cred-x loans with harvestable yield and outstanding debt
+//! and cranks `cron_repayment` for them.
+
+pub mod chain;
+pub mod keeper;
+
+pub use chain::*;
+pub use keeper::*;
@@ -0,0 +1,80 @@
+use std::{path::PathBuf, thread, time::Duration};
+
+use anchor_lang::prelude::Pubkey;
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use cred_x_keeper::{Keeper, KeeperConfig, RpcChain};
+use log::{error, info};
+use solana_sdk::signature::read_keypair_file;
+
+/// Periodically repays cred-x loans from their staking yield.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    #[arg(long, default_value = "http://127.0.0.1:8899")]
+    rpc_url: String,
+
+    /// Keypair paying for the cron_repayment transactions
+    #[arg(long)]
+    keypair: PathBuf,
+
+    /// Admin of the protocol to crank
+    #[arg(long)]
+    admin: Pubkey,
+
+    /// Seconds between scans
+    #[arg(long, default_value_t = 86_400)]
+    interval_secs: u64,
+
+    /// Scan and repay once, then exit
+    #[arg(long)]
+    once: bool,
+
+    /// Simulate repayments without sending them
+    #[arg(long)]
+    dry_run: bool,
+
+    #[arg(long, default_value_t = 3)]
+    max_retries: u32,
+
+    /// Seconds before the first retry, doubled on each further attempt
+    #[arg(long, default_value_t = 2)]
+    retry_delay_secs: u64,
+}
+
+fn main() -> Result<()> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let args = Args::parse();
+
+    let payer = read_keypair_file(&args.keypair)
+        .map_err(|err| anyhow!("reading {}: {err}", args.keypair.display()))?;
+    let keeper = Keeper::new(
+        RpcChain::new(args.rpc_url, payer),
+        args.admin,
+        KeeperConfig {
+            dry_run: args.dry_run,
+            max_retries: args.max_retries,
+            retry_delay: Duration::from_secs(args.retry_delay_secs),
+        },
+    )?;
+
+    loop {
+        match keeper.run_once() {
+            Ok(summary) => {
+                info!(
+                    "run finished: {} loans scanned, {} repaid, {} failed",
+                    summary.loans_scanned, summary.repaid, summary.failed
+                );
+                if args.once && summary.failed > 0 {
+                    return Err(anyhow!("{} repayments failed", summary.failed));
+                }
+            }
+            Err(err) if !args.once => error!("run failed: {err:#}"),
+            Err(err) => return Err(err),
+        }
+        if args.once {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_secs(args.interval_secs));
+    }
+}
@@ -0,0 +1,62 @@
+//! Drives a loan through borrow, yield repayment and withdrawal with
+//! arbitrary inputs and checks the invariants of `cred_x::math` at each step.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use cred_x::{borrow_quote, collateral_value, covers_debt, harvestable_yield, yield_repayment};
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Arbitrary, Debug)]
+struct LoanInput {
+    collateral: u64,
+    price: u64,
+    ltv_bps: u16,
+    fee_bps: u16,
+    existing_debt: u64,
+    vault_balance: u64,
+    repayment_price: u64,
+}
+
+fuzz_target!(|input: LoanInput| {
+    let Some(limit) =
+        collateral_value(input.collateral, input.price).checked_mul(u128::from(input.ltv_bps))
+    else {
+        assert!(borrow_quote(input.collateral, input.price, input.ltv_bps, 0, 0).is_err());
+        return;
+    };
+    let limit = limit / 10_000;
+
+    let debt = match borrow_quote(
+        input.collateral,
+        input.price,
+        input.ltv_bps,
+        input.existing_debt,
+        input.fee_bps % 10_001,
+    ) {
+        Ok(quote) => {
+            let debt = u128::from(input.existing_debt) + u128::from(quote.borrow_amount);
+            assert_eq!(debt, limit, "debt must land exactly on the borrow limit");
+            assert_eq!(quote.user_amount + quote.fee_amount, quote.borrow_amount);
+            u64::try_from(debt).expect("debt fits in u64")
+        }
+        Err(_) => input.existing_debt,
+    };
+
+    let Ok(yield_amount) = harvestable_yield(input.vault_balance, input.collateral) else {
+        assert!(input.vault_balance < input.collateral);
+        return;
+    };
+    assert_eq!(yield_amount + input.collateral, input.vault_balance);
+
+    if let Ok(repayment) = yield_repayment(yield_amount, input.repayment_price, debt) {
+        assert!(repayment <= debt, "repayment exceeds debt");
+        let value = collateral_value(yield_amount, input.repayment_price);
+        assert_eq!(u128::from(repayment), value.min(u128::from(debt)));
+    }
+
+    assert_eq!(
+        covers_debt(input.vault_balance, input.price, debt),
+        collateral_value(input.vault_balance, input.price) >= u128::from(debt)
+    );
+});
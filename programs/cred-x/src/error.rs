@@ -12,10 +12,6 @@ pub enum CredXError {
     ProtocolAlreadyInitialized,
     #[msg("Invalid collateral amount")]
     InvalidCollateralAmount,
-    #[msg("Unsupported collateral mint")]
-    UnsupportedCollateralMint,
-    #[msg("Invalid user")]
-    InvalidUser,
     #[msg("Invalid oracle account")]
     InvalidOracleAccount,
     #[msg("Insufficient balance")]
@@ -46,7 +42,7 @@ pub enum CredXError {
     InvalidPriceStatus,
     #[msg("Invalid price of pyth account")]
     InvalidPrice,
-    #[msg("Stale oracle data: price not updated within the last 5 minutes")]
+    #[msg("Stale oracle data: price older than the configured staleness window")]
     StalePrice,
     #[msg("Borrow value should be positive")]
     ZeroBorrowAmount,
@@ -86,4 +82,116 @@ pub enum CredXError {
     NoActiveLoan,
     #[msg("Insufficient collateral value")]
     InsufficientCollateralValue,
+    #[msg("Credit transfer restrictions require a Token-2022 credit mint")]
+    TransferHookRequiresToken2022,
+    #[msg("Credit mint does not use this program as its transfer hook")]
+    TransferHookNotEnabled,
+    #[msg("Transfer hook invoked outside of a token transfer")]
+    NotTransferring,
+    #[msg("Credit balance would fall below outstanding debt")]
+    CreditBelowOutstandingDebt,
+    #[msg("Destination is not on the credit allow-list")]
+    DestinationNotAllowlisted,
+    #[msg("Invalid liquidation threshold: must be above LTV and at most 10000 basis points")]
+    InvalidLiquidationThreshold,
+    #[msg("Invalid credit mint decimals")]
+    InvalidCreditDecimals,
+    #[msg("Invalid staleness window")]
+    InvalidStalenessWindow,
+    #[msg("Invalid fee: must be at most 1000 basis points")]
+    InvalidFee,
+    #[msg("Invalid credit metadata: name, symbol or uri too long")]
+    InvalidCreditMetadata,
+    #[msg("Credit metadata requires a Token-2022 credit mint created by the protocol")]
+    CreditMetadataNotSupported,
+    #[msg("Borrow would exceed the protocol debt ceiling")]
+    DebtCeilingExceeded,
+    #[msg("Deposit would exceed the collateral supply cap")]
+    CollateralSupplyCapExceeded,
+    #[msg("Invalid pricing policy: confidence multiplier too large")]
+    InvalidPricingPolicy,
+    #[msg("Oracle sources disagree by more than the allowed deviation")]
+    OracleDeviationTooLarge,
+    #[msg("Secondary oracle account is required for this collateral")]
+    MissingSecondaryOracle,
+    #[msg("Invalid oracle sources configuration")]
+    InvalidOracleSources,
+    #[msg("Signer is not the oracle authority")]
+    UnauthorizedOracleAuthority,
+    #[msg("Oracle price is timestamped in the future")]
+    FuturePriceTimestamp,
+    #[msg("Price moved too far from the last accepted price; borrowing and withdrawals are paused for this collateral")]
+    CircuitBreakerTripped,
+    #[msg("Invalid circuit breaker threshold: must be at most 10000 basis points")]
+    InvalidCircuitBreaker,
+    #[msg("Account layout version is not supported by this program; migrate it first")]
+    UnsupportedAccountVersion,
+    #[msg("Account is already on the current layout version")]
+    AccountAlreadyMigrated,
+    #[msg("Flash mint must be followed by a matching flash repay in the same transaction")]
+    FlashRepayMissing,
+    #[msg("Flash repay must follow a matching flash mint in the same transaction")]
+    FlashMintMissing,
+    #[msg("Flash mint and repay must be top-level instructions")]
+    FlashMintCpiNotAllowed,
+    #[msg("Delegate is not permitted to take this action on the position")]
+    DelegatePermissionDenied,
+    #[msg("Borrow would exceed the delegate's borrow limit")]
+    DelegateBorrowLimitExceeded,
+    #[msg("A position owner cannot be their own delegate")]
+    InvalidDelegate,
+    #[msg("No swap program is configured for the protocol")]
+    SwapProgramNotSet,
+    #[msg("Swap program does not match the protocol's swap program")]
+    InvalidSwapProgram,
+    #[msg("Swap returned less than the minimum amount out")]
+    SlippageExceeded,
+    #[msg("Swap did not consume the whole input amount")]
+    SwapInputNotConsumed,
+    #[msg("Loan would be below the liquidation threshold")]
+    LoanUnhealthy,
+    #[msg("Target LTV must be above the current LTV to leverage, below it to deleverage, and within the protocol LTV")]
+    InvalidTargetLtv,
+    #[msg("Redemption loans must be loan, vault and vault token account triples, riskiest first")]
+    InvalidRedemptionLoans,
+    #[msg("Loan is worth less than its debt and cannot be redeemed against")]
+    UnderwaterLoan,
+    #[msg("Loan's collateral still covers its debt")]
+    LoanNotUnderwater,
+    #[msg("Loan is above the liquidation threshold")]
+    LoanHealthy,
+    #[msg("Stability pool does not hold enough credit to absorb debt")]
+    StabilityPoolEmpty,
+    #[msg("Stability pool losses exceed the precision it can track")]
+    StabilityPoolExhausted,
+    #[msg("No stability pool deposit to withdraw")]
+    NoStabilityDeposit,
+    #[msg("No collateral gains to claim")]
+    NoStabilityGains,
+    #[msg("Parameter changes must go through the governance timelock")]
+    TimelockActive,
+    #[msg("Queued parameter change is not executable yet")]
+    ParamChangeNotReady,
+    #[msg("Invalid governance delay: must be between 0 and 30 days")]
+    InvalidGovernanceDelay,
+    #[msg("Invalid admin")]
+    InvalidAdmin,
+    #[msg("Collateral config does not match the queued change")]
+    InvalidParamChangeCollateral,
+    #[msg("Loan was opened under a different protocol")]
+    LoanProtocolMismatch,
+    #[msg("Minimum swap output is worth too little of the collateral swapped")]
+    SwapValueLossTooHigh,
+    #[msg("Loan is not among the legacy loans this protocol migrated")]
+    LegacyLoanNotCovered,
+    #[msg(
+        "Credit mint has a supply, a freeze authority or an extension the protocol does not manage"
+    )]
+    CreditMintNotAdoptable,
+    #[msg("Swaps through the vault need unrestricted credit transfers")]
+    CreditTransfersRestricted,
+    #[msg("Price move trips the circuit breaker; record it with tripCircuitBreaker")]
+    CircuitBreakerTripPending,
+    #[msg("Price is within the circuit breaker's bounds")]
+    CircuitBreakerNotTripped,
 }
@@ -0,0 +1,25 @@
+use anchor_lang::prelude::*;
+
+use crate::ParamChange;
+
+#[event]
+pub struct ParamChangeQueued {
+    pub protocol: Pubkey,
+    pub id: u64,
+    pub change: ParamChange,
+    pub eta: i64,
+}
+
+#[event]
+pub struct ParamChangeExecuted {
+    pub protocol: Pubkey,
+    pub id: u64,
+    pub change: ParamChange,
+}
+
+#[event]
+pub struct ParamChangeCancelled {
+    pub protocol: Pubkey,
+    pub id: u64,
+    pub change: ParamChange,
+}
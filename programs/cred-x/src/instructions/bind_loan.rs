@@ -0,0 +1,87 @@
+use crate::{
+    error::CredXError, CollateralConfig, CollateralVault, LoanAccount, ProtocolState, Versioned,
+};
+use anchor_lang::prelude::*;
+
+/// Records the protocol of a loan opened before loans carried one. Only the
+/// admin can vouch for a loan, only for a collateral its protocol accepts, and
+/// only within the legacy loans and debt it declared in `migrate_protocol`, so
+/// a protocol created since cannot claim another's loan. The loan's collateral
+/// joins the collateral's totals as if deposited. An unbound loan is refused
+/// by every other loan instruction.
+#[derive(Accounts)]
+pub struct BindLoan<'info> {
+    #[account(
+        constraint = admin.key() == protocol.admin @ CredXError::UnauthorizedAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    /// CHECK: Only used to derive the loan PDAs
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"collateral_vault", user.key().as_ref()],
+        bump,
+        constraint = collateral_vault.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub collateral_vault: Account<'info, CollateralVault>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral_config", protocol.key().as_ref(), collateral_vault.mint.as_ref()],
+        bump = collateral_config.bump
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"loan", user.key().as_ref(), collateral_vault.key().as_ref()],
+        bump,
+        constraint = loan_account.is_current() @ CredXError::UnsupportedAccountVersion,
+        constraint = loan_account.protocol == Pubkey::default() @ CredXError::AccountAlreadyMigrated
+    )]
+    pub loan_account: Account<'info, LoanAccount>,
+}
+
+impl<'info> BindLoan<'info> {
+    pub fn bind_loan(&mut self) -> Result<()> {
+        let remaining_debt = self.loan_account.remaining_debt;
+        require!(
+            self.protocol.unbound_legacy_loans > 0
+                && remaining_debt <= self.protocol.unbound_legacy_debt,
+            CredXError::LegacyLoanNotCovered
+        );
+
+        let total_collateral = self
+            .collateral_config
+            .total_collateral
+            .checked_add(self.loan_account.collateral_amount)
+            .ok_or(CredXError::MathOverflow)?;
+        require!(
+            total_collateral <= self.collateral_config.supply_cap,
+            CredXError::CollateralSupplyCapExceeded
+        );
+        self.collateral_config.total_collateral = total_collateral;
+
+        self.protocol.unbound_legacy_loans -= 1;
+        self.protocol.unbound_legacy_debt -= remaining_debt;
+        self.loan_account.protocol = self.protocol.key();
+        self.collateral_config
+            .track_loan(self.loan_account.key(), &self.loan_account);
+
+        msg!(
+            "Loan of user {} bound to protocol {}",
+            self.user.key(),
+            self.protocol.key()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use crate::{
+    error::CredXError, ParamChangeCancelled, PendingParamChange, ProtocolState, Versioned,
+};
+use anchor_lang::prelude::*;
+
+/// Drops a queued parameter change before it is executed.
+#[derive(Accounts)]
+pub struct CancelParamChange<'info> {
+    #[account(
+        mut,
+        constraint = admin.key() == protocol.admin @ CredXError::UnauthorizedAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        close = admin,
+        seeds = [
+            b"param_change",
+            protocol.key().as_ref(),
+            &pending_change.id.to_le_bytes()
+        ],
+        bump = pending_change.bump
+    )]
+    pub pending_change: Account<'info, PendingParamChange>,
+}
+
+impl<'info> CancelParamChange<'info> {
+    pub fn cancel_param_change(&mut self) -> Result<()> {
+        emit!(ParamChangeCancelled {
+            protocol: self.protocol.key(),
+            id: self.pending_change.id,
+            change: self.pending_change.change.clone(),
+        });
+        msg!("Parameter change {} cancelled", self.pending_change.id);
+        Ok(())
+    }
+}
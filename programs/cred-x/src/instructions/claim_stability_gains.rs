@@ -0,0 +1,96 @@
+use crate::{error::CredXError, ProtocolState, StabilityDeposit, StabilityPool, Versioned};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+/// Pays out the collateral a stability pool deposit has gained from
+/// liquidations, leaving the deposit itself in the pool.
+#[derive(Accounts)]
+pub struct ClaimStabilityGains<'info> {
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    #[account(
+        constraint = !protocol.is_locked @ CredXError::ProtocolLocked,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [b"stability_pool", protocol.key().as_ref(), collateral_mint.key().as_ref()],
+        bump = stability_pool.bump
+    )]
+    pub stability_pool: Account<'info, StabilityPool>,
+
+    #[account(
+        mut,
+        seeds = [b"stability_deposit", stability_pool.key().as_ref(), depositor.key().as_ref()],
+        bump = stability_deposit.bump
+    )]
+    pub stability_deposit: Account<'info, StabilityDeposit>,
+
+    #[account(mint::token_program = collateral_token_program)]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = stability_pool,
+        associated_token::token_program = collateral_token_program
+    )]
+    pub pool_collateral_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = depositor,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = depositor,
+        associated_token::token_program = collateral_token_program
+    )]
+    pub depositor_collateral_ata: InterfaceAccount<'info, TokenAccount>,
+
+    pub collateral_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ClaimStabilityGains<'info> {
+    pub fn claim_stability_gains(&mut self) -> Result<()> {
+        let (compounded, gain) = self.stability_pool.settle(&mut self.stability_deposit)?;
+        require!(gain > 0, CredXError::NoStabilityGains);
+
+        let protocol_key = self.protocol.key();
+        let mint_key = self.collateral_mint.key();
+        let pool_seeds = &[
+            b"stability_pool",
+            protocol_key.as_ref(),
+            mint_key.as_ref(),
+            &[self.stability_pool.bump],
+        ];
+        transfer_checked(
+            CpiContext::new_with_signer(
+                self.collateral_token_program.to_account_info(),
+                TransferChecked {
+                    from: self.pool_collateral_ata.to_account_info(),
+                    mint: self.collateral_mint.to_account_info(),
+                    to: self.depositor_collateral_ata.to_account_info(),
+                    authority: self.stability_pool.to_account_info(),
+                },
+                &[&pool_seeds[..]],
+            ),
+            gain,
+            self.collateral_mint.decimals,
+        )?;
+
+        msg!(
+            "Depositor: {} claimed {} collateral from the stability pool, deposit: {}",
+            self.depositor.key(),
+            gain,
+            compounded
+        );
+        Ok(())
+    }
+}
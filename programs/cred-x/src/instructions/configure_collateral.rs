@@ -0,0 +1,133 @@
+use crate::{
+    error::CredXError, CollateralConfig, OracleSources, PriceUsage, PricingPolicy, ProtocolState,
+    StalenessPolicy, Versioned,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, InitSpace)]
+pub struct CollateralConfigParams {
+    pub supply_cap: u64,
+    pub pricing: PricingPolicy,
+    pub oracle: OracleSources,
+    pub staleness: StalenessPolicy,
+    // Largest price move against the last accepted price before borrowing
+    // and withdrawals pause, 0 disables the breaker
+    pub max_price_move_bps: u16,
+}
+
+impl CollateralConfigParams {
+    pub fn validate(&self, protocol_max_age_secs: i64) -> Result<()> {
+        self.staleness.validate()?;
+        require!(
+            self.max_price_move_bps <= 10_000,
+            CredXError::InvalidCircuitBreaker
+        );
+        let max_price_age_secs = self
+            .staleness
+            .max_age_secs(protocol_max_age_secs, PriceUsage::Valuation);
+
+        require!(
+            self.pricing.conf_multiplier_bps <= 50_000,
+            CredXError::InvalidPricingPolicy
+        );
+
+        let oracle = &self.oracle;
+        require!(
+            oracle.primary != Pubkey::default() && oracle.primary != oracle.secondary,
+            CredXError::InvalidOracleSources
+        );
+        if oracle.has_secondary() {
+            require!(
+                oracle.max_deviation_bps > 0 && oracle.max_deviation_bps <= 10_000,
+                CredXError::InvalidOracleSources
+            );
+            require!(
+                oracle.fallback_max_age_secs > 0
+                    && oracle.fallback_max_age_secs <= max_price_age_secs,
+                CredXError::InvalidOracleSources
+            );
+            require!(
+                oracle.fallback_conf_multiplier_bps >= 10_000
+                    && oracle.fallback_conf_multiplier_bps <= 50_000,
+                CredXError::InvalidOracleSources
+            );
+        }
+        Ok(())
+    }
+
+    pub fn apply(&self, config: &mut CollateralConfig) {
+        config.supply_cap = self.supply_cap;
+        config.pricing = self.pricing;
+        config.oracle = self.oracle;
+        config.staleness = self.staleness;
+        config.circuit_breaker.max_move_bps = self.max_price_move_bps;
+    }
+}
+
+#[derive(Accounts)]
+pub struct ConfigureCollateral<'info> {
+    #[account(
+        mut,
+        constraint = admin.key() == protocol.admin @ CredXError::UnauthorizedAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    #[account(
+        constraint = collateral_mint.key() != protocol.credit_mint @ CredXError::InvalidCollateralMint
+    )]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + CollateralConfig::INIT_SPACE,
+        seeds = [b"collateral_config", protocol.key().as_ref(), collateral_mint.key().as_ref()],
+        bump
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ConfigureCollateral<'info> {
+    pub fn configure_collateral(
+        &mut self,
+        params: CollateralConfigParams,
+        bumps: &ConfigureCollateralBumps,
+    ) -> Result<()> {
+        params.validate(self.protocol.max_price_age_secs)?;
+
+        let config = &mut self.collateral_config;
+        // Listing a new collateral stays direct; reconfiguring one users
+        // already hold goes through the timelock
+        require!(
+            !self.protocol.is_timelocked() || config.mint == Pubkey::default(),
+            CredXError::TimelockActive
+        );
+        config.protocol = self.protocol.key();
+        config.mint = self.collateral_mint.key();
+        params.apply(config);
+        config.bump = bumps.collateral_config;
+
+        msg!(
+            "Collateral {} configured with supply cap: {} (currently deposited: {})",
+            config.mint,
+            config.supply_cap,
+            config.total_collateral
+        );
+        msg!(
+            "Oracle sources: primary {}, secondary {}",
+            config.oracle.primary,
+            config.oracle.secondary
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+use crate::{
+    error::CredXError, CreditAllowlistEntry, CreditTransferMode, LoanAccount, ProtocolState,
+    Versioned,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    spl_token_2022::{
+        extension::{
+            transfer_hook::TransferHookAccount, BaseStateWithExtensions, StateWithExtensions,
+        },
+        state::Account as SplTokenAccount,
+    },
+    Mint, TokenAccount,
+};
+
+#[derive(Accounts)]
+pub struct CreditTransferHook<'info> {
+    #[account(token::mint = credit_mint)]
+    pub source_token: InterfaceAccount<'info, TokenAccount>,
+
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(token::mint = credit_mint)]
+    pub destination_token: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Source owner or delegate, already verified by the token program
+    pub owner: UncheckedAccount<'info>,
+
+    /// CHECK: ExtraAccountMetaList PDA, validated by seeds
+    #[account(seeds = [b"extra-account-metas", credit_mint.key().as_ref()], bump)]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+
+    #[account(
+        constraint = protocol.credit_mint == credit_mint.key() @ CredXError::InvalidCreditMint,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    /// CHECK: Collateral vault PDA of the source owner, only used to derive the loan
+    #[account(seeds = [b"collateral_vault", source_token.owner.as_ref()], bump)]
+    pub source_collateral_vault: UncheckedAccount<'info>,
+
+    /// CHECK: Loan PDA of the source owner, empty when the owner never opened a loan
+    #[account(
+        seeds = [b"loan", source_token.owner.as_ref(), source_collateral_vault.key().as_ref()],
+        bump
+    )]
+    pub source_loan: UncheckedAccount<'info>,
+
+    /// CHECK: Allow-list PDA of the destination owner, empty when not listed
+    #[account(
+        seeds = [b"credit_allowlist", protocol.key().as_ref(), destination_token.owner.as_ref()],
+        bump
+    )]
+    pub destination_allowlist: UncheckedAccount<'info>,
+}
+
+impl<'info> CreditTransferHook<'info> {
+    pub fn assert_is_transferring(&self) -> Result<()> {
+        let source_info = self.source_token.to_account_info();
+        let data = source_info.try_borrow_data()?;
+        let source = StateWithExtensions::<SplTokenAccount>::unpack(&data)?;
+        let extension = source.get_extension::<TransferHookAccount>()?;
+
+        require!(
+            bool::from(extension.transferring),
+            CredXError::NotTransferring
+        );
+        Ok(())
+    }
+
+    pub fn source_remaining_debt(&self) -> Result<u64> {
+        if self.source_loan.owner != &crate::ID || self.source_loan.data_is_empty() {
+            return Ok(0);
+        }
+        let data = self.source_loan.try_borrow_data()?;
+        let loan = LoanAccount::try_deserialize(&mut &data[..])?;
+        Ok(loan.remaining_debt)
+    }
+
+    pub fn destination_is_allowlisted(&self) -> Result<bool> {
+        if self.destination_allowlist.owner != &crate::ID
+            || self.destination_allowlist.data_is_empty()
+        {
+            return Ok(false);
+        }
+        let data = self.destination_allowlist.try_borrow_data()?;
+        let entry = CreditAllowlistEntry::try_deserialize(&mut &data[..])?;
+        Ok(entry.allowed && entry.protocol == self.protocol.key())
+    }
+
+    pub fn credit_transfer_hook(&self, amount: u64) -> Result<()> {
+        self.assert_is_transferring()?;
+
+        match self.protocol.credit_transfer_mode {
+            CreditTransferMode::Unrestricted => {}
+            CreditTransferMode::DebtFloor => {
+                // Balances are already moved when the hook runs
+                let remaining_debt = self.source_remaining_debt()?;
+                require!(
+                    self.source_token.amount >= remaining_debt,
+                    CredXError::CreditBelowOutstandingDebt
+                );
+            }
+            CreditTransferMode::Allowlist => {
+                require!(
+                    self.destination_is_allowlisted()?,
+                    CredXError::DestinationNotAllowlisted
+                );
+            }
+        }
+
+        msg!(
+            "Credit transfer of {} approved from {} to {}",
+            amount,
+            self.source_token.owner,
+            self.destination_token.owner
+        );
+        Ok(())
+    }
+}
@@ -1,16 +1,15 @@
-use crate::SimplePriceOracle;
-use crate::{error::CredXError, CollateralVault, LoanAccount, ProtocolState};
+use crate::{debt_collateral, harvestable_yield, load_collateral_price, yield_repayment, PriceUsage};
+use crate::{error::CredXError, CollateralConfig, CollateralVault, LoanAccount, ProtocolState, Versioned};
 use anchor_lang::prelude::*;
-use anchor_spl::token::{burn, Burn, Mint, Token, TokenAccount};
-use pyth_sdk_solana::state::{load_price_account, GenericPriceAccount};
-use pyth_sdk_solana::Price;
+use anchor_spl::token_interface::{burn, Burn, Mint, TokenAccount, TokenInterface};
 
 #[derive(Accounts)]
 pub struct CronRepayment<'info> {
 
     #[account(
         mut, 
-        constraint = !protocol.is_locked @ CredXError::ProtocolLocked
+        constraint = !protocol.is_locked @ CredXError::ProtocolLocked,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
     )]
     pub protocol: Account<'info, ProtocolState>,
 
@@ -18,32 +17,44 @@ pub struct CronRepayment<'info> {
         mut,
         seeds = [b"collateral_vault", loan_account.user.as_ref()],
         bump,
-        constraint = collateral_vault.mint != credit_mint.key() @ CredXError::InvalidCollateralMint
+        constraint = collateral_vault.mint != credit_mint.key() @ CredXError::InvalidCollateralMint,
+        constraint = collateral_vault.is_current() @ CredXError::UnsupportedAccountVersion
     )]
     pub collateral_vault: Account<'info, CollateralVault>,
 
+    #[account(
+        mut,
+        seeds = [b"collateral_config", protocol.key().as_ref(), collateral_vault.mint.as_ref()],
+        bump = collateral_config.bump
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+
     #[account(
         mut,
         associated_token::mint = collateral_vault.mint,
         associated_token::authority = collateral_vault,
+        associated_token::token_program = collateral_token_program,
         constraint = collateral_vault_ata.amount >= loan_account.collateral_amount @ CredXError::InsufficientCollateral
     )]
-    pub collateral_vault_ata: Account<'info, TokenAccount>,
+    pub collateral_vault_ata: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         seeds = [b"loan", loan_account.user.as_ref(), collateral_vault.key().as_ref()],
         bump,
-        constraint = loan_account.remaining_debt > 0 @ CredXError::NoOutstandingDebt
+        constraint = loan_account.remaining_debt > 0 @ CredXError::NoOutstandingDebt,
+        constraint = loan_account.is_current() @ CredXError::UnsupportedAccountVersion,
+        constraint = loan_account.protocol == protocol.key() @ CredXError::LoanProtocolMismatch
     )]
     pub loan_account: Account<'info, LoanAccount>,
 
     #[account(
         mut,
-        mint::decimals = 6,
+        mint::decimals = protocol.credit_decimals,
+        mint::token_program = credit_token_program,
         constraint = credit_mint.key() == protocol.credit_mint @ CredXError::InvalidCreditMint
     )]
-    pub credit_mint: Account<'info, Mint>,
+    pub credit_mint: InterfaceAccount<'info, Mint>,
 
     /// CHECK: PDA used as program authority
     #[account(seeds = [b"program_authority"], bump)]
@@ -53,72 +64,35 @@ pub struct CronRepayment<'info> {
         mut,
         associated_token::mint = credit_mint,
         associated_token::authority = program_authority,
+        associated_token::token_program = credit_token_program,
     )]
-    pub protocol_credit_ata: Account<'info, TokenAccount>,
+    pub protocol_credit_ata: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         associated_token::mint = credit_mint,
         associated_token::authority = loan_account.user,
+        associated_token::token_program = credit_token_program,
         constraint = user_credit_ata.amount > 0 @ CredXError::NoTokensToBurn
     )]
-    pub user_credit_ata: Account<'info, TokenAccount>,
-    /// CHECK: Oracle price account is validated by comparing its key with the stored oracle_price_account in loan_account
-    // #[account(
-    //     constraint = oracle_price_account.key() == loan_account.oracle_price_account @ CredXError::InvalidOracleAccount
-    // )]
-    // pub oracle_price_account: AccountInfo<'info>,
+    pub user_credit_ata: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: Primary price source, parsed and checked against the collateral's oracle binding
+    #[account(
+        constraint = oracle_price_account.key() == collateral_config.oracle.primary @ CredXError::InvalidOracleAccount
+    )]
+    pub oracle_price_account: UncheckedAccount<'info>,
 
-    pub oracle_price_account: Account<'info, SimplePriceOracle>,
+    /// CHECK: Secondary price source, required when the collateral has one configured
+    #[account(
+        constraint = secondary_oracle_account.key() == collateral_config.oracle.secondary @ CredXError::InvalidOracleAccount
+    )]
+    pub secondary_oracle_account: Option<UncheckedAccount<'info>>,
 
-    pub token_program: Program<'info, Token>,
+    pub credit_token_program: Interface<'info, TokenInterface>,
+    pub collateral_token_program: Interface<'info, TokenInterface>,
 }
 
 impl<'info> CronRepayment<'info> {
-    pub fn get_price(&mut self) -> Result<i64> {
-        // require!(
-        //     !self.oracle_price_account.data_is_empty(),
-        //     CredXError::EmptyOracleAccount
-        // );
-        // let price_feed = self.oracle_price_account.try_borrow_data()
-        //     .map_err(|_| CredXError::FailedToBorrowOracleData)?;
-
-        // let price_account: &GenericPriceAccount<32, Price> = load_price_account(&price_feed)
-        //     .map_err(|_| CredXError::FailedToLoadPriceAccount)?;
-        // let current_price = price_account.agg.price;
-
-        // let expo = price_account.expo;
-
-        // require!(current_price > 0, CredXError::InvalidPrice);
-
-        // let current_time = Clock::get()?.unix_timestamp;
-        // let price_time = price_account.agg.pub_slot as i64; 
-        // require!(
-        //     current_time - price_time < 300, 
-        //     CredXError::StalePrice
-        // );
-
-        // let normalized_price = if expo < 0 {
-        //     current_price
-        //         .checked_div(10_i64.pow((-expo) as u32))
-        //         .ok_or(CredXError::MathOverflow)?
-        // } else {
-        //     current_price
-        //         .checked_mul(10_i64.pow(expo as u32))
-        //         .ok_or(CredXError::MathOverflow)?
-        // };
-
-        // Ok(normalized_price)
-        let current_price = self.oracle_price_account.price;
-        require!(current_price > 0, CredXError::InvalidPrice);
-
-        let current_time = Clock::get()?.unix_timestamp;
-        let price_time = self.oracle_price_account.timestamp;
-        require!(current_time - price_time < 300, CredXError::StalePrice);
-
-        Ok(current_price as i64)
-    }
-
     pub fn cron_repayment(&mut self, bumps: &CronRepaymentBumps) -> Result<()> {
         require!(!self.protocol.is_locked, CredXError::ProtocolLocked);
         require!(self.loan_account.remaining_debt > 0, CredXError::NoOutstandingDebt);
@@ -127,23 +101,37 @@ impl<'info> CronRepayment<'info> {
         let vault_balance = self.collateral_vault_ata.amount;
         let original_collateral = self.loan_account.collateral_amount;
 
-        let yield_amount = vault_balance.checked_sub(original_collateral)
-            .ok_or(CredXError::NegativeYield)?;
+        let yield_amount = harvestable_yield(vault_balance, original_collateral)?;
 
         if yield_amount == 0 {
             msg!("No yield available for repayment");
             return Ok(());
         }
 
-        let normalized_price = self.get_price()?;
-        let yield_value_in_usd = (yield_amount as i64)
-            .checked_mul(normalized_price)
-            .ok_or(CredXError::MathOverflow)?;
-
-        require!(yield_value_in_usd > 0, CredXError::ZeroRepaymentValue);
-
-        let repayment_value = yield_value_in_usd as u64;
-        let actual_repayment = std::cmp::min(repayment_value, self.loan_account.remaining_debt);
+        let quote = load_collateral_price(
+            &self.collateral_config,
+            &self.protocol,
+            &self.oracle_price_account,
+            self.secondary_oracle_account.as_deref(),
+            PriceUsage::Valuation,
+        )?;
+        // Repayments keep working while the breaker is tripped; only a
+        // healthy reading moves the reference price, and a trip is saved.
+        self.collateral_config.record_price(&quote)?;
+        let normalized_price = quote.collateral_price(&self.collateral_config.pricing)?;
+        let actual_repayment = yield_repayment(
+            yield_amount,
+            normalized_price,
+            self.loan_account.remaining_debt,
+        )?;
+        // When the yield more than clears the debt, only the part worth the
+        // debt at the debt price is used up
+        let yield_used = if actual_repayment == self.loan_account.remaining_debt {
+            let debt_price = quote.debt_price(&self.collateral_config.pricing)?;
+            debt_collateral(actual_repayment, debt_price)?.min(yield_amount)
+        } else {
+            yield_amount
+        };
 
         require!(self.user_credit_ata.amount >= actual_repayment, CredXError::InsufficientCreditTokens);
 
@@ -158,7 +146,7 @@ impl<'info> CronRepayment<'info> {
         let signer_seeds = &[&seeds[..]];
 
         let burn_ctx = CpiContext::new_with_signer(
-            self.token_program.to_account_info(),
+            self.credit_token_program.to_account_info(),
             cpi_accounts,
             signer_seeds,
         );
@@ -168,11 +156,31 @@ impl<'info> CronRepayment<'info> {
         self.loan_account.remaining_debt = self.loan_account.remaining_debt
             .checked_sub(actual_repayment)
             .ok_or(CredXError::MathUnderflow)?;
+        // The yield used stays in the vault as the owner's collateral, so
+        // the next run only sees yield earned since
+        self.loan_account.collateral_amount = original_collateral
+            .checked_add(yield_used)
+            .ok_or(CredXError::MathOverflow)?;
+        self.collateral_config.total_collateral = self.collateral_config.total_collateral
+            .checked_add(yield_used)
+            .ok_or(CredXError::MathOverflow)?;
+        self.collateral_config
+            .track_loan(self.loan_account.key(), &self.loan_account);
+
+        self.protocol.total_debt = self.protocol.total_debt
+            .checked_sub(actual_repayment)
+            .ok_or(CredXError::MathUnderflow)?;
 
         self.loan_account.yield_earned = self.loan_account.yield_earned
-            .checked_add(yield_amount)
+            .checked_add(yield_used)
             .ok_or(CredXError::MathOverflow)?;
 
+        self.protocol.stats.record_yield_repayment(
+            actual_repayment,
+            yield_used,
+            self.loan_account.remaining_debt == 0,
+        )?;
+
         msg!(
             "Repaid {} credit tokens for user: {}, remaining debt: {}",
             actual_repayment,
@@ -0,0 +1,274 @@
+use crate::{
+    collateral_value, deleverage_collateral_amount, error::CredXError, invoke_swap, is_healthy,
+    load_collateral_price, CollateralConfig, CollateralVault, CreditTransferMode, LoanAccount,
+    PriceUsage, ProtocolState, SwapAccounts, Versioned,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{burn, mint_to, Burn, Mint, MintTo, TokenAccount, TokenInterface},
+};
+
+/// Sells part of the loan's collateral for credit and repays debt with it in
+/// one step. Proceeds above the outstanding debt go to the owner's credit
+/// account. Like `leverage`, only available while credit transfers are
+/// unrestricted.
+#[derive(Accounts)]
+pub struct Deleverage<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = !protocol.is_locked @ CredXError::ProtocolLocked,
+        constraint = protocol.credit_mint == credit_mint.key() @ CredXError::InvalidCreditMint,
+        constraint = protocol.swap_program != Pubkey::default() @ CredXError::SwapProgramNotSet,
+        constraint = protocol.credit_transfer_mode == CreditTransferMode::Unrestricted @ CredXError::CreditTransfersRestricted,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    /// CHECK: PDA used as program authority
+    #[account(seeds = [b"program_authority"], bump)]
+    pub program_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        mint::authority = program_authority,
+        mint::token_program = credit_token_program
+    )]
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = credit_mint,
+        associated_token::authority = user,
+        associated_token::token_program = credit_token_program
+    )]
+    pub user_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"collateral_vault", user.key().as_ref()],
+        bump,
+        constraint = collateral_vault.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub collateral_vault: Account<'info, CollateralVault>,
+
+    #[account(
+        mint::token_program = collateral_token_program,
+        constraint = collateral_mint.key() == collateral_vault.mint @ CredXError::MintMismatch
+    )]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral_config", protocol.key().as_ref(), collateral_mint.key().as_ref()],
+        bump = collateral_config.bump,
+        constraint = !collateral_config.circuit_breaker.tripped @ CredXError::CircuitBreakerTripped
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+
+    #[account(
+        mut,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = collateral_vault,
+        associated_token::token_program = collateral_token_program
+    )]
+    pub collateral_vault_ata: InterfaceAccount<'info, TokenAccount>,
+
+    // Receives the swap proceeds before they are burned
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = credit_mint,
+        associated_token::authority = collateral_vault,
+        associated_token::token_program = credit_token_program
+    )]
+    pub vault_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"loan", user.key().as_ref(), collateral_vault.key().as_ref()],
+        bump,
+        constraint = loan_account.user == user.key() @ CredXError::UnauthorizedUser,
+        constraint = loan_account.remaining_debt > 0 @ CredXError::NoActiveLoan,
+        constraint = loan_account.is_current() @ CredXError::UnsupportedAccountVersion,
+        constraint = loan_account.protocol == protocol.key() @ CredXError::LoanProtocolMismatch
+    )]
+    pub loan_account: Account<'info, LoanAccount>,
+
+    /// CHECK: Primary price source, parsed and checked against the collateral's oracle binding
+    #[account(
+        constraint = oracle_price_account.key() == collateral_config.oracle.primary @ CredXError::InvalidOracleAccount
+    )]
+    pub oracle_price_account: UncheckedAccount<'info>,
+
+    /// CHECK: Secondary price source, required when the collateral has one configured
+    #[account(
+        constraint = secondary_oracle_account.key() == collateral_config.oracle.secondary @ CredXError::InvalidOracleAccount
+    )]
+    pub secondary_oracle_account: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Invoked with the swap adapter interface, must be the protocol's swap program
+    #[account(
+        executable,
+        constraint = swap_program.key() == protocol.swap_program @ CredXError::InvalidSwapProgram
+    )]
+    pub swap_program: UncheckedAccount<'info>,
+
+    pub credit_token_program: Interface<'info, TokenInterface>,
+    pub collateral_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Deleverage<'info> {
+    /// `swap_accounts` are passed through to the swap program after the
+    /// accounts of the adapter interface.
+    pub fn deleverage(
+        &mut self,
+        target_ltv_bps: u16,
+        min_credit_out: u64,
+        swap_accounts: &[AccountInfo<'info>],
+        bumps: &DeleverageBumps,
+    ) -> Result<()> {
+        let quote = load_collateral_price(
+            &self.collateral_config,
+            &self.protocol,
+            &self.oracle_price_account,
+            self.secondary_oracle_account.as_deref(),
+            PriceUsage::Valuation,
+        )?;
+        self.collateral_config.observe_price(&quote)?;
+        let price = quote.collateral_price(&self.collateral_config.pricing)?;
+
+        let remaining_debt = self.loan_account.remaining_debt;
+        let sell_amount = deleverage_collateral_amount(
+            self.loan_account.collateral_amount,
+            price,
+            remaining_debt,
+            target_ltv_bps,
+        )?;
+        require!(sell_amount > 0, CredXError::InvalidAmount);
+
+        let binding = self.user.key();
+        let vault_seeds = &[
+            b"collateral_vault",
+            binding.as_ref(),
+            &[bumps.collateral_vault],
+        ];
+        let vault_signer = &[&vault_seeds[..]];
+        let collateral_before = self.collateral_vault_ata.amount;
+        let credit_before = self.vault_credit_ata.amount;
+
+        invoke_swap(
+            &self.swap_program,
+            SwapAccounts {
+                authority: self.collateral_vault.to_account_info(),
+                source: self.collateral_vault_ata.to_account_info(),
+                destination: self.vault_credit_ata.to_account_info(),
+                input_mint: self.collateral_mint.to_account_info(),
+                output_mint: self.credit_mint.to_account_info(),
+                input_token_program: self.collateral_token_program.to_account_info(),
+                output_token_program: self.credit_token_program.to_account_info(),
+            },
+            swap_accounts,
+            sell_amount,
+            min_credit_out,
+            vault_signer,
+        )?;
+
+        self.collateral_vault_ata.reload()?;
+        self.vault_credit_ata.reload()?;
+        let sold = collateral_before
+            .checked_sub(self.collateral_vault_ata.amount)
+            .ok_or(CredXError::MathUnderflow)?;
+        require!(sold == sell_amount, CredXError::SwapInputNotConsumed);
+        let received = self
+            .vault_credit_ata
+            .amount
+            .checked_sub(credit_before)
+            .ok_or(CredXError::MathUnderflow)?;
+        require!(
+            received > 0 && received >= min_credit_out,
+            CredXError::SlippageExceeded
+        );
+
+        // All proceeds are burned and the surplus re-minted to the owner: a
+        // transfer would re-enter this program through the credit mint's
+        // transfer hook
+        let repaid = received.min(remaining_debt);
+        burn(
+            CpiContext::new_with_signer(
+                self.credit_token_program.to_account_info(),
+                Burn {
+                    mint: self.credit_mint.to_account_info(),
+                    from: self.vault_credit_ata.to_account_info(),
+                    authority: self.collateral_vault.to_account_info(),
+                },
+                vault_signer,
+            ),
+            received,
+        )?;
+        let surplus = received - repaid;
+        if surplus > 0 {
+            let authority_seeds = &[b"program_authority".as_ref(), &[bumps.program_authority]];
+            mint_to(
+                CpiContext::new_with_signer(
+                    self.credit_token_program.to_account_info(),
+                    MintTo {
+                        mint: self.credit_mint.to_account_info(),
+                        to: self.user_credit_ata.to_account_info(),
+                        authority: self.program_authority.to_account_info(),
+                    },
+                    &[&authority_seeds[..]],
+                ),
+                surplus,
+            )?;
+            self.protocol.stats.record_mint(surplus)?;
+        }
+
+        let collateral_amount = self
+            .loan_account
+            .collateral_amount
+            .checked_sub(sold)
+            .ok_or(CredXError::MathUnderflow)?;
+        let new_debt = remaining_debt - repaid;
+        require!(
+            is_healthy(
+                collateral_value(collateral_amount, price),
+                self.protocol.liquidation_threshold_bps,
+                new_debt
+            )?,
+            CredXError::LoanUnhealthy
+        );
+
+        self.collateral_config.total_collateral = self
+            .collateral_config
+            .total_collateral
+            .checked_sub(sold)
+            .ok_or(CredXError::MathUnderflow)?;
+        self.loan_account.collateral_amount = collateral_amount;
+        self.loan_account.remaining_debt = new_debt;
+        self.collateral_config
+            .track_loan(self.loan_account.key(), &self.loan_account);
+        self.protocol.total_debt = self
+            .protocol
+            .total_debt
+            .checked_sub(repaid)
+            .ok_or(CredXError::MathUnderflow)?;
+        self.protocol
+            .stats
+            .record_user_repayment(repaid, new_debt == 0)?;
+
+        msg!(
+            "Deleveraged user: {} - sold {} collateral for {} credit tokens, repaid {}, remaining debt: {}",
+            self.user.key(),
+            sold,
+            received,
+            repaid,
+            new_debt
+        );
+        Ok(())
+    }
+}
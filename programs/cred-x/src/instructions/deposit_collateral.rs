@@ -1,51 +1,70 @@
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{approve, transfer, Approve, Mint, Token, TokenAccount, Transfer},
+    token_interface::{
+        approve, transfer_checked, Approve, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
 };
 
-use crate::{error::CredXError, supported_collateral, CollateralVault, LoanAccount, ProtocolState};
+use crate::{
+    authorize_position, error::CredXError, CollateralConfig, CollateralVault, LoanAccount,
+    PositionAction, PositionDelegate, ProtocolState, Versioned,
+};
 
 #[derive(Accounts)]
 #[instruction(amount: u64)]
 pub struct DepositCollateral<'info> {
+    /// The position owner, or a delegate allowed to deposit
     #[account(mut)]
-    user: Signer<'info>,
+    pub authority: Signer<'info>,
+
+    /// CHECK: Owner of the position, checked against `loan_account.user`
+    pub user: UncheckedAccount<'info>,
+
     #[account(
-        seeds = [b"protocol", protocol.admin.as_ref()],
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
         bump = protocol.bump,
-        constraint = !protocol.is_locked @ CredXError::ProtocolLocked
+        constraint = !protocol.is_locked @ CredXError::ProtocolLocked,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
     )]
     pub protocol: Account<'info, ProtocolState>,
 
-    // #[account(
-    //     constraint = supported_collateral(&collateral_mint.key()) @ CredXError::UnsupportedCollateralMint
-    // )]
-    pub collateral_mint: Account<'info, Mint>,
+    #[account(mint::token_program = token_program)]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral_config", protocol.key().as_ref(), collateral_mint.key().as_ref()],
+        bump = collateral_config.bump
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
 
     #[account(
         mut,
         associated_token::mint = collateral_mint,
-        associated_token::authority = user,
-        constraint = user_collateral_ata.amount >= amount @ CredXError::InsufficientBalance
+        associated_token::authority = authority,
+        associated_token::token_program = token_program,
+        constraint = authority_collateral_ata.amount >= amount @ CredXError::InsufficientBalance
     )]
-    pub user_collateral_ata: Account<'info, TokenAccount>,
+    pub authority_collateral_ata: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         seeds = [b"collateral_vault", user.key().as_ref()],
         bump,
-        constraint = collateral_vault.mint == collateral_mint.key() @ CredXError::MintMismatch
+        constraint = collateral_vault.mint == collateral_mint.key() @ CredXError::MintMismatch,
+        constraint = collateral_vault.is_current() @ CredXError::UnsupportedAccountVersion
     )]
     pub collateral_vault: Account<'info, CollateralVault>,
 
     #[account(
         init_if_needed,
-        payer = user,
+        payer = authority,
         associated_token::mint = collateral_mint,
-        associated_token::authority = collateral_vault
+        associated_token::authority = collateral_vault,
+        associated_token::token_program = token_program
     )]
-    pub collateral_vault_ata: Account<'info, TokenAccount>,
+    pub collateral_vault_ata: InterfaceAccount<'info, TokenAccount>,
 
     /// CHECK: This is a PDA derived from seeds, used as program authority for various operations
     #[account(seeds = [b"program_authority"], bump)]
@@ -55,12 +74,16 @@ pub struct DepositCollateral<'info> {
         mut,
         seeds = [b"loan", user.key().as_ref(), collateral_vault.key().as_ref()],
         bump,
-        constraint = loan_account.user == user.key() @ CredXError::UnauthorizedUser
+        constraint = loan_account.user == user.key() @ CredXError::UnauthorizedUser,
+        constraint = loan_account.is_current() @ CredXError::UnsupportedAccountVersion,
+        constraint = loan_account.protocol == protocol.key() @ CredXError::LoanProtocolMismatch
     )]
     pub loan_account: Account<'info, LoanAccount>,
 
+    pub position_delegate: Option<Account<'info, PositionDelegate>>,
+
     pub associated_token_program: Program<'info, AssociatedToken>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
@@ -69,24 +92,54 @@ impl<'info> DepositCollateral<'info> {
         require!(amount > 0, CredXError::InvalidAmount);
 
         require!(
-            self.user_collateral_ata.amount >= amount,
+            self.authority_collateral_ata.amount >= amount,
             CredXError::InsufficientBalance
         );
         require!(
             self.loan_account.user == self.user.key(),
             CredXError::UnauthorizedUser
         );
+        authorize_position(
+            &self.user.key(),
+            &self.authority.key(),
+            self.position_delegate.as_deref(),
+            PositionAction::Deposit,
+        )?;
+
+        let vault_balance_before = self.collateral_vault_ata.amount;
 
         let program = self.token_program.to_account_info();
-        let accounts = Transfer {
-            from: self.user_collateral_ata.to_account_info(),
+        let accounts = TransferChecked {
+            from: self.authority_collateral_ata.to_account_info(),
+            mint: self.collateral_mint.to_account_info(),
             to: self.collateral_vault_ata.to_account_info(),
-            authority: self.user.to_account_info(),
+            authority: self.authority.to_account_info(),
         };
 
         let ctx = CpiContext::new(program, accounts);
 
-        transfer(ctx, amount)?;
+        transfer_checked(ctx, amount, self.collateral_mint.decimals)?;
+
+        // Token-2022 mints with a transfer fee withhold part of `amount` in the
+        // vault, so only credit what actually landed in the vault balance.
+        self.collateral_vault_ata.reload()?;
+        let received = self
+            .collateral_vault_ata
+            .amount
+            .checked_sub(vault_balance_before)
+            .ok_or(CredXError::MathUnderflow)?;
+        require!(received > 0, CredXError::InvalidAmount);
+
+        let total_collateral = self
+            .collateral_config
+            .total_collateral
+            .checked_add(received)
+            .ok_or(CredXError::MathOverflow)?;
+        require!(
+            total_collateral <= self.collateral_config.supply_cap,
+            CredXError::CollateralSupplyCapExceeded
+        );
+        self.collateral_config.total_collateral = total_collateral;
 
         let approve_accounts = Approve {
             to: self.collateral_vault_ata.to_account_info(),
@@ -108,17 +161,19 @@ impl<'info> DepositCollateral<'info> {
             vault_signer,
         );
 
-        approve(approve_ctx, amount)?;
+        approve(approve_ctx, received)?;
 
         self.loan_account.collateral_amount = self
             .loan_account
             .collateral_amount
-            .checked_add(amount)
+            .checked_add(received)
             .ok_or(CredXError::MathOverflow)?;
+        self.collateral_config
+            .track_loan(self.loan_account.key(), &self.loan_account);
 
         msg!(
             "Deposited {} collateral tokens for user: {}",
-            amount,
+            received,
             self.user.key()
         );
 
@@ -0,0 +1,186 @@
+use crate::{error::CredXError, ProtocolState, StabilityDeposit, StabilityPool, Versioned};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        burn, mint_to, transfer_checked, Burn, Mint, MintTo, TokenAccount, TokenInterface,
+        TransferChecked,
+    },
+};
+
+/// Adds credit to a stability pool. Collateral gained by the existing deposit
+/// is paid out first, so the new snapshot starts from zero gains.
+#[derive(Accounts)]
+pub struct DepositToStabilityPool<'info> {
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    #[account(
+        constraint = !protocol.is_locked @ CredXError::ProtocolLocked,
+        constraint = protocol.credit_mint == credit_mint.key() @ CredXError::InvalidCreditMint,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [b"stability_pool", protocol.key().as_ref(), collateral_mint.key().as_ref()],
+        bump = stability_pool.bump
+    )]
+    pub stability_pool: Account<'info, StabilityPool>,
+
+    #[account(
+        init_if_needed,
+        payer = depositor,
+        space = 8 + StabilityDeposit::INIT_SPACE,
+        seeds = [b"stability_deposit", stability_pool.key().as_ref(), depositor.key().as_ref()],
+        bump
+    )]
+    pub stability_deposit: Account<'info, StabilityDeposit>,
+
+    /// CHECK: PDA used as program authority
+    #[account(seeds = [b"program_authority"], bump)]
+    pub program_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        mint::authority = program_authority,
+        mint::token_program = credit_token_program
+    )]
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = credit_mint,
+        associated_token::authority = depositor,
+        associated_token::token_program = credit_token_program
+    )]
+    pub depositor_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = credit_mint,
+        associated_token::authority = stability_pool,
+        associated_token::token_program = credit_token_program
+    )]
+    pub pool_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mint::token_program = collateral_token_program)]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = stability_pool,
+        associated_token::token_program = collateral_token_program
+    )]
+    pub pool_collateral_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = depositor,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = depositor,
+        associated_token::token_program = collateral_token_program
+    )]
+    pub depositor_collateral_ata: InterfaceAccount<'info, TokenAccount>,
+
+    pub credit_token_program: Interface<'info, TokenInterface>,
+    pub collateral_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> DepositToStabilityPool<'info> {
+    pub fn deposit_to_stability_pool(
+        &mut self,
+        amount: u64,
+        bumps: &DepositToStabilityPoolBumps,
+    ) -> Result<()> {
+        require!(amount > 0, CredXError::InvalidAmount);
+        require!(
+            self.depositor_credit_ata.amount >= amount,
+            CredXError::InsufficientCreditTokens
+        );
+
+        let deposit = &mut self.stability_deposit;
+        if deposit.owner == Pubkey::default() {
+            deposit.pool = self.stability_pool.key();
+            deposit.owner = self.depositor.key();
+            deposit.bump = bumps.stability_deposit;
+        }
+        let (compounded, gain) = self.stability_pool.settle(deposit)?;
+
+        if gain > 0 {
+            let protocol_key = self.protocol.key();
+            let mint_key = self.collateral_mint.key();
+            let pool_seeds = &[
+                b"stability_pool",
+                protocol_key.as_ref(),
+                mint_key.as_ref(),
+                &[self.stability_pool.bump],
+            ];
+            transfer_checked(
+                CpiContext::new_with_signer(
+                    self.collateral_token_program.to_account_info(),
+                    TransferChecked {
+                        from: self.pool_collateral_ata.to_account_info(),
+                        mint: self.collateral_mint.to_account_info(),
+                        to: self.depositor_collateral_ata.to_account_info(),
+                        authority: self.stability_pool.to_account_info(),
+                    },
+                    &[&pool_seeds[..]],
+                ),
+                gain,
+                self.collateral_mint.decimals,
+            )?;
+        }
+
+        // Burned from the depositor and re-minted to the pool: a transfer
+        // would re-enter this program through the credit mint's transfer hook
+        burn(
+            CpiContext::new(
+                self.credit_token_program.to_account_info(),
+                Burn {
+                    mint: self.credit_mint.to_account_info(),
+                    from: self.depositor_credit_ata.to_account_info(),
+                    authority: self.depositor.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+        let authority_seeds = &[b"program_authority".as_ref(), &[bumps.program_authority]];
+        mint_to(
+            CpiContext::new_with_signer(
+                self.credit_token_program.to_account_info(),
+                MintTo {
+                    mint: self.credit_mint.to_account_info(),
+                    to: self.pool_credit_ata.to_account_info(),
+                    authority: self.program_authority.to_account_info(),
+                },
+                &[&authority_seeds[..]],
+            ),
+            amount,
+        )?;
+
+        let deposited = compounded
+            .checked_add(amount)
+            .ok_or(CredXError::MathOverflow)?;
+        self.stability_pool.total_deposits = self
+            .stability_pool
+            .total_deposits
+            .checked_add(amount)
+            .ok_or(CredXError::MathOverflow)?;
+        self.stability_pool
+            .snapshot(&mut self.stability_deposit, deposited);
+
+        msg!(
+            "Depositor: {} added {} credit tokens to the stability pool, deposit: {}, collateral gain paid: {}",
+            self.depositor.key(),
+            amount,
+            deposited,
+            gain
+        );
+        Ok(())
+    }
+}
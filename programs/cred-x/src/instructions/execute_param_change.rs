@@ -0,0 +1,134 @@
+use crate::{
+    credit_mint_uses_transfer_hook, error::CredXError, write_credit_metadata, CollateralConfig,
+    CreditTransferMode, ParamChange, ParamChangeExecuted, PendingParamChange, ProtocolState,
+    Versioned,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenInterface};
+
+/// Applies a queued parameter change whose delay has passed. A change to a
+/// collateral also takes that collateral's config; a change to the credit
+/// token takes the credit mint and what is needed to update it.
+#[derive(Accounts)]
+pub struct ExecuteParamChange<'info> {
+    #[account(
+        mut,
+        constraint = admin.key() == protocol.admin @ CredXError::UnauthorizedAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        close = admin,
+        seeds = [
+            b"param_change",
+            protocol.key().as_ref(),
+            &pending_change.id.to_le_bytes()
+        ],
+        bump = pending_change.bump
+    )]
+    pub pending_change: Account<'info, PendingParamChange>,
+
+    #[account(
+        mut,
+        constraint = collateral_config.protocol == protocol.key() @ CredXError::InvalidParamChangeCollateral
+    )]
+    pub collateral_config: Option<Account<'info, CollateralConfig>>,
+
+    /// CHECK: PDA used as program authority
+    #[account(seeds = [b"program_authority"], bump)]
+    pub program_authority: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        mut,
+        constraint = credit_mint.key() == protocol.credit_mint @ CredXError::InvalidCreditMint
+    )]
+    pub credit_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    pub credit_token_program: Option<Interface<'info, TokenInterface>>,
+    pub system_program: Option<Program<'info, System>>,
+}
+
+impl<'info> ExecuteParamChange<'info> {
+    pub fn execute_param_change(&mut self, bumps: &ExecuteParamChangeBumps) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        require!(
+            now >= self.pending_change.eta,
+            CredXError::ParamChangeNotReady
+        );
+
+        let change = self.pending_change.change.clone();
+        change.validate(&self.protocol)?;
+        let collateral_config = match change.collateral_mint() {
+            Some(mint) => {
+                let config = self
+                    .collateral_config
+                    .as_deref_mut()
+                    .ok_or(CredXError::InvalidParamChangeCollateral)?;
+                require_keys_eq!(config.mint, mint, CredXError::InvalidParamChangeCollateral);
+                Some(config)
+            }
+            None => None,
+        };
+        change.apply(&mut self.protocol, collateral_config)?;
+
+        match &change {
+            ParamChange::CreditTransferMode { mode } => {
+                let credit_mint = self
+                    .credit_mint
+                    .as_ref()
+                    .ok_or(CredXError::InvalidCreditMint)?;
+                require!(
+                    *mode == CreditTransferMode::Unrestricted
+                        || credit_mint_uses_transfer_hook(&credit_mint.to_account_info()),
+                    CredXError::TransferHookNotEnabled
+                );
+            }
+            ParamChange::CreditMetadata { metadata } => {
+                let (
+                    Some(credit_mint),
+                    Some(program_authority),
+                    Some(token_program),
+                    Some(system_program),
+                ) = (
+                    self.credit_mint.as_ref(),
+                    self.program_authority.as_ref(),
+                    self.credit_token_program.as_ref(),
+                    self.system_program.as_ref(),
+                )
+                else {
+                    return err!(CredXError::InvalidCreditMint);
+                };
+                let program_authority_bump = bumps
+                    .program_authority
+                    .ok_or(CredXError::InvalidCreditMint)?;
+                write_credit_metadata(
+                    &self.admin.to_account_info(),
+                    &credit_mint.to_account_info(),
+                    &program_authority.to_account_info(),
+                    &token_program.to_account_info(),
+                    &system_program.to_account_info(),
+                    metadata,
+                    program_authority_bump,
+                )?;
+            }
+            _ => {}
+        }
+
+        emit!(ParamChangeExecuted {
+            protocol: self.protocol.key(),
+            id: self.pending_change.id,
+            change,
+        });
+        msg!("Parameter change {} executed", self.pending_change.id);
+        Ok(())
+    }
+}
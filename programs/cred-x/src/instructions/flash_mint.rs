@@ -0,0 +1,152 @@
+use crate::{error::CredXError, instruction, ProtocolState, Versioned};
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        instruction::{get_stack_height, Instruction, TRANSACTION_LEVEL_STACK_HEIGHT},
+        sysvar::instructions::{load_current_index_checked, load_instruction_at_checked},
+    },
+    Discriminator,
+};
+use anchor_spl::token_interface::{mint_to, Mint, MintTo, TokenAccount, TokenInterface};
+
+/// Position of `credit_mint` in both `FlashMint` and `FlashRepay`, used to
+/// pair the two within a transaction.
+const FLASH_CREDIT_MINT_INDEX: usize = 2;
+
+#[derive(Accounts)]
+pub struct FlashMint<'info> {
+    #[account(
+        constraint = !protocol.is_locked @ CredXError::ProtocolLocked,
+        constraint = protocol.credit_mint == credit_mint.key() @ CredXError::InvalidCreditMint,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    /// CHECK: PDA used as program authority
+    #[account(seeds = [b"program_authority"], bump)]
+    pub program_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        mint::authority = program_authority,
+        mint::token_program = token_program
+    )]
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = credit_mint,
+        token::token_program = token_program
+    )]
+    pub receiver: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Instructions sysvar, read to find the matching flash repay
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> FlashMint<'info> {
+    pub fn flash_mint(&mut self, amount: u64, bumps: &FlashMintBumps) -> Result<()> {
+        require!(amount > 0, CredXError::InvalidAmount);
+
+        // The first flash instruction after this one must repay it
+        let repaid = match next_flash_leg(&self.instructions, Direction::Forward)? {
+            Some((FlashLeg::Repay(repaid), mint)) => {
+                repaid == amount && mint == Some(self.credit_mint.key())
+            }
+            _ => false,
+        };
+        require!(repaid, CredXError::FlashRepayMissing);
+
+        let seeds = &[b"program_authority".as_ref(), &[bumps.program_authority]];
+        let signer_seeds = &[&seeds[..]];
+        mint_to(
+            CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                MintTo {
+                    mint: self.credit_mint.to_account_info(),
+                    to: self.receiver.to_account_info(),
+                    authority: self.program_authority.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            amount,
+        )?;
+
+        msg!(
+            "Flash minted {} credit tokens to: {}",
+            amount,
+            self.receiver.key()
+        );
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum FlashLeg {
+    Mint(u64),
+    Repay(u64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Direction {
+    Forward,
+    Backward,
+}
+
+/// Nearest `flash_mint` or `flash_repay` after or before the executing
+/// instruction, with the credit mint it names. Only top-level calls may
+/// flash mint, so the executing instruction is this program's.
+pub(crate) fn next_flash_leg(
+    instructions: &AccountInfo,
+    direction: Direction,
+) -> Result<Option<(FlashLeg, Option<Pubkey>)>> {
+    require_eq!(
+        get_stack_height(),
+        TRANSACTION_LEVEL_STACK_HEIGHT,
+        CredXError::FlashMintCpiNotAllowed
+    );
+
+    let current = load_current_index_checked(instructions)? as usize;
+    let mut index = current;
+    loop {
+        index = match direction {
+            Direction::Forward => index + 1,
+            Direction::Backward if index > 0 => index - 1,
+            Direction::Backward => return Ok(None),
+        };
+        // Reading past the last instruction fails
+        let Ok(ix) = load_instruction_at_checked(index, instructions) else {
+            return Ok(None);
+        };
+        if let Some(leg) = flash_leg(&ix)? {
+            let mint = ix
+                .accounts
+                .get(FLASH_CREDIT_MINT_INDEX)
+                .map(|meta| meta.pubkey);
+            return Ok(Some((leg, mint)));
+        }
+    }
+}
+
+fn flash_leg(ix: &Instruction) -> Result<Option<FlashLeg>> {
+    if ix.program_id != crate::ID {
+        return Ok(None);
+    }
+    let amount = |data: &[u8]| -> Result<u64> {
+        let bytes = data
+            .get(..8)
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or(ErrorCode::InstructionDidNotDeserialize)?;
+        Ok(u64::from_le_bytes(bytes))
+    };
+    if let Some(data) = ix.data.strip_prefix(instruction::FlashMint::DISCRIMINATOR) {
+        return Ok(Some(FlashLeg::Mint(amount(data)?)));
+    }
+    if let Some(data) = ix.data.strip_prefix(instruction::FlashRepay::DISCRIMINATOR) {
+        return Ok(Some(FlashLeg::Repay(amount(data)?)));
+    }
+    Ok(None)
+}
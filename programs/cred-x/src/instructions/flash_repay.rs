@@ -0,0 +1,112 @@
+use crate::{
+    bps_of, error::CredXError, next_flash_leg, Direction, FlashLeg, ProtocolState, Versioned,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    burn, mint_to, Burn, Mint, MintTo, TokenAccount, TokenInterface,
+};
+
+#[derive(Accounts)]
+pub struct FlashRepay<'info> {
+    #[account(
+        constraint = protocol.credit_mint == credit_mint.key() @ CredXError::InvalidCreditMint,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    /// CHECK: PDA used as program authority
+    #[account(seeds = [b"program_authority"], bump)]
+    pub program_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        mint::authority = program_authority,
+        mint::token_program = token_program
+    )]
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        token::mint = credit_mint,
+        token::authority = payer,
+        token::token_program = token_program
+    )]
+    pub payer_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = credit_mint,
+        associated_token::authority = program_authority,
+        associated_token::token_program = token_program
+    )]
+    pub protocol_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Instructions sysvar, read to find the matching flash mint
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> FlashRepay<'info> {
+    pub fn flash_repay(&mut self, amount: u64, bumps: &FlashRepayBumps) -> Result<()> {
+        // Pairs each repay with exactly one mint, the nearest flash instruction before it
+        let minted = match next_flash_leg(&self.instructions, Direction::Backward)? {
+            Some((FlashLeg::Mint(minted), mint)) => {
+                minted == amount && mint == Some(self.credit_mint.key())
+            }
+            _ => false,
+        };
+        require!(minted, CredXError::FlashMintMissing);
+
+        // The fee is burned with the principal and re-minted to the protocol,
+        // which keeps transfer hooks out of the repayment
+        let fee_amount = bps_of(amount, self.protocol.flash_fee_bps)?;
+        let total = amount
+            .checked_add(fee_amount)
+            .ok_or(CredXError::MathOverflow)?;
+        require!(
+            self.payer_credit_ata.amount >= total,
+            CredXError::InsufficientBalance
+        );
+
+        burn(
+            CpiContext::new(
+                self.token_program.to_account_info(),
+                Burn {
+                    mint: self.credit_mint.to_account_info(),
+                    from: self.payer_credit_ata.to_account_info(),
+                    authority: self.payer.to_account_info(),
+                },
+            ),
+            total,
+        )?;
+
+        if fee_amount > 0 {
+            let seeds = &[b"program_authority".as_ref(), &[bumps.program_authority]];
+            let signer_seeds = &[&seeds[..]];
+            mint_to(
+                CpiContext::new_with_signer(
+                    self.token_program.to_account_info(),
+                    MintTo {
+                        mint: self.credit_mint.to_account_info(),
+                        to: self.protocol_credit_ata.to_account_info(),
+                        authority: self.program_authority.to_account_info(),
+                    },
+                    signer_seeds,
+                ),
+                fee_amount,
+            )?;
+        }
+
+        msg!(
+            "Flash repaid {} credit tokens from: {}, Fee: {}",
+            amount,
+            self.payer.key(),
+            fee_amount
+        );
+        Ok(())
+    }
+}
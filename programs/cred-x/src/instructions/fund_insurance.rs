@@ -0,0 +1,122 @@
+use crate::{error::CredXError, InsuranceFund, ProtocolState, Versioned};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{burn, mint_to, Burn, Mint, MintTo, TokenAccount, TokenInterface},
+};
+
+/// Moves collected protocol fees into the insurance fund, creating the fund
+/// on first use.
+#[derive(Accounts)]
+pub struct FundInsurance<'info> {
+    #[account(
+        mut,
+        constraint = admin.key() == protocol.admin @ CredXError::UnauthorizedAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = protocol.credit_mint == credit_mint.key() @ CredXError::InvalidCreditMint,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    /// CHECK: PDA used as program authority
+    #[account(seeds = [b"program_authority"], bump)]
+    pub program_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        mint::authority = program_authority,
+        mint::token_program = credit_token_program
+    )]
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = credit_mint,
+        associated_token::authority = program_authority,
+        associated_token::token_program = credit_token_program
+    )]
+    pub protocol_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + InsuranceFund::INIT_SPACE,
+        seeds = [b"insurance_fund", protocol.key().as_ref()],
+        bump
+    )]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        associated_token::mint = credit_mint,
+        associated_token::authority = insurance_fund,
+        associated_token::token_program = credit_token_program
+    )]
+    pub fund_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    pub credit_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> FundInsurance<'info> {
+    pub fn fund_insurance(&mut self, amount: u64, bumps: &FundInsuranceBumps) -> Result<()> {
+        require!(amount > 0, CredXError::InvalidAmount);
+        require!(
+            self.protocol_credit_ata.amount >= amount,
+            CredXError::InsufficientCreditTokens
+        );
+
+        let fund = &mut self.insurance_fund;
+        fund.protocol = self.protocol.key();
+        fund.bump = bumps.insurance_fund;
+
+        // Burned and re-minted rather than transferred, which keeps the
+        // credit mint's transfer hook from re-entering this program
+        let authority_seeds = &[b"program_authority".as_ref(), &[bumps.program_authority]];
+        let authority_signer = &[&authority_seeds[..]];
+        burn(
+            CpiContext::new_with_signer(
+                self.credit_token_program.to_account_info(),
+                Burn {
+                    mint: self.credit_mint.to_account_info(),
+                    from: self.protocol_credit_ata.to_account_info(),
+                    authority: self.program_authority.to_account_info(),
+                },
+                authority_signer,
+            ),
+            amount,
+        )?;
+        mint_to(
+            CpiContext::new_with_signer(
+                self.credit_token_program.to_account_info(),
+                MintTo {
+                    mint: self.credit_mint.to_account_info(),
+                    to: self.fund_credit_ata.to_account_info(),
+                    authority: self.program_authority.to_account_info(),
+                },
+                authority_signer,
+            ),
+            amount,
+        )?;
+
+        self.insurance_fund.total_funded = self
+            .insurance_fund
+            .total_funded
+            .checked_add(amount)
+            .ok_or(CredXError::MathOverflow)?;
+
+        msg!(
+            "Insurance fund received {} credit tokens, total funded: {}",
+            amount,
+            self.insurance_fund.total_funded
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+use crate::{error::CredXError, ProtocolState, Versioned};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenInterface};
+use spl_tlv_account_resolution::{
+    account::ExtraAccountMeta, seeds::Seed, state::ExtraAccountMetaList,
+};
+use spl_transfer_hook_interface::instruction::ExecuteInstruction;
+
+/// Extra accounts the token program passes to `credit_transfer_hook`, after the
+/// source (0), mint (1), destination (2), owner (3) and meta list (4).
+pub fn credit_hook_extra_account_metas(protocol: &Pubkey) -> Result<Vec<ExtraAccountMeta>> {
+    Ok(vec![
+        // 5: protocol state holding the transfer mode
+        ExtraAccountMeta::new_with_pubkey(protocol, false, false)?,
+        // 6: collateral vault of the source owner
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: b"collateral_vault".to_vec(),
+                },
+                Seed::AccountData {
+                    account_index: 0,
+                    data_index: 32,
+                    length: 32,
+                },
+            ],
+            false,
+            false,
+        )?,
+        // 7: loan account of the source owner
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: b"loan".to_vec(),
+                },
+                Seed::AccountData {
+                    account_index: 0,
+                    data_index: 32,
+                    length: 32,
+                },
+                Seed::AccountKey { index: 6 },
+            ],
+            false,
+            false,
+        )?,
+        // 8: allow-list entry of the destination owner
+        ExtraAccountMeta::new_with_seeds(
+            &[
+                Seed::Literal {
+                    bytes: b"credit_allowlist".to_vec(),
+                },
+                Seed::AccountKey { index: 5 },
+                Seed::AccountData {
+                    account_index: 2,
+                    data_index: 32,
+                    length: 32,
+                },
+            ],
+            false,
+            false,
+        )?,
+    ])
+}
+
+#[derive(Accounts)]
+pub struct InitializeCreditTransferHook<'info> {
+    #[account(
+        mut,
+        constraint = admin.key() == protocol.admin @ CredXError::UnauthorizedAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = protocol.credit_mint == credit_mint.key() @ CredXError::InvalidCreditMint,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    #[account(mint::token_program = token_program)]
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: ExtraAccountMetaList PDA, written in the handler
+    #[account(
+        init,
+        payer = admin,
+        space = ExtraAccountMetaList::size_of(credit_hook_extra_account_metas(&protocol.key())?.len())?,
+        seeds = [b"extra-account-metas", credit_mint.key().as_ref()],
+        bump
+    )]
+    pub extra_account_meta_list: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeCreditTransferHook<'info> {
+    pub fn initialize_credit_transfer_hook(&mut self) -> Result<()> {
+        let extra_account_metas = credit_hook_extra_account_metas(&self.protocol.key())?;
+
+        ExtraAccountMetaList::init::<ExecuteInstruction>(
+            &mut self.extra_account_meta_list.try_borrow_mut_data()?,
+            &extra_account_metas,
+        )?;
+
+        msg!(
+            "Credit transfer hook accounts initialized for mint: {}",
+            self.credit_mint.key()
+        );
+        Ok(())
+    }
+}
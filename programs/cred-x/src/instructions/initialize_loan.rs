@@ -1,15 +1,8 @@
 use crate::error::CredXError;
-use crate::{CollateralVault, LoanAccount, ProtocolState};
+use crate::{CollateralVault, LoanAccount, ProtocolState, Versioned};
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, Token};
-use anchor_spl::{associated_token::AssociatedToken, token::TokenAccount};
-
-pub fn supported_collateral(mint: &Pubkey) -> bool {
-    const MSOL_MINT: Pubkey = pubkey!("mSoLzYCxHdYgdzU16g5QSh3i5K3z3KZK7ytfqcJm7So");
-    const JITO_SOL_MINT: Pubkey = pubkey!("J1toso1uCk3RLmjorhTtrVwY9HJ7X8V9yYac6Y7kGCPn");
-
-    *mint == MSOL_MINT || *mint == JITO_SOL_MINT
-}
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
+use anchor_spl::associated_token::AssociatedToken;
 
 #[derive(Accounts)]
 pub struct InitializeLoan<'info> {
@@ -17,8 +10,9 @@ pub struct InitializeLoan<'info> {
     pub user: Signer<'info>,
 
     #[account(
-        seeds = [b"protocol", protocol.admin.as_ref()],
-        bump = protocol.bump
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
     )]
     pub protocol: Account<'info, ProtocolState>,
 
@@ -28,19 +22,21 @@ pub struct InitializeLoan<'info> {
 
     #[account(
         mut,
-        mint::decimals = 6,
+        mint::decimals = protocol.credit_decimals,
         mint::authority = program_authority,
+        mint::token_program = token_program,
         constraint = credit_mint.key() == protocol.credit_mint @ CredXError::InvalidCreditMint
     )]
-    pub credit_mint: Account<'info, Mint>,
+    pub credit_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         init_if_needed,
         payer = user,
         associated_token::mint = credit_mint,
-        associated_token::authority = user
+        associated_token::authority = user,
+        associated_token::token_program = token_program
     )]
-    pub user_credit_ata: Account<'info, TokenAccount>,
+    pub user_credit_ata: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         init,
@@ -63,7 +59,7 @@ pub struct InitializeLoan<'info> {
     /// CHECK: Oracle price account is validated by comparing its key with the stored oracle_price_account in loan_account
     pub oracle_price_account: AccountInfo<'info>,
     pub associated_token_program: Program<'info, AssociatedToken>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
@@ -73,15 +69,6 @@ impl<'info> InitializeLoan<'info> {
         collateral_mint: Pubkey,
         bumps: &InitializeLoanBumps,
     ) -> Result<()> {
-        require!(
-            !self.user.key().eq(&Pubkey::default()),
-            CredXError::InvalidUser
-        );
-
-        // require!(
-        //     supported_collateral(&collateral_mint),
-        //     CredXError::UnsupportedCollateralMint
-        // );
         require!(
             !self.oracle_price_account.key().eq(&Pubkey::default()),
             CredXError::InvalidOracleAccount
@@ -90,6 +77,8 @@ impl<'info> InitializeLoan<'info> {
         self.collateral_vault.set_inner(CollateralVault {
             mint: collateral_mint,
             bump: bumps.collateral_vault,
+            version: CollateralVault::VERSION,
+            reserved: [0; 32],
         });
 
         self.loan_account.set_inner(LoanAccount {
@@ -99,6 +88,9 @@ impl<'info> InitializeLoan<'info> {
             yield_earned: 0,
             bump: bumps.loan_account,
             oracle_price_account: self.oracle_price_account.key(),
+            version: LoanAccount::VERSION,
+            protocol: self.protocol.key(),
+            reserved: [0; 32],
         });
 
         msg!("Loan account initialized for user: {}", self.user.key());
@@ -1,8 +1,125 @@
-
 use crate::error::CredXError;
-use crate::ProtocolState;
+use crate::{CreditTransferMode, ProtocolState, ProtocolStats, Versioned};
 use anchor_lang::prelude::*;
-use anchor_spl::{associated_token::AssociatedToken, token::{Mint, Token, TokenAccount}};
+use anchor_lang::system_program::{create_account, CreateAccount};
+use anchor_spl::{
+    associated_token::{create, AssociatedToken, Create},
+    token_interface::{
+        find_mint_account_size, initialize_mint2, metadata_pointer_initialize, spl_pod,
+        spl_token_2022, spl_token_2022::extension::ExtensionType,
+        spl_token_metadata_interface::state::TokenMetadata, token_metadata_initialize,
+        transfer_hook_initialize, InitializeMint2, MetadataPointerInitialize, TokenInterface,
+        TokenMetadataInitialize, TransferHookInitialize,
+    },
+};
+
+pub const MAX_CREDIT_NAME_LEN: usize = 32;
+pub const MAX_CREDIT_SYMBOL_LEN: usize = 10;
+pub const MAX_CREDIT_URI_LEN: usize = 200;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, InitSpace)]
+pub struct CreditMetadata {
+    #[max_len(MAX_CREDIT_NAME_LEN)]
+    pub name: String,
+    #[max_len(MAX_CREDIT_SYMBOL_LEN)]
+    pub symbol: String,
+    #[max_len(MAX_CREDIT_URI_LEN)]
+    pub uri: String,
+}
+
+impl CreditMetadata {
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.name.len() <= MAX_CREDIT_NAME_LEN
+                && self.symbol.len() <= MAX_CREDIT_SYMBOL_LEN
+                && self.uri.len() <= MAX_CREDIT_URI_LEN,
+            CredXError::InvalidCreditMetadata
+        );
+        Ok(())
+    }
+
+    /// Size of the Token-2022 metadata entry this produces on the credit mint.
+    pub fn tlv_size(&self, mint: Pubkey, update_authority: Pubkey) -> Result<usize> {
+        let metadata = TokenMetadata {
+            update_authority: spl_pod::optional_keys::OptionalNonZeroPubkey(update_authority),
+            mint,
+            name: self.name.clone(),
+            symbol: self.symbol.clone(),
+            uri: self.uri.clone(),
+            additional_metadata: vec![],
+        };
+        Ok(metadata.tlv_size_of()?)
+    }
+}
+
+pub fn validate_loan_ratios(ltv_ratio_bps: u16, liquidation_threshold_bps: u16) -> Result<()> {
+    require!(
+        ltv_ratio_bps > 0 && ltv_ratio_bps <= 9000,
+        CredXError::InvalidLtvRatio
+    );
+    require!(
+        liquidation_threshold_bps > ltv_ratio_bps && liquidation_threshold_bps <= 10_000,
+        CredXError::InvalidLiquidationThreshold
+    );
+    Ok(())
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct InitializeProtocolParams {
+    pub credit_decimals: u8,
+    pub ltv_ratio_bps: u16,
+    pub liquidation_threshold_bps: u16,
+    pub max_price_age_secs: i64,
+    pub borrow_fee_bps: u16,
+    pub debt_ceiling: u64,
+    pub credit_transfer_mode: CreditTransferMode,
+    pub credit_metadata: Option<CreditMetadata>,
+}
+
+impl InitializeProtocolParams {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(metadata) = &self.credit_metadata {
+            metadata.validate()?;
+        }
+        require!(self.credit_decimals <= 9, CredXError::InvalidCreditDecimals);
+        validate_loan_ratios(self.ltv_ratio_bps, self.liquidation_threshold_bps)?;
+        require!(
+            self.max_price_age_secs > 0,
+            CredXError::InvalidStalenessWindow
+        );
+        require!(self.borrow_fee_bps <= 1000, CredXError::InvalidFee);
+        Ok(())
+    }
+
+    pub fn to_protocol_state(&self, admin: Pubkey, credit_mint: Pubkey, bump: u8) -> ProtocolState {
+        ProtocolState {
+            admin,
+            ltv_ratio_bps: self.ltv_ratio_bps,
+            liquidation_threshold_bps: self.liquidation_threshold_bps,
+            credit_mint,
+            credit_decimals: self.credit_decimals,
+            max_price_age_secs: self.max_price_age_secs,
+            borrow_fee_bps: self.borrow_fee_bps,
+            debt_ceiling: self.debt_ceiling,
+            total_debt: 0,
+            is_locked: false,
+            bump,
+            credit_transfer_mode: self.credit_transfer_mode,
+            stats: ProtocolStats::default(),
+            version: ProtocolState::VERSION,
+            flash_fee_bps: 0,
+            swap_program: Pubkey::default(),
+            redemption_fee_bps: 0,
+            total_redeemed: 0,
+            original_admin: Pubkey::default(),
+            governance_delay_secs: 0,
+            param_changes_queued: 0,
+            unbound_legacy_loans: 0,
+            unbound_legacy_debt: 0,
+            reserved: [0; 20],
+        }
+    }
+}
 
 #[derive(Accounts)]
 pub struct InitializeProtocol<'info> {
@@ -11,61 +128,194 @@ pub struct InitializeProtocol<'info> {
     /// CHECK: PDA used as program authority
     #[account(seeds = [b"program_authority"], bump)]
     pub program_authority: UncheckedAccount<'info>,
-    
+
+    /// CHECK: Created and initialized as the credit mint in the handler, so the
+    /// transfer hook extension can be added when the protocol needs it
     #[account(
-        init,
-        payer = admin,
-        mint::decimals = 6,
-        mint::authority = program_authority, 
+        mut,
         seeds = [b"credit", admin.key().as_ref()],
         bump
     )]
-    pub credit_mint: Account<'info, Mint>,
+    pub credit_mint: UncheckedAccount<'info>,
 
     #[account(
-        init,                                  
-        payer = admin,                         
-        space = 8 + ProtocolState::INIT_SPACE,  
+        init,
+        payer = admin,
+        space = 8 + ProtocolState::INIT_SPACE,
         seeds = [b"protocol", admin.key().as_ref()],
         bump
     )]
     pub protocol: Account<'info, ProtocolState>,
 
-    #[account(
-        init,
-        payer = admin,
-        associated_token::mint = credit_mint,
-        associated_token::authority = program_authority,
-    )]
-    pub protocol_credit_ata: Account<'info, TokenAccount>,
+    /// CHECK: Created by the associated token program once the credit mint exists
+    #[account(mut)]
+    pub protocol_credit_ata: UncheckedAccount<'info>,
 
     pub associated_token_program: Program<'info, AssociatedToken>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> InitializeProtocol<'info> {
-    pub fn initialize_protocol(&mut self, bumps: &InitializeProtocolBumps) -> Result<()> {
-       
-        let ltv_ratio_bps = 6000; 
+    pub fn initialize_protocol(
+        &mut self,
+        params: InitializeProtocolParams,
+        bumps: &InitializeProtocolBumps,
+    ) -> Result<()> {
+        params.validate()?;
+
+        let with_transfer_hook = params.credit_transfer_mode != CreditTransferMode::Unrestricted;
+        let is_token_2022 = self.token_program.key() == spl_token_2022::ID;
         require!(
-            ltv_ratio_bps > 0 && ltv_ratio_bps <= 9000,
-            CredXError::InvalidLtvRatio
+            !with_transfer_hook || is_token_2022,
+            CredXError::TransferHookRequiresToken2022
         );
-        self.protocol.set_inner(ProtocolState {
-            admin: self.admin.key(),
-            ltv_ratio_bps,
-            credit_mint: self.credit_mint.key(),
-            is_locked: false,
-            bump: bumps.protocol,
-        });
+        require!(
+            params.credit_metadata.is_none() || is_token_2022,
+            CredXError::CreditMetadataNotSupported
+        );
+
+        self.create_credit_mint(
+            params.credit_decimals,
+            with_transfer_hook,
+            params.credit_metadata.as_ref(),
+            bumps,
+        )?;
+
+        create(CpiContext::new(
+            self.associated_token_program.to_account_info(),
+            Create {
+                payer: self.admin.to_account_info(),
+                associated_token: self.protocol_credit_ata.to_account_info(),
+                authority: self.program_authority.to_account_info(),
+                mint: self.credit_mint.to_account_info(),
+                system_program: self.system_program.to_account_info(),
+                token_program: self.token_program.to_account_info(),
+            },
+        ))?;
+
+        self.protocol.set_inner(params.to_protocol_state(
+            self.admin.key(),
+            self.credit_mint.key(),
+            bumps.protocol,
+        ));
         msg!("Protocol initialized by admin: {}", self.admin.key());
         msg!(
             "Credit mint created with admin as authority: {}",
             self.credit_mint.key()
         );
-        msg!("LTV ratio set to: {}%", ltv_ratio_bps / 100);
+        msg!("LTV ratio set to: {}%", params.ltv_ratio_bps / 100);
+        msg!("Credit transfer mode: {:?}", params.credit_transfer_mode);
+        Ok(())
+    }
+
+    fn create_credit_mint(
+        &self,
+        decimals: u8,
+        with_transfer_hook: bool,
+        metadata: Option<&CreditMetadata>,
+        bumps: &InitializeProtocolBumps,
+    ) -> Result<()> {
+        let mut extensions = vec![];
+        if with_transfer_hook {
+            extensions.push(ExtensionType::TransferHook);
+        }
+        if metadata.is_some() {
+            extensions.push(ExtensionType::MetadataPointer);
+        }
+        let space = find_mint_account_size(Some(&extensions))?;
+
+        // The metadata entry is appended by the token program after the mint is
+        // initialized, so fund its rent upfront but leave it out of `space`.
+        let metadata_space = match metadata {
+            Some(metadata) => {
+                metadata.tlv_size(self.credit_mint.key(), self.program_authority.key())?
+            }
+            None => 0,
+        };
+        let lamports = Rent::get()?.minimum_balance(space + metadata_space);
+
+        let admin_key = self.admin.key();
+        let credit_seeds = &[b"credit".as_ref(), admin_key.as_ref(), &[bumps.credit_mint]];
+        let signer_seeds = &[&credit_seeds[..]];
+
+        create_account(
+            CpiContext::new_with_signer(
+                self.system_program.to_account_info(),
+                CreateAccount {
+                    from: self.admin.to_account_info(),
+                    to: self.credit_mint.to_account_info(),
+                },
+                signer_seeds,
+            ),
+            lamports,
+            space as u64,
+            &self.token_program.key(),
+        )?;
+
+        if with_transfer_hook {
+            transfer_hook_initialize(
+                CpiContext::new(
+                    self.token_program.to_account_info(),
+                    TransferHookInitialize {
+                        token_program_id: self.token_program.to_account_info(),
+                        mint: self.credit_mint.to_account_info(),
+                    },
+                ),
+                Some(self.program_authority.key()),
+                Some(crate::ID),
+            )?;
+        }
+
+        if metadata.is_some() {
+            metadata_pointer_initialize(
+                CpiContext::new(
+                    self.token_program.to_account_info(),
+                    MetadataPointerInitialize {
+                        token_program_id: self.token_program.to_account_info(),
+                        mint: self.credit_mint.to_account_info(),
+                    },
+                ),
+                Some(self.program_authority.key()),
+                Some(self.credit_mint.key()),
+            )?;
+        }
+
+        initialize_mint2(
+            CpiContext::new(
+                self.token_program.to_account_info(),
+                InitializeMint2 {
+                    mint: self.credit_mint.to_account_info(),
+                },
+            ),
+            decimals,
+            &self.program_authority.key(),
+            None,
+        )?;
+
+        if let Some(metadata) = metadata {
+            let authority_seeds = &[b"program_authority".as_ref(), &[bumps.program_authority]];
+            let authority_signer = &[&authority_seeds[..]];
+
+            token_metadata_initialize(
+                CpiContext::new_with_signer(
+                    self.token_program.to_account_info(),
+                    TokenMetadataInitialize {
+                        program_id: self.token_program.to_account_info(),
+                        metadata: self.credit_mint.to_account_info(),
+                        update_authority: self.program_authority.to_account_info(),
+                        mint_authority: self.program_authority.to_account_info(),
+                        mint: self.credit_mint.to_account_info(),
+                    },
+                    authority_signer,
+                ),
+                metadata.name.clone(),
+                metadata.symbol.clone(),
+                metadata.uri.clone(),
+            )?;
+        }
+
         Ok(())
     }
 }
@@ -0,0 +1,142 @@
+use crate::{
+    credit_mint_uses_transfer_hook, error::CredXError, CreditTransferMode,
+    InitializeProtocolParams, ProtocolState,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        set_authority,
+        spl_token_2022::{
+            extension::{
+                transfer_hook::TransferHook, BaseStateWithExtensions, ExtensionType,
+                StateWithExtensions,
+            },
+            instruction::AuthorityType,
+            state::Mint as MintState,
+        },
+        Mint, SetAuthority, TokenAccount, TokenInterface,
+    },
+};
+
+/// Same as `initialize_protocol`, but adopts an existing mint as the credit
+/// token by handing its mint authority to `program_authority`. The mint must
+/// be unused and leave the protocol in sole control of the token: no supply,
+/// no freeze authority, and no extensions beyond metadata and a transfer hook
+/// into this program that only `program_authority` may repoint.
+#[derive(Accounts)]
+pub struct InitializeProtocolWithMint<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub current_mint_authority: Signer<'info>,
+
+    /// CHECK: PDA used as program authority
+    #[account(seeds = [b"program_authority"], bump)]
+    pub program_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        mint::authority = current_mint_authority,
+        mint::token_program = token_program
+    )]
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + ProtocolState::INIT_SPACE,
+        seeds = [b"protocol", admin.key().as_ref()],
+        bump
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    #[account(
+        init,
+        payer = admin,
+        associated_token::mint = credit_mint,
+        associated_token::authority = program_authority,
+        associated_token::token_program = token_program,
+    )]
+    pub protocol_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeProtocolWithMint<'info> {
+    pub fn initialize_protocol_with_mint(
+        &mut self,
+        params: InitializeProtocolParams,
+        bumps: &InitializeProtocolWithMintBumps,
+    ) -> Result<()> {
+        params.validate()?;
+        require!(
+            params.credit_metadata.is_none(),
+            CredXError::CreditMetadataNotSupported
+        );
+        require!(
+            params.credit_decimals == self.credit_mint.decimals,
+            CredXError::InvalidCreditDecimals
+        );
+        require!(
+            params.credit_transfer_mode == CreditTransferMode::Unrestricted
+                || credit_mint_uses_transfer_hook(&self.credit_mint.to_account_info()),
+            CredXError::TransferHookNotEnabled
+        );
+        require!(
+            self.credit_mint.supply == 0 && self.credit_mint.freeze_authority.is_none(),
+            CredXError::CreditMintNotAdoptable
+        );
+        self.check_extensions()?;
+
+        let ctx = CpiContext::new(
+            self.token_program.to_account_info(),
+            SetAuthority {
+                current_authority: self.current_mint_authority.to_account_info(),
+                account_or_mint: self.credit_mint.to_account_info(),
+            },
+        );
+        set_authority(
+            ctx,
+            AuthorityType::MintTokens,
+            Some(self.program_authority.key()),
+        )?;
+
+        self.protocol.set_inner(params.to_protocol_state(
+            self.admin.key(),
+            self.credit_mint.key(),
+            bumps.protocol,
+        ));
+
+        msg!("Protocol initialized by admin: {}", self.admin.key());
+        msg!(
+            "Adopted existing credit mint with program authority: {}",
+            self.credit_mint.key()
+        );
+        msg!("LTV ratio set to: {}%", params.ltv_ratio_bps / 100);
+        Ok(())
+    }
+    fn check_extensions(&self) -> Result<()> {
+        let account = self.credit_mint.to_account_info();
+        let data = account.try_borrow_data()?;
+        let mint = StateWithExtensions::<MintState>::unpack(&data)?;
+        for extension in mint.get_extension_types()? {
+            match extension {
+                ExtensionType::MetadataPointer | ExtensionType::TokenMetadata => {}
+                ExtensionType::TransferHook => {
+                    let hook = mint.get_extension::<TransferHook>()?;
+                    let authority = Option::<Pubkey>::from(hook.authority);
+                    require!(
+                        Option::<Pubkey>::from(hook.program_id) == Some(crate::ID)
+                            && authority.is_none_or(|key| key == self.program_authority.key()),
+                        CredXError::CreditMintNotAdoptable
+                    );
+                }
+                _ => return err!(CredXError::CreditMintNotAdoptable),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use crate::{
+    error::CredXError, CollateralConfig, ProtocolState, StabilityPool, Versioned, PRODUCT_PRECISION,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{Mint, TokenAccount, TokenInterface},
+};
+
+/// Opens the stability pool for a configured collateral, along with the
+/// pool's credit and collateral accounts.
+#[derive(Accounts)]
+pub struct InitializeStabilityPool<'info> {
+    #[account(
+        mut,
+        constraint = admin.key() == protocol.admin @ CredXError::UnauthorizedAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = protocol.credit_mint == credit_mint.key() @ CredXError::InvalidCreditMint,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    #[account(mint::token_program = credit_token_program)]
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(mint::token_program = collateral_token_program)]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"collateral_config", protocol.key().as_ref(), collateral_mint.key().as_ref()],
+        bump = collateral_config.bump
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + StabilityPool::INIT_SPACE,
+        seeds = [b"stability_pool", protocol.key().as_ref(), collateral_mint.key().as_ref()],
+        bump
+    )]
+    pub stability_pool: Account<'info, StabilityPool>,
+
+    #[account(
+        init,
+        payer = admin,
+        associated_token::mint = credit_mint,
+        associated_token::authority = stability_pool,
+        associated_token::token_program = credit_token_program
+    )]
+    pub pool_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = admin,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = stability_pool,
+        associated_token::token_program = collateral_token_program
+    )]
+    pub pool_collateral_ata: InterfaceAccount<'info, TokenAccount>,
+
+    pub credit_token_program: Interface<'info, TokenInterface>,
+    pub collateral_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> InitializeStabilityPool<'info> {
+    pub fn initialize_stability_pool(
+        &mut self,
+        bumps: &InitializeStabilityPoolBumps,
+    ) -> Result<()> {
+        self.stability_pool.set_inner(StabilityPool {
+            protocol: self.protocol.key(),
+            collateral_mint: self.collateral_mint.key(),
+            total_deposits: 0,
+            total_collateral: 0,
+            total_debt_offset: 0,
+            product: PRODUCT_PRECISION,
+            scale: 0,
+            scale_sums: Default::default(),
+            bump: bumps.stability_pool,
+        });
+
+        msg!(
+            "Stability pool {} opened for collateral {}",
+            self.stability_pool.key(),
+            self.collateral_mint.key()
+        );
+        Ok(())
+    }
+}
@@ -1,18 +1,23 @@
-use crate::{error::CredXError, CollateralVault, LoanAccount, ProtocolState, SimplePriceOracle};
+use crate::{
+    authorize_position, borrow_quote, error::CredXError, load_collateral_price, BorrowQuote,
+    CollateralConfig, CollateralVault, LoanAccount, PositionAction, PositionDelegate, PriceUsage,
+    ProtocolState, Versioned,
+};
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{approve, mint_to, Approve, Mint, MintTo, Token, TokenAccount},
-};
-use pyth_sdk_solana::{
-    state::{load_price_account, GenericPriceAccount},
-    Price,
+    token_interface::{approve, mint_to, Approve, Mint, MintTo, TokenAccount, TokenInterface},
 };
 
 #[derive(Accounts)]
 pub struct LendCreditToken<'info> {
+    /// The position owner, or a delegate allowed to borrow
     #[account(mut)]
-    pub user: Signer<'info>,
+    pub authority: Signer<'info>,
+
+    /// CHECK: Owner of the position, checked against `loan_account.user`. When
+    /// it also signs, the borrowed credit is approved for automated repayment.
+    pub user: UncheckedAccount<'info>,
 
     #[account(
         mut,
@@ -23,7 +28,8 @@ pub struct LendCreditToken<'info> {
     #[account(
         mut,
         constraint = !protocol.is_locked @ CredXError::ProtocolLocked,
-        constraint = protocol.credit_mint == credit_mint.key() @ CredXError::InvalidCreditMint
+        constraint = protocol.credit_mint == credit_mint.key() @ CredXError::InvalidCreditMint,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
     )]
     pub protocol: Account<'info, ProtocolState>,
 
@@ -33,96 +39,78 @@ pub struct LendCreditToken<'info> {
 
     #[account(
         mut,
-        mint::decimals = 6,
+        mint::decimals = protocol.credit_decimals,
         mint::authority = program_authority,
+        mint::token_program = token_program,
         constraint = credit_mint.key() == protocol.credit_mint @ CredXError::InvalidCreditMint
     )]
-    pub credit_mint: Account<'info, Mint>,
+    pub credit_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         associated_token::mint = credit_mint,
-        associated_token::authority = user
+        associated_token::authority = user,
+        associated_token::token_program = token_program
     )]
-    pub user_credit_ata: Account<'info, TokenAccount>,
+    pub user_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = credit_mint,
+        associated_token::authority = program_authority,
+        associated_token::token_program = token_program
+    )]
+    pub protocol_credit_ata: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         seeds = [b"collateral_vault", user.key().as_ref()],
         bump,
-        constraint = collateral_vault.mint != credit_mint.key() @ CredXError::InvalidCollateralMint
+        constraint = collateral_vault.mint != credit_mint.key() @ CredXError::InvalidCollateralMint,
+        constraint = collateral_vault.is_current() @ CredXError::UnsupportedAccountVersion
     )]
     pub collateral_vault: Account<'info, CollateralVault>,
 
+    #[account(
+        mut,
+        seeds = [b"collateral_config", protocol.key().as_ref(), collateral_vault.mint.as_ref()],
+        bump = collateral_config.bump,
+        constraint = !collateral_config.circuit_breaker.tripped @ CredXError::CircuitBreakerTripped
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+
     #[account(
         mut,
         seeds = [b"loan", user.key().as_ref(), collateral_vault.key().as_ref()],
         bump,
         constraint = loan_account.user == user.key() @ CredXError::UnauthorizedUser,
-        constraint = loan_account.collateral_amount > 0 @ CredXError::NoCollateralDeposited
+        constraint = loan_account.collateral_amount > 0 @ CredXError::NoCollateralDeposited,
+        constraint = loan_account.is_current() @ CredXError::UnsupportedAccountVersion,
+        constraint = loan_account.protocol == protocol.key() @ CredXError::LoanProtocolMismatch
     )]
     pub loan_account: Account<'info, LoanAccount>,
 
-    /// CHECK: Oracle price account is validated by comparing its key with the stored oracle_price_account in loan_account
-    // #[account(
-    //     constraint = oracle_price_account.key() == loan_account.oracle_price_account @ CredXError::InvalidOracleAccount
-    // )]
-    // pub oracle_price_account: AccountInfo<'info>,
-    pub oracle_price_account: Account<'info, SimplePriceOracle>,
+    #[account(mut)]
+    pub position_delegate: Option<Account<'info, PositionDelegate>>,
+
+    /// CHECK: Primary price source, parsed and checked against the collateral's oracle binding
+    #[account(
+        constraint = oracle_price_account.key() == collateral_config.oracle.primary @ CredXError::InvalidOracleAccount
+    )]
+    pub oracle_price_account: UncheckedAccount<'info>,
+
+    /// CHECK: Secondary price source, required when the collateral has one configured
+    #[account(
+        constraint = secondary_oracle_account.key() == collateral_config.oracle.secondary @ CredXError::InvalidOracleAccount
+    )]
+    pub secondary_oracle_account: Option<UncheckedAccount<'info>>,
 
     pub associated_token_program: Program<'info, AssociatedToken>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> LendCreditToken<'info> {
-    pub fn get_price(&mut self) -> Result<i64> {
-        // require!(
-        //     !self.oracle_price_account.data_is_empty(),
-        //     CredXError::EmptyOracleAccount
-        // );
-        // let price_feed = self
-        //     .oracle_price_account
-        //     .try_borrow_data()
-        //     .map_err(|_| CredXError::FailedToBorrowOracleData)?;
-
-        // let price_account: &GenericPriceAccount<32, Price> =
-        //     load_price_account(&price_feed).map_err(|_| CredXError::FailedToLoadPriceAccount)?;
-
-        // let price_status = price_account.agg.status;
-        // require!(
-        //     price_status == pyth_sdk_solana::state::PriceStatus::Trading,
-        //     CredXError::InvalidPriceStatus
-        // );
-
-        // let current_price = price_account.agg.price;
-        // let expo = price_account.expo;
-        // require!(current_price > 0, CredXError::InvalidPrice);
-
-        // let current_time = Clock::get()?.unix_timestamp;
-        // let price_time = price_account.agg.pub_slot as i64;
-        let price = self.oracle_price_account.price;
-
-        require!(price > 0, CredXError::InvalidPrice);
-
-        let current_time = Clock::get()?.unix_timestamp;
-        let price_time = self.oracle_price_account.timestamp;
-        require!(current_time - price_time < 300, CredXError::StalePrice);
-
-        // let normalized_price = if expo < 0 {
-        //     current_price
-        //         .checked_div(10_i64.pow((-expo) as u32))
-        //         .ok_or(CredXError::MathOverflow)?
-        // } else {
-        //     current_price
-        //         .checked_mul(10_i64.pow(expo as u32))
-        //         .ok_or(CredXError::MathOverflow)?
-        // };
-
-        // Ok(normalized_price)
-        Ok(price as i64)
-    }
-
     pub fn lend_credit_token(&mut self, bumps: &LendCreditTokenBumps) -> Result<()> {
         require!(!self.protocol.is_locked, CredXError::ProtocolLocked);
         require!(
@@ -133,34 +121,51 @@ impl<'info> LendCreditToken<'info> {
             self.protocol.ltv_ratio_bps > 0 && self.protocol.ltv_ratio_bps <= 9000,
             CredXError::InvalidLtvRatio
         );
-
-        let price = self.get_price()? as u64;
-        let collateral_amount = self.loan_account.collateral_amount as u128;
-        let ltv_ratio = self.protocol.ltv_ratio_bps as u128;
-
-        let collateral_value = collateral_amount
-            .checked_mul(price as u128)
+        let delegated = authorize_position(
+            &self.user.key(),
+            &self.authority.key(),
+            self.position_delegate.as_deref(),
+            PositionAction::Borrow,
+        )?;
+
+        let quote = load_collateral_price(
+            &self.collateral_config,
+            &self.protocol,
+            &self.oracle_price_account,
+            self.secondary_oracle_account.as_deref(),
+            PriceUsage::Borrow,
+        )?;
+        self.collateral_config.observe_price(&quote)?;
+        let price = quote.collateral_price(&self.collateral_config.pricing)?;
+        // The fee is part of the debt but is minted to the protocol instead of the user
+        let BorrowQuote {
+            borrow_amount,
+            fee_amount,
+            user_amount,
+        } = borrow_quote(
+            self.loan_account.collateral_amount,
+            price,
+            self.protocol.ltv_ratio_bps,
+            self.loan_account.remaining_debt,
+            self.protocol.borrow_fee_bps,
+        )?;
+
+        let total_debt = self
+            .protocol
+            .total_debt
+            .checked_add(borrow_amount)
             .ok_or(CredXError::MathOverflow)?;
+        require!(
+            total_debt <= self.protocol.debt_ceiling,
+            CredXError::DebtCeilingExceeded
+        );
 
-        let borrow_value = collateral_value
-            .checked_mul(ltv_ratio)
-            .ok_or(CredXError::MathOverflow)?
-            .checked_div(10_000)
-            .ok_or(CredXError::MathOverflow)?;
-
-        let additional_borrowable = borrow_value
-            .checked_sub(self.loan_account.remaining_debt as u128)
-            .ok_or(CredXError::ExceedsMaxBorrow)?;
-
-        let borrow_amount =
-            u64::try_from(additional_borrowable).map_err(|_| CredXError::MathOverflow)?;
-
-        require!(borrow_value > 0, CredXError::ZeroBorrowAmount);
-
-        let max_borrowable = borrow_amount;
-        let current_debt = self.loan_account.remaining_debt;
+        if let Some(delegate) = self.position_delegate.as_mut().filter(|_| delegated) {
+            delegate.record_borrow(borrow_amount)?;
+        }
 
-        require!(additional_borrowable > 0, CredXError::MaxBorrowLimitReached);
+        let seeds = &[b"program_authority".as_ref(), &[bumps.program_authority]];
+        let signer_seeds = &[&seeds[..]];
 
         let accounts = MintTo {
             mint: self.credit_mint.to_account_info(),
@@ -168,38 +173,64 @@ impl<'info> LendCreditToken<'info> {
             authority: self.program_authority.to_account_info(),
         };
 
-        let seeds = &[b"program_authority".as_ref(), &[bumps.program_authority]];
-        let signer_seeds = &[&seeds[..]];
-
         let ctx = CpiContext::new_with_signer(
             self.token_program.to_account_info(),
             accounts,
             signer_seeds,
         );
 
-        mint_to(ctx, additional_borrowable as u64)?;
-
-        let approve_accounts = Approve {
-            to: self.user_credit_ata.to_account_info(),
-            delegate: self.program_authority.to_account_info(),
-            authority: self.user.to_account_info(), // User approves program authority
-        };
-
-        let approve_ctx = CpiContext::new(self.token_program.to_account_info(), approve_accounts);
-
-        // Approve the full borrowed amount for future automated repayment
-        approve(approve_ctx, borrow_amount)?;
-
+        mint_to(ctx, user_amount)?;
+
+        if fee_amount > 0 {
+            let fee_accounts = MintTo {
+                mint: self.credit_mint.to_account_info(),
+                to: self.protocol_credit_ata.to_account_info(),
+                authority: self.program_authority.to_account_info(),
+            };
+
+            let fee_ctx = CpiContext::new_with_signer(
+                self.token_program.to_account_info(),
+                fee_accounts,
+                signer_seeds,
+            );
+
+            mint_to(fee_ctx, fee_amount)?;
+        }
+
+        // A delegate cannot approve the owner's credit account, so borrows it
+        // makes alone rely on the approval the owner already granted
+        if self.user.is_signer {
+            let approve_accounts = Approve {
+                to: self.user_credit_ata.to_account_info(),
+                delegate: self.program_authority.to_account_info(),
+                authority: self.user.to_account_info(), // User approves program authority
+            };
+
+            let approve_ctx =
+                CpiContext::new(self.token_program.to_account_info(), approve_accounts);
+
+            // Approve the full borrowed amount for future automated repayment
+            approve(approve_ctx, borrow_amount)?;
+        }
+
+        let opens_loan = self.loan_account.remaining_debt == 0;
         self.loan_account.remaining_debt = self
             .loan_account
             .remaining_debt
-            .checked_add(additional_borrowable as u64)
+            .checked_add(borrow_amount)
             .ok_or(CredXError::MathOverflow)?;
+        self.protocol.total_debt = total_debt;
+        self.collateral_config
+            .track_loan(self.loan_account.key(), &self.loan_account);
+        self.protocol
+            .stats
+            .record_borrow(borrow_amount, opens_loan)?;
 
         msg!(
-            "Admin minted {} credit tokens to user: {}, Total debt: {}",
-            additional_borrowable,
+            "Admin minted {} credit tokens to user: {}, Fee: {}, Total debt: {}",
+            user_amount,
             self.user.key(),
+            fee_amount,
             self.loan_account.remaining_debt
         );
 
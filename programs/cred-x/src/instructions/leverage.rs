@@ -0,0 +1,304 @@
+use crate::{
+    borrow_limit, bps_of, collateral_value, error::CredXError, invoke_swap, leverage_borrow_amount,
+    load_collateral_price, CollateralConfig, CollateralVault, CreditTransferMode, LoanAccount,
+    PriceUsage, ProtocolState, SwapAccounts, Versioned,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{approve, mint_to, Approve, Mint, MintTo, TokenAccount, TokenInterface},
+};
+
+/// Borrows credit, swaps it into more of the loan's collateral and deposits
+/// it in one step. The credit is minted into a vault-owned account and only
+/// becomes debt once the swap has landed, so nothing is borrowed unless the
+/// whole loop succeeds. Only available while credit transfers are
+/// unrestricted, since the swap moves credit through accounts the transfer
+/// hook does not know about.
+#[derive(Accounts)]
+pub struct Leverage<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        constraint = admin.key() == protocol.admin @ CredXError::UnauthorizedAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = !protocol.is_locked @ CredXError::ProtocolLocked,
+        constraint = protocol.credit_mint == credit_mint.key() @ CredXError::InvalidCreditMint,
+        constraint = protocol.swap_program != Pubkey::default() @ CredXError::SwapProgramNotSet,
+        constraint = protocol.credit_transfer_mode == CreditTransferMode::Unrestricted @ CredXError::CreditTransfersRestricted,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    /// CHECK: PDA used as program authority
+    #[account(seeds = [b"program_authority"], bump)]
+    pub program_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        mint::authority = program_authority,
+        mint::token_program = credit_token_program
+    )]
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = credit_mint,
+        associated_token::authority = program_authority,
+        associated_token::token_program = credit_token_program
+    )]
+    pub protocol_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"collateral_vault", user.key().as_ref()],
+        bump,
+        constraint = collateral_vault.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub collateral_vault: Account<'info, CollateralVault>,
+
+    #[account(
+        mint::token_program = collateral_token_program,
+        constraint = collateral_mint.key() == collateral_vault.mint @ CredXError::MintMismatch
+    )]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral_config", protocol.key().as_ref(), collateral_mint.key().as_ref()],
+        bump = collateral_config.bump,
+        constraint = !collateral_config.circuit_breaker.tripped @ CredXError::CircuitBreakerTripped
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+
+    #[account(
+        mut,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = collateral_vault,
+        associated_token::token_program = collateral_token_program
+    )]
+    pub collateral_vault_ata: InterfaceAccount<'info, TokenAccount>,
+
+    // Holds the borrowed credit between the mint and the swap
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = credit_mint,
+        associated_token::authority = collateral_vault,
+        associated_token::token_program = credit_token_program
+    )]
+    pub vault_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"loan", user.key().as_ref(), collateral_vault.key().as_ref()],
+        bump,
+        constraint = loan_account.user == user.key() @ CredXError::UnauthorizedUser,
+        constraint = loan_account.collateral_amount > 0 @ CredXError::NoCollateralDeposited,
+        constraint = loan_account.is_current() @ CredXError::UnsupportedAccountVersion,
+        constraint = loan_account.protocol == protocol.key() @ CredXError::LoanProtocolMismatch
+    )]
+    pub loan_account: Account<'info, LoanAccount>,
+
+    /// CHECK: Primary price source, parsed and checked against the collateral's oracle binding
+    #[account(
+        constraint = oracle_price_account.key() == collateral_config.oracle.primary @ CredXError::InvalidOracleAccount
+    )]
+    pub oracle_price_account: UncheckedAccount<'info>,
+
+    /// CHECK: Secondary price source, required when the collateral has one configured
+    #[account(
+        constraint = secondary_oracle_account.key() == collateral_config.oracle.secondary @ CredXError::InvalidOracleAccount
+    )]
+    pub secondary_oracle_account: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Invoked with the swap adapter interface, must be the protocol's swap program
+    #[account(
+        executable,
+        constraint = swap_program.key() == protocol.swap_program @ CredXError::InvalidSwapProgram
+    )]
+    pub swap_program: UncheckedAccount<'info>,
+
+    pub credit_token_program: Interface<'info, TokenInterface>,
+    pub collateral_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> Leverage<'info> {
+    /// `swap_accounts` are passed through to the swap program after the
+    /// accounts of the adapter interface.
+    pub fn leverage(
+        &mut self,
+        target_ltv_bps: u16,
+        min_collateral_out: u64,
+        swap_accounts: &[AccountInfo<'info>],
+        bumps: &LeverageBumps,
+    ) -> Result<()> {
+        require!(
+            target_ltv_bps <= self.protocol.ltv_ratio_bps,
+            CredXError::InvalidTargetLtv
+        );
+
+        let quote = load_collateral_price(
+            &self.collateral_config,
+            &self.protocol,
+            &self.oracle_price_account,
+            self.secondary_oracle_account.as_deref(),
+            PriceUsage::Borrow,
+        )?;
+        self.collateral_config.observe_price(&quote)?;
+        let price = quote.collateral_price(&self.collateral_config.pricing)?;
+
+        let remaining_debt = self.loan_account.remaining_debt;
+        let borrow_amount = leverage_borrow_amount(
+            collateral_value(self.loan_account.collateral_amount, price),
+            remaining_debt,
+            target_ltv_bps,
+            self.protocol.borrow_fee_bps,
+        )?;
+        let fee_amount = bps_of(borrow_amount, self.protocol.borrow_fee_bps)?;
+        let swap_amount = borrow_amount
+            .checked_sub(fee_amount)
+            .ok_or(CredXError::MathUnderflow)?;
+        require!(swap_amount > 0, CredXError::ZeroBorrowAmount);
+
+        let total_debt = self
+            .protocol
+            .total_debt
+            .checked_add(borrow_amount)
+            .ok_or(CredXError::MathOverflow)?;
+        require!(
+            total_debt <= self.protocol.debt_ceiling,
+            CredXError::DebtCeilingExceeded
+        );
+
+        let authority_seeds = &[b"program_authority".as_ref(), &[bumps.program_authority]];
+        let authority_signer = &[&authority_seeds[..]];
+        for (to, amount) in [
+            (self.vault_credit_ata.to_account_info(), swap_amount),
+            (self.protocol_credit_ata.to_account_info(), fee_amount),
+        ] {
+            if amount == 0 {
+                continue;
+            }
+            mint_to(
+                CpiContext::new_with_signer(
+                    self.credit_token_program.to_account_info(),
+                    MintTo {
+                        mint: self.credit_mint.to_account_info(),
+                        to,
+                        authority: self.program_authority.to_account_info(),
+                    },
+                    authority_signer,
+                ),
+                amount,
+            )?;
+        }
+
+        let binding = self.user.key();
+        let vault_seeds = &[
+            b"collateral_vault",
+            binding.as_ref(),
+            &[bumps.collateral_vault],
+        ];
+        let vault_signer = &[&vault_seeds[..]];
+        let collateral_before = self.collateral_vault_ata.amount;
+
+        invoke_swap(
+            &self.swap_program,
+            SwapAccounts {
+                authority: self.collateral_vault.to_account_info(),
+                source: self.vault_credit_ata.to_account_info(),
+                destination: self.collateral_vault_ata.to_account_info(),
+                input_mint: self.credit_mint.to_account_info(),
+                output_mint: self.collateral_mint.to_account_info(),
+                input_token_program: self.credit_token_program.to_account_info(),
+                output_token_program: self.collateral_token_program.to_account_info(),
+            },
+            swap_accounts,
+            swap_amount,
+            min_collateral_out,
+            vault_signer,
+        )?;
+
+        self.vault_credit_ata.reload()?;
+        self.collateral_vault_ata.reload()?;
+        require!(
+            self.vault_credit_ata.amount == 0,
+            CredXError::SwapInputNotConsumed
+        );
+        let received = self
+            .collateral_vault_ata
+            .amount
+            .checked_sub(collateral_before)
+            .ok_or(CredXError::MathUnderflow)?;
+        require!(
+            received > 0 && received >= min_collateral_out,
+            CredXError::SlippageExceeded
+        );
+
+        let total_collateral = self
+            .collateral_config
+            .total_collateral
+            .checked_add(received)
+            .ok_or(CredXError::MathOverflow)?;
+        require!(
+            total_collateral <= self.collateral_config.supply_cap,
+            CredXError::CollateralSupplyCapExceeded
+        );
+        let collateral_amount = self
+            .loan_account
+            .collateral_amount
+            .checked_add(received)
+            .ok_or(CredXError::MathOverflow)?;
+        let new_debt = remaining_debt
+            .checked_add(borrow_amount)
+            .ok_or(CredXError::MathOverflow)?;
+
+        // The swap price decides where the loan lands, so the result must
+        // still be within the origination LTV
+        let limit = borrow_limit(
+            collateral_value(collateral_amount, price),
+            self.protocol.ltv_ratio_bps,
+        )?;
+        require!(u128::from(new_debt) <= limit, CredXError::ExceedsMaxBorrow);
+
+        approve(
+            CpiContext::new_with_signer(
+                self.collateral_token_program.to_account_info(),
+                Approve {
+                    to: self.collateral_vault_ata.to_account_info(),
+                    delegate: self.program_authority.to_account_info(),
+                    authority: self.collateral_vault.to_account_info(),
+                },
+                vault_signer,
+            ),
+            collateral_amount,
+        )?;
+
+        self.collateral_config.total_collateral = total_collateral;
+        self.loan_account.collateral_amount = collateral_amount;
+        self.loan_account.remaining_debt = new_debt;
+        self.collateral_config
+            .track_loan(self.loan_account.key(), &self.loan_account);
+        self.protocol.total_debt = total_debt;
+        self.protocol
+            .stats
+            .record_borrow(borrow_amount, remaining_debt == 0)?;
+
+        msg!(
+            "Leveraged user: {} - borrowed {} credit tokens, Fee: {}, swapped into {} collateral, Total debt: {}",
+            self.user.key(),
+            borrow_amount,
+            fee_amount,
+            received,
+            new_debt
+        );
+        Ok(())
+    }
+}
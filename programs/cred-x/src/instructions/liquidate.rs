@@ -0,0 +1,238 @@
+use crate::{
+    collateral_value, debt_collateral, error::CredXError, is_healthy, load_collateral_price,
+    CollateralConfig, CollateralVault, LoanAccount, PriceUsage, ProtocolState, StabilityPool,
+    Versioned,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    burn, transfer_checked, Burn, Mint, TokenAccount, TokenInterface, TransferChecked,
+};
+
+/// Cancels the debt of a loan below the liquidation threshold with credit
+/// from the collateral's stability pool. The pool's depositors receive the
+/// loan's collateral in proportion to the debt cancelled; when the pool
+/// cannot absorb all of it, the loan is liquidated in part. Anyone may call
+/// it.
+#[derive(Accounts)]
+pub struct Liquidate<'info> {
+    #[account(
+        mut,
+        constraint = !protocol.is_locked @ CredXError::ProtocolLocked,
+        constraint = protocol.credit_mint == credit_mint.key() @ CredXError::InvalidCreditMint,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        mint::token_program = credit_token_program
+    )]
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"stability_pool", protocol.key().as_ref(), collateral_mint.key().as_ref()],
+        bump = stability_pool.bump
+    )]
+    pub stability_pool: Account<'info, StabilityPool>,
+
+    #[account(
+        mut,
+        associated_token::mint = credit_mint,
+        associated_token::authority = stability_pool,
+        associated_token::token_program = credit_token_program
+    )]
+    pub pool_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = stability_pool,
+        associated_token::token_program = collateral_token_program
+    )]
+    pub pool_collateral_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"collateral_vault", loan_account.user.as_ref()],
+        bump = collateral_vault.bump,
+        constraint = collateral_vault.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub collateral_vault: Account<'info, CollateralVault>,
+
+    #[account(
+        mint::token_program = collateral_token_program,
+        constraint = collateral_mint.key() == collateral_vault.mint @ CredXError::MintMismatch
+    )]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral_config", protocol.key().as_ref(), collateral_mint.key().as_ref()],
+        bump = collateral_config.bump,
+        constraint = !collateral_config.circuit_breaker.tripped @ CredXError::CircuitBreakerTripped
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+
+    #[account(
+        mut,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = collateral_vault,
+        associated_token::token_program = collateral_token_program
+    )]
+    pub collateral_vault_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"loan", loan_account.user.as_ref(), collateral_vault.key().as_ref()],
+        bump = loan_account.bump,
+        constraint = loan_account.remaining_debt > 0 @ CredXError::NoOutstandingDebt,
+        constraint = loan_account.is_current() @ CredXError::UnsupportedAccountVersion,
+        constraint = loan_account.protocol == protocol.key() @ CredXError::LoanProtocolMismatch
+    )]
+    pub loan_account: Account<'info, LoanAccount>,
+
+    /// CHECK: Primary price source, parsed and checked against the collateral's oracle binding
+    #[account(
+        constraint = oracle_price_account.key() == collateral_config.oracle.primary @ CredXError::InvalidOracleAccount
+    )]
+    pub oracle_price_account: UncheckedAccount<'info>,
+
+    /// CHECK: Secondary price source, required when the collateral has one configured
+    #[account(
+        constraint = secondary_oracle_account.key() == collateral_config.oracle.secondary @ CredXError::InvalidOracleAccount
+    )]
+    pub secondary_oracle_account: Option<UncheckedAccount<'info>>,
+
+    pub credit_token_program: Interface<'info, TokenInterface>,
+    pub collateral_token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> Liquidate<'info> {
+    pub fn liquidate(&mut self) -> Result<()> {
+        let quote = load_collateral_price(
+            &self.collateral_config,
+            &self.protocol,
+            &self.oracle_price_account,
+            self.secondary_oracle_account.as_deref(),
+            PriceUsage::Valuation,
+        )?;
+        self.collateral_config.observe_price(&quote)?;
+        let price = quote.collateral_price(&self.collateral_config.pricing)?;
+        let debt_price = quote.debt_price(&self.collateral_config.pricing)?;
+
+        let remaining_debt = self.loan_account.remaining_debt;
+        let collateral_amount = self.loan_account.collateral_amount;
+        require!(
+            !is_healthy(
+                collateral_value(collateral_amount, price),
+                self.protocol.liquidation_threshold_bps,
+                remaining_debt
+            )?,
+            CredXError::LoanHealthy
+        );
+
+        // The pool always keeps a whole credit token so its product never
+        // reaches zero
+        let min_remaining = 10u64
+            .checked_pow(u32::from(self.protocol.credit_decimals))
+            .ok_or(CredXError::MathOverflow)?;
+        let offset = remaining_debt.min(self.stability_pool.offset_capacity(min_remaining));
+        require!(offset > 0, CredXError::StabilityPoolEmpty);
+        let seized = if offset == remaining_debt {
+            collateral_amount
+        } else {
+            let pro_rata = u64::try_from(
+                u128::from(collateral_amount) * u128::from(offset) / u128::from(remaining_debt),
+            )
+            .map_err(|_| CredXError::MathOverflow)?;
+            // An underwater loan's pro-rata share is worth less than the debt
+            // the pool cancels, so the pool takes at least that debt's worth
+            pro_rata
+                .max(debt_collateral(offset, debt_price)?)
+                .min(collateral_amount)
+        };
+
+        let protocol_key = self.protocol.key();
+        let mint_key = self.collateral_mint.key();
+        let pool_seeds = &[
+            b"stability_pool",
+            protocol_key.as_ref(),
+            mint_key.as_ref(),
+            &[self.stability_pool.bump],
+        ];
+        burn(
+            CpiContext::new_with_signer(
+                self.credit_token_program.to_account_info(),
+                Burn {
+                    mint: self.credit_mint.to_account_info(),
+                    from: self.pool_credit_ata.to_account_info(),
+                    authority: self.stability_pool.to_account_info(),
+                },
+                &[&pool_seeds[..]],
+            ),
+            offset,
+        )?;
+
+        let user = self.loan_account.user;
+        let vault_seeds = &[
+            b"collateral_vault",
+            user.as_ref(),
+            &[self.collateral_vault.bump],
+        ];
+        let pool_balance_before = self.pool_collateral_ata.amount;
+        if seized > 0 {
+            transfer_checked(
+                CpiContext::new_with_signer(
+                    self.collateral_token_program.to_account_info(),
+                    TransferChecked {
+                        from: self.collateral_vault_ata.to_account_info(),
+                        mint: self.collateral_mint.to_account_info(),
+                        to: self.pool_collateral_ata.to_account_info(),
+                        authority: self.collateral_vault.to_account_info(),
+                    },
+                    &[&vault_seeds[..]],
+                ),
+                seized,
+                self.collateral_mint.decimals,
+            )?;
+        }
+
+        // Token-2022 mints with a transfer fee withhold part of `seized`, so
+        // depositors are only credited what the pool actually received
+        self.pool_collateral_ata.reload()?;
+        let received = self
+            .pool_collateral_ata
+            .amount
+            .checked_sub(pool_balance_before)
+            .ok_or(CredXError::MathUnderflow)?;
+        self.stability_pool.offset(offset, received)?;
+
+        let new_debt = remaining_debt - offset;
+        self.loan_account.remaining_debt = new_debt;
+        self.loan_account.collateral_amount = collateral_amount - seized;
+        self.collateral_config
+            .track_loan(self.loan_account.key(), &self.loan_account);
+        self.collateral_config.total_collateral = self
+            .collateral_config
+            .total_collateral
+            .checked_sub(seized)
+            .ok_or(CredXError::MathUnderflow)?;
+        self.protocol.total_debt = self
+            .protocol
+            .total_debt
+            .checked_sub(offset)
+            .ok_or(CredXError::MathUnderflow)?;
+        if new_debt == 0 {
+            self.protocol.stats.close_loan()?;
+        }
+
+        msg!(
+            "Liquidated user: {} - stability pool cancelled {} debt for {} collateral, remaining debt: {}",
+            user,
+            offset,
+            seized,
+            new_debt
+        );
+        Ok(())
+    }
+}
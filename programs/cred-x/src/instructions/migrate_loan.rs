@@ -0,0 +1,55 @@
+use crate::{error::CredXError, migrate_account, CollateralVault, LoanAccount};
+use anchor_lang::prelude::*;
+
+/// Grows a user's loan and collateral vault to the current layout. Anyone may
+/// pay for the migration.
+#[derive(Accounts)]
+pub struct MigrateLoan<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: Only used to derive the loan PDAs
+    pub user: UncheckedAccount<'info>,
+
+    /// CHECK: Owner and discriminator are checked before the account is resized
+    #[account(
+        mut,
+        seeds = [b"collateral_vault", user.key().as_ref()],
+        bump
+    )]
+    pub collateral_vault: UncheckedAccount<'info>,
+
+    /// CHECK: Owner and discriminator are checked before the account is resized
+    #[account(
+        mut,
+        seeds = [b"loan", user.key().as_ref(), collateral_vault.key().as_ref()],
+        bump
+    )]
+    pub loan_account: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MigrateLoan<'info> {
+    pub fn migrate_loan(&mut self) -> Result<()> {
+        let payer = self.payer.to_account_info();
+        let system_program = self.system_program.to_account_info();
+        let vault_migrated = migrate_account::<CollateralVault>(
+            &self.collateral_vault.to_account_info(),
+            &payer,
+            &system_program,
+        )?;
+        let loan_migrated = migrate_account::<LoanAccount>(
+            &self.loan_account.to_account_info(),
+            &payer,
+            &system_program,
+        )?;
+        require!(
+            vault_migrated || loan_migrated,
+            CredXError::AccountAlreadyMigrated
+        );
+
+        msg!("Loan migrated for user: {}", self.user.key());
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+use crate::{
+    check_account_type, error::CredXError, grow_account, validate_loan_ratios, CreditTransferMode,
+    ProtocolState, ProtocolStateV0, ProtocolStats, Versioned,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+/// Settings the original layout never stored. The debt totals must be
+/// reconciled off-chain from the open loans, since repayments subtract from
+/// them.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct MigrateProtocolParams {
+    pub liquidation_threshold_bps: u16,
+    pub max_price_age_secs: i64,
+    pub debt_ceiling: u64,
+    pub total_debt: u64,
+    pub open_loans: u64,
+    // Every loan account opened under the original layout, with or without
+    // debt. `bind_loan` binds at most this many, owing at most `total_debt`
+    pub legacy_loans: u64,
+}
+
+impl MigrateProtocolParams {
+    pub fn to_protocol_state(&self, legacy: ProtocolStateV0, credit_decimals: u8) -> ProtocolState {
+        ProtocolState {
+            admin: legacy.admin,
+            ltv_ratio_bps: legacy.ltv_ratio_bps,
+            liquidation_threshold_bps: self.liquidation_threshold_bps,
+            credit_mint: legacy.credit_mint,
+            credit_decimals,
+            max_price_age_secs: self.max_price_age_secs,
+            borrow_fee_bps: 0,
+            debt_ceiling: self.debt_ceiling,
+            total_debt: self.total_debt,
+            is_locked: legacy.is_locked,
+            bump: legacy.bump,
+            credit_transfer_mode: CreditTransferMode::Unrestricted,
+            stats: ProtocolStats {
+                open_loans: self.open_loans,
+                ..Default::default()
+            },
+            version: ProtocolState::VERSION,
+            flash_fee_bps: 0,
+            swap_program: Pubkey::default(),
+            redemption_fee_bps: 0,
+            total_redeemed: 0,
+            original_admin: Pubkey::default(),
+            governance_delay_secs: 0,
+            param_changes_queued: 0,
+            unbound_legacy_loans: self.legacy_loans,
+            unbound_legacy_debt: self.total_debt,
+            reserved: [0; 20],
+        }
+    }
+}
+
+/// Rewrites a protocol created before account versioning in the current
+/// layout.
+#[derive(Accounts)]
+pub struct MigrateProtocol<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// CHECK: Owner and discriminator are checked before the account is decoded,
+    /// then its address against the seed admin it records
+    #[account(mut)]
+    pub protocol: UncheckedAccount<'info>,
+
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MigrateProtocol<'info> {
+    pub fn migrate_protocol(&mut self, params: MigrateProtocolParams) -> Result<()> {
+        let account = self.protocol.to_account_info();
+        check_account_type::<ProtocolState>(&account)?;
+
+        let space = 8 + ProtocolState::INIT_SPACE;
+        if account.data_len() == space {
+            let state = ProtocolState::try_deserialize(&mut &account.try_borrow_data()?[..])?;
+            self.check_protocol(state.seed_admin(), state.bump, state.admin)?;
+            require!(
+                state.version() <= ProtocolState::VERSION,
+                CredXError::UnsupportedAccountVersion
+            );
+            return err!(CredXError::AccountAlreadyMigrated);
+        }
+        require!(
+            account.data_len() == 8 + ProtocolStateV0::INIT_SPACE,
+            CredXError::UnsupportedAccountVersion
+        );
+
+        // The admin could not be rotated before versioning, so it is also
+        // the seed admin
+        let legacy = ProtocolStateV0::deserialize(&mut &account.try_borrow_data()?[8..])?;
+        self.check_protocol(legacy.admin, legacy.bump, legacy.admin)?;
+        require_keys_eq!(
+            legacy.credit_mint,
+            self.credit_mint.key(),
+            CredXError::InvalidCreditMint
+        );
+        validate_loan_ratios(legacy.ltv_ratio_bps, params.liquidation_threshold_bps)?;
+        require!(
+            params.max_price_age_secs > 0,
+            CredXError::InvalidStalenessWindow
+        );
+
+        grow_account(
+            &account,
+            &self.admin.to_account_info(),
+            &self.system_program.to_account_info(),
+            space,
+        )?;
+        let state = params.to_protocol_state(legacy, self.credit_mint.decimals);
+        state.try_serialize(&mut &mut account.try_borrow_mut_data()?[..])?;
+
+        msg!(
+            "Protocol {} migrated, total debt: {}, open loans: {}, legacy loans: {}",
+            self.protocol.key(),
+            params.total_debt,
+            params.open_loans,
+            params.legacy_loans
+        );
+        Ok(())
+    }
+
+    /// Seeds are checked here rather than in the accounts struct, since
+    /// `seed_admin` has to be read from whichever layout the account is in.
+    fn check_protocol(&self, seed_admin: Pubkey, bump: u8, admin: Pubkey) -> Result<()> {
+        let address = Pubkey::create_program_address(
+            &[b"protocol", seed_admin.as_ref(), &[bump]],
+            &crate::ID,
+        )
+        .map_err(|_| ErrorCode::ConstraintSeeds)?;
+        require_keys_eq!(address, self.protocol.key(), ErrorCode::ConstraintSeeds);
+        require_keys_eq!(admin, self.admin.key(), CredXError::UnauthorizedAdmin);
+        Ok(())
+    }
+}
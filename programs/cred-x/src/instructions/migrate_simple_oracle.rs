@@ -0,0 +1,63 @@
+use crate::{
+    error::CredXError, migrate_account, CollateralConfig, ProtocolState, SimplePriceOracle,
+    Versioned,
+};
+use anchor_lang::prelude::*;
+
+/// Grows a `SimplePriceOracle` to the current layout; the oracle keeps its
+/// last reading. The original layout recorded no authority, so the admin
+/// names the signer allowed to publish prices from then on. Only an oracle
+/// one of the protocol's collaterals is priced by can be claimed this way.
+#[derive(Accounts)]
+pub struct MigrateSimpleOracle<'info> {
+    #[account(
+        mut,
+        constraint = admin.key() == protocol.admin @ CredXError::UnauthorizedAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    #[account(
+        seeds = [b"collateral_config", protocol.key().as_ref(), collateral_config.mint.as_ref()],
+        bump = collateral_config.bump,
+        constraint = collateral_config.oracle.primary == price_account.key()
+            || collateral_config.oracle.secondary == price_account.key()
+            @ CredXError::InvalidOracleAccount
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+
+    /// CHECK: Owner and discriminator are checked before the account is resized
+    #[account(mut)]
+    pub price_account: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> MigrateSimpleOracle<'info> {
+    pub fn migrate_simple_oracle(&mut self, authority: Pubkey) -> Result<()> {
+        let account = self.price_account.to_account_info();
+        let migrated = migrate_account::<SimplePriceOracle>(
+            &account,
+            &self.admin.to_account_info(),
+            &self.system_program.to_account_info(),
+        )?;
+        require!(migrated, CredXError::AccountAlreadyMigrated);
+
+        let mut oracle = SimplePriceOracle::try_deserialize(&mut &account.try_borrow_data()?[..])?;
+        oracle.authority = authority;
+        oracle.try_serialize(&mut &mut account.try_borrow_mut_data()?[..])?;
+
+        msg!(
+            "Oracle {} migrated, authority: {}",
+            self.price_account.key(),
+            authority
+        );
+        Ok(())
+    }
+}
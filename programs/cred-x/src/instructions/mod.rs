@@ -1,13 +1,89 @@
+pub mod bind_loan;
+pub mod cancel_param_change;
+pub mod claim_stability_gains;
+pub mod configure_collateral;
+pub mod credit_transfer_hook;
 pub mod cron_repayment;
+pub mod deleverage;
 pub mod deposit_collateral;
+pub mod deposit_to_stability_pool;
+pub mod execute_param_change;
+pub mod flash_mint;
+pub mod flash_repay;
+pub mod fund_insurance;
+pub mod initialize_credit_transfer_hook;
 pub mod initialize_loan;
 pub mod initialize_protocol;
+pub mod initialize_protocol_with_mint;
+pub mod initialize_stability_pool;
 pub mod lend_credit_token;
+pub mod leverage;
+pub mod liquidate;
+pub mod migrate_loan;
+pub mod migrate_protocol;
+pub mod migrate_simple_oracle;
+pub mod queue_param_change;
+pub mod redeem_credit;
+pub mod repay_debt;
+pub mod reset_circuit_breaker;
+pub mod resolve_bad_debt;
+pub mod sell_fund_collateral;
+pub mod revoke_position_delegate;
+pub mod set_credit_allowlist;
+pub mod set_credit_transfer_mode;
+pub mod set_debt_ceiling;
+pub mod set_flash_fee;
+pub mod set_position_delegate;
+pub mod set_protocol_locked;
+pub mod set_redemption_fee;
+pub mod set_swap_program;
+pub mod swap_collateral;
+pub mod trip_circuit_breaker;
+pub mod update_credit_metadata;
 pub mod withdraw;
+pub mod withdraw_from_stability_pool;
 
+pub use bind_loan::*;
+pub use cancel_param_change::*;
+pub use claim_stability_gains::*;
+pub use configure_collateral::*;
+pub use credit_transfer_hook::*;
 pub use cron_repayment::*;
+pub use deleverage::*;
 pub use deposit_collateral::*;
+pub use deposit_to_stability_pool::*;
+pub use execute_param_change::*;
+pub use flash_mint::*;
+pub use flash_repay::*;
+pub use fund_insurance::*;
+pub use initialize_credit_transfer_hook::*;
 pub use initialize_loan::*;
 pub use initialize_protocol::*;
+pub use initialize_protocol_with_mint::*;
+pub use initialize_stability_pool::*;
 pub use lend_credit_token::*;
+pub use leverage::*;
+pub use liquidate::*;
+pub use migrate_loan::*;
+pub use migrate_protocol::*;
+pub use migrate_simple_oracle::*;
+pub use queue_param_change::*;
+pub use redeem_credit::*;
+pub use repay_debt::*;
+pub use reset_circuit_breaker::*;
+pub use resolve_bad_debt::*;
+pub use sell_fund_collateral::*;
+pub use revoke_position_delegate::*;
+pub use set_credit_allowlist::*;
+pub use set_credit_transfer_mode::*;
+pub use set_debt_ceiling::*;
+pub use set_flash_fee::*;
+pub use set_position_delegate::*;
+pub use set_protocol_locked::*;
+pub use set_redemption_fee::*;
+pub use set_swap_program::*;
+pub use swap_collateral::*;
+pub use trip_circuit_breaker::*;
+pub use update_credit_metadata::*;
 pub use withdraw::*;
+pub use withdraw_from_stability_pool::*;
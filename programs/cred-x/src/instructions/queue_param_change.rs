@@ -0,0 +1,72 @@
+use crate::{
+    error::CredXError, ParamChange, ParamChangeQueued, PendingParamChange, ProtocolState, Versioned,
+};
+use anchor_lang::prelude::*;
+
+/// Records a parameter change that can be executed once the protocol's
+/// governance delay has passed.
+#[derive(Accounts)]
+pub struct QueueParamChange<'info> {
+    #[account(
+        mut,
+        constraint = admin.key() == protocol.admin @ CredXError::UnauthorizedAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + PendingParamChange::INIT_SPACE,
+        seeds = [
+            b"param_change",
+            protocol.key().as_ref(),
+            &protocol.param_changes_queued.to_le_bytes()
+        ],
+        bump
+    )]
+    pub pending_change: Account<'info, PendingParamChange>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> QueueParamChange<'info> {
+    pub fn queue_param_change(
+        &mut self,
+        change: ParamChange,
+        bumps: &QueueParamChangeBumps,
+    ) -> Result<()> {
+        change.validate(&self.protocol)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let eta = now
+            .checked_add(self.protocol.governance_delay_secs)
+            .ok_or(CredXError::MathOverflow)?;
+        let id = self.protocol.param_changes_queued;
+        self.protocol.param_changes_queued = id.checked_add(1).ok_or(CredXError::MathOverflow)?;
+        self.pending_change.set_inner(PendingParamChange {
+            protocol: self.protocol.key(),
+            id,
+            change: change.clone(),
+            queued_at: now,
+            eta,
+            bump: bumps.pending_change,
+        });
+
+        emit!(ParamChangeQueued {
+            protocol: self.protocol.key(),
+            id,
+            change,
+            eta,
+        });
+        msg!("Parameter change {} queued, executable from: {}", id, eta);
+        Ok(())
+    }
+}
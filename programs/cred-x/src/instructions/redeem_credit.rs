@@ -0,0 +1,296 @@
+use crate::{
+    covers_debt, error::CredXError, load_collateral_price, redemption_payout, CollateralConfig,
+    CollateralVault, LoanAccount, PriceUsage, ProtocolState, Versioned,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::{get_associated_token_address_with_program_id, AssociatedToken},
+    token_interface::{
+        burn, transfer_checked, Burn, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+/// Burns credit in exchange for collateral at the oracle price, less the
+/// redemption fee. The collateral comes out of the riskiest loans of one
+/// collateral mint, whose debt is cancelled by the redeemed amount. Loans
+/// below the collateral's `RedemptionMark` are refused, so the riskiest loan
+/// cannot be left out.
+///
+/// Known limitation: the mark follows the loan holding it down when that
+/// loan de-risks, and nothing raises it again until a riskier loan changes.
+/// Until then a riskier loan that has not changed can be skipped.
+#[derive(Accounts)]
+pub struct RedeemCredit<'info> {
+    #[account(mut)]
+    pub redeemer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = !protocol.is_locked @ CredXError::ProtocolLocked,
+        constraint = protocol.credit_mint == credit_mint.key() @ CredXError::InvalidCreditMint,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        mint::token_program = credit_token_program
+    )]
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = credit_mint,
+        associated_token::authority = redeemer,
+        associated_token::token_program = credit_token_program
+    )]
+    pub redeemer_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mint::token_program = collateral_token_program,
+        constraint = collateral_mint.key() != protocol.credit_mint @ CredXError::InvalidCollateralMint
+    )]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral_config", protocol.key().as_ref(), collateral_mint.key().as_ref()],
+        bump = collateral_config.bump,
+        constraint = !collateral_config.circuit_breaker.tripped @ CredXError::CircuitBreakerTripped
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = redeemer,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = redeemer,
+        associated_token::token_program = collateral_token_program
+    )]
+    pub redeemer_collateral_ata: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Primary price source, parsed and checked against the collateral's oracle binding
+    #[account(
+        constraint = oracle_price_account.key() == collateral_config.oracle.primary @ CredXError::InvalidOracleAccount
+    )]
+    pub oracle_price_account: UncheckedAccount<'info>,
+
+    /// CHECK: Secondary price source, required when the collateral has one configured
+    #[account(
+        constraint = secondary_oracle_account.key() == collateral_config.oracle.secondary @ CredXError::InvalidOracleAccount
+    )]
+    pub secondary_oracle_account: Option<UncheckedAccount<'info>>,
+
+    pub credit_token_program: Interface<'info, TokenInterface>,
+    pub collateral_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> RedeemCredit<'info> {
+    /// `loans` holds a loan account, its collateral vault and the vault's
+    /// token account for each loan redeemed against, ordered from the highest
+    /// LTV down. Loans are used up in order until `amount` is redeemed.
+    pub fn redeem_credit(
+        &mut self,
+        amount: u64,
+        min_collateral_out: u64,
+        loans: &'info [AccountInfo<'info>],
+    ) -> Result<()> {
+        require!(amount > 0, CredXError::InvalidAmount);
+        require!(
+            !loans.is_empty() && loans.len().is_multiple_of(3),
+            CredXError::InvalidRedemptionLoans
+        );
+        require!(
+            self.redeemer_credit_ata.amount >= amount,
+            CredXError::InsufficientCreditTokens
+        );
+
+        let quote = load_collateral_price(
+            &self.collateral_config,
+            &self.protocol,
+            &self.oracle_price_account,
+            self.secondary_oracle_account.as_deref(),
+            PriceUsage::Valuation,
+        )?;
+        self.collateral_config.observe_price(&quote)?;
+        let price = quote.collateral_price(&self.collateral_config.pricing)?;
+        // Paying out at the upper bound keeps an uncertain price from handing
+        // the redeemer more collateral than the credit is worth
+        let payout_price = quote.debt_price(&self.collateral_config.pricing)?;
+        let fee_bps = self.protocol.redemption_fee_bps;
+
+        let mut remaining = amount;
+        let mut total_redeemed = 0u64;
+        let mut total_paid = 0u64;
+        // (debt, collateral) of the previous loan, to hold the caller to the
+        // riskiest-first order
+        let mut previous: Option<(u64, u64)> = None;
+
+        for accounts in loans.chunks(3) {
+            if remaining == 0 {
+                break;
+            }
+            let (loan_info, vault_info, vault_ata_info) =
+                (&accounts[0], &accounts[1], &accounts[2]);
+            require!(
+                loan_info.is_writable && vault_ata_info.is_writable,
+                CredXError::InvalidRedemptionLoans
+            );
+            let mut loan = Account::<LoanAccount>::try_from(loan_info)?;
+            let vault = Account::<CollateralVault>::try_from(vault_info)?;
+            require!(
+                loan.is_current() && vault.is_current(),
+                CredXError::UnsupportedAccountVersion
+            );
+            let vault_key = Pubkey::create_program_address(
+                &[b"collateral_vault", loan.user.as_ref(), &[vault.bump]],
+                &crate::ID,
+            )
+            .map_err(|_| CredXError::InvalidRedemptionLoans)?;
+            let loan_key = Pubkey::create_program_address(
+                &[
+                    b"loan",
+                    loan.user.as_ref(),
+                    vault_key.as_ref(),
+                    &[loan.bump],
+                ],
+                &crate::ID,
+            )
+            .map_err(|_| CredXError::InvalidRedemptionLoans)?;
+            require_keys_eq!(vault_key, vault.key(), CredXError::InvalidRedemptionLoans);
+            require_keys_eq!(loan_key, loan.key(), CredXError::InvalidRedemptionLoans);
+            require_keys_eq!(
+                loan.protocol,
+                self.protocol.key(),
+                CredXError::LoanProtocolMismatch
+            );
+            require_keys_eq!(
+                vault.mint,
+                self.collateral_mint.key(),
+                CredXError::MintMismatch
+            );
+            require_keys_eq!(
+                vault_ata_info.key(),
+                get_associated_token_address_with_program_id(
+                    &vault_key,
+                    &vault.mint,
+                    &self.collateral_token_program.key(),
+                ),
+                CredXError::InvalidRedemptionLoans
+            );
+
+            let debt = loan.remaining_debt;
+            let collateral = loan.collateral_amount;
+            require!(debt > 0, CredXError::NoOutstandingDebt);
+            // Redeeming against an underwater loan would take collateral
+            // worth more than the debt it cancels
+            require!(
+                covers_debt(collateral, price, debt),
+                CredXError::UnderwaterLoan
+            );
+            // Skipping the riskiest loan is only allowed while it is
+            // underwater, as it cannot be redeemed against until liquidated
+            let mark = self.collateral_config.redemption_mark;
+            require!(
+                mark.admits(debt, collateral) || !covers_debt(mark.collateral, price, mark.debt),
+                CredXError::InvalidRedemptionLoans
+            );
+            if let Some((previous_debt, previous_collateral)) = previous {
+                require!(
+                    u128::from(debt) * u128::from(previous_collateral)
+                        <= u128::from(previous_debt) * u128::from(collateral),
+                    CredXError::InvalidRedemptionLoans
+                );
+            }
+            previous = Some((debt, collateral));
+
+            let redeemed = remaining.min(debt);
+            let paid = redemption_payout(redeemed, payout_price, fee_bps)?;
+            if paid > 0 {
+                let vault_seeds = &[b"collateral_vault", loan.user.as_ref(), &[vault.bump]];
+                transfer_checked(
+                    CpiContext::new_with_signer(
+                        self.collateral_token_program.to_account_info(),
+                        TransferChecked {
+                            from: vault_ata_info.clone(),
+                            mint: self.collateral_mint.to_account_info(),
+                            to: self.redeemer_collateral_ata.to_account_info(),
+                            authority: vault_info.clone(),
+                        },
+                        &[&vault_seeds[..]],
+                    ),
+                    paid,
+                    self.collateral_mint.decimals,
+                )?;
+            }
+
+            let new_debt = debt - redeemed;
+            loan.remaining_debt = new_debt;
+            loan.collateral_amount = collateral
+                .checked_sub(paid)
+                .ok_or(CredXError::MathUnderflow)?;
+            loan.exit(&crate::ID)?;
+            self.collateral_config.track_loan(loan.key(), &loan);
+            if new_debt == 0 {
+                self.protocol.stats.close_loan()?;
+            }
+
+            remaining -= redeemed;
+            total_redeemed += redeemed;
+            total_paid = total_paid
+                .checked_add(paid)
+                .ok_or(CredXError::MathOverflow)?;
+            msg!(
+                "Redeemed {} credit tokens against user: {}, collateral paid: {}, remaining debt: {}",
+                redeemed,
+                loan.user,
+                paid,
+                new_debt
+            );
+        }
+
+        require!(
+            total_paid > 0 && total_paid >= min_collateral_out,
+            CredXError::SlippageExceeded
+        );
+
+        burn(
+            CpiContext::new(
+                self.credit_token_program.to_account_info(),
+                Burn {
+                    mint: self.credit_mint.to_account_info(),
+                    from: self.redeemer_credit_ata.to_account_info(),
+                    authority: self.redeemer.to_account_info(),
+                },
+            ),
+            total_redeemed,
+        )?;
+
+        self.collateral_config.total_collateral = self
+            .collateral_config
+            .total_collateral
+            .checked_sub(total_paid)
+            .ok_or(CredXError::MathUnderflow)?;
+        self.protocol.total_debt = self
+            .protocol
+            .total_debt
+            .checked_sub(total_redeemed)
+            .ok_or(CredXError::MathUnderflow)?;
+        self.protocol.total_redeemed = self
+            .protocol
+            .total_redeemed
+            .checked_add(total_redeemed)
+            .ok_or(CredXError::MathOverflow)?;
+
+        msg!(
+            "Redeemer: {} burned {} credit tokens for {} collateral {}",
+            self.redeemer.key(),
+            total_redeemed,
+            total_paid,
+            self.collateral_mint.key()
+        );
+        Ok(())
+    }
+}
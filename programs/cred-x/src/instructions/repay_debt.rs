@@ -0,0 +1,123 @@
+use crate::{
+    authorize_position, error::CredXError, CollateralConfig, CollateralVault, LoanAccount,
+    PositionAction, PositionDelegate, ProtocolState, Versioned,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{burn, Burn, Mint, TokenAccount, TokenInterface};
+
+/// Burns credit from the signer's own account against a loan, without
+/// closing it. The signer is the owner or a delegate allowed to repay.
+#[derive(Accounts)]
+pub struct RepayDebt<'info> {
+    pub authority: Signer<'info>,
+
+    /// CHECK: Owner of the position, checked against `loan_account.user`
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = !protocol.is_locked @ CredXError::ProtocolLocked,
+        constraint = protocol.credit_mint == credit_mint.key() @ CredXError::InvalidCreditMint,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        mint::token_program = credit_token_program
+    )]
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = credit_mint,
+        token::authority = authority,
+        token::token_program = credit_token_program
+    )]
+    pub authority_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"collateral_vault", user.key().as_ref()],
+        bump,
+        constraint = collateral_vault.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub collateral_vault: Account<'info, CollateralVault>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral_config", protocol.key().as_ref(), collateral_vault.mint.as_ref()],
+        bump = collateral_config.bump
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"loan", user.key().as_ref(), collateral_vault.key().as_ref()],
+        bump,
+        constraint = loan_account.user == user.key() @ CredXError::UnauthorizedUser,
+        constraint = loan_account.is_current() @ CredXError::UnsupportedAccountVersion,
+        constraint = loan_account.protocol == protocol.key() @ CredXError::LoanProtocolMismatch
+    )]
+    pub loan_account: Account<'info, LoanAccount>,
+
+    pub position_delegate: Option<Account<'info, PositionDelegate>>,
+
+    pub credit_token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> RepayDebt<'info> {
+    pub fn repay_debt(&mut self, amount: u64) -> Result<()> {
+        require!(amount > 0, CredXError::InvalidAmount);
+        authorize_position(
+            &self.user.key(),
+            &self.authority.key(),
+            self.position_delegate.as_deref(),
+            PositionAction::Repay,
+        )?;
+
+        let remaining_debt = self.loan_account.remaining_debt;
+        require!(remaining_debt > 0, CredXError::NoActiveLoan);
+        let repaid = amount.min(remaining_debt);
+        require!(
+            self.authority_credit_ata.amount >= repaid,
+            CredXError::InsufficientCreditTokens
+        );
+
+        burn(
+            CpiContext::new(
+                self.credit_token_program.to_account_info(),
+                Burn {
+                    mint: self.credit_mint.to_account_info(),
+                    from: self.authority_credit_ata.to_account_info(),
+                    authority: self.authority.to_account_info(),
+                },
+            ),
+            repaid,
+        )?;
+
+        self.loan_account.remaining_debt = remaining_debt
+            .checked_sub(repaid)
+            .ok_or(CredXError::MathUnderflow)?;
+        self.collateral_config
+            .track_loan(self.loan_account.key(), &self.loan_account);
+        self.protocol.total_debt = self
+            .protocol
+            .total_debt
+            .checked_sub(repaid)
+            .ok_or(CredXError::MathUnderflow)?;
+        self.protocol
+            .stats
+            .record_user_repayment(repaid, self.loan_account.remaining_debt == 0)?;
+
+        msg!(
+            "Repaid {} credit tokens for user: {}, remaining debt: {}, paid by: {}",
+            repaid,
+            self.user.key(),
+            self.loan_account.remaining_debt,
+            self.authority.key()
+        );
+        Ok(())
+    }
+}
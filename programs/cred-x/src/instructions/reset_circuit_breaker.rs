@@ -0,0 +1,63 @@
+use crate::{
+    error::CredXError, load_collateral_price, CollateralConfig, PriceUsage, ProtocolState,
+    Versioned,
+};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct ResetCircuitBreaker<'info> {
+    #[account(
+        constraint = admin.key() == protocol.admin @ CredXError::UnauthorizedAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion,
+        constraint = !protocol.is_timelocked() @ CredXError::TimelockActive
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral_config", protocol.key().as_ref(), collateral_config.mint.as_ref()],
+        bump = collateral_config.bump
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+
+    /// CHECK: Primary price source, parsed and checked against the collateral's oracle binding
+    #[account(
+        constraint = oracle_price_account.key() == collateral_config.oracle.primary @ CredXError::InvalidOracleAccount
+    )]
+    pub oracle_price_account: UncheckedAccount<'info>,
+
+    /// CHECK: Secondary price source, required when the collateral has one configured
+    #[account(
+        constraint = secondary_oracle_account.key() == collateral_config.oracle.secondary @ CredXError::InvalidOracleAccount
+    )]
+    pub secondary_oracle_account: Option<UncheckedAccount<'info>>,
+}
+
+impl<'info> ResetCircuitBreaker<'info> {
+    pub fn reset_circuit_breaker(&mut self) -> Result<()> {
+        let quote = load_collateral_price(
+            &self.collateral_config,
+            &self.protocol,
+            &self.oracle_price_account,
+            self.secondary_oracle_account.as_deref(),
+            PriceUsage::Valuation,
+        )?;
+
+        self.collateral_config
+            .circuit_breaker
+            .accept(quote.price, quote.publish_time);
+
+        msg!(
+            "Circuit breaker re-armed for collateral {} at price {}",
+            self.collateral_config.mint,
+            quote.price
+        );
+        Ok(())
+    }
+}
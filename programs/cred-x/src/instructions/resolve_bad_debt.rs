@@ -0,0 +1,235 @@
+use crate::{
+    collateral_value, covers_debt, error::CredXError, load_collateral_price, CollateralConfig,
+    CollateralVault, InsuranceFund, LoanAccount, PriceUsage, ProtocolState, Versioned,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        burn, transfer_checked, Burn, Mint, TokenAccount, TokenInterface, TransferChecked,
+    },
+};
+
+/// Closes out a loan whose collateral is worth less than its debt. Anyone
+/// may call it: the insurance fund takes the whole vault and burns credit
+/// against the debt, and whatever the fund cannot cover is socialized.
+#[derive(Accounts)]
+pub struct ResolveBadDebt<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = !protocol.is_locked @ CredXError::ProtocolLocked,
+        constraint = protocol.credit_mint == credit_mint.key() @ CredXError::InvalidCreditMint,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [b"insurance_fund", protocol.key().as_ref()],
+        bump = insurance_fund.bump
+    )]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+
+    #[account(
+        mut,
+        mint::token_program = credit_token_program
+    )]
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = credit_mint,
+        associated_token::authority = insurance_fund,
+        associated_token::token_program = credit_token_program
+    )]
+    pub fund_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"collateral_vault", loan_account.user.as_ref()],
+        bump = collateral_vault.bump,
+        constraint = collateral_vault.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub collateral_vault: Account<'info, CollateralVault>,
+
+    #[account(
+        mint::token_program = collateral_token_program,
+        constraint = collateral_mint.key() == collateral_vault.mint @ CredXError::MintMismatch
+    )]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral_config", protocol.key().as_ref(), collateral_mint.key().as_ref()],
+        bump = collateral_config.bump,
+        constraint = !collateral_config.circuit_breaker.tripped @ CredXError::CircuitBreakerTripped
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+
+    #[account(
+        mut,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = collateral_vault,
+        associated_token::token_program = collateral_token_program
+    )]
+    pub collateral_vault_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = payer,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = insurance_fund,
+        associated_token::token_program = collateral_token_program
+    )]
+    pub fund_collateral_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"loan", loan_account.user.as_ref(), collateral_vault.key().as_ref()],
+        bump = loan_account.bump,
+        constraint = loan_account.remaining_debt > 0 @ CredXError::NoOutstandingDebt,
+        constraint = loan_account.is_current() @ CredXError::UnsupportedAccountVersion,
+        constraint = loan_account.protocol == protocol.key() @ CredXError::LoanProtocolMismatch
+    )]
+    pub loan_account: Account<'info, LoanAccount>,
+
+    /// CHECK: Primary price source, parsed and checked against the collateral's oracle binding
+    #[account(
+        constraint = oracle_price_account.key() == collateral_config.oracle.primary @ CredXError::InvalidOracleAccount
+    )]
+    pub oracle_price_account: UncheckedAccount<'info>,
+
+    /// CHECK: Secondary price source, required when the collateral has one configured
+    #[account(
+        constraint = secondary_oracle_account.key() == collateral_config.oracle.secondary @ CredXError::InvalidOracleAccount
+    )]
+    pub secondary_oracle_account: Option<UncheckedAccount<'info>>,
+
+    pub credit_token_program: Interface<'info, TokenInterface>,
+    pub collateral_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> ResolveBadDebt<'info> {
+    pub fn resolve_bad_debt(&mut self) -> Result<()> {
+        let quote = load_collateral_price(
+            &self.collateral_config,
+            &self.protocol,
+            &self.oracle_price_account,
+            self.secondary_oracle_account.as_deref(),
+            PriceUsage::Valuation,
+        )?;
+        self.collateral_config.observe_price(&quote)?;
+        let price = quote.collateral_price(&self.collateral_config.pricing)?;
+
+        // Same test `withdraw_collateral` applies, so only loans that could
+        // never be closed by their owner are written off
+        let seized = self.collateral_vault_ata.amount;
+        let remaining_debt = self.loan_account.remaining_debt;
+        require!(
+            !covers_debt(seized, price, remaining_debt),
+            CredXError::LoanNotUnderwater
+        );
+
+        let user = self.loan_account.user;
+        let vault_seeds = &[
+            b"collateral_vault",
+            user.as_ref(),
+            &[self.collateral_vault.bump],
+        ];
+        let fund_balance_before = self.fund_collateral_ata.amount;
+        if seized > 0 {
+            transfer_checked(
+                CpiContext::new_with_signer(
+                    self.collateral_token_program.to_account_info(),
+                    TransferChecked {
+                        from: self.collateral_vault_ata.to_account_info(),
+                        mint: self.collateral_mint.to_account_info(),
+                        to: self.fund_collateral_ata.to_account_info(),
+                        authority: self.collateral_vault.to_account_info(),
+                    },
+                    &[&vault_seeds[..]],
+                ),
+                seized,
+                self.collateral_mint.decimals,
+            )?;
+        }
+
+        // Token-2022 mints with a transfer fee withhold part of `seized`
+        self.fund_collateral_ata.reload()?;
+        let received = self
+            .fund_collateral_ata
+            .amount
+            .checked_sub(fund_balance_before)
+            .ok_or(CredXError::MathUnderflow)?;
+
+        // The fund pays the full debt for collateral worth less, so its loss
+        // is exactly the shortfall
+        let covered = remaining_debt.min(self.fund_credit_ata.amount);
+        let socialized = remaining_debt - covered;
+        if covered > 0 {
+            let protocol_key = self.protocol.key();
+            let fund_seeds = &[
+                b"insurance_fund",
+                protocol_key.as_ref(),
+                &[self.insurance_fund.bump],
+            ];
+            burn(
+                CpiContext::new_with_signer(
+                    self.credit_token_program.to_account_info(),
+                    Burn {
+                        mint: self.credit_mint.to_account_info(),
+                        from: self.fund_credit_ata.to_account_info(),
+                        authority: self.insurance_fund.to_account_info(),
+                    },
+                    &[&fund_seeds[..]],
+                ),
+                covered,
+            )?;
+        }
+
+        self.collateral_config.total_collateral = self
+            .collateral_config
+            .total_collateral
+            .checked_sub(self.loan_account.collateral_amount)
+            .ok_or(CredXError::MathUnderflow)?;
+        self.loan_account.collateral_amount = 0;
+        self.loan_account.remaining_debt = 0;
+        self.collateral_config
+            .track_loan(self.loan_account.key(), &self.loan_account);
+        self.protocol.total_debt = self
+            .protocol
+            .total_debt
+            .checked_sub(remaining_debt)
+            .ok_or(CredXError::MathUnderflow)?;
+        self.protocol.stats.close_loan()?;
+
+        let fund = &mut self.insurance_fund;
+        fund.total_covered = fund
+            .total_covered
+            .checked_add(covered)
+            .ok_or(CredXError::MathOverflow)?;
+        fund.total_socialized = fund
+            .total_socialized
+            .checked_add(socialized)
+            .ok_or(CredXError::MathOverflow)?;
+        fund.bad_debts_resolved = fund
+            .bad_debts_resolved
+            .checked_add(1)
+            .ok_or(CredXError::MathOverflow)?;
+
+        msg!(
+            "Resolved bad debt of user: {} - debt: {}, collateral received: {} worth {}, covered by fund: {}, socialized: {}",
+            user,
+            remaining_debt,
+            received,
+            collateral_value(received, price),
+            covered,
+            socialized
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,28 @@
+use crate::PositionDelegate;
+use anchor_lang::prelude::*;
+
+/// Removes a manager from the signer's position, returning the rent.
+#[derive(Accounts)]
+pub struct RevokePositionDelegate<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"position_delegate", owner.key().as_ref(), position_delegate.delegate.as_ref()],
+        bump = position_delegate.bump
+    )]
+    pub position_delegate: Account<'info, PositionDelegate>,
+}
+
+impl<'info> RevokePositionDelegate<'info> {
+    pub fn revoke_position_delegate(&mut self) -> Result<()> {
+        msg!(
+            "Position delegate {} revoked by owner: {}",
+            self.position_delegate.delegate,
+            self.owner.key()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+use crate::{
+    collateral_value, error::CredXError, load_collateral_price, CollateralConfig, InsuranceFund,
+    PriceUsage, ProtocolState, StabilityPool, Versioned,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    burn, mint_to, transfer_checked, Burn, Mint, MintTo, TokenAccount, TokenInterface,
+    TransferChecked,
+};
+
+/// Sells collateral the insurance fund seized from bad debt to the
+/// collateral's stability pool, turning it back into credit the fund can
+/// cover bad debt with. The pool pays the collateral's value at the lower
+/// bound of the price, and its depositors share the collateral as if it had
+/// been liquidated. Anyone may call it.
+#[derive(Accounts)]
+pub struct SellFundCollateral<'info> {
+    #[account(
+        constraint = !protocol.is_locked @ CredXError::ProtocolLocked,
+        constraint = protocol.credit_mint == credit_mint.key() @ CredXError::InvalidCreditMint,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [b"insurance_fund", protocol.key().as_ref()],
+        bump = insurance_fund.bump
+    )]
+    pub insurance_fund: Account<'info, InsuranceFund>,
+
+    /// CHECK: PDA used as program authority
+    #[account(seeds = [b"program_authority"], bump)]
+    pub program_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        mint::authority = program_authority,
+        mint::token_program = credit_token_program
+    )]
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = credit_mint,
+        associated_token::authority = insurance_fund,
+        associated_token::token_program = credit_token_program
+    )]
+    pub fund_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = insurance_fund,
+        associated_token::token_program = collateral_token_program
+    )]
+    pub fund_collateral_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"stability_pool", protocol.key().as_ref(), collateral_mint.key().as_ref()],
+        bump = stability_pool.bump
+    )]
+    pub stability_pool: Account<'info, StabilityPool>,
+
+    #[account(
+        mut,
+        associated_token::mint = credit_mint,
+        associated_token::authority = stability_pool,
+        associated_token::token_program = credit_token_program
+    )]
+    pub pool_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = stability_pool,
+        associated_token::token_program = collateral_token_program
+    )]
+    pub pool_collateral_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mint::token_program = collateral_token_program)]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral_config", protocol.key().as_ref(), collateral_mint.key().as_ref()],
+        bump = collateral_config.bump,
+        constraint = !collateral_config.circuit_breaker.tripped @ CredXError::CircuitBreakerTripped
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+
+    /// CHECK: Primary price source, parsed and checked against the collateral's oracle binding
+    #[account(
+        constraint = oracle_price_account.key() == collateral_config.oracle.primary @ CredXError::InvalidOracleAccount
+    )]
+    pub oracle_price_account: UncheckedAccount<'info>,
+
+    /// CHECK: Secondary price source, required when the collateral has one configured
+    #[account(
+        constraint = secondary_oracle_account.key() == collateral_config.oracle.secondary @ CredXError::InvalidOracleAccount
+    )]
+    pub secondary_oracle_account: Option<UncheckedAccount<'info>>,
+
+    pub credit_token_program: Interface<'info, TokenInterface>,
+    pub collateral_token_program: Interface<'info, TokenInterface>,
+}
+
+impl<'info> SellFundCollateral<'info> {
+    pub fn sell_fund_collateral(
+        &mut self,
+        amount: u64,
+        bumps: &SellFundCollateralBumps,
+    ) -> Result<()> {
+        require!(amount > 0, CredXError::InvalidAmount);
+        require!(
+            amount <= self.fund_collateral_ata.amount,
+            CredXError::InsufficientBalance
+        );
+        let quote = load_collateral_price(
+            &self.collateral_config,
+            &self.protocol,
+            &self.oracle_price_account,
+            self.secondary_oracle_account.as_deref(),
+            PriceUsage::Valuation,
+        )?;
+        self.collateral_config.observe_price(&quote)?;
+        let price = quote.collateral_price(&self.collateral_config.pricing)?;
+
+        let protocol_key = self.protocol.key();
+        let fund_seeds = &[
+            b"insurance_fund",
+            protocol_key.as_ref(),
+            &[self.insurance_fund.bump],
+        ];
+        let pool_balance_before = self.pool_collateral_ata.amount;
+        transfer_checked(
+            CpiContext::new_with_signer(
+                self.collateral_token_program.to_account_info(),
+                TransferChecked {
+                    from: self.fund_collateral_ata.to_account_info(),
+                    mint: self.collateral_mint.to_account_info(),
+                    to: self.pool_collateral_ata.to_account_info(),
+                    authority: self.insurance_fund.to_account_info(),
+                },
+                &[&fund_seeds[..]],
+            ),
+            amount,
+            self.collateral_mint.decimals,
+        )?;
+
+        // Token-2022 mints with a transfer fee withhold part of `amount`, so
+        // the pool only pays for what it received
+        self.pool_collateral_ata.reload()?;
+        let received = self
+            .pool_collateral_ata
+            .amount
+            .checked_sub(pool_balance_before)
+            .ok_or(CredXError::MathUnderflow)?;
+        let paid = u64::try_from(collateral_value(received, price))
+            .map_err(|_| CredXError::MathOverflow)?;
+        require!(paid > 0, CredXError::InvalidAmount);
+
+        // Same floor `liquidate` leaves in the pool
+        let min_remaining = 10u64
+            .checked_pow(u32::from(self.protocol.credit_decimals))
+            .ok_or(CredXError::MathOverflow)?;
+        require!(
+            paid <= self.stability_pool.offset_capacity(min_remaining),
+            CredXError::StabilityPoolEmpty
+        );
+        self.stability_pool.offset(paid, received)?;
+
+        // Burned from the pool and re-minted to the fund: a transfer would
+        // re-enter this program through the credit mint's transfer hook
+        let mint_key = self.collateral_mint.key();
+        let pool_seeds = &[
+            b"stability_pool",
+            protocol_key.as_ref(),
+            mint_key.as_ref(),
+            &[self.stability_pool.bump],
+        ];
+        burn(
+            CpiContext::new_with_signer(
+                self.credit_token_program.to_account_info(),
+                Burn {
+                    mint: self.credit_mint.to_account_info(),
+                    from: self.pool_credit_ata.to_account_info(),
+                    authority: self.stability_pool.to_account_info(),
+                },
+                &[&pool_seeds[..]],
+            ),
+            paid,
+        )?;
+        let authority_seeds = &[b"program_authority".as_ref(), &[bumps.program_authority]];
+        mint_to(
+            CpiContext::new_with_signer(
+                self.credit_token_program.to_account_info(),
+                MintTo {
+                    mint: self.credit_mint.to_account_info(),
+                    to: self.fund_credit_ata.to_account_info(),
+                    authority: self.program_authority.to_account_info(),
+                },
+                &[&authority_seeds[..]],
+            ),
+            paid,
+        )?;
+
+        self.insurance_fund.total_recovered = self
+            .insurance_fund
+            .total_recovered
+            .checked_add(paid)
+            .ok_or(CredXError::MathOverflow)?;
+
+        msg!(
+            "Insurance fund sold {} collateral to the stability pool for {} credit",
+            received,
+            paid
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+use crate::{error::CredXError, CreditAllowlistEntry, ProtocolState, Versioned};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+#[instruction(owner: Pubkey)]
+pub struct SetCreditAllowlist<'info> {
+    #[account(
+        mut,
+        constraint = admin.key() == protocol.admin @ CredXError::UnauthorizedAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = 8 + CreditAllowlistEntry::INIT_SPACE,
+        seeds = [b"credit_allowlist", protocol.key().as_ref(), owner.as_ref()],
+        bump
+    )]
+    pub allowlist_entry: Account<'info, CreditAllowlistEntry>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SetCreditAllowlist<'info> {
+    pub fn set_credit_allowlist(
+        &mut self,
+        owner: Pubkey,
+        allowed: bool,
+        bumps: &SetCreditAllowlistBumps,
+    ) -> Result<()> {
+        self.allowlist_entry.set_inner(CreditAllowlistEntry {
+            protocol: self.protocol.key(),
+            owner,
+            allowed,
+            bump: bumps.allowlist_entry,
+        });
+
+        msg!("Credit allow-list entry for {} set to: {}", owner, allowed);
+        Ok(())
+    }
+}
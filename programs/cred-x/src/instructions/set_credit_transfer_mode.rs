@@ -0,0 +1,47 @@
+use crate::{error::CredXError, CreditTransferMode, ProtocolState, Versioned};
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{
+    get_mint_extension_data, spl_token_2022::extension::transfer_hook::TransferHook, Mint,
+};
+
+/// True when the mint's transfer hook extension points at this program.
+pub fn credit_mint_uses_transfer_hook(credit_mint: &AccountInfo) -> bool {
+    get_mint_extension_data::<TransferHook>(credit_mint)
+        .map(|hook| Option::<Pubkey>::from(hook.program_id) == Some(crate::ID))
+        .unwrap_or(false)
+}
+
+#[derive(Accounts)]
+pub struct SetCreditTransferMode<'info> {
+    #[account(
+        constraint = admin.key() == protocol.admin @ CredXError::UnauthorizedAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = protocol.credit_mint == credit_mint.key() @ CredXError::InvalidCreditMint,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion,
+        constraint = !protocol.is_timelocked() @ CredXError::TimelockActive
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+}
+
+impl<'info> SetCreditTransferMode<'info> {
+    pub fn set_credit_transfer_mode(&mut self, mode: CreditTransferMode) -> Result<()> {
+        require!(
+            mode == CreditTransferMode::Unrestricted
+                || credit_mint_uses_transfer_hook(&self.credit_mint.to_account_info()),
+            CredXError::TransferHookNotEnabled
+        );
+
+        self.protocol.credit_transfer_mode = mode;
+
+        msg!("Credit transfer mode set to: {:?}", mode);
+        Ok(())
+    }
+}
@@ -0,0 +1,32 @@
+use crate::{error::CredXError, ProtocolState, Versioned};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct SetDebtCeiling<'info> {
+    #[account(
+        constraint = admin.key() == protocol.admin @ CredXError::UnauthorizedAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = !protocol.is_timelocked() @ CredXError::TimelockActive,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+}
+
+impl<'info> SetDebtCeiling<'info> {
+    pub fn set_debt_ceiling(&mut self, debt_ceiling: u64) -> Result<()> {
+        self.protocol.debt_ceiling = debt_ceiling;
+
+        msg!(
+            "Debt ceiling set to: {} (outstanding debt: {})",
+            debt_ceiling,
+            self.protocol.total_debt
+        );
+        Ok(())
+    }
+}
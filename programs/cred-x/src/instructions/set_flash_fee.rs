@@ -0,0 +1,29 @@
+use crate::{error::CredXError, ProtocolState, Versioned};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct SetFlashFee<'info> {
+    #[account(
+        constraint = admin.key() == protocol.admin @ CredXError::UnauthorizedAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = !protocol.is_timelocked() @ CredXError::TimelockActive,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+}
+
+impl<'info> SetFlashFee<'info> {
+    pub fn set_flash_fee(&mut self, flash_fee_bps: u16) -> Result<()> {
+        require!(flash_fee_bps <= 1000, CredXError::InvalidFee);
+        self.protocol.flash_fee_bps = flash_fee_bps;
+
+        msg!("Flash mint fee set to: {} bps", flash_fee_bps);
+        Ok(())
+    }
+}
@@ -0,0 +1,53 @@
+use crate::{error::CredXError, DelegatePermissions, PositionDelegate};
+use anchor_lang::prelude::*;
+
+/// Grants or updates a manager's permissions on the signer's position. The
+/// borrow limit caps everything the delegate has borrowed since the grant was
+/// created, so revoking and re-granting starts a fresh allowance.
+#[derive(Accounts)]
+#[instruction(delegate: Pubkey)]
+pub struct SetPositionDelegate<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + PositionDelegate::INIT_SPACE,
+        seeds = [b"position_delegate", owner.key().as_ref(), delegate.as_ref()],
+        bump
+    )]
+    pub position_delegate: Account<'info, PositionDelegate>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SetPositionDelegate<'info> {
+    pub fn set_position_delegate(
+        &mut self,
+        delegate: Pubkey,
+        permissions: DelegatePermissions,
+        borrow_limit: u64,
+        bumps: &SetPositionDelegateBumps,
+    ) -> Result<()> {
+        require_keys_neq!(delegate, self.owner.key(), CredXError::InvalidDelegate);
+
+        self.position_delegate.set_inner(PositionDelegate {
+            owner: self.owner.key(),
+            delegate,
+            permissions,
+            borrow_limit,
+            borrowed: self.position_delegate.borrowed,
+            bump: bumps.position_delegate,
+        });
+
+        msg!(
+            "Position delegate {} set for owner: {}, permissions: {:?}, borrow limit: {}",
+            delegate,
+            self.owner.key(),
+            permissions,
+            borrow_limit
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,28 @@
+use crate::{error::CredXError, ProtocolState, Versioned};
+use anchor_lang::prelude::*;
+
+/// Pauses or resumes deposits, borrowing, repayments and withdrawals.
+#[derive(Accounts)]
+pub struct SetProtocolLocked<'info> {
+    #[account(
+        constraint = admin.key() == protocol.admin @ CredXError::UnauthorizedAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+}
+
+impl<'info> SetProtocolLocked<'info> {
+    pub fn set_protocol_locked(&mut self, locked: bool) -> Result<()> {
+        self.protocol.is_locked = locked;
+
+        msg!("Protocol {}", if locked { "paused" } else { "unpaused" });
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+use crate::{error::CredXError, ProtocolState, Versioned};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct SetRedemptionFee<'info> {
+    #[account(
+        constraint = admin.key() == protocol.admin @ CredXError::UnauthorizedAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = !protocol.is_timelocked() @ CredXError::TimelockActive,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+}
+
+impl<'info> SetRedemptionFee<'info> {
+    pub fn set_redemption_fee(&mut self, redemption_fee_bps: u16) -> Result<()> {
+        require!(redemption_fee_bps <= 1000, CredXError::InvalidFee);
+        self.protocol.redemption_fee_bps = redemption_fee_bps;
+
+        msg!("Redemption fee set to: {} bps", redemption_fee_bps);
+        Ok(())
+    }
+}
@@ -0,0 +1,28 @@
+use crate::{error::CredXError, ProtocolState, Versioned};
+use anchor_lang::prelude::*;
+
+#[derive(Accounts)]
+pub struct SetSwapProgram<'info> {
+    #[account(
+        constraint = admin.key() == protocol.admin @ CredXError::UnauthorizedAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion,
+        constraint = !protocol.is_timelocked() @ CredXError::TimelockActive
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+}
+
+impl<'info> SetSwapProgram<'info> {
+    pub fn set_swap_program(&mut self, swap_program: Pubkey) -> Result<()> {
+        self.protocol.swap_program = swap_program;
+
+        msg!("Swap program set to: {}", swap_program);
+        Ok(())
+    }
+}
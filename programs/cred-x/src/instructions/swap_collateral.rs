@@ -0,0 +1,294 @@
+use crate::{
+    borrow_limit, collateral_value, error::CredXError, harvestable_yield, invoke_swap,
+    load_collateral_price, mul_div_floor, CollateralConfig, CollateralVault, LoanAccount,
+    PriceUsage, ProtocolState, SwapAccounts, Versioned, BPS_DENOMINATOR, MAX_SWAP_VALUE_LOSS_BPS,
+};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{approve, Approve, Mint, TokenAccount, TokenInterface},
+};
+
+/// Moves a position to another collateral mint without repaying it. The
+/// whole vault balance, yield included, is swapped through the protocol's
+/// swap program. `min_amount_out` must be worth all but
+/// `MAX_SWAP_VALUE_LOSS_BPS` of the swapped collateral, and the loan must stay
+/// within the origination LTV at the new collateral's price.
+#[derive(Accounts)]
+pub struct SwapCollateral<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = !protocol.is_locked @ CredXError::ProtocolLocked,
+        constraint = protocol.swap_program != Pubkey::default() @ CredXError::SwapProgramNotSet,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral_vault", user.key().as_ref()],
+        bump,
+        constraint = collateral_vault.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub collateral_vault: Account<'info, CollateralVault>,
+
+    #[account(
+        mut,
+        seeds = [b"loan", user.key().as_ref(), collateral_vault.key().as_ref()],
+        bump,
+        constraint = loan_account.user == user.key() @ CredXError::UnauthorizedUser,
+        constraint = loan_account.collateral_amount > 0 @ CredXError::NoCollateralDeposited,
+        constraint = loan_account.is_current() @ CredXError::UnsupportedAccountVersion,
+        constraint = loan_account.protocol == protocol.key() @ CredXError::LoanProtocolMismatch
+    )]
+    pub loan_account: Account<'info, LoanAccount>,
+
+    #[account(
+        mint::token_program = source_token_program,
+        constraint = source_mint.key() == collateral_vault.mint @ CredXError::MintMismatch
+    )]
+    pub source_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral_config", protocol.key().as_ref(), source_mint.key().as_ref()],
+        bump = source_config.bump,
+        constraint = !source_config.circuit_breaker.tripped @ CredXError::CircuitBreakerTripped
+    )]
+    pub source_config: Account<'info, CollateralConfig>,
+
+    #[account(
+        mut,
+        associated_token::mint = source_mint,
+        associated_token::authority = collateral_vault,
+        associated_token::token_program = source_token_program
+    )]
+    pub source_vault_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mint::token_program = target_token_program,
+        constraint = target_mint.key() != source_mint.key() @ CredXError::InvalidCollateralMint,
+        constraint = target_mint.key() != protocol.credit_mint @ CredXError::InvalidCollateralMint
+    )]
+    pub target_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral_config", protocol.key().as_ref(), target_mint.key().as_ref()],
+        bump = target_config.bump,
+        constraint = !target_config.circuit_breaker.tripped @ CredXError::CircuitBreakerTripped
+    )]
+    pub target_config: Account<'info, CollateralConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = target_mint,
+        associated_token::authority = collateral_vault,
+        associated_token::token_program = target_token_program
+    )]
+    pub target_vault_ata: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: This is a PDA derived from seeds, used as program authority for various operations
+    #[account(seeds = [b"program_authority"], bump)]
+    pub program_authority: UncheckedAccount<'info>,
+
+    /// CHECK: Primary price source of the current collateral
+    #[account(
+        constraint = source_oracle_account.key() == source_config.oracle.primary @ CredXError::InvalidOracleAccount
+    )]
+    pub source_oracle_account: UncheckedAccount<'info>,
+
+    /// CHECK: Secondary price source of the current collateral, when it has one
+    #[account(
+        constraint = source_secondary_oracle_account.key() == source_config.oracle.secondary @ CredXError::InvalidOracleAccount
+    )]
+    pub source_secondary_oracle_account: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Primary price source of the new collateral
+    #[account(
+        constraint = target_oracle_account.key() == target_config.oracle.primary @ CredXError::InvalidOracleAccount
+    )]
+    pub target_oracle_account: UncheckedAccount<'info>,
+
+    /// CHECK: Secondary price source of the new collateral, when it has one
+    #[account(
+        constraint = target_secondary_oracle_account.key() == target_config.oracle.secondary @ CredXError::InvalidOracleAccount
+    )]
+    pub target_secondary_oracle_account: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Invoked with the swap adapter interface, must be the protocol's swap program
+    #[account(
+        executable,
+        constraint = swap_program.key() == protocol.swap_program @ CredXError::InvalidSwapProgram
+    )]
+    pub swap_program: UncheckedAccount<'info>,
+
+    pub source_token_program: Interface<'info, TokenInterface>,
+    pub target_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> SwapCollateral<'info> {
+    /// `swap_accounts` are passed through to the swap program after the
+    /// accounts of the adapter interface.
+    pub fn swap_collateral(
+        &mut self,
+        min_amount_out: u64,
+        swap_accounts: &[AccountInfo<'info>],
+    ) -> Result<()> {
+        let source_quote = load_collateral_price(
+            &self.source_config,
+            &self.protocol,
+            &self.source_oracle_account,
+            self.source_secondary_oracle_account.as_deref(),
+            PriceUsage::Valuation,
+        )?;
+        // The new collateral backs the debt from here on, so it is held to
+        // the stricter borrow window
+        let target_quote = load_collateral_price(
+            &self.target_config,
+            &self.protocol,
+            &self.target_oracle_account,
+            self.target_secondary_oracle_account.as_deref(),
+            PriceUsage::Borrow,
+        )?;
+        self.source_config.observe_price(&source_quote)?;
+        self.target_config.observe_price(&target_quote)?;
+        let source_price = source_quote.collateral_price(&self.source_config.pricing)?;
+        let target_price = target_quote.collateral_price(&self.target_config.pricing)?;
+
+        let amount_in = self.source_vault_ata.amount;
+        let original_collateral = self.loan_account.collateral_amount;
+        let yield_earned = harvestable_yield(amount_in, original_collateral)?;
+
+        let value_in = collateral_value(amount_in, source_price);
+        let max_loss = mul_div_floor(
+            value_in,
+            u64::from(MAX_SWAP_VALUE_LOSS_BPS),
+            BPS_DENOMINATOR,
+        )?;
+        require!(
+            collateral_value(min_amount_out, target_price) >= value_in - max_loss,
+            CredXError::SwapValueLossTooHigh
+        );
+        let target_balance_before = self.target_vault_ata.amount;
+
+        let binding = self.user.key();
+        let vault_seeds = &[
+            b"collateral_vault",
+            binding.as_ref(),
+            &[self.collateral_vault.bump],
+        ];
+        let vault_signer = &[&vault_seeds[..]];
+
+        invoke_swap(
+            &self.swap_program,
+            SwapAccounts {
+                authority: self.collateral_vault.to_account_info(),
+                source: self.source_vault_ata.to_account_info(),
+                destination: self.target_vault_ata.to_account_info(),
+                input_mint: self.source_mint.to_account_info(),
+                output_mint: self.target_mint.to_account_info(),
+                input_token_program: self.source_token_program.to_account_info(),
+                output_token_program: self.target_token_program.to_account_info(),
+            },
+            swap_accounts,
+            amount_in,
+            min_amount_out,
+            vault_signer,
+        )?;
+
+        // Only trust balances: a Token-2022 fee or a misbehaving adapter can
+        // move less than it reports
+        self.source_vault_ata.reload()?;
+        self.target_vault_ata.reload()?;
+        require!(
+            self.source_vault_ata.amount == 0,
+            CredXError::SwapInputNotConsumed
+        );
+        let received = self
+            .target_vault_ata
+            .amount
+            .checked_sub(target_balance_before)
+            .ok_or(CredXError::MathUnderflow)?;
+        require!(
+            received > 0 && received >= min_amount_out,
+            CredXError::SlippageExceeded
+        );
+
+        let remaining_debt = self.loan_account.remaining_debt;
+        let value_out = collateral_value(received, target_price);
+        msg!(
+            "Swap value - given: {} USD, received: {} USD, Remaining debt: {} USD",
+            value_in,
+            value_out,
+            remaining_debt
+        );
+        // The loan is re-based on the new collateral, so it is held to the
+        // origination LTV as a fresh borrow would be
+        let limit = borrow_limit(value_out, self.protocol.ltv_ratio_bps)?;
+        require!(
+            u128::from(remaining_debt) <= limit,
+            CredXError::ExceedsMaxBorrow
+        );
+
+        let target_total = self
+            .target_config
+            .total_collateral
+            .checked_add(received)
+            .ok_or(CredXError::MathOverflow)?;
+        require!(
+            target_total <= self.target_config.supply_cap,
+            CredXError::CollateralSupplyCapExceeded
+        );
+        self.target_config.total_collateral = target_total;
+        self.source_config.total_collateral = self
+            .source_config
+            .total_collateral
+            .checked_sub(original_collateral)
+            .ok_or(CredXError::MathUnderflow)?;
+
+        approve(
+            CpiContext::new_with_signer(
+                self.target_token_program.to_account_info(),
+                Approve {
+                    to: self.target_vault_ata.to_account_info(),
+                    delegate: self.program_authority.to_account_info(),
+                    authority: self.collateral_vault.to_account_info(),
+                },
+                vault_signer,
+            ),
+            received,
+        )?;
+
+        // Yield in the old vault was swapped along with the principal, so it
+        // is realized here instead of by a later harvest
+        self.protocol.stats.record_yield(yield_earned)?;
+        self.loan_account.yield_earned = self
+            .loan_account
+            .yield_earned
+            .checked_add(yield_earned)
+            .ok_or(CredXError::MathOverflow)?;
+        self.loan_account.collateral_amount = received;
+        self.loan_account.oracle_price_account = self.target_config.oracle.primary;
+        self.collateral_vault.mint = self.target_mint.key();
+        self.source_config.untrack_loan(self.loan_account.key());
+        self.target_config
+            .track_loan(self.loan_account.key(), &self.loan_account);
+
+        msg!(
+            "Swapped {} collateral {} for {} collateral {} for user: {}",
+            amount_in,
+            self.source_mint.key(),
+            received,
+            self.target_mint.key(),
+            self.user.key()
+        );
+        Ok(())
+    }
+}
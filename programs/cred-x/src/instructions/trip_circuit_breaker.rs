@@ -0,0 +1,56 @@
+use crate::{
+    error::CredXError, load_collateral_price, CollateralConfig, PriceUsage, ProtocolState,
+    Versioned,
+};
+use anchor_lang::prelude::*;
+
+/// Saves a circuit breaker trip. Instructions that pause on a tripped breaker
+/// fail with `CircuitBreakerTripPending` when their price reading would trip
+/// it, which rolls the trip back; anyone may then record it here so they stay
+/// paused until the admin re-arms the breaker.
+#[derive(Accounts)]
+pub struct TripCircuitBreaker<'info> {
+    #[account(
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral_config", protocol.key().as_ref(), collateral_config.mint.as_ref()],
+        bump = collateral_config.bump,
+        constraint = !collateral_config.circuit_breaker.tripped @ CredXError::CircuitBreakerTripped
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+
+    /// CHECK: Primary price source, parsed and checked against the collateral's oracle binding
+    #[account(
+        constraint = oracle_price_account.key() == collateral_config.oracle.primary @ CredXError::InvalidOracleAccount
+    )]
+    pub oracle_price_account: UncheckedAccount<'info>,
+
+    /// CHECK: Secondary price source, required when the collateral has one configured
+    #[account(
+        constraint = secondary_oracle_account.key() == collateral_config.oracle.secondary @ CredXError::InvalidOracleAccount
+    )]
+    pub secondary_oracle_account: Option<UncheckedAccount<'info>>,
+}
+
+impl<'info> TripCircuitBreaker<'info> {
+    pub fn trip_circuit_breaker(&mut self) -> Result<()> {
+        let quote = load_collateral_price(
+            &self.collateral_config,
+            &self.protocol,
+            &self.oracle_price_account,
+            self.secondary_oracle_account.as_deref(),
+            PriceUsage::Valuation,
+        )?;
+        require!(
+            self.collateral_config.record_price(&quote)?,
+            CredXError::CircuitBreakerNotTripped
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{error::CredXError, CreditMetadata, ProtocolState, Versioned};
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{transfer, Transfer};
+use anchor_spl::token_interface::{
+    spl_token_2022::{
+        self,
+        extension::{BaseStateWithExtensions, StateWithExtensions},
+    },
+    spl_token_metadata_interface::state::{Field, TokenMetadata},
+    token_metadata_update_field, Mint, TokenInterface, TokenMetadataUpdateField,
+};
+
+#[derive(Accounts)]
+pub struct UpdateCreditMetadata<'info> {
+    #[account(
+        mut,
+        constraint = admin.key() == protocol.admin @ CredXError::UnauthorizedAdmin
+    )]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"protocol", protocol.seed_admin().as_ref()],
+        bump = protocol.bump,
+        constraint = protocol.credit_mint == credit_mint.key() @ CredXError::InvalidCreditMint,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion,
+        constraint = !protocol.is_timelocked() @ CredXError::TimelockActive
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    /// CHECK: PDA used as program authority
+    #[account(seeds = [b"program_authority"], bump)]
+    pub program_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        mint::token_program = token_program
+    )]
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        constraint = token_program.key() == spl_token_2022::ID @ CredXError::CreditMetadataNotSupported
+    )]
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> UpdateCreditMetadata<'info> {
+    pub fn update_credit_metadata(
+        &mut self,
+        metadata: CreditMetadata,
+        bumps: &UpdateCreditMetadataBumps,
+    ) -> Result<()> {
+        metadata.validate()?;
+
+        write_credit_metadata(
+            &self.admin.to_account_info(),
+            &self.credit_mint.to_account_info(),
+            &self.program_authority.to_account_info(),
+            &self.token_program.to_account_info(),
+            &self.system_program.to_account_info(),
+            &metadata,
+            bumps.program_authority,
+        )
+    }
+}
+
+fn current_metadata(credit_mint: &AccountInfo) -> Result<TokenMetadata> {
+    let data = credit_mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+
+    mint.get_variable_len_extension::<TokenMetadata>()
+        .map_err(|_| error!(CredXError::CreditMetadataNotSupported))
+}
+
+/// Rewrites the credit mint's Token-2022 metadata, topping up the mint's rent
+/// from `payer` first. Shared by the direct update and the timelocked one.
+pub fn write_credit_metadata<'info>(
+    payer: &AccountInfo<'info>,
+    credit_mint: &AccountInfo<'info>,
+    program_authority: &AccountInfo<'info>,
+    token_program: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    metadata: &CreditMetadata,
+    program_authority_bump: u8,
+) -> Result<()> {
+    require_keys_eq!(
+        token_program.key(),
+        spl_token_2022::ID,
+        CredXError::CreditMetadataNotSupported
+    );
+
+    let current = current_metadata(credit_mint)?;
+    let current_size = current.tlv_size_of()?;
+    let new_size = metadata.tlv_size(credit_mint.key(), program_authority.key())?;
+
+    // The token program reallocs the mint in place, so top up its rent first
+    let new_len = credit_mint
+        .data_len()
+        .checked_sub(current_size)
+        .and_then(|len| len.checked_add(new_size))
+        .ok_or(CredXError::MathOverflow)?;
+    let required_lamports = Rent::get()?.minimum_balance(new_len);
+    let shortfall = required_lamports.saturating_sub(credit_mint.lamports());
+
+    if shortfall > 0 {
+        transfer(
+            CpiContext::new(
+                system_program.clone(),
+                Transfer {
+                    from: payer.clone(),
+                    to: credit_mint.clone(),
+                },
+            ),
+            shortfall,
+        )?;
+    }
+
+    let seeds = &[b"program_authority".as_ref(), &[program_authority_bump]];
+    let signer_seeds = &[&seeds[..]];
+
+    for (field, value) in [
+        (Field::Name, metadata.name.clone()),
+        (Field::Symbol, metadata.symbol.clone()),
+        (Field::Uri, metadata.uri.clone()),
+    ] {
+        token_metadata_update_field(
+            CpiContext::new_with_signer(
+                token_program.clone(),
+                TokenMetadataUpdateField {
+                    program_id: token_program.clone(),
+                    metadata: credit_mint.clone(),
+                    update_authority: program_authority.clone(),
+                },
+                signer_seeds,
+            ),
+            field,
+            value,
+        )?;
+    }
+
+    msg!(
+        "Credit metadata updated: {} ({}) {}",
+        metadata.name,
+        metadata.symbol,
+        metadata.uri
+    );
+    Ok(())
+}
@@ -1,36 +1,48 @@
-use crate::{error::CredXError, CollateralVault, LoanAccount, ProtocolState, SimplePriceOracle};
+use crate::{
+    authorize_position, collateral_value, covers_debt, error::CredXError, harvestable_yield,
+    load_collateral_price, CollateralConfig, CollateralVault, LoanAccount, PositionAction,
+    PositionDelegate, PriceUsage, ProtocolState, Versioned,
+};
 use anchor_lang::prelude::*;
 use anchor_spl::{
     associated_token::AssociatedToken,
-    token::{
-        burn, close_account, transfer, Burn, CloseAccount, Mint, Token, TokenAccount, Transfer,
+    token_2022::spl_token_2022::extension::{
+        transfer_fee::TransferFeeAmount, BaseStateWithExtensions, StateWithExtensions,
+    },
+    token_interface::{
+        burn, close_account, harvest_withheld_tokens_to_mint, transfer_checked, Burn,
+        CloseAccount, HarvestWithheldTokensToMint, Mint, TokenAccount, TokenInterface,
+        TransferChecked,
     },
-};
-use pyth_sdk_solana::{
-    state::{load_price_account, GenericPriceAccount},
-    Price,
 };
 
 #[derive(Accounts)]
 pub struct WithdrawCollateral<'info> {
+    /// The position owner, or a delegate allowed to withdraw
+    pub authority: Signer<'info>,
+
+    /// CHECK: Owner of the position, checked against `loan_account.user`.
+    /// Collateral and the vault's rent always go back to it.
     #[account(
         mut,
         constraint = user.key() == loan_account.user @ CredXError::UnauthorizedUser
     )]
-    pub user: Signer<'info>,
+    pub user: UncheckedAccount<'info>,
 
     #[account(
         mut,
-        constraint = !protocol.is_locked @ CredXError::ProtocolLocked
+        constraint = !protocol.is_locked @ CredXError::ProtocolLocked,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
     )]
     pub protocol: Account<'info, ProtocolState>,
 
     #[account(
         mut,
         mint::authority = program_authority,
+        mint::token_program = credit_token_program,
         constraint = credit_mint.key() == protocol.credit_mint @ CredXError::InvalidCreditMint
     )]
-    pub credit_mint: Account<'info, Mint>,
+    pub credit_mint: InterfaceAccount<'info, Mint>,
     /// CHECK: This is a PDA derived from seeds, used as program authority for various operations
     #[account(seeds = [b"program_authority"], bump)]
     pub program_authority: UncheckedAccount<'info>,
@@ -39,105 +51,94 @@ pub struct WithdrawCollateral<'info> {
         mut,
         seeds = [b"collateral_vault", user.key().as_ref()],
         bump,
-        constraint = collateral_vault.mint != credit_mint.key() @ CredXError::InvalidCollateralMint
+        constraint = collateral_vault.mint != credit_mint.key() @ CredXError::InvalidCollateralMint,
+        constraint = collateral_vault.is_current() @ CredXError::UnsupportedAccountVersion
     )]
     pub collateral_vault: Account<'info, CollateralVault>,
 
+    #[account(
+        mut,
+        mint::token_program = collateral_token_program,
+        constraint = collateral_mint.key() == collateral_vault.mint @ CredXError::MintMismatch
+    )]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"collateral_config", protocol.key().as_ref(), collateral_vault.mint.as_ref()],
+        bump = collateral_config.bump,
+        constraint = !collateral_config.circuit_breaker.tripped @ CredXError::CircuitBreakerTripped
+    )]
+    pub collateral_config: Account<'info, CollateralConfig>,
+
     #[account(
         mut,
         associated_token::mint = collateral_vault.mint,
         associated_token::authority = collateral_vault,
+        associated_token::token_program = collateral_token_program,
         constraint = collateral_vault_ata.amount >= loan_account.collateral_amount @ CredXError::InsufficientCollateral
     )]
-    pub collateral_vault_ata: Account<'info, TokenAccount>,
+    pub collateral_vault_ata: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         associated_token::mint = collateral_vault.mint,
         associated_token::authority = user,
+        associated_token::token_program = collateral_token_program,
         constraint = user_collateral_ata.mint == collateral_vault.mint @ CredXError::MintMismatch
     )]
-    pub user_collateral_ata: Account<'info, TokenAccount>,
+    pub user_collateral_ata: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         seeds = [b"loan", user.key().as_ref(), collateral_vault.key().as_ref()],
         bump,
-        constraint = loan_account.user == user.key() @ CredXError::UnauthorizedUser
+        constraint = loan_account.user == user.key() @ CredXError::UnauthorizedUser,
+        constraint = loan_account.is_current() @ CredXError::UnsupportedAccountVersion,
+        constraint = loan_account.protocol == protocol.key() @ CredXError::LoanProtocolMismatch
     )]
     pub loan_account: Account<'info, LoanAccount>,
 
+    pub position_delegate: Option<Account<'info, PositionDelegate>>,
+
     #[account(
         mut,
         associated_token::mint = credit_mint,
-        associated_token::authority = user
+        associated_token::authority = user,
+        associated_token::token_program = credit_token_program
     )]
-    pub user_credit_ata: Account<'info, TokenAccount>,
+    pub user_credit_ata: InterfaceAccount<'info, TokenAccount>,
 
-    /// CHECK: Oracle price account is validated by comparing its key with the stored oracle_price_account in loan_account
-    // #[account(
-    //     constraint = oracle_price_account.key() == loan_account.oracle_price_account @ CredXError::InvalidOracleAccount
-    // )]
-    // pub oracle_price_account: AccountInfo<'info>,
-    pub oracle_price_account: Account<'info, SimplePriceOracle>,
+    /// CHECK: Primary price source, parsed and checked against the collateral's oracle binding
+    #[account(
+        constraint = oracle_price_account.key() == collateral_config.oracle.primary @ CredXError::InvalidOracleAccount
+    )]
+    pub oracle_price_account: UncheckedAccount<'info>,
+
+    /// CHECK: Secondary price source, required when the collateral has one configured
+    #[account(
+        constraint = secondary_oracle_account.key() == collateral_config.oracle.secondary @ CredXError::InvalidOracleAccount
+    )]
+    pub secondary_oracle_account: Option<UncheckedAccount<'info>>,
 
     pub associated_token_program: Program<'info, AssociatedToken>,
-    pub token_program: Program<'info, Token>,
+    pub credit_token_program: Interface<'info, TokenInterface>,
+    pub collateral_token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
 impl<'info> WithdrawCollateral<'info> {
-    pub fn get_price(&mut self) -> Result<i64> {
-        // require!(
-        //     !self.oracle_price_account.data_is_empty(),
-        //     CredXError::EmptyOracleAccount
-        // );
-        // let price_feed = self
-        //     .oracle_price_account
-        //     .try_borrow_data()
-        //     .map_err(|_| CredXError::FailedToBorrowOracleData)?;
-
-        // let price_account: &GenericPriceAccount<32, Price> =
-        //     load_price_account(&price_feed).map_err(|_| CredXError::FailedToLoadPriceAccount)?;
-
-        // let price_status = price_account.agg.status;
-        // require!(
-        //     price_status == pyth_sdk_solana::state::PriceStatus::Trading,
-        //     CredXError::InvalidPriceStatus
-        // );
-
-        // let current_price = price_account.agg.price;
-
-        // let expo = price_account.expo;
-
-        // require!(current_price > 0, CredXError::InvalidPrice);
-
-        // let current_time = Clock::get()?.unix_timestamp;
-        // let price_time = price_account.agg.pub_slot as i64;
-        // require!(current_time - price_time < 300, CredXError::StalePrice);
-
-        // let normalized_price = if expo < 0 {
-        //     current_price
-        //         .checked_div(10_i64.pow((-expo) as u32))
-        //         .ok_or(CredXError::MathOverflow)?
-        // } else {
-        //     current_price
-        //         .checked_mul(10_i64.pow(expo as u32))
-        //         .ok_or(CredXError::MathOverflow)?
-        // };
-
-        let current_price = self.oracle_price_account.price as u64;
-
-        require!(current_price > 0, CredXError::InvalidPrice);
-
-        let current_time = Clock::get()?.unix_timestamp;
-        let price_time = self.oracle_price_account.timestamp;
-        require!(current_time - price_time < 300, CredXError::StalePrice);
-
-        require!(current_price > 0, CredXError::InvalidPrice);
-
-        // Ok(normalized_price)
-        Ok(current_price as i64)
+    pub fn vault_has_withheld_fees(&self) -> Result<bool> {
+        let vault_info = self.collateral_vault_ata.to_account_info();
+        let data = vault_info.try_borrow_data()?;
+        let vault = StateWithExtensions::<
+            anchor_spl::token_2022::spl_token_2022::state::Account,
+        >::unpack(&data)?;
+
+        Ok(vault
+            .get_extension::<TransferFeeAmount>()
+            .map(|fee| u64::from(fee.withheld_amount) > 0)
+            .unwrap_or(false))
     }
 
     pub fn withdraw_collateral(&mut self, bumps: &WithdrawCollateralBumps) -> Result<()> {
@@ -146,66 +147,76 @@ impl<'info> WithdrawCollateral<'info> {
             self.user.key() == self.loan_account.user,
             CredXError::UnauthorizedUser
         );
+        authorize_position(
+            &self.user.key(),
+            &self.authority.key(),
+            self.position_delegate.as_deref(),
+            PositionAction::Withdraw,
+        )?;
 
         let vault_balance = self.collateral_vault_ata.amount;
         let remaining_debt = self.loan_account.remaining_debt;
 
-        require!(remaining_debt > 0, CredXError::NoActiveLoan);
-
-        msg!(
-            "Checking withdrawal eligibility - Vault balance: {}, Remaining debt: {}",
-            vault_balance,
-            remaining_debt
-        );
-
-        let normalized_price = self.get_price()? as u128;
-
-        let vault_balance_u128 = vault_balance as u128;
-        let collateral_value_usd = vault_balance_u128
-            .checked_mul(normalized_price)
-            .ok_or(CredXError::MathOverflow)?;
-
-        let collateral_value_u64 = if collateral_value_usd > u64::MAX as u128 {
-            u64::MAX
-        } else {
-            collateral_value_usd as u64
-        };
-
-        msg!(
-            "Current collateral value: {} USD, Remaining debt: {} USD, Price: {}",
-            collateral_value_u64,
-            remaining_debt,
-            normalized_price
-        );
-
-        require!(
-            collateral_value_u64 >= remaining_debt,
-            CredXError::InsufficientCollateralValue
-        );
-
+        // A loan whose debt was already paid off, e.g. by repayment or
+        // redemption, only has its collateral left to return
         require!(
-            self.user_credit_ata.amount >= remaining_debt,
-            CredXError::InsufficientCreditTokens
+            remaining_debt > 0 || self.loan_account.collateral_amount > 0,
+            CredXError::NoActiveLoan
         );
 
-        let burn_accounts = Burn {
-            mint: self.credit_mint.to_account_info(),
-            from: self.user_credit_ata.to_account_info(),
-            authority: self.program_authority.to_account_info(),
-        };
-
-        let authority_seeds = &[b"program_authority".as_ref(), &[bumps.program_authority]];
-        let signer_seeds = &[&authority_seeds[..]];
-
-        let burn_ctx = CpiContext::new_with_signer(
-            self.token_program.to_account_info(),
-            burn_accounts,
-            signer_seeds,
-        );
-
-        burn(burn_ctx, remaining_debt)?;
-
-        msg!("Repaid full debt: {}", remaining_debt);
+        if remaining_debt > 0 {
+            msg!(
+                "Checking withdrawal eligibility - Vault balance: {}, Remaining debt: {}",
+                vault_balance,
+                remaining_debt
+            );
+
+            let quote = load_collateral_price(
+                &self.collateral_config,
+                &self.protocol,
+                &self.oracle_price_account,
+                self.secondary_oracle_account.as_deref(),
+                PriceUsage::Valuation,
+            )?;
+            self.collateral_config.observe_price(&quote)?;
+            let normalized_price = quote.collateral_price(&self.collateral_config.pricing)?;
+
+            msg!(
+                "Current collateral value: {} USD, Remaining debt: {} USD, Price: {}",
+                collateral_value(vault_balance, normalized_price),
+                remaining_debt,
+                normalized_price
+            );
+
+            require!(
+                covers_debt(vault_balance, normalized_price, remaining_debt),
+                CredXError::InsufficientCollateralValue
+            );
+
+            require!(
+                self.user_credit_ata.amount >= remaining_debt,
+                CredXError::InsufficientCreditTokens
+            );
+
+            let burn_accounts = Burn {
+                mint: self.credit_mint.to_account_info(),
+                from: self.user_credit_ata.to_account_info(),
+                authority: self.program_authority.to_account_info(),
+            };
+
+            let authority_seeds = &[b"program_authority".as_ref(), &[bumps.program_authority]];
+            let signer_seeds = &[&authority_seeds[..]];
+
+            let burn_ctx = CpiContext::new_with_signer(
+                self.credit_token_program.to_account_info(),
+                burn_accounts,
+                signer_seeds,
+            );
+
+            burn(burn_ctx, remaining_debt)?;
+
+            msg!("Repaid full debt: {}", remaining_debt);
+        }
 
         let binding = self.user.key();
         let seeds = &[
@@ -216,19 +227,36 @@ impl<'info> WithdrawCollateral<'info> {
         let signer_seeds = &[&seeds[..]];
 
         let transfer_ctx = CpiContext::new_with_signer(
-            self.token_program.to_account_info(),
-            Transfer {
+            self.collateral_token_program.to_account_info(),
+            TransferChecked {
                 from: self.collateral_vault_ata.to_account_info(),
+                mint: self.collateral_mint.to_account_info(),
                 to: self.user_collateral_ata.to_account_info(),
                 authority: self.collateral_vault.to_account_info(),
             },
             signer_seeds,
         );
 
-        transfer(transfer_ctx, vault_balance)?;
+        transfer_checked(transfer_ctx, vault_balance, self.collateral_mint.decimals)?;
+
+        // A Token-2022 vault cannot be closed while it still holds withheld
+        // transfer fees, so sweep them to the mint first.
+        if self.vault_has_withheld_fees()? {
+            let harvest_ctx = CpiContext::new(
+                self.collateral_token_program.to_account_info(),
+                HarvestWithheldTokensToMint {
+                    token_program_id: self.collateral_token_program.to_account_info(),
+                    mint: self.collateral_mint.to_account_info(),
+                },
+            );
+            harvest_withheld_tokens_to_mint(
+                harvest_ctx,
+                vec![self.collateral_vault_ata.to_account_info()],
+            )?;
+        }
 
         let close_ctx = CpiContext::new_with_signer(
-            self.token_program.to_account_info(),
+            self.collateral_token_program.to_account_info(),
             CloseAccount {
                 account: self.collateral_vault_ata.to_account_info(),
                 destination: self.user.to_account_info(),
@@ -239,9 +267,26 @@ impl<'info> WithdrawCollateral<'info> {
         close_account(close_ctx)?;
 
         let original_collateral = self.loan_account.collateral_amount;
-        let yield_earned = vault_balance.checked_sub(original_collateral).unwrap_or(0);
+        let yield_earned = harvestable_yield(vault_balance, original_collateral)?;
+
+        self.collateral_config.total_collateral = self
+            .collateral_config
+            .total_collateral
+            .checked_sub(original_collateral)
+            .ok_or(CredXError::MathUnderflow)?;
+        self.protocol.total_debt = self
+            .protocol
+            .total_debt
+            .checked_sub(remaining_debt)
+            .ok_or(CredXError::MathUnderflow)?;
+        self.protocol
+            .stats
+            .record_user_repayment(remaining_debt, remaining_debt > 0)?;
+        self.protocol.stats.record_yield(yield_earned)?;
         self.loan_account.collateral_amount = 0;
         self.loan_account.remaining_debt = 0;
+        self.collateral_config
+            .track_loan(self.loan_account.key(), &self.loan_account);
         self.loan_account.yield_earned = self
             .loan_account
             .yield_earned
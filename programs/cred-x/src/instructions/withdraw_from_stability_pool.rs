@@ -0,0 +1,175 @@
+use crate::{error::CredXError, ProtocolState, StabilityDeposit, StabilityPool, Versioned};
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{
+        burn, mint_to, transfer_checked, Burn, Mint, MintTo, TokenAccount, TokenInterface,
+        TransferChecked,
+    },
+};
+
+/// Takes credit back out of a stability pool, up to what is left of the
+/// deposit after liquidations, and pays out its collateral gain.
+#[derive(Accounts)]
+pub struct WithdrawFromStabilityPool<'info> {
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    #[account(
+        constraint = !protocol.is_locked @ CredXError::ProtocolLocked,
+        constraint = protocol.credit_mint == credit_mint.key() @ CredXError::InvalidCreditMint,
+        constraint = protocol.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
+    pub protocol: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [b"stability_pool", protocol.key().as_ref(), collateral_mint.key().as_ref()],
+        bump = stability_pool.bump
+    )]
+    pub stability_pool: Account<'info, StabilityPool>,
+
+    #[account(
+        mut,
+        seeds = [b"stability_deposit", stability_pool.key().as_ref(), depositor.key().as_ref()],
+        bump = stability_deposit.bump
+    )]
+    pub stability_deposit: Account<'info, StabilityDeposit>,
+
+    /// CHECK: PDA used as program authority
+    #[account(seeds = [b"program_authority"], bump)]
+    pub program_authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        mint::authority = program_authority,
+        mint::token_program = credit_token_program
+    )]
+    pub credit_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = credit_mint,
+        associated_token::authority = depositor,
+        associated_token::token_program = credit_token_program
+    )]
+    pub depositor_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = credit_mint,
+        associated_token::authority = stability_pool,
+        associated_token::token_program = credit_token_program
+    )]
+    pub pool_credit_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mint::token_program = collateral_token_program)]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = stability_pool,
+        associated_token::token_program = collateral_token_program
+    )]
+    pub pool_collateral_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = depositor,
+        associated_token::mint = collateral_mint,
+        associated_token::authority = depositor,
+        associated_token::token_program = collateral_token_program
+    )]
+    pub depositor_collateral_ata: InterfaceAccount<'info, TokenAccount>,
+
+    pub credit_token_program: Interface<'info, TokenInterface>,
+    pub collateral_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl<'info> WithdrawFromStabilityPool<'info> {
+    /// `u64::MAX` withdraws the whole remaining deposit.
+    pub fn withdraw_from_stability_pool(
+        &mut self,
+        amount: u64,
+        bumps: &WithdrawFromStabilityPoolBumps,
+    ) -> Result<()> {
+        require!(amount > 0, CredXError::InvalidAmount);
+        let (compounded, gain) = self.stability_pool.settle(&mut self.stability_deposit)?;
+        require!(compounded > 0, CredXError::NoStabilityDeposit);
+        let withdrawn = amount.min(compounded);
+
+        let protocol_key = self.protocol.key();
+        let mint_key = self.collateral_mint.key();
+        let pool_seeds = &[
+            b"stability_pool",
+            protocol_key.as_ref(),
+            mint_key.as_ref(),
+            &[self.stability_pool.bump],
+        ];
+        let pool_signer = &[&pool_seeds[..]];
+        if gain > 0 {
+            transfer_checked(
+                CpiContext::new_with_signer(
+                    self.collateral_token_program.to_account_info(),
+                    TransferChecked {
+                        from: self.pool_collateral_ata.to_account_info(),
+                        mint: self.collateral_mint.to_account_info(),
+                        to: self.depositor_collateral_ata.to_account_info(),
+                        authority: self.stability_pool.to_account_info(),
+                    },
+                    pool_signer,
+                ),
+                gain,
+                self.collateral_mint.decimals,
+            )?;
+        }
+        // Burned from the pool and re-minted to the depositor: a transfer
+        // would re-enter this program through the credit mint's transfer hook
+        burn(
+            CpiContext::new_with_signer(
+                self.credit_token_program.to_account_info(),
+                Burn {
+                    mint: self.credit_mint.to_account_info(),
+                    from: self.pool_credit_ata.to_account_info(),
+                    authority: self.stability_pool.to_account_info(),
+                },
+                pool_signer,
+            ),
+            withdrawn,
+        )?;
+        let authority_seeds = &[b"program_authority".as_ref(), &[bumps.program_authority]];
+        mint_to(
+            CpiContext::new_with_signer(
+                self.credit_token_program.to_account_info(),
+                MintTo {
+                    mint: self.credit_mint.to_account_info(),
+                    to: self.depositor_credit_ata.to_account_info(),
+                    authority: self.program_authority.to_account_info(),
+                },
+                &[&authority_seeds[..]],
+            ),
+            withdrawn,
+        )?;
+
+        let remaining = compounded - withdrawn;
+        self.stability_pool.total_deposits = self
+            .stability_pool
+            .total_deposits
+            .checked_sub(withdrawn)
+            .ok_or(CredXError::MathUnderflow)?;
+        self.stability_pool
+            .snapshot(&mut self.stability_deposit, remaining);
+
+        msg!(
+            "Depositor: {} withdrew {} credit tokens from the stability pool, deposit: {}, collateral gain paid: {}",
+            self.depositor.key(),
+            withdrawn,
+            remaining,
+            gain
+        );
+        Ok(())
+    }
+}
@@ -1,14 +1,24 @@
 #![allow(deprecated)]
 use anchor_lang::prelude::*;
+use spl_discriminator::SplDiscriminate;
+use spl_transfer_hook_interface::instruction::ExecuteInstruction;
 pub mod constants;
 pub mod error;
+pub mod events;
 pub mod instructions;
+pub mod math;
+pub mod oracle;
 pub mod state;
+pub mod swap;
 
 pub use constants::*;
 pub use error::*;
+pub use events::*;
 pub use instructions::*;
+pub use math::*;
+pub use oracle::*;
 pub use state::*;
+pub use swap::*;
 
 declare_id!("Ces2ZsycAiQy79EKb9JPcCVosr3FzvrzWEpEy9XRZif5");
 
@@ -16,8 +26,18 @@ declare_id!("Ces2ZsycAiQy79EKb9JPcCVosr3FzvrzWEpEy9XRZif5");
 pub mod cred_x {
     use super::*;
 
-    pub fn initialize_protocol(ctx: Context<InitializeProtocol>) -> Result<()> {
-        ctx.accounts.initialize_protocol(&ctx.bumps)
+    pub fn initialize_protocol(
+        ctx: Context<InitializeProtocol>,
+        params: InitializeProtocolParams,
+    ) -> Result<()> {
+        ctx.accounts.initialize_protocol(params, &ctx.bumps)
+    }
+
+    pub fn initialize_protocol_with_mint(
+        ctx: Context<InitializeProtocolWithMint>,
+        params: InitializeProtocolParams,
+    ) -> Result<()> {
+        ctx.accounts.initialize_protocol_with_mint(params, &ctx.bumps)
     }
 
     pub fn initialize_loan(ctx: Context<InitializeLoan>, collateral_mint: Pubkey) -> Result<()> {
@@ -39,15 +59,250 @@ pub mod cred_x {
     pub fn withdraw_collateral(ctx: Context<WithdrawCollateral>) -> Result<()> {
         ctx.accounts.withdraw_collateral(&ctx.bumps)
     }
-    pub fn create_simple_oracle(ctx: Context<CreateSimpleOracle>, price: u64) -> Result<()> {
-        ctx.accounts.price_account.price = price;
-        ctx.accounts.price_account.timestamp = Clock::get()?.unix_timestamp;
+
+    pub fn repay_debt(ctx: Context<RepayDebt>, amount: u64) -> Result<()> {
+        ctx.accounts.repay_debt(amount)
+    }
+
+    pub fn set_position_delegate(
+        ctx: Context<SetPositionDelegate>,
+        delegate: Pubkey,
+        permissions: DelegatePermissions,
+        borrow_limit: u64,
+    ) -> Result<()> {
+        ctx.accounts
+            .set_position_delegate(delegate, permissions, borrow_limit, &ctx.bumps)
+    }
+
+    pub fn revoke_position_delegate(ctx: Context<RevokePositionDelegate>) -> Result<()> {
+        ctx.accounts.revoke_position_delegate()
+    }
+
+    pub fn swap_collateral<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SwapCollateral<'info>>,
+        min_amount_out: u64,
+    ) -> Result<()> {
+        ctx.accounts
+            .swap_collateral(min_amount_out, ctx.remaining_accounts)
+    }
+
+    pub fn leverage<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Leverage<'info>>,
+        target_ltv_bps: u16,
+        min_collateral_out: u64,
+    ) -> Result<()> {
+        ctx.accounts.leverage(
+            target_ltv_bps,
+            min_collateral_out,
+            ctx.remaining_accounts,
+            &ctx.bumps,
+        )
+    }
+
+    pub fn deleverage<'info>(
+        ctx: Context<'_, '_, 'info, 'info, Deleverage<'info>>,
+        target_ltv_bps: u16,
+        min_credit_out: u64,
+    ) -> Result<()> {
+        ctx.accounts.deleverage(
+            target_ltv_bps,
+            min_credit_out,
+            ctx.remaining_accounts,
+            &ctx.bumps,
+        )
+    }
+
+    pub fn redeem_credit<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RedeemCredit<'info>>,
+        amount: u64,
+        min_collateral_out: u64,
+    ) -> Result<()> {
+        ctx.accounts
+            .redeem_credit(amount, min_collateral_out, ctx.remaining_accounts)
+    }
+
+    pub fn fund_insurance(ctx: Context<FundInsurance>, amount: u64) -> Result<()> {
+        ctx.accounts.fund_insurance(amount, &ctx.bumps)
+    }
+
+    pub fn resolve_bad_debt(ctx: Context<ResolveBadDebt>) -> Result<()> {
+        ctx.accounts.resolve_bad_debt()
+    }
+
+    pub fn sell_fund_collateral(ctx: Context<SellFundCollateral>, amount: u64) -> Result<()> {
+        ctx.accounts.sell_fund_collateral(amount, &ctx.bumps)
+    }
+
+    pub fn initialize_stability_pool(ctx: Context<InitializeStabilityPool>) -> Result<()> {
+        ctx.accounts.initialize_stability_pool(&ctx.bumps)
+    }
+
+    pub fn deposit_to_stability_pool(
+        ctx: Context<DepositToStabilityPool>,
+        amount: u64,
+    ) -> Result<()> {
+        ctx.accounts.deposit_to_stability_pool(amount, &ctx.bumps)
+    }
+
+    pub fn withdraw_from_stability_pool(
+        ctx: Context<WithdrawFromStabilityPool>,
+        amount: u64,
+    ) -> Result<()> {
+        ctx.accounts.withdraw_from_stability_pool(amount, &ctx.bumps)
+    }
+
+    pub fn claim_stability_gains(ctx: Context<ClaimStabilityGains>) -> Result<()> {
+        ctx.accounts.claim_stability_gains()
+    }
+
+    pub fn liquidate(ctx: Context<Liquidate>) -> Result<()> {
+        ctx.accounts.liquidate()
+    }
+
+    pub fn flash_mint(ctx: Context<FlashMint>, amount: u64) -> Result<()> {
+        ctx.accounts.flash_mint(amount, &ctx.bumps)
+    }
+
+    pub fn flash_repay(ctx: Context<FlashRepay>, amount: u64) -> Result<()> {
+        ctx.accounts.flash_repay(amount, &ctx.bumps)
+    }
+
+    pub fn configure_collateral(
+        ctx: Context<ConfigureCollateral>,
+        params: CollateralConfigParams,
+    ) -> Result<()> {
+        ctx.accounts.configure_collateral(params, &ctx.bumps)
+    }
+
+    pub fn set_debt_ceiling(ctx: Context<SetDebtCeiling>, debt_ceiling: u64) -> Result<()> {
+        ctx.accounts.set_debt_ceiling(debt_ceiling)
+    }
+
+    pub fn set_flash_fee(ctx: Context<SetFlashFee>, flash_fee_bps: u16) -> Result<()> {
+        ctx.accounts.set_flash_fee(flash_fee_bps)
+    }
+
+    pub fn set_redemption_fee(
+        ctx: Context<SetRedemptionFee>,
+        redemption_fee_bps: u16,
+    ) -> Result<()> {
+        ctx.accounts.set_redemption_fee(redemption_fee_bps)
+    }
+
+    pub fn set_swap_program(ctx: Context<SetSwapProgram>, swap_program: Pubkey) -> Result<()> {
+        ctx.accounts.set_swap_program(swap_program)
+    }
+
+    pub fn queue_param_change(ctx: Context<QueueParamChange>, change: ParamChange) -> Result<()> {
+        ctx.accounts.queue_param_change(change, &ctx.bumps)
+    }
+
+    pub fn execute_param_change(ctx: Context<ExecuteParamChange>) -> Result<()> {
+        ctx.accounts.execute_param_change(&ctx.bumps)
+    }
+
+    pub fn cancel_param_change(ctx: Context<CancelParamChange>) -> Result<()> {
+        ctx.accounts.cancel_param_change()
+    }
+
+    pub fn set_protocol_locked(ctx: Context<SetProtocolLocked>, locked: bool) -> Result<()> {
+        ctx.accounts.set_protocol_locked(locked)
+    }
+
+    pub fn reset_circuit_breaker(ctx: Context<ResetCircuitBreaker>) -> Result<()> {
+        ctx.accounts.reset_circuit_breaker()
+    }
+
+    pub fn trip_circuit_breaker(ctx: Context<TripCircuitBreaker>) -> Result<()> {
+        ctx.accounts.trip_circuit_breaker()
+    }
+
+    pub fn update_credit_metadata(
+        ctx: Context<UpdateCreditMetadata>,
+        metadata: CreditMetadata,
+    ) -> Result<()> {
+        ctx.accounts.update_credit_metadata(metadata, &ctx.bumps)
+    }
+
+    pub fn initialize_credit_transfer_hook(
+        ctx: Context<InitializeCreditTransferHook>,
+    ) -> Result<()> {
+        ctx.accounts.initialize_credit_transfer_hook()
+    }
+
+    #[instruction(discriminator = ExecuteInstruction::SPL_DISCRIMINATOR_SLICE)]
+    pub fn credit_transfer_hook(ctx: Context<CreditTransferHook>, amount: u64) -> Result<()> {
+        ctx.accounts.credit_transfer_hook(amount)
+    }
+
+    pub fn set_credit_transfer_mode(
+        ctx: Context<SetCreditTransferMode>,
+        mode: CreditTransferMode,
+    ) -> Result<()> {
+        ctx.accounts.set_credit_transfer_mode(mode)
+    }
+
+    pub fn set_credit_allowlist(
+        ctx: Context<SetCreditAllowlist>,
+        owner: Pubkey,
+        allowed: bool,
+    ) -> Result<()> {
+        ctx.accounts.set_credit_allowlist(owner, allowed, &ctx.bumps)
+    }
+
+    pub fn migrate_protocol(
+        ctx: Context<MigrateProtocol>,
+        params: MigrateProtocolParams,
+    ) -> Result<()> {
+        ctx.accounts.migrate_protocol(params)
+    }
+
+    pub fn migrate_loan(ctx: Context<MigrateLoan>) -> Result<()> {
+        ctx.accounts.migrate_loan()
+    }
+
+    pub fn migrate_simple_oracle(
+        ctx: Context<MigrateSimpleOracle>,
+        authority: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.migrate_simple_oracle(authority)
+    }
+
+    pub fn bind_loan(ctx: Context<BindLoan>) -> Result<()> {
+        ctx.accounts.bind_loan()
+    }
+
+    pub fn create_simple_oracle(
+        ctx: Context<CreateSimpleOracle>,
+        price: u64,
+        conf: u64,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts.price_account.authority = ctx.accounts.authority.key();
+        ctx.accounts.price_account.version = SimplePriceOracle::VERSION;
+        ctx.accounts.price_account.record(price, conf, now);
+        msg!(
+            "Oracle {} price set to {}, conf: {}",
+            ctx.accounts.price_account.key(),
+            price,
+            conf
+        );
         Ok(())
     }
 
-    pub fn update_simple_oracle(ctx: Context<UpdateSimpleOracle>, price: u64) -> Result<()> {
-        ctx.accounts.price_account.price = price;
-        ctx.accounts.price_account.timestamp = Clock::get()?.unix_timestamp;
+    pub fn update_simple_oracle(
+        ctx: Context<UpdateSimpleOracle>,
+        price: u64,
+        conf: u64,
+    ) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts.price_account.record(price, conf, now);
+        msg!(
+            "Oracle {} price set to {}, conf: {}",
+            ctx.accounts.price_account.key(),
+            price,
+            conf
+        );
         Ok(())
     }
 }
@@ -60,7 +315,7 @@ pub struct CreateSimpleOracle<'info> {
     #[account(
         init,
         payer = authority,
-        space = 8 + 8 + 8, 
+        space = 8 + SimplePriceOracle::INIT_SPACE,
     )]
     pub price_account: Account<'info, SimplePriceOracle>,
 
@@ -72,12 +327,23 @@ pub struct UpdateSimpleOracle<'info> {
     #[account(mut)]
     pub authority: Signer<'info>,
 
-    #[account(mut)]
+    #[account(
+        mut,
+        has_one = authority @ CredXError::UnauthorizedOracleAuthority,
+        constraint = price_account.is_current() @ CredXError::UnsupportedAccountVersion
+    )]
     pub price_account: Account<'info, SimplePriceOracle>,
 }
 
 #[account]
+#[derive(InitSpace)]
 pub struct SimplePriceOracle {
     pub price: u64, 
-    pub timestamp: i64
+    pub timestamp: i64,
+    pub ema_price: u64,
+    pub conf: u64,
+    // Only this signer may publish prices to the account
+    pub authority: Pubkey,
+    pub version: u8,
+    pub reserved: [u8; 32],
 }
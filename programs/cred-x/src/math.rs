@@ -0,0 +1,191 @@
+use anchor_lang::prelude::*;
+
+use crate::error::CredXError;
+
+/// Basis points in 100%.
+pub const BPS_DENOMINATOR: u128 = 10_000;
+
+/// Split of a `lend_credit_token` call: the loan's debt grows by
+/// `borrow_amount`, of which the user receives `user_amount` and the
+/// protocol `fee_amount`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BorrowQuote {
+    pub borrow_amount: u64,
+    pub fee_amount: u64,
+    pub user_amount: u64,
+}
+
+/// Value of `amount` collateral at `price`. Two u64s always fit in a u128.
+pub fn collateral_value(amount: u64, price: u64) -> u128 {
+    u128::from(amount) * u128::from(price)
+}
+
+/// Most a loan backed by `collateral_value` may owe at origination.
+pub fn borrow_limit(collateral_value: u128, ltv_bps: u16) -> Result<u128> {
+    Ok(collateral_value
+        .checked_mul(u128::from(ltv_bps))
+        .ok_or(CredXError::MathOverflow)?
+        / BPS_DENOMINATOR)
+}
+
+/// `bps` of `amount`, rounded down.
+pub fn bps_of(amount: u64, bps: u16) -> Result<u64> {
+    let share = u128::from(amount) * u128::from(bps) / BPS_DENOMINATOR;
+    Ok(u64::try_from(share).map_err(|_| CredXError::MathOverflow)?)
+}
+
+/// What `lend_credit_token` mints: everything between the current debt and
+/// the borrow limit, with the borrow fee carved out for the protocol.
+pub fn borrow_quote(
+    collateral_amount: u64,
+    price: u64,
+    ltv_bps: u16,
+    remaining_debt: u64,
+    borrow_fee_bps: u16,
+) -> Result<BorrowQuote> {
+    let limit = borrow_limit(collateral_value(collateral_amount, price), ltv_bps)?;
+    let additional = limit
+        .checked_sub(u128::from(remaining_debt))
+        .ok_or(CredXError::ExceedsMaxBorrow)?;
+    let borrow_amount = u64::try_from(additional).map_err(|_| CredXError::MathOverflow)?;
+
+    require!(limit > 0, CredXError::ZeroBorrowAmount);
+    require!(borrow_amount > 0, CredXError::MaxBorrowLimitReached);
+
+    let fee_amount = bps_of(borrow_amount, borrow_fee_bps)?;
+    Ok(BorrowQuote {
+        borrow_amount,
+        fee_amount,
+        user_amount: borrow_amount
+            .checked_sub(fee_amount)
+            .ok_or(CredXError::MathUnderflow)?,
+    })
+}
+
+/// Collateral in the vault above what the user deposited.
+pub fn harvestable_yield(vault_balance: u64, collateral_amount: u64) -> Result<u64> {
+    Ok(vault_balance
+        .checked_sub(collateral_amount)
+        .ok_or(CredXError::NegativeYield)?)
+}
+
+/// Credit burned by `cron_repayment` for `yield_amount` of collateral at
+/// `price`, capped at the outstanding debt.
+pub fn yield_repayment(yield_amount: u64, price: u64, remaining_debt: u64) -> Result<u64> {
+    let yield_value = collateral_value(yield_amount, price);
+    require!(yield_value > 0, CredXError::ZeroRepaymentValue);
+
+    let repayment = yield_value.min(u128::from(remaining_debt));
+    Ok(u64::try_from(repayment).map_err(|_| CredXError::MathOverflow)?)
+}
+
+/// Collateral worth `debt` at `price`, rounded up so that converting debt
+/// never comes out short. Callers pass the debt price.
+pub fn debt_collateral(debt: u64, price: u64) -> Result<u64> {
+    require!(price > 0, CredXError::InvalidPrice);
+    Ok(debt.div_ceil(price))
+}
+
+/// Whether `vault_balance` at `price` still covers the outstanding debt, as
+/// `withdraw_collateral` requires.
+pub fn covers_debt(vault_balance: u64, price: u64, remaining_debt: u64) -> bool {
+    collateral_value(vault_balance, price) >= u128::from(remaining_debt)
+}
+
+/// Whether a loan owing `remaining_debt` stays above the liquidation
+/// threshold with `collateral_value` of collateral.
+pub fn is_healthy(
+    collateral_value: u128,
+    liquidation_threshold_bps: u16,
+    remaining_debt: u64,
+) -> Result<bool> {
+    let threshold_value = collateral_value
+        .checked_mul(u128::from(liquidation_threshold_bps))
+        .ok_or(CredXError::MathOverflow)?
+        / BPS_DENOMINATOR;
+    Ok(threshold_value >= u128::from(remaining_debt))
+}
+
+/// Credit `leverage` borrows so that, once the part left after the borrow fee
+/// is swapped into collateral at the oracle price, the loan sits at
+/// `target_ltv_bps`. Each unit borrowed adds one of debt but only
+/// `1 - fee` of collateral value, so the loop `borrow, swap, deposit` is
+/// solved in closed form instead of iterated.
+pub fn leverage_borrow_amount(
+    collateral_value: u128,
+    remaining_debt: u64,
+    target_ltv_bps: u16,
+    borrow_fee_bps: u16,
+) -> Result<u64> {
+    require!(
+        u128::from(target_ltv_bps) < BPS_DENOMINATOR
+            && u128::from(borrow_fee_bps) <= BPS_DENOMINATOR,
+        CredXError::InvalidTargetLtv
+    );
+    let headroom = borrow_limit(collateral_value, target_ltv_bps)?
+        .checked_sub(u128::from(remaining_debt))
+        .filter(|headroom| *headroom > 0)
+        .ok_or(CredXError::InvalidTargetLtv)?;
+    let net_bps = BPS_DENOMINATOR * BPS_DENOMINATOR
+        - u128::from(target_ltv_bps) * (BPS_DENOMINATOR - u128::from(borrow_fee_bps));
+    let borrow_amount = headroom
+        .checked_mul(BPS_DENOMINATOR * BPS_DENOMINATOR)
+        .ok_or(CredXError::MathOverflow)?
+        / net_bps;
+    Ok(u64::try_from(borrow_amount).map_err(|_| CredXError::MathOverflow)?)
+}
+
+/// Collateral `deleverage` sells at `price` so that repaying the proceeds
+/// brings the loan down to `target_ltv_bps`, rounded up and capped at the
+/// whole deposit.
+pub fn deleverage_collateral_amount(
+    collateral_amount: u64,
+    price: u64,
+    remaining_debt: u64,
+    target_ltv_bps: u16,
+) -> Result<u64> {
+    require!(price > 0, CredXError::InvalidPrice);
+    require!(
+        u128::from(target_ltv_bps) < BPS_DENOMINATOR,
+        CredXError::InvalidTargetLtv
+    );
+    let target_debt = borrow_limit(collateral_value(collateral_amount, price), target_ltv_bps)?;
+    let excess = u128::from(remaining_debt)
+        .checked_sub(target_debt)
+        .filter(|excess| *excess > 0)
+        .ok_or(CredXError::InvalidTargetLtv)?;
+    // Selling a unit of value repays one of debt but also lowers the target
+    // debt by the LTV
+    let keep_bps = BPS_DENOMINATOR - u128::from(target_ltv_bps);
+    let value_to_sell = (excess * BPS_DENOMINATOR).div_ceil(keep_bps);
+    let amount = value_to_sell.div_ceil(u128::from(price));
+    Ok(u64::try_from(amount.min(u128::from(collateral_amount)))
+        .map_err(|_| CredXError::MathOverflow)?)
+}
+
+/// Collateral paid for redeeming `credit_amount` at `price`, after the
+/// redemption fee.
+pub fn redemption_payout(credit_amount: u64, price: u64, redemption_fee_bps: u16) -> Result<u64> {
+    require!(price > 0, CredXError::InvalidPrice);
+    let fee = bps_of(credit_amount, redemption_fee_bps)?;
+    let net_value = credit_amount
+        .checked_sub(fee)
+        .ok_or(CredXError::InvalidFee)?;
+    Ok(net_value / price)
+}
+
+/// `value * multiplier / divisor` rounded down, without overflowing when the
+/// full product does not fit in a u128. `divisor` must fit in a u64.
+pub fn mul_div_floor(value: u128, multiplier: u64, divisor: u128) -> Result<u128> {
+    require!(
+        divisor > 0 && divisor <= u128::from(u64::MAX),
+        CredXError::MathOverflow
+    );
+    let multiplier = u128::from(multiplier);
+    let whole = (value / divisor)
+        .checked_mul(multiplier)
+        .ok_or(CredXError::MathOverflow)?;
+    // Both factors are below 2^64, so the remainder term always fits
+    let part = (value % divisor) * multiplier / divisor;
+    Ok(whole.checked_add(part).ok_or(CredXError::MathOverflow)?)
+}
@@ -0,0 +1,325 @@
+use anchor_lang::prelude::*;
+use pyth_sdk_solana::state::{load_price_account, PriceStatus, SolanaPriceAccount};
+
+use crate::{error::CredXError, CollateralConfig, ProtocolState, SimplePriceOracle, Versioned};
+
+/// Window over which `SimplePriceOracle` updates are blended into the EMA.
+pub const EMA_WINDOW_SECS: u64 = 3600;
+
+/// Decimals every price source is normalized to (matches `SimplePriceOracle`).
+pub const PRICE_DECIMALS: i32 = 6;
+
+/// Price sources bound to a collateral and the rules for combining them.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct OracleSources {
+    pub primary: Pubkey,
+    // Pubkey::default() when the collateral is priced from a single source
+    pub secondary: Pubkey,
+    // Largest allowed spread between the two sources, relative to the lower one
+    pub max_deviation_bps: u16,
+    // Tighter limits applied when only one of the two sources is usable
+    pub fallback_max_age_secs: i64,
+    pub fallback_conf_multiplier_bps: u16,
+}
+
+impl OracleSources {
+    pub fn has_secondary(&self) -> bool {
+        self.secondary != Pubkey::default()
+    }
+}
+
+/// How a collateral's oracle readings are turned into a price.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct PricingPolicy {
+    // Compare spot against the EMA and take the worse of the two
+    pub use_ema: bool,
+    // Haircut applied per unit of confidence interval, 10_000 = 1x conf
+    pub conf_multiplier_bps: u16,
+}
+
+/// Per-collateral staleness windows. A zero window falls back to the
+/// protocol-wide `max_price_age_secs`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct StalenessPolicy {
+    pub max_age_secs: i64,
+    // Stricter window applied when opening or growing debt
+    pub borrow_max_age_secs: i64,
+}
+
+/// What a price is being read for, which decides the staleness window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PriceUsage {
+    Borrow,
+    Valuation,
+}
+
+impl StalenessPolicy {
+    pub fn validate(&self) -> Result<()> {
+        require!(
+            self.max_age_secs >= 0 && self.borrow_max_age_secs >= 0,
+            CredXError::InvalidStalenessWindow
+        );
+        require!(
+            self.max_age_secs == 0
+                || self.borrow_max_age_secs == 0
+                || self.borrow_max_age_secs <= self.max_age_secs,
+            CredXError::InvalidStalenessWindow
+        );
+        Ok(())
+    }
+
+    pub fn max_age_secs(&self, protocol_max_age_secs: i64, usage: PriceUsage) -> i64 {
+        let window = if self.max_age_secs > 0 {
+            self.max_age_secs
+        } else {
+            protocol_max_age_secs
+        };
+        match usage {
+            PriceUsage::Borrow if self.borrow_max_age_secs > 0 => {
+                self.borrow_max_age_secs.min(window)
+            }
+            _ => window,
+        }
+    }
+}
+
+/// A single oracle reading, before any pricing policy is applied.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PriceQuote {
+    pub price: u64,
+    pub ema_price: u64,
+    pub conf: u64,
+    pub publish_time: i64,
+}
+
+impl PriceQuote {
+    pub fn from_simple_oracle(oracle: &SimplePriceOracle) -> Self {
+        Self {
+            price: oracle.price,
+            ema_price: oracle.ema_price,
+            conf: oracle.conf,
+            publish_time: oracle.timestamp,
+        }
+    }
+
+    /// Reads either a `SimplePriceOracle` owned by this program or a Pyth
+    /// price account, normalized to `PRICE_DECIMALS`.
+    pub fn load(account: &AccountInfo) -> Result<Self> {
+        require!(!account.data_is_empty(), CredXError::EmptyOracleAccount);
+        let data = account
+            .try_borrow_data()
+            .map_err(|_| CredXError::FailedToBorrowOracleData)?;
+
+        if account.owner == &crate::ID {
+            let oracle = SimplePriceOracle::try_deserialize(&mut &data[..])?;
+            require!(oracle.is_current(), CredXError::UnsupportedAccountVersion);
+            return Ok(Self::from_simple_oracle(&oracle));
+        }
+
+        let price_account: &SolanaPriceAccount =
+            load_price_account(&data).map_err(|_| CredXError::FailedToLoadPriceAccount)?;
+        require!(
+            price_account.agg.status == PriceStatus::Trading,
+            CredXError::InvalidPriceStatus
+        );
+        require!(price_account.agg.price > 0, CredXError::InvalidPrice);
+
+        let expo = price_account.expo;
+        Ok(Self {
+            price: normalize_price(price_account.agg.price, expo)?,
+            ema_price: normalize_price(price_account.ema_price.val.max(0), expo)?,
+            conf: normalize_price(
+                i64::try_from(price_account.agg.conf).map_err(|_| CredXError::MathOverflow)?,
+                expo,
+            )?,
+            publish_time: price_account.timestamp,
+        })
+    }
+
+    /// Rejects zero prices, readings published after `now` and readings at
+    /// least `max_age_secs` old.
+    pub fn check_fresh(&self, now: i64, max_age_secs: i64) -> Result<()> {
+        require!(self.price > 0, CredXError::InvalidPrice);
+        require!(self.publish_time <= now, CredXError::FuturePriceTimestamp);
+        require!(
+            now - self.publish_time < max_age_secs,
+            CredXError::StalePrice
+        );
+        Ok(())
+    }
+
+    /// Spread between two prices in basis points of the lower one.
+    fn deviation_bps(&self, other: &Self) -> Result<u128> {
+        let low = self.price.min(other.price) as u128;
+        let high = self.price.max(other.price) as u128;
+        require!(low > 0, CredXError::InvalidPrice);
+        Ok((high - low)
+            .checked_mul(10_000)
+            .ok_or(CredXError::MathOverflow)?
+            / low)
+    }
+
+    fn haircut(&self, policy: &PricingPolicy) -> Result<u64> {
+        let haircut = (self.conf as u128)
+            .checked_mul(policy.conf_multiplier_bps as u128)
+            .ok_or(CredXError::MathOverflow)?
+            / 10_000;
+        u64::try_from(haircut).map_err(|_| error!(CredXError::MathOverflow))
+    }
+
+    /// Lower bound used to value collateral: the lesser of spot and EMA,
+    /// minus the confidence haircut.
+    pub fn collateral_price(&self, policy: &PricingPolicy) -> Result<u64> {
+        let base = if policy.use_ema && self.ema_price > 0 {
+            self.price.min(self.ema_price)
+        } else {
+            self.price
+        };
+        let price = base.saturating_sub(self.haircut(policy)?);
+
+        require!(price > 0, CredXError::InvalidPrice);
+        Ok(price)
+    }
+
+    /// Upper bound used when converting debt into collateral: the greater of
+    /// spot and EMA, plus the confidence haircut.
+    pub fn debt_price(&self, policy: &PricingPolicy) -> Result<u64> {
+        let base = if policy.use_ema {
+            self.price.max(self.ema_price)
+        } else {
+            self.price
+        };
+        let price = base
+            .checked_add(self.haircut(policy)?)
+            .ok_or(CredXError::MathOverflow)?;
+
+        require!(price > 0, CredXError::InvalidPrice);
+        Ok(price)
+    }
+}
+
+impl SimplePriceOracle {
+    /// Records a new reading and folds it into the time-weighted EMA.
+    pub fn record(&mut self, price: u64, conf: u64, now: i64) {
+        let elapsed = now.saturating_sub(self.timestamp).max(0) as u64;
+
+        self.ema_price = if self.ema_price == 0 {
+            price
+        } else {
+            let weight = elapsed.min(EMA_WINDOW_SECS) as u128;
+            let blended = (self.ema_price as u128 * (EMA_WINDOW_SECS as u128 - weight)
+                + price as u128 * weight)
+                / EMA_WINDOW_SECS as u128;
+            blended as u64
+        };
+        self.price = price;
+        self.conf = conf;
+        self.timestamp = now;
+    }
+}
+
+/// Scales a Pyth fixed-point value with exponent `expo` to `PRICE_DECIMALS`.
+fn normalize_price(value: i64, expo: i32) -> Result<u64> {
+    let value = u64::try_from(value).map_err(|_| CredXError::InvalidPrice)? as u128;
+    let shift = expo
+        .checked_add(PRICE_DECIMALS)
+        .ok_or(CredXError::MathOverflow)?;
+    let scale = 10u128
+        .checked_pow(shift.unsigned_abs())
+        .ok_or(CredXError::MathOverflow)?;
+    let scaled = if shift >= 0 {
+        value.checked_mul(scale).ok_or(CredXError::MathOverflow)?
+    } else {
+        value / scale
+    };
+    u64::try_from(scaled).map_err(|_| error!(CredXError::MathOverflow))
+}
+
+/// Combines the primary and (optional) secondary readings into one quote.
+///
+/// With both sources fresh, they must agree within `max_deviation_bps` and the
+/// wider confidence interval is kept. If only one of them is usable it is
+/// accepted under the fallback limits: a shorter staleness window and a scaled
+/// up confidence haircut.
+pub fn aggregate_quotes(
+    primary: Result<PriceQuote>,
+    secondary: Option<Result<PriceQuote>>,
+    sources: &OracleSources,
+    now: i64,
+    max_age_secs: i64,
+) -> Result<PriceQuote> {
+    let primary = primary.and_then(|quote| quote.check_fresh(now, max_age_secs).map(|_| quote));
+    let Some(secondary) = secondary else {
+        return primary;
+    };
+    let secondary = secondary
+        .and_then(|quote| quote.check_fresh(now, max_age_secs).map(|_| quote))
+        .ok();
+
+    let survivor = match (primary, secondary) {
+        (Ok(primary), Some(secondary)) => {
+            require!(
+                primary.deviation_bps(&secondary)? <= sources.max_deviation_bps as u128,
+                CredXError::OracleDeviationTooLarge
+            );
+            return Ok(PriceQuote {
+                conf: primary.conf.max(secondary.conf),
+                publish_time: primary.publish_time.min(secondary.publish_time),
+                ..primary
+            });
+        }
+        (Ok(primary), None) => primary,
+        (Err(_), Some(secondary)) => secondary,
+        (Err(err), None) => return Err(err),
+    };
+
+    survivor.check_fresh(now, sources.fallback_max_age_secs.min(max_age_secs))?;
+    let conf = (survivor.conf as u128)
+        .checked_mul(sources.fallback_conf_multiplier_bps as u128)
+        .ok_or(CredXError::MathOverflow)?
+        / 10_000;
+    Ok(PriceQuote {
+        conf: u64::try_from(conf).map_err(|_| CredXError::MathOverflow)?,
+        ..survivor
+    })
+}
+
+/// Loads a collateral's bound price sources and aggregates them under the
+/// staleness window for `usage`. The secondary account must be supplied
+/// whenever the collateral has one configured.
+pub fn load_collateral_price(
+    config: &CollateralConfig,
+    protocol: &ProtocolState,
+    primary: &AccountInfo,
+    secondary: Option<&AccountInfo>,
+    usage: PriceUsage,
+) -> Result<PriceQuote> {
+    let sources = &config.oracle;
+    require!(
+        primary.key() == sources.primary,
+        CredXError::InvalidOracleAccount
+    );
+    let secondary = match (sources.has_secondary(), secondary) {
+        (true, Some(account)) => {
+            require!(
+                account.key() == sources.secondary,
+                CredXError::InvalidOracleAccount
+            );
+            Some(PriceQuote::load(account))
+        }
+        (true, None) => return err!(CredXError::MissingSecondaryOracle),
+        (false, _) => None,
+    };
+
+    let now = Clock::get()?.unix_timestamp;
+    let max_age_secs = config
+        .staleness
+        .max_age_secs(protocol.max_price_age_secs, usage);
+    aggregate_quotes(
+        PriceQuote::load(primary),
+        secondary,
+        sources,
+        now,
+        max_age_secs,
+    )
+}
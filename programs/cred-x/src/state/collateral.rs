@@ -5,4 +5,6 @@ use anchor_lang::prelude::*;
 pub struct CollateralVault {
     pub mint: Pubkey,
     pub bump: u8,
+    pub version: u8,
+    pub reserved: [u8; 32],
 }
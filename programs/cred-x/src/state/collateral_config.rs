@@ -0,0 +1,168 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::CredXError, LoanAccount, OracleSources, PriceQuote, PricingPolicy, StalenessPolicy,
+};
+
+/// Start of the log line written when a collateral's circuit breaker is
+/// tripped, followed by the collateral mint.
+pub const CIRCUIT_BREAKER_TRIPPED_LOG: &str = "Circuit breaker tripped for collateral ";
+
+/// Per-collateral risk settings. A mint can only be deposited once the admin
+/// has configured it.
+#[account]
+#[derive(InitSpace)]
+pub struct CollateralConfig {
+    pub protocol: Pubkey,
+    pub mint: Pubkey,
+    pub supply_cap: u64,
+    pub total_collateral: u64,
+    pub pricing: PricingPolicy,
+    pub oracle: OracleSources,
+    pub staleness: StalenessPolicy,
+    pub circuit_breaker: CircuitBreaker,
+    pub bump: u8,
+    pub redemption_mark: RedemptionMark,
+}
+
+impl CollateralConfig {
+    /// Called whenever a loan backed by this collateral changes its debt or
+    /// collateral, so the redemption mark follows it.
+    pub fn track_loan(&mut self, loan: Pubkey, state: &LoanAccount) {
+        self.redemption_mark
+            .update(loan, state.remaining_debt, state.collateral_amount);
+    }
+
+    /// Called when a loan moves to another collateral.
+    pub fn untrack_loan(&mut self, loan: Pubkey) {
+        self.redemption_mark.update(loan, 0, 0);
+    }
+
+    /// Feeds `quote` to the circuit breaker and returns whether this reading
+    /// tripped it, logging the trip once. `tripped_circuit_breaker` in the
+    /// client finds the log line. Used where the trip should be saved:
+    /// `trip_circuit_breaker` and `cron_repayment`, which keeps working while
+    /// the breaker is tripped.
+    pub fn record_price(&mut self, quote: &PriceQuote) -> Result<bool> {
+        let was_tripped = self.circuit_breaker.tripped;
+        if self
+            .circuit_breaker
+            .observe(quote.price, quote.publish_time)?
+            || was_tripped
+        {
+            return Ok(false);
+        }
+        msg!(
+            "{}{}: price {} vs last accepted {}",
+            CIRCUIT_BREAKER_TRIPPED_LOG,
+            self.mint,
+            quote.price,
+            self.circuit_breaker.last_price
+        );
+        Ok(true)
+    }
+
+    /// Feeds `quote` to the circuit breaker for instructions that pause while
+    /// it is tripped, which refuse a tripped breaker in their account
+    /// constraints. A reading that would trip it fails with
+    /// `CircuitBreakerTripPending`, rolling the trip back with the
+    /// transaction; `trip_circuit_breaker` saves it.
+    pub fn observe_price(&mut self, quote: &PriceQuote) -> Result<()> {
+        require!(
+            !self.record_price(quote)?,
+            CredXError::CircuitBreakerTripPending
+        );
+        Ok(())
+    }
+}
+
+/// Debt per unit of collateral of the riskiest loan seen for a collateral.
+/// Redemptions may not take from a loan below it, so the riskiest loan
+/// cannot be skipped by leaving it out of the accounts.
+///
+/// A loan above the mark takes it over, and the loan holding it moves it
+/// with every change, down to zero once it is repaid. The mark therefore
+/// never exceeds the riskiest open loan, but after the holder de-risks it
+/// can sit below a loan that has not changed since.
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace,
+)]
+pub struct RedemptionMark {
+    pub loan: Pubkey,
+    pub debt: u64,
+    pub collateral: u64,
+}
+
+impl RedemptionMark {
+    pub fn update(&mut self, loan: Pubkey, debt: u64, collateral: u64) {
+        let riskier = u128::from(debt) * u128::from(self.collateral)
+            > u128::from(self.debt) * u128::from(collateral);
+        if loan == self.loan || self.debt == 0 || riskier {
+            *self = Self {
+                loan,
+                debt,
+                collateral,
+            };
+        }
+    }
+
+    /// Whether a loan's debt per unit of collateral is at least the mark's.
+    pub fn admits(&self, debt: u64, collateral: u64) -> bool {
+        u128::from(debt) * u128::from(self.collateral)
+            >= u128::from(self.debt) * u128::from(collateral)
+    }
+}
+
+/// Pauses borrowing and withdrawals for a collateral when its price moves too
+/// far from the last accepted reading. Only the admin can re-arm it.
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace,
+)]
+pub struct CircuitBreaker {
+    // 0 disables the breaker
+    pub max_move_bps: u16,
+    pub last_price: u64,
+    pub last_price_time: i64,
+    pub tripped: bool,
+}
+
+impl CircuitBreaker {
+    /// Checks `price` against the last accepted price. Returns `false` and
+    /// trips the breaker when the move exceeds `max_move_bps`; otherwise the
+    /// price becomes the new reference. Instructions go through
+    /// `CollateralConfig::observe_price` or `CollateralConfig::record_price`.
+    pub fn observe(&mut self, price: u64, publish_time: i64) -> Result<bool> {
+        if self.tripped {
+            return Ok(false);
+        }
+
+        if self.max_move_bps > 0 && self.last_price > 0 {
+            let last = self.last_price as u128;
+            let moved_bps = (price as u128)
+                .abs_diff(last)
+                .checked_mul(10_000)
+                .ok_or(CredXError::MathOverflow)?
+                / last;
+            if moved_bps > self.max_move_bps as u128 {
+                self.tripped = true;
+                return Ok(false);
+            }
+        }
+
+        self.accept(price, publish_time);
+        Ok(true)
+    }
+
+    /// Takes `price` as the new reference and re-arms the breaker.
+    pub fn accept(&mut self, price: u64, publish_time: i64) {
+        self.last_price = price;
+        self.last_price_time = publish_time;
+        self.tripped = false;
+    }
+
+    /// Re-arms the breaker without a reference, so the next price observed
+    /// is accepted and becomes one.
+    pub fn rearm(&mut self) {
+        self.accept(0, 0);
+    }
+}
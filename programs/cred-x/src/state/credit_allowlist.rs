@@ -0,0 +1,10 @@
+use anchor_lang::prelude::*;
+
+#[account]
+#[derive(InitSpace)]
+pub struct CreditAllowlistEntry {
+    pub protocol: Pubkey,
+    pub owner: Pubkey,
+    pub allowed: bool,
+    pub bump: u8,
+}
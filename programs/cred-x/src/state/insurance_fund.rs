@@ -0,0 +1,21 @@
+use anchor_lang::prelude::*;
+
+/// Credit set aside from protocol fees to absorb bad debt. The fund's credit
+/// and the collateral it seizes sit in associated token accounts owned by
+/// this PDA.
+#[account]
+#[derive(InitSpace)]
+pub struct InsuranceFund {
+    pub protocol: Pubkey,
+    // All-time credit moved in from protocol fees
+    pub total_funded: u64,
+    // Bad debt burned from the fund
+    pub total_covered: u64,
+    // Bad debt written off without being burned, left as credit in
+    // circulation above `ProtocolState::total_debt`
+    pub total_socialized: u64,
+    pub bad_debts_resolved: u64,
+    // Credit stability pools paid for collateral the fund seized
+    pub total_recovered: u64,
+    pub bump: u8,
+}
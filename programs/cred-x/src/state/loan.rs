@@ -9,4 +9,10 @@ pub struct LoanAccount {
     pub yield_earned: u64,
     pub bump: u8,
     pub oracle_price_account: Pubkey,
+    pub version: u8,
+    // Protocol the loan was opened under; default for loans opened before
+    // this was recorded, until `migrate_loan` binds them
+    pub protocol: Pubkey,
+    // Room for new fields, e.g. interest indices, without a realloc
+    pub reserved: [u8; 32],
 }
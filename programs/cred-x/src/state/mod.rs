@@ -1,7 +1,21 @@
 pub mod collateral;
+pub mod collateral_config;
+pub mod credit_allowlist;
+pub mod insurance_fund;
 pub mod loan;
+pub mod param_change;
+pub mod position_delegate;
 pub mod protocol;
+pub mod stability_pool;
+pub mod versioned;
 
 pub use collateral::*;
+pub use collateral_config::*;
+pub use credit_allowlist::*;
+pub use insurance_fund::*;
 pub use loan::*;
+pub use param_change::*;
+pub use position_delegate::*;
 pub use protocol::*;
+pub use stability_pool::*;
+pub use versioned::*;
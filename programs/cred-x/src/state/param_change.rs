@@ -0,0 +1,167 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    error::CredXError, validate_loan_ratios, CollateralConfig, CollateralConfigParams,
+    CreditMetadata, CreditTransferMode, ProtocolState,
+};
+
+/// Longest delay the timelock can be set to, so a mistaken value cannot
+/// freeze governance for good.
+pub const MAX_GOVERNANCE_DELAY_SECS: i64 = 30 * 24 * 60 * 60;
+
+/// A protocol parameter change that only takes effect through the timelock.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, InitSpace)]
+pub enum ParamChange {
+    LoanRatios {
+        ltv_ratio_bps: u16,
+        liquidation_threshold_bps: u16,
+    },
+    BorrowFee {
+        fee_bps: u16,
+    },
+    FlashFee {
+        fee_bps: u16,
+    },
+    RedemptionFee {
+        fee_bps: u16,
+    },
+    DebtCeiling {
+        debt_ceiling: u64,
+    },
+    // Supply cap, pricing and oracle bindings of a configured collateral
+    Collateral {
+        mint: Pubkey,
+        params: CollateralConfigParams,
+    },
+    Admin {
+        admin: Pubkey,
+    },
+    GovernanceDelay {
+        delay_secs: i64,
+    },
+    // Pubkey::default() disables swaps
+    SwapProgram {
+        swap_program: Pubkey,
+    },
+    // Executing it checks the credit mint carries the transfer hook
+    CreditTransferMode {
+        mode: CreditTransferMode,
+    },
+    // Written to the credit mint when executed
+    CreditMetadata {
+        metadata: CreditMetadata,
+    },
+    // Re-arms a tripped breaker; the next price read becomes its reference
+    ResetCircuitBreaker {
+        mint: Pubkey,
+    },
+}
+
+impl ParamChange {
+    /// Checked when queued and again when executed, since other changes may
+    /// land in between.
+    pub fn validate(&self, protocol: &ProtocolState) -> Result<()> {
+        match self {
+            ParamChange::LoanRatios {
+                ltv_ratio_bps,
+                liquidation_threshold_bps,
+            } => validate_loan_ratios(*ltv_ratio_bps, *liquidation_threshold_bps),
+            ParamChange::BorrowFee { fee_bps }
+            | ParamChange::FlashFee { fee_bps }
+            | ParamChange::RedemptionFee { fee_bps } => {
+                require!(*fee_bps <= 1000, CredXError::InvalidFee);
+                Ok(())
+            }
+            ParamChange::DebtCeiling { .. } => Ok(()),
+            ParamChange::Collateral { params, .. } => params.validate(protocol.max_price_age_secs),
+            ParamChange::Admin { admin } => {
+                require!(*admin != Pubkey::default(), CredXError::InvalidAdmin);
+                Ok(())
+            }
+            ParamChange::GovernanceDelay { delay_secs } => {
+                require!(
+                    (0..=MAX_GOVERNANCE_DELAY_SECS).contains(delay_secs),
+                    CredXError::InvalidGovernanceDelay
+                );
+                Ok(())
+            }
+            ParamChange::SwapProgram { .. }
+            | ParamChange::CreditTransferMode { .. }
+            | ParamChange::ResetCircuitBreaker { .. } => Ok(()),
+            ParamChange::CreditMetadata { metadata } => metadata.validate(),
+        }
+    }
+
+    /// Collateral whose config must be passed to execute the change.
+    pub fn collateral_mint(&self) -> Option<Pubkey> {
+        match self {
+            ParamChange::Collateral { mint, .. } | ParamChange::ResetCircuitBreaker { mint } => {
+                Some(*mint)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether the credit mint and the accounts to update it must be passed
+    /// to execute the change.
+    pub fn needs_credit_mint(&self) -> bool {
+        matches!(
+            self,
+            ParamChange::CreditTransferMode { .. } | ParamChange::CreditMetadata { .. }
+        )
+    }
+
+    pub fn apply(
+        &self,
+        protocol: &mut ProtocolState,
+        collateral_config: Option<&mut CollateralConfig>,
+    ) -> Result<()> {
+        match self {
+            ParamChange::LoanRatios {
+                ltv_ratio_bps,
+                liquidation_threshold_bps,
+            } => {
+                protocol.ltv_ratio_bps = *ltv_ratio_bps;
+                protocol.liquidation_threshold_bps = *liquidation_threshold_bps;
+            }
+            ParamChange::BorrowFee { fee_bps } => protocol.borrow_fee_bps = *fee_bps,
+            ParamChange::FlashFee { fee_bps } => protocol.flash_fee_bps = *fee_bps,
+            ParamChange::RedemptionFee { fee_bps } => protocol.redemption_fee_bps = *fee_bps,
+            ParamChange::DebtCeiling { debt_ceiling } => protocol.debt_ceiling = *debt_ceiling,
+            ParamChange::Collateral { params, .. } => {
+                let config = collateral_config.ok_or(CredXError::InvalidParamChangeCollateral)?;
+                params.apply(config);
+            }
+            ParamChange::Admin { admin } => {
+                protocol.original_admin = protocol.seed_admin();
+                protocol.admin = *admin;
+            }
+            ParamChange::GovernanceDelay { delay_secs } => {
+                protocol.governance_delay_secs = *delay_secs
+            }
+            ParamChange::SwapProgram { swap_program } => protocol.swap_program = *swap_program,
+            ParamChange::CreditTransferMode { mode } => protocol.credit_transfer_mode = *mode,
+            // Lives on the mint, which `execute_param_change` updates
+            ParamChange::CreditMetadata { .. } => {}
+            ParamChange::ResetCircuitBreaker { .. } => {
+                let config = collateral_config.ok_or(CredXError::InvalidParamChangeCollateral)?;
+                config.circuit_breaker.rearm();
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A queued `ParamChange`, closed to the admin when it is executed or
+/// cancelled.
+#[account]
+#[derive(InitSpace)]
+pub struct PendingParamChange {
+    pub protocol: Pubkey,
+    pub id: u64,
+    pub change: ParamChange,
+    pub queued_at: i64,
+    // Earliest time the change can be executed
+    pub eta: i64,
+    pub bump: u8,
+}
@@ -0,0 +1,88 @@
+use anchor_lang::prelude::*;
+
+use crate::error::CredXError;
+
+/// A manager the loan owner lets act on their position, such as a vault
+/// strategy or a custodian. Collateral withdrawn by a delegate still goes to
+/// the owner.
+#[account]
+#[derive(InitSpace)]
+pub struct PositionDelegate {
+    pub owner: Pubkey,
+    pub delegate: Pubkey,
+    pub permissions: DelegatePermissions,
+    // Most debt the delegate may add through `lend_credit_token`, fees included
+    pub borrow_limit: u64,
+    pub borrowed: u64,
+    pub bump: u8,
+}
+
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace,
+)]
+pub struct DelegatePermissions {
+    pub deposit: bool,
+    pub repay: bool,
+    pub borrow: bool,
+    pub withdraw: bool,
+}
+
+/// Actions gated by `DelegatePermissions`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PositionAction {
+    Deposit,
+    Repay,
+    Borrow,
+    Withdraw,
+}
+
+impl DelegatePermissions {
+    pub fn allows(&self, action: PositionAction) -> bool {
+        match action {
+            PositionAction::Deposit => self.deposit,
+            PositionAction::Repay => self.repay,
+            PositionAction::Borrow => self.borrow,
+            PositionAction::Withdraw => self.withdraw,
+        }
+    }
+}
+
+impl PositionDelegate {
+    /// Records `amount` of new debt taken on by the delegate.
+    pub fn record_borrow(&mut self, amount: u64) -> Result<()> {
+        let borrowed = self
+            .borrowed
+            .checked_add(amount)
+            .ok_or(CredXError::MathOverflow)?;
+        require!(
+            borrowed <= self.borrow_limit,
+            CredXError::DelegateBorrowLimitExceeded
+        );
+        self.borrowed = borrowed;
+        Ok(())
+    }
+}
+
+/// Checks that `authority` may take `action` on `owner`'s position: either it
+/// is the owner, or `delegate` is the owner's grant to it and allows `action`.
+/// Returns whether the grant is in use.
+pub fn authorize_position(
+    owner: &Pubkey,
+    authority: &Pubkey,
+    delegate: Option<&PositionDelegate>,
+    action: PositionAction,
+) -> Result<bool> {
+    if authority == owner {
+        return Ok(false);
+    }
+    let delegate = delegate.ok_or(CredXError::UnauthorizedUser)?;
+    require!(
+        delegate.owner == *owner && delegate.delegate == *authority,
+        CredXError::UnauthorizedUser
+    );
+    require!(
+        delegate.permissions.allows(action),
+        CredXError::DelegatePermissionDenied
+    );
+    Ok(true)
+}
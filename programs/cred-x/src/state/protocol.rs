@@ -1,11 +1,164 @@
 use anchor_lang::prelude::*;
 
+use crate::error::CredXError;
+
 #[account]
 #[derive(InitSpace)]
 pub struct ProtocolState {
     pub admin: Pubkey,
     pub ltv_ratio_bps: u16, // e.g. 6000 = 60.00%
+    pub liquidation_threshold_bps: u16,
     pub credit_mint: Pubkey,
+    pub credit_decimals: u8,
+    pub max_price_age_secs: i64,
+    pub borrow_fee_bps: u16, // charged on each borrow, minted to the protocol
+    pub debt_ceiling: u64,
+    pub total_debt: u64,
     pub is_locked: bool,
     pub bump: u8,
+    pub credit_transfer_mode: CreditTransferMode,
+    pub stats: ProtocolStats,
+    pub version: u8,
+    // Taken from `reserved`, so protocols created at version 1 read it as zero
+    pub flash_fee_bps: u16, // charged on each flash mint, minted to the protocol
+    // Program `swap_collateral` routes through, Pubkey::default() when disabled
+    pub swap_program: Pubkey,
+    // Haircut on redemptions, left in the redeemed borrowers' vaults
+    pub redemption_fee_bps: u16,
+    // All-time debt cancelled by `redeem_credit`
+    pub total_redeemed: u64,
+    // Admin the protocol PDA was derived from, recorded when the admin is
+    // first rotated; Pubkey::default() while it is still in place
+    pub original_admin: Pubkey,
+    // Least time between queueing a parameter change and executing it. The
+    // direct setters it covers are refused while it is non-zero
+    pub governance_delay_secs: i64,
+    // Ids handed out to queued parameter changes
+    pub param_changes_queued: u64,
+    // Loans opened before loans recorded their protocol, and the debt they
+    // owe, that `migrate_protocol` took on and `bind_loan` has not claimed yet.
+    // Zero for protocols created since, so they can never bind a legacy loan
+    pub unbound_legacy_loans: u64,
+    pub unbound_legacy_debt: u64,
+    // Room for new fields without a realloc
+    pub reserved: [u8; 20],
+}
+
+impl ProtocolState {
+    /// Key in the protocol PDA's seeds, which survives admin rotation.
+    pub fn seed_admin(&self) -> Pubkey {
+        if self.original_admin == Pubkey::default() {
+            self.admin
+        } else {
+            self.original_admin
+        }
+    }
+
+    pub fn is_timelocked(&self) -> bool {
+        self.governance_delay_secs > 0
+    }
+}
+
+/// `ProtocolState` as laid out before account versioning, read field by
+/// field by `migrate_protocol`. Later fields were inserted between these, so
+/// the current layout cannot simply be read over it.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub struct ProtocolStateV0 {
+    pub admin: Pubkey,
+    pub ltv_ratio_bps: u16,
+    pub credit_mint: Pubkey,
+    pub is_locked: bool,
+    pub bump: u8,
+}
+
+/// Running totals for dashboards. Outstanding debt is `total_debt` and
+/// deposited collateral is tracked per mint in `CollateralConfig`.
+#[derive(
+    AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace,
+)]
+pub struct ProtocolStats {
+    // All-time credit minted, borrow fees included
+    pub total_minted: u64,
+    pub total_repaid_via_yield: u64,
+    pub total_repaid_via_user: u64,
+    pub total_yield_harvested: u64,
+    // Loans with outstanding debt
+    pub open_loans: u64,
+}
+
+impl ProtocolStats {
+    pub fn record_borrow(&mut self, amount: u64, opens_loan: bool) -> Result<()> {
+        self.record_mint(amount)?;
+        if opens_loan {
+            self.open_loans = self
+                .open_loans
+                .checked_add(1)
+                .ok_or(CredXError::MathOverflow)?;
+        }
+        Ok(())
+    }
+
+    // Credit minted outside a borrow, e.g. a swap surplus paid out to the owner
+    pub fn record_mint(&mut self, amount: u64) -> Result<()> {
+        self.total_minted = self
+            .total_minted
+            .checked_add(amount)
+            .ok_or(CredXError::MathOverflow)?;
+        Ok(())
+    }
+
+    pub fn record_yield_repayment(
+        &mut self,
+        repaid: u64,
+        yield_amount: u64,
+        closes_loan: bool,
+    ) -> Result<()> {
+        self.total_repaid_via_yield = self
+            .total_repaid_via_yield
+            .checked_add(repaid)
+            .ok_or(CredXError::MathOverflow)?;
+        self.record_yield(yield_amount)?;
+        if closes_loan {
+            self.close_loan()?;
+        }
+        Ok(())
+    }
+
+    pub fn record_user_repayment(&mut self, repaid: u64, closes_loan: bool) -> Result<()> {
+        self.total_repaid_via_user = self
+            .total_repaid_via_user
+            .checked_add(repaid)
+            .ok_or(CredXError::MathOverflow)?;
+        if closes_loan {
+            self.close_loan()?;
+        }
+        Ok(())
+    }
+
+    pub fn record_yield(&mut self, yield_amount: u64) -> Result<()> {
+        self.total_yield_harvested = self
+            .total_yield_harvested
+            .checked_add(yield_amount)
+            .ok_or(CredXError::MathOverflow)?;
+        Ok(())
+    }
+
+    pub(crate) fn close_loan(&mut self) -> Result<()> {
+        self.open_loans = self
+            .open_loans
+            .checked_sub(1)
+            .ok_or(CredXError::MathUnderflow)?;
+        Ok(())
+    }
+}
+
+/// Rules enforced by the credit mint transfer hook. Anything other than
+/// `Unrestricted` requires the credit mint to be created with the hook.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum CreditTransferMode {
+    Unrestricted,
+    // Holders with a loan must keep at least `remaining_debt` credit tokens
+    DebtFloor,
+    // Transfers only go to owners with an allow-list entry
+    Allowlist,
 }
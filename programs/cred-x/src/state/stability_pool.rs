@@ -0,0 +1,168 @@
+use anchor_lang::prelude::*;
+
+use crate::{error::CredXError, mul_div_floor};
+
+/// Fixed-point one for `StabilityPool::product`.
+pub const PRODUCT_PRECISION: u128 = 1_000_000_000_000_000_000;
+/// The product is multiplied by this, and the scale bumped, whenever it
+/// drops below it.
+pub const SCALE_FACTOR: u128 = 1_000_000_000;
+/// Scales whose sums are kept. A deposit snapshotted this many scales ago
+/// forfeits any collateral gain it has not claimed.
+pub const MAX_SCALES: usize = 16;
+
+/// Credit deposited to absorb liquidated debt of one collateral mint, in
+/// exchange for the seized collateral. Each depositor's share is tracked
+/// with the product-sum scheme: `product` is what one unit deposited at the
+/// start of the current scale is worth after liquidations, and the sum of
+/// scale `s` is the collateral it has earned during that scale, both over
+/// `PRODUCT_PRECISION`. Sums are kept for the last `MAX_SCALES` scales only,
+/// scale `s` in `scale_sums[s % MAX_SCALES]`; a deposit is worth nothing two
+/// scales after its snapshot, so older sums only serve unclaimed gains.
+#[account]
+#[derive(InitSpace)]
+pub struct StabilityPool {
+    pub protocol: Pubkey,
+    pub collateral_mint: Pubkey,
+    // Credit held for depositors
+    pub total_deposits: u64,
+    // Seized collateral not yet claimed
+    pub total_collateral: u64,
+    // All-time debt cancelled by liquidations
+    pub total_debt_offset: u64,
+    pub product: u128,
+    pub scale: u64,
+    pub scale_sums: [u128; MAX_SCALES],
+    pub bump: u8,
+}
+
+/// One owner's position in a stability pool as of its last snapshot.
+#[account]
+#[derive(InitSpace)]
+pub struct StabilityDeposit {
+    pub pool: Pubkey,
+    pub owner: Pubkey,
+    // Deposit at the snapshot, before any later liquidations
+    pub amount: u64,
+    pub product_snapshot: u128,
+    pub sum_snapshot: u128,
+    pub scale_snapshot: u64,
+    pub bump: u8,
+}
+
+impl StabilityPool {
+    /// Most debt a liquidation may cancel. The pool keeps at least
+    /// `min_remaining` and a billionth of its deposits, so one liquidation
+    /// never needs more than one rescale of the product.
+    pub fn offset_capacity(&self, min_remaining: u64) -> u64 {
+        let floor = self
+            .total_deposits
+            .div_ceil(SCALE_FACTOR as u64)
+            .max(min_remaining);
+        self.total_deposits.saturating_sub(floor)
+    }
+
+    /// Cancels `debt` of deposits against `collateral` seized for the
+    /// depositors. `debt` must be within `offset_capacity`.
+    pub fn offset(&mut self, debt: u64, collateral: u64) -> Result<()> {
+        let deposits = u128::from(self.total_deposits);
+        require!(
+            debt > 0 && u128::from(debt) < deposits,
+            CredXError::StabilityPoolEmpty
+        );
+
+        let slot = scale_slot(self.scale);
+        let gain_per_unit = mul_div_floor(self.product, collateral, deposits)?;
+        self.scale_sums[slot] = self.scale_sums[slot]
+            .checked_add(gain_per_unit)
+            .ok_or(CredXError::MathOverflow)?;
+
+        let remaining = self.total_deposits - debt;
+        let mut product = mul_div_floor(self.product, remaining, deposits)?;
+        if product < SCALE_FACTOR {
+            product = mul_div_floor(self.product * SCALE_FACTOR, remaining, deposits)?;
+            require!(product > 0, CredXError::StabilityPoolExhausted);
+            // The slot last held the sum of `MAX_SCALES` scales ago
+            self.scale = self.scale.checked_add(1).ok_or(CredXError::MathOverflow)?;
+            self.scale_sums[scale_slot(self.scale)] = 0;
+        }
+        self.product = product;
+
+        self.total_deposits = remaining;
+        self.total_collateral = self
+            .total_collateral
+            .checked_add(collateral)
+            .ok_or(CredXError::MathOverflow)?;
+        self.total_debt_offset = self
+            .total_debt_offset
+            .checked_add(debt)
+            .ok_or(CredXError::MathOverflow)?;
+        Ok(())
+    }
+
+    /// What is left of `deposit` after the liquidations since its snapshot.
+    pub fn compounded_deposit(&self, deposit: &StabilityDeposit) -> Result<u64> {
+        if deposit.amount == 0 {
+            return Ok(0);
+        }
+        let amount = u128::from(deposit.amount);
+        let scales = self
+            .scale
+            .checked_sub(deposit.scale_snapshot)
+            .ok_or(CredXError::MathUnderflow)?;
+        let compounded = match scales {
+            0 => amount * self.product / deposit.product_snapshot,
+            1 => amount * self.product / deposit.product_snapshot / SCALE_FACTOR,
+            // Worth less than a billionth of a billionth of the snapshot
+            _ => 0,
+        };
+        Ok(u64::try_from(compounded).map_err(|_| CredXError::MathOverflow)?)
+    }
+
+    /// Collateral `deposit` has earned since its snapshot. Gains from two or
+    /// more scales later are below rounding and dropped, and gains left
+    /// unclaimed for `MAX_SCALES` scales are forfeited with their sums.
+    pub fn collateral_gain(&self, deposit: &StabilityDeposit) -> Result<u64> {
+        let scales = self
+            .scale
+            .checked_sub(deposit.scale_snapshot)
+            .ok_or(CredXError::MathUnderflow)?;
+        if deposit.amount == 0 || scales >= MAX_SCALES as u64 {
+            return Ok(0);
+        }
+        let mut sum = self.scale_sums[scale_slot(deposit.scale_snapshot)]
+            .checked_sub(deposit.sum_snapshot)
+            .ok_or(CredXError::MathUnderflow)?;
+        if scales > 0 {
+            sum += self.scale_sums[scale_slot(deposit.scale_snapshot + 1)] / SCALE_FACTOR;
+        }
+        let gain = mul_div_floor(sum, deposit.amount, deposit.product_snapshot)?;
+        Ok(u64::try_from(gain).map_err(|_| CredXError::MathOverflow)?)
+    }
+
+    /// Brings `deposit` up to date: returns its compounded deposit and pays
+    /// out its collateral gain from `total_collateral`, then snapshots it at
+    /// the compounded amount.
+    pub fn settle(&mut self, deposit: &mut StabilityDeposit) -> Result<(u64, u64)> {
+        let compounded = self.compounded_deposit(deposit)?;
+        let gain = self.collateral_gain(deposit)?;
+        self.total_collateral = self
+            .total_collateral
+            .checked_sub(gain)
+            .ok_or(CredXError::MathUnderflow)?;
+        self.snapshot(deposit, compounded);
+        Ok((compounded, gain))
+    }
+
+    /// Restarts `deposit` at `amount` from the pool's current product and sum.
+    pub fn snapshot(&self, deposit: &mut StabilityDeposit, amount: u64) {
+        deposit.amount = amount;
+        deposit.product_snapshot = self.product;
+        deposit.sum_snapshot = self.scale_sums[scale_slot(self.scale)];
+        deposit.scale_snapshot = self.scale;
+    }
+}
+
+fn scale_slot(scale: u64) -> usize {
+    (scale % MAX_SCALES as u64) as usize
+}
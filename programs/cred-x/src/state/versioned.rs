@@ -0,0 +1,125 @@
+use anchor_lang::{
+    prelude::*,
+    system_program::{transfer, Transfer},
+    Discriminator,
+};
+
+use crate::error::CredXError;
+
+/// Accounts that carry a layout version followed by reserved space. Accounts
+/// created before versioning are shorter and do not deserialize until a
+/// `migrate_*` instruction grows them; the appended bytes read as version 0.
+pub trait Versioned {
+    const VERSION: u8;
+
+    fn version(&self) -> u8;
+
+    fn set_version(&mut self, version: u8);
+
+    fn is_current(&self) -> bool {
+        self.version() == Self::VERSION
+    }
+}
+
+macro_rules! versioned {
+    ($($account:ty => $version:expr),* $(,)?) => {
+        $(impl Versioned for $account {
+            const VERSION: u8 = $version;
+
+            fn version(&self) -> u8 {
+                self.version
+            }
+
+            fn set_version(&mut self, version: u8) {
+                self.version = version;
+            }
+        })*
+    };
+}
+
+versioned!(
+    crate::ProtocolState => 1,
+    crate::LoanAccount => 1,
+    crate::CollateralVault => 1,
+    crate::SimplePriceOracle => 1,
+);
+
+/// Checks that `account` is a `T` owned by this program, before its bytes
+/// are trusted to be in any particular layout.
+pub fn check_account_type<T: Discriminator>(account: &AccountInfo) -> Result<()> {
+    require_keys_eq!(
+        *account.owner,
+        crate::ID,
+        ErrorCode::AccountOwnedByWrongProgram
+    );
+    require!(
+        account.try_borrow_data()?.starts_with(T::DISCRIMINATOR),
+        ErrorCode::AccountDiscriminatorMismatch
+    );
+    Ok(())
+}
+
+/// Grows `account` to `space` bytes, topping up rent from `payer`. The
+/// appended bytes are zeroed.
+pub fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    space: usize,
+) -> Result<()> {
+    let rent = Rent::get()?.minimum_balance(space);
+    let top_up = rent.saturating_sub(account.lamports());
+    if top_up > 0 {
+        transfer(
+            CpiContext::new(
+                system_program.clone(),
+                Transfer {
+                    from: payer.clone(),
+                    to: account.clone(),
+                },
+            ),
+            top_up,
+        )?;
+    }
+    account.resize(space)?;
+    Ok(())
+}
+
+/// Upgrades a program-owned `T` in place: grows it to the current size,
+/// topping up rent from `payer`, and stamps the current version. Returns
+/// `false` when the account is already current.
+///
+/// Only valid for types whose version 0 layout is a prefix of the current
+/// one, so the fields added since read as zero. `ProtocolState` had fields
+/// inserted mid-struct and is decoded by `migrate_protocol` instead.
+pub fn migrate_account<'info, T>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<bool>
+where
+    T: AccountSerialize + AccountDeserialize + Discriminator + Space + Versioned,
+{
+    check_account_type::<T>(account)?;
+
+    let space = 8 + T::INIT_SPACE;
+    // Larger accounts were written by a newer layout than this program knows
+    require!(
+        account.data_len() <= space,
+        CredXError::UnsupportedAccountVersion
+    );
+    if account.data_len() < space {
+        grow_account(account, payer, system_program, space)?;
+    }
+
+    let mut state = T::try_deserialize(&mut &account.try_borrow_data()?[..])?;
+    match state.version() {
+        version if version == T::VERSION => return Ok(false),
+        version if version > T::VERSION => return err!(CredXError::UnsupportedAccountVersion),
+        // Version 0: the original fields, with everything appended since zeroed
+        _ => {}
+    }
+    state.set_version(T::VERSION);
+    state.try_serialize(&mut &mut account.try_borrow_mut_data()?[..])?;
+    Ok(true)
+}
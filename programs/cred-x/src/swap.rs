@@ -0,0 +1,84 @@
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        instruction::{AccountMeta, Instruction},
+        program::invoke_signed,
+    },
+};
+
+/// Anchor discriminator of `swap(amount_in: u64, min_amount_out: u64)`, the
+/// one instruction a swap program must expose to be set as
+/// `ProtocolState.swap_program`.
+pub const SWAP_DISCRIMINATOR: [u8; 8] = [248, 198, 158, 145, 225, 117, 135, 200];
+
+/// Most of a position's oracle value a collateral swap may give up, in bps.
+/// `min_amount_out` valued at the new collateral's price has to cover the
+/// rest, so a loose minimum cannot hand the difference to the pool.
+pub const MAX_SWAP_VALUE_LOSS_BPS: u16 = 500;
+
+/// Accounts every swap call starts with. The adapter's own accounts, such as
+/// pools and reserves, follow as given by the caller.
+pub struct SwapAccounts<'info> {
+    // Owner of `source`, signed for by this program
+    pub authority: AccountInfo<'info>,
+    pub source: AccountInfo<'info>,
+    pub destination: AccountInfo<'info>,
+    pub input_mint: AccountInfo<'info>,
+    pub output_mint: AccountInfo<'info>,
+    pub input_token_program: AccountInfo<'info>,
+    pub output_token_program: AccountInfo<'info>,
+}
+
+/// Swaps `amount_in` out of `source` into `destination` through
+/// `swap_program`. The adapter enforces `min_amount_out` too, but callers
+/// must still measure what actually landed in `destination`.
+pub fn invoke_swap<'info>(
+    swap_program: &AccountInfo<'info>,
+    accounts: SwapAccounts<'info>,
+    adapter_accounts: &[AccountInfo<'info>],
+    amount_in: u64,
+    min_amount_out: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let mut metas = vec![
+        AccountMeta::new_readonly(accounts.authority.key(), true),
+        AccountMeta::new(accounts.source.key(), false),
+        AccountMeta::new(accounts.destination.key(), false),
+        AccountMeta::new_readonly(accounts.input_mint.key(), false),
+        AccountMeta::new_readonly(accounts.output_mint.key(), false),
+        AccountMeta::new_readonly(accounts.input_token_program.key(), false),
+        AccountMeta::new_readonly(accounts.output_token_program.key(), false),
+    ];
+    metas.extend(adapter_accounts.iter().map(|account| AccountMeta {
+        pubkey: account.key(),
+        is_signer: account.is_signer,
+        is_writable: account.is_writable,
+    }));
+
+    let mut data = SWAP_DISCRIMINATOR.to_vec();
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&min_amount_out.to_le_bytes());
+
+    let mut infos = vec![
+        accounts.authority,
+        accounts.source,
+        accounts.destination,
+        accounts.input_mint,
+        accounts.output_mint,
+        accounts.input_token_program,
+        accounts.output_token_program,
+    ];
+    infos.extend_from_slice(adapter_accounts);
+    infos.push(swap_program.clone());
+
+    invoke_signed(
+        &Instruction {
+            program_id: swap_program.key(),
+            accounts: metas,
+            data,
+        },
+        &infos,
+        signer_seeds,
+    )?;
+    Ok(())
+}
@@ -0,0 +1,190 @@
+mod common;
+
+use common::*;
+use cred_x::{CredXError, InsuranceFund, LoanAccount, ProtocolState, StabilityPool};
+use cred_x_client::{
+    deposit_to_stability_pool, fund_insurance, initialize_stability_pool, insurance_fund_pda,
+    loan_pda, resolve_bad_debt, sell_fund_collateral, stability_pool_pda,
+};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+/// Collateral of the loan that goes bad, a hundredth of a unit.
+const SMALL_COLLATERAL: u64 = ONE_COLLATERAL / 100;
+/// Full borrow against `SMALL_COLLATERAL` at the initial price, fee included.
+const SMALL_DEBT: u64 = 900_000_000_000_000;
+/// Borrow fee collected from a full borrow against four units.
+const FEES: u64 = 3_600_000_000_000_000;
+/// Leaves `SMALL_COLLATERAL` worth 80% of its debt.
+const CRASH_PRICE: u64 = 80_000_000;
+
+struct BadDebtEnv {
+    env: TestEnv,
+    user: Keypair,
+}
+
+/// A 1% borrow fee, a large borrower whose fee funds the insurance fund and
+/// a small loan at the maximum LTV.
+async fn bad_debt_env() -> BadDebtEnv {
+    let mut params = protocol_params();
+    params.borrow_fee_bps = 100;
+    let mut env = TestEnv::with_params(params, anchor_spl::token::ID).await;
+    env.borrower(4 * ONE_COLLATERAL).await;
+    let user = env.borrower(SMALL_COLLATERAL).await;
+    BadDebtEnv { env, user }
+}
+
+impl BadDebtEnv {
+    async fn fund(&mut self, amount: u64) {
+        let admin = self.env.admin.insecure_clone();
+        let ix = fund_insurance(&self.env.protocol, amount);
+        self.env.process(&[ix], &[&admin]).await.unwrap();
+    }
+
+    async fn resolve(&mut self) -> std::result::Result<(), solana_program_test::BanksClientError> {
+        let ix = resolve_bad_debt(
+            &self.env.protocol,
+            self.env.admin.pubkey(),
+            self.user.pubkey(),
+            &self.env.collateral,
+        );
+        let admin = self.env.admin.insecure_clone();
+        self.env.process(&[ix], &[&admin]).await
+    }
+
+    async fn sell(
+        &mut self,
+        amount: u64,
+    ) -> std::result::Result<(), solana_program_test::BanksClientError> {
+        let ix = sell_fund_collateral(&self.env.protocol, &self.env.collateral, amount);
+        self.env.process(&[ix], &[]).await
+    }
+
+    fn fund_address(&self) -> Pubkey {
+        insurance_fund_pda(&self.env.protocol.protocol()).0
+    }
+}
+
+#[tokio::test]
+async fn resolve_bad_debt_burns_from_the_insurance_fund() {
+    let mut s = bad_debt_env().await;
+    let admin = s.env.admin.insecure_clone();
+    assert_error(
+        s.env
+            .process(&[fund_insurance(&s.env.protocol, FEES * 2)], &[&admin])
+            .await,
+        CredXError::InsufficientCreditTokens,
+    );
+    s.fund(FEES).await;
+    let fund: InsuranceFund = s.env.account(&s.fund_address()).await;
+    assert_eq!(fund.total_funded, FEES);
+
+    assert_error(s.resolve().await, CredXError::LoanNotUnderwater);
+
+    s.env.set_price(CRASH_PRICE, 0).await;
+    s.resolve().await.unwrap();
+
+    let loan: LoanAccount = s.env.account(&loan_pda(&s.user.pubkey()).0).await;
+    assert_eq!(loan.remaining_debt, 0);
+    assert_eq!(loan.collateral_amount, 0);
+    let vault_ata = s.env.collateral.vault_ata(&s.user.pubkey());
+    assert_eq!(s.env.token_balance(&vault_ata).await, 0);
+
+    let fund_address = s.fund_address();
+    let fund_collateral = s.env.collateral.user_ata(&fund_address);
+    assert_eq!(
+        s.env.token_balance(&fund_collateral).await,
+        SMALL_COLLATERAL
+    );
+    let fund_credit = s.env.protocol.credit_ata(&fund_address);
+    assert_eq!(s.env.token_balance(&fund_credit).await, FEES - SMALL_DEBT);
+    let fund: InsuranceFund = s.env.account(&fund_address).await;
+    assert_eq!(fund.total_covered, SMALL_DEBT);
+    assert_eq!(fund.total_socialized, 0);
+    assert_eq!(fund.bad_debts_resolved, 1);
+
+    let state: ProtocolState = s.env.account(&s.env.protocol.protocol()).await;
+    assert_eq!(state.total_debt, 4 * 90_000_000_000_000_000);
+    assert_eq!(state.stats.open_loans, 1);
+
+    assert_error(s.resolve().await, CredXError::NoOutstandingDebt);
+}
+
+#[tokio::test]
+async fn resolve_bad_debt_socializes_what_the_fund_cannot_cover() {
+    let mut s = bad_debt_env().await;
+    let covered = SMALL_DEBT / 9;
+    s.fund(covered).await;
+    s.env.set_price(CRASH_PRICE, 0).await;
+    s.resolve().await.unwrap();
+
+    let fund_address = s.fund_address();
+    let fund_credit = s.env.protocol.credit_ata(&fund_address);
+    assert_eq!(s.env.token_balance(&fund_credit).await, 0);
+    let fund: InsuranceFund = s.env.account(&fund_address).await;
+    assert_eq!(fund.total_covered, covered);
+    assert_eq!(fund.total_socialized, SMALL_DEBT - covered);
+
+    // The written-off debt is left as credit with no loan behind it
+    let state: ProtocolState = s.env.account(&s.env.protocol.protocol()).await;
+    let credit_mint = s.env.protocol.credit_mint;
+    let supply = s.env.mint_supply(&credit_mint).await;
+    assert_eq!(supply - state.total_debt, fund.total_socialized);
+}
+
+#[tokio::test]
+async fn seized_collateral_is_sold_to_the_stability_pool() {
+    let mut s = bad_debt_env().await;
+    let depositor = s.env.borrower(ONE_COLLATERAL).await;
+    let admin = s.env.admin.insecure_clone();
+    let ix = initialize_stability_pool(&s.env.protocol, &s.env.collateral);
+    s.env.process(&[ix], &[&admin]).await.unwrap();
+    s.fund(FEES).await;
+    s.env.set_price(CRASH_PRICE, 0).await;
+    s.resolve().await.unwrap();
+
+    // The pool has no credit to pay with yet
+    assert_error(
+        s.sell(SMALL_COLLATERAL).await,
+        CredXError::StabilityPoolEmpty,
+    );
+    let depositor_credit = s.env.protocol.credit_ata(&depositor.pubkey());
+    let deposited = s.env.token_balance(&depositor_credit).await;
+    let ix = deposit_to_stability_pool(
+        &s.env.protocol,
+        depositor.pubkey(),
+        &s.env.collateral,
+        deposited,
+    );
+    s.env.process(&[ix], &[&depositor]).await.unwrap();
+
+    assert_error(
+        s.sell(SMALL_COLLATERAL + 1).await,
+        CredXError::InsufficientBalance,
+    );
+    s.sell(SMALL_COLLATERAL).await.unwrap();
+
+    let paid = SMALL_COLLATERAL * CRASH_PRICE;
+    let fund_address = s.fund_address();
+    let fund_collateral = s.env.collateral.user_ata(&fund_address);
+    assert_eq!(s.env.token_balance(&fund_collateral).await, 0);
+    let fund_credit = s.env.protocol.credit_ata(&fund_address);
+    assert_eq!(
+        s.env.token_balance(&fund_credit).await,
+        FEES - SMALL_DEBT + paid
+    );
+    let fund: InsuranceFund = s.env.account(&fund_address).await;
+    assert_eq!(fund.total_recovered, paid);
+
+    let pool_address = stability_pool_pda(&s.env.protocol.protocol(), &s.env.collateral.mint).0;
+    let pool: StabilityPool = s.env.account(&pool_address).await;
+    assert_eq!(pool.total_deposits, deposited - paid);
+    assert_eq!(pool.total_collateral, SMALL_COLLATERAL);
+    let pool_collateral = s.env.collateral.user_ata(&pool_address);
+    assert_eq!(
+        s.env.token_balance(&pool_collateral).await,
+        SMALL_COLLATERAL
+    );
+}
@@ -0,0 +1,246 @@
+mod common;
+
+use common::*;
+use cred_x::{CollateralConfig, CredXError, LoanAccount, ProtocolState};
+use cred_x_client::{
+    lend_credit_token, loan_pda, reset_circuit_breaker, set_debt_ceiling, trip_circuit_breaker,
+    tripped_circuit_breaker,
+};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+/// Credit minted for `collateral` at `price` under the default 60% LTV.
+fn borrow_limit(collateral: u64, price: u64) -> u64 {
+    (collateral as u128 * price as u128 * 6_000 / 10_000) as u64
+}
+
+#[tokio::test]
+async fn lend_mints_credit_up_to_ltv() {
+    let mut env = TestEnv::new().await;
+    let user = env.borrower(ONE_COLLATERAL).await;
+
+    let expected = borrow_limit(ONE_COLLATERAL, INITIAL_PRICE);
+    let loan: LoanAccount = env.account(&loan_pda(&user.pubkey()).0).await;
+    assert_eq!(loan.remaining_debt, expected);
+    let credit_ata = env.protocol.credit_ata(&user.pubkey());
+    assert_eq!(env.token_balance(&credit_ata).await, expected);
+
+    let state: ProtocolState = env.account(&env.protocol.protocol()).await;
+    assert_eq!(state.total_debt, expected);
+    assert_eq!(state.stats.total_minted, expected);
+    assert_eq!(state.stats.open_loans, 1);
+}
+
+#[tokio::test]
+async fn lend_splits_the_borrow_fee() {
+    let mut params = protocol_params();
+    params.borrow_fee_bps = 100;
+    let mut env = TestEnv::with_params(params, anchor_spl::token::ID).await;
+    let user = env.borrower(ONE_COLLATERAL).await;
+
+    let debt = borrow_limit(ONE_COLLATERAL, INITIAL_PRICE);
+    let fee = debt / 100;
+    let credit_ata = env.protocol.credit_ata(&user.pubkey());
+    assert_eq!(env.token_balance(&credit_ata).await, debt - fee);
+    let protocol_ata = env.protocol.protocol_credit_ata();
+    assert_eq!(env.token_balance(&protocol_ata).await, fee);
+    let loan: LoanAccount = env.account(&loan_pda(&user.pubkey()).0).await;
+    assert_eq!(loan.remaining_debt, debt);
+}
+
+#[tokio::test]
+async fn lend_tops_up_after_a_price_rise() {
+    let mut env = TestEnv::new().await;
+    let user = env.borrower(ONE_COLLATERAL).await;
+
+    assert_error(env.borrow(&user).await, CredXError::MaxBorrowLimitReached);
+
+    env.set_price(2 * INITIAL_PRICE, 0).await;
+    env.borrow(&user).await.unwrap();
+    let loan: LoanAccount = env.account(&loan_pda(&user.pubkey()).0).await;
+    assert_eq!(
+        loan.remaining_debt,
+        borrow_limit(ONE_COLLATERAL, 2 * INITIAL_PRICE)
+    );
+
+    let state: ProtocolState = env.account(&env.protocol.protocol()).await;
+    assert_eq!(state.stats.open_loans, 1);
+}
+
+#[tokio::test]
+async fn lend_rejects_underwater_loans() {
+    let mut env = TestEnv::new().await;
+    let user = env.borrower(ONE_COLLATERAL).await;
+
+    env.set_price(INITIAL_PRICE / 2, 0).await;
+    assert_error(env.borrow(&user).await, CredXError::ExceedsMaxBorrow);
+}
+
+#[tokio::test]
+async fn lend_requires_collateral() {
+    let mut env = TestEnv::new().await;
+    let user = env.new_user(ONE_COLLATERAL).await;
+    assert_error(env.borrow(&user).await, CredXError::NoCollateralDeposited);
+}
+
+#[tokio::test]
+async fn lend_rejects_a_zero_borrow() {
+    let mut env = TestEnv::new().await;
+    let user = env.new_user(1).await;
+    env.deposit(&user, 1).await.unwrap();
+    env.set_price(1, 0).await;
+    assert_error(env.borrow(&user).await, CredXError::ZeroBorrowAmount);
+}
+
+#[tokio::test]
+async fn lend_respects_the_debt_ceiling() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let ceiling = borrow_limit(ONE_COLLATERAL, INITIAL_PRICE) - 1;
+    env.process(&[set_debt_ceiling(&env.protocol, ceiling)], &[&admin])
+        .await
+        .unwrap();
+
+    let user = env.new_user(ONE_COLLATERAL).await;
+    env.deposit(&user, ONE_COLLATERAL).await.unwrap();
+    assert_error(env.borrow(&user).await, CredXError::DebtCeilingExceeded);
+}
+
+#[tokio::test]
+async fn lend_rejects_stale_and_future_prices() {
+    let mut env = TestEnv::new().await;
+    let user = env.new_user(ONE_COLLATERAL).await;
+    env.deposit(&user, ONE_COLLATERAL).await.unwrap();
+
+    env.warp_seconds(300).await;
+    assert_error(env.borrow(&user).await, CredXError::StalePrice);
+
+    // A stricter borrow window applies to lending only.
+    env.set_price(INITIAL_PRICE, 0).await;
+    let mut params = collateral_params(env.oracle.pubkey());
+    params.staleness.borrow_max_age_secs = 30;
+    env.configure_collateral(params).await.unwrap();
+    env.warp_seconds(30).await;
+    assert_error(env.borrow(&user).await, CredXError::StalePrice);
+
+    env.set_price(INITIAL_PRICE, 0).await;
+    env.warp_seconds(-60).await;
+    assert_error(env.borrow(&user).await, CredXError::FuturePriceTimestamp);
+}
+
+#[tokio::test]
+async fn lend_rejects_a_zero_price() {
+    let mut env = TestEnv::new().await;
+    let user = env.new_user(ONE_COLLATERAL).await;
+    env.deposit(&user, ONE_COLLATERAL).await.unwrap();
+    env.set_price(0, 0).await;
+    assert_error(env.borrow(&user).await, CredXError::InvalidPrice);
+}
+
+#[tokio::test]
+async fn lend_checks_admin_and_oracle_binding() {
+    let mut env = TestEnv::new().await;
+    let user = env.new_user(ONE_COLLATERAL).await;
+    env.deposit(&user, ONE_COLLATERAL).await.unwrap();
+
+    let intruder = Keypair::new();
+    let admin = env.admin.pubkey();
+    let ix = replace_account(
+        lend_credit_token(&env.protocol, user.pubkey(), &env.collateral),
+        &admin,
+        &intruder.pubkey(),
+    );
+    assert_error(
+        env.process(&[ix], &[&user, &intruder]).await,
+        CredXError::UnauthorizedAdmin,
+    );
+
+    let other_oracle = TestEnv::collateral_vault(&user.pubkey());
+    let ix = replace_account(
+        lend_credit_token(&env.protocol, user.pubkey(), &env.collateral),
+        &env.oracle.pubkey(),
+        &other_oracle,
+    );
+    let admin = env.admin.insecure_clone();
+    assert_error(
+        env.process(&[ix], &[&user, &admin]).await,
+        CredXError::InvalidOracleAccount,
+    );
+}
+
+#[tokio::test]
+async fn circuit_breaker_pauses_lending_until_reset() {
+    let mut env = TestEnv::new().await;
+    let mut params = collateral_params(env.oracle.pubkey());
+    params.max_price_move_bps = 1_000;
+    env.configure_collateral(params).await.unwrap();
+
+    let user = env.borrower(ONE_COLLATERAL).await;
+    let debt = borrow_limit(ONE_COLLATERAL, INITIAL_PRICE);
+
+    // Nothing to trip at the reference price
+    let trip = trip_circuit_breaker(&env.protocol, &env.collateral);
+    assert_error(
+        env.process(std::slice::from_ref(&trip), &[]).await,
+        CredXError::CircuitBreakerNotTripped,
+    );
+
+    // The tripping borrow fails, and the trip with it until anyone saves it
+    env.set_price(2 * INITIAL_PRICE, 0).await;
+    assert_error(
+        env.borrow(&user).await,
+        CredXError::CircuitBreakerTripPending,
+    );
+    let config: CollateralConfig = env.account(&env.collateral.config(&env.protocol)).await;
+    assert!(!config.circuit_breaker.tripped);
+    env.process(std::slice::from_ref(&trip), &[]).await.unwrap();
+    let config: CollateralConfig = env.account(&env.collateral.config(&env.protocol)).await;
+    assert!(config.circuit_breaker.tripped);
+    assert_eq!(config.circuit_breaker.last_price, INITIAL_PRICE);
+    let loan: LoanAccount = env.account(&loan_pda(&user.pubkey()).0).await;
+    assert_eq!(loan.remaining_debt, debt);
+
+    assert_error(env.borrow(&user).await, CredXError::CircuitBreakerTripped);
+    // A fresh blockhash keeps the retry from being deduplicated
+    env.warp_seconds(1).await;
+    assert_error(
+        env.process(&[trip], &[]).await,
+        CredXError::CircuitBreakerTripped,
+    );
+
+    let admin = env.admin.insecure_clone();
+    let ix = reset_circuit_breaker(&env.protocol, &env.collateral);
+    env.process(&[ix], &[&admin]).await.unwrap();
+    let config: CollateralConfig = env.account(&env.collateral.config(&env.protocol)).await;
+    assert!(!config.circuit_breaker.tripped);
+    assert_eq!(config.circuit_breaker.last_price, 2 * INITIAL_PRICE);
+
+    env.borrow(&user).await.unwrap();
+    let loan: LoanAccount = env.account(&loan_pda(&user.pubkey()).0).await;
+    assert_eq!(
+        loan.remaining_debt,
+        borrow_limit(ONE_COLLATERAL, 2 * INITIAL_PRICE)
+    );
+}
+
+/// The native program-test runtime prints `msg!` output to stdout instead of
+/// the transaction logs, so the client is fed logs laid out the way a
+/// validator records them.
+#[test]
+fn tripped_circuit_breaker_is_read_from_the_logs() {
+    let program = cred_x::ID.to_string();
+    let mint = Pubkey::new_unique();
+    let mut logs = vec![
+        format!("Program {program} invoke [1]"),
+        "Program log: Instruction: LendCreditToken".to_string(),
+    ];
+    assert_eq!(tripped_circuit_breaker(&logs), None);
+
+    logs.push(format!(
+        "Program log: Circuit breaker tripped for collateral {mint}: price 200000000 vs last accepted 100000000"
+    ));
+    logs.push(format!("Program {program} success"));
+    assert_eq!(tripped_circuit_breaker(&logs), Some(mint));
+}
@@ -0,0 +1,235 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::token::spl_token;
+use common::*;
+use cred_x::{CollateralConfig, CollateralConfigParams, CollateralVault, CredXError, LoanAccount};
+use cred_x_client::{
+    collateral_config_pda, configure_collateral, initialize_loan, loan_pda, CollateralContext,
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+type Tweak<'a> = Box<dyn Fn(&mut CollateralConfigParams) + 'a>;
+
+#[tokio::test]
+async fn configure_collateral_stores_config() {
+    let mut env = TestEnv::new().await;
+    let mut params = collateral_params(env.oracle.pubkey());
+    params.supply_cap = 5 * ONE_COLLATERAL;
+    params.max_price_move_bps = 2_000;
+    env.configure_collateral(params).await.unwrap();
+
+    let config: CollateralConfig = env.account(&env.collateral.config(&env.protocol)).await;
+    assert_eq!(config.protocol, env.protocol.protocol());
+    assert_eq!(config.mint, env.collateral.mint);
+    assert_eq!(config.supply_cap, 5 * ONE_COLLATERAL);
+    assert_eq!(config.oracle.primary, env.oracle.pubkey());
+    assert_eq!(config.circuit_breaker.max_move_bps, 2_000);
+    assert_eq!(config.total_collateral, 0);
+}
+
+#[tokio::test]
+async fn configure_collateral_rejects_invalid_params() {
+    let mut env = TestEnv::new().await;
+    let oracle = env.oracle.pubkey();
+    let secondary = Pubkey::new_unique();
+
+    let with_secondary = |p: &mut CollateralConfigParams| {
+        p.oracle.secondary = secondary;
+        p.oracle.max_deviation_bps = 500;
+        p.oracle.fallback_max_age_secs = 60;
+        p.oracle.fallback_conf_multiplier_bps = 20_000;
+    };
+    let cases: Vec<(Tweak<'_>, CredXError)> = vec![
+        (
+            Box::new(|p| p.pricing.conf_multiplier_bps = 50_001),
+            CredXError::InvalidPricingPolicy,
+        ),
+        (
+            Box::new(|p| p.oracle.primary = Pubkey::default()),
+            CredXError::InvalidOracleSources,
+        ),
+        (
+            Box::new(move |p| p.oracle.secondary = p.oracle.primary),
+            CredXError::InvalidOracleSources,
+        ),
+        (
+            Box::new(move |p| {
+                with_secondary(p);
+                p.oracle.max_deviation_bps = 0;
+            }),
+            CredXError::InvalidOracleSources,
+        ),
+        (
+            Box::new(move |p| {
+                with_secondary(p);
+                p.oracle.fallback_max_age_secs = 301;
+            }),
+            CredXError::InvalidOracleSources,
+        ),
+        (
+            Box::new(move |p| {
+                with_secondary(p);
+                p.oracle.fallback_conf_multiplier_bps = 9_999;
+            }),
+            CredXError::InvalidOracleSources,
+        ),
+        (
+            Box::new(|p| p.staleness.max_age_secs = -1),
+            CredXError::InvalidStalenessWindow,
+        ),
+        (
+            Box::new(|p| {
+                p.staleness.max_age_secs = 60;
+                p.staleness.borrow_max_age_secs = 120;
+            }),
+            CredXError::InvalidStalenessWindow,
+        ),
+        (
+            Box::new(|p| p.max_price_move_bps = 10_001),
+            CredXError::InvalidCircuitBreaker,
+        ),
+    ];
+
+    for (tweak, expected) in cases {
+        let mut params = collateral_params(oracle);
+        tweak(&mut params);
+        assert_error(env.configure_collateral(params).await, expected);
+    }
+}
+
+#[tokio::test]
+async fn configure_collateral_is_admin_only_and_rejects_the_credit_mint() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+
+    let ix = configure_collateral(
+        &env.protocol,
+        env.protocol.credit_mint,
+        collateral_params(env.oracle.pubkey()),
+    );
+    assert_error(
+        env.process(&[ix], &[&admin]).await,
+        CredXError::InvalidCollateralMint,
+    );
+
+    let intruder = Keypair::new();
+    env.airdrop(&intruder.pubkey(), 1_000_000_000).await;
+    let ix = replace_account(
+        configure_collateral(
+            &env.protocol,
+            env.collateral.mint,
+            collateral_params(env.oracle.pubkey()),
+        ),
+        &admin.pubkey(),
+        &intruder.pubkey(),
+    );
+    assert_error(
+        env.process(&[ix], &[&intruder]).await,
+        CredXError::UnauthorizedAdmin,
+    );
+}
+
+#[tokio::test]
+async fn initialize_loan_creates_vault_and_loan() {
+    let mut env = TestEnv::new().await;
+    let user = env.new_user(ONE_COLLATERAL).await;
+
+    let loan: LoanAccount = env.account(&loan_pda(&user.pubkey()).0).await;
+    assert_eq!(loan.user, user.pubkey());
+    assert_eq!(loan.collateral_amount, 0);
+    assert_eq!(loan.remaining_debt, 0);
+    assert_eq!(loan.oracle_price_account, env.oracle.pubkey());
+
+    let vault: CollateralVault = env
+        .account(&TestEnv::collateral_vault(&user.pubkey()))
+        .await;
+    assert_eq!(vault.mint, env.collateral.mint);
+
+    let user_credit_ata = env.protocol.credit_ata(&user.pubkey());
+    assert!(env.account_exists(&user_credit_ata).await);
+}
+
+#[tokio::test]
+async fn initialize_loan_requires_an_oracle() {
+    let mut env = TestEnv::new().await;
+    let user = Keypair::new();
+    env.airdrop(&user.pubkey(), 1_000_000_000).await;
+
+    let mut collateral = env.collateral;
+    collateral.oracle.primary = Pubkey::default();
+    let ix = initialize_loan(&env.protocol, user.pubkey(), &collateral);
+    assert_error(
+        env.process(&[ix], &[&user]).await,
+        CredXError::InvalidOracleAccount,
+    );
+}
+
+#[tokio::test]
+async fn deposit_collateral_credits_loan_and_config() {
+    let mut env = TestEnv::new().await;
+    let user = env.new_user(2 * ONE_COLLATERAL).await;
+
+    env.deposit(&user, ONE_COLLATERAL).await.unwrap();
+    env.deposit(&user, ONE_COLLATERAL / 2).await.unwrap();
+
+    let loan: LoanAccount = env.account(&loan_pda(&user.pubkey()).0).await;
+    assert_eq!(loan.collateral_amount, 3 * ONE_COLLATERAL / 2);
+    let vault_ata = env.collateral.vault_ata(&user.pubkey());
+    assert_eq!(env.token_balance(&vault_ata).await, 3 * ONE_COLLATERAL / 2);
+    let config: CollateralConfig = env.account(&env.collateral.config(&env.protocol)).await;
+    assert_eq!(config.total_collateral, 3 * ONE_COLLATERAL / 2);
+}
+
+#[tokio::test]
+async fn deposit_collateral_rejects_bad_amounts() {
+    let mut env = TestEnv::new().await;
+    let user = env.new_user(ONE_COLLATERAL).await;
+
+    assert_error(env.deposit(&user, 0).await, CredXError::InvalidAmount);
+    assert_error(
+        env.deposit(&user, ONE_COLLATERAL + 1).await,
+        CredXError::InsufficientBalance,
+    );
+
+    let mut params = collateral_params(env.oracle.pubkey());
+    params.supply_cap = ONE_COLLATERAL / 2;
+    env.configure_collateral(params).await.unwrap();
+    assert_error(
+        env.deposit(&user, ONE_COLLATERAL).await,
+        CredXError::CollateralSupplyCapExceeded,
+    );
+    env.deposit(&user, ONE_COLLATERAL / 2).await.unwrap();
+}
+
+#[tokio::test]
+async fn deposit_collateral_rejects_a_different_mint() {
+    let mut env = TestEnv::new().await;
+    let user = env.new_user(ONE_COLLATERAL).await;
+    let admin = env.admin.insecure_clone();
+
+    let other_mint = env
+        .create_mint(&spl_token::ID, COLLATERAL_DECIMALS, &admin.pubkey())
+        .await;
+    let ix = configure_collateral(
+        &env.protocol,
+        other_mint,
+        collateral_params(env.oracle.pubkey()),
+    );
+    env.process(&[ix], &[&admin]).await.unwrap();
+    env.mint_to_owner(&other_mint, &spl_token::ID, &user.pubkey(), ONE_COLLATERAL)
+        .await;
+    assert!(
+        env.account_exists(&collateral_config_pda(&env.protocol.protocol(), &other_mint).0)
+            .await
+    );
+
+    env.collateral = CollateralContext {
+        mint: other_mint,
+        ..env.collateral
+    };
+    assert_error(
+        env.deposit(&user, ONE_COLLATERAL).await,
+        CredXError::MintMismatch,
+    );
+}
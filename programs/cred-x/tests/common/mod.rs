@@ -0,0 +1,643 @@
+//! In-process harness shared by the integration tests. The program runs as a
+//! native builtin next to the SPL Token, Token-2022 and ATA programs bundled
+//! with `solana-program-test`, the mock swap program stands in for a DEX and
+//! a forwarding program stands in for third parties calling in through CPI.
+
+#![allow(dead_code)]
+
+use anchor_lang::{
+    prelude::*,
+    solana_program::{
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction},
+        program::invoke,
+        program_pack::Pack,
+    },
+    system_program, InstructionData, ToAccountMetas,
+};
+use anchor_spl::{
+    associated_token::{
+        self, spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+    },
+    token::spl_token,
+    token_2022::spl_token_2022::{
+        self,
+        extension::{transfer_fee, ExtensionType, StateWithExtensions},
+        state::{Account as TokenAccount, Mint},
+    },
+};
+use cred_x::{
+    CollateralConfigParams, CreditTransferMode, InitializeProtocolParams, OracleSources,
+    PricingPolicy, StalenessPolicy,
+};
+use cred_x_client::{
+    associated_token_address, collateral_vault_pda, configure_collateral, create_simple_oracle,
+    deposit_collateral, initialize_loan, initialize_protocol, lend_credit_token,
+    update_simple_oracle, CollateralContext, ProtocolContext,
+};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestContext};
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solana_system_interface::instruction as system_instruction;
+
+pub const COLLATERAL_DECIMALS: u8 = 9;
+pub const ONE_COLLATERAL: u64 = 1_000_000_000;
+pub const CREDIT_DECIMALS: u8 = 6;
+/// $150 with the oracle's 6 decimals.
+pub const INITIAL_PRICE: u64 = 150_000_000;
+
+fn entry(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    // Anchor ties the account slice to the account infos' lifetime, which the
+    // native processor signature cannot express.
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    cred_x::entry(program_id, accounts, data)
+}
+
+fn mock_swap_entry(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    mock_swap::entry(program_id, accounts, data)
+}
+
+/// Program that forwards its data and every account after the first to the
+/// program named by the first account.
+pub const CPI_WRAPPER_ID: Pubkey = Pubkey::new_from_array([0xcc; 32]);
+
+fn cpi_wrapper_entry(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let (program, forwarded) = accounts
+        .split_first()
+        .ok_or(ProgramError::NotEnoughAccountKeys)?;
+    let ix = Instruction {
+        program_id: *program.key,
+        accounts: forwarded
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: *account.key,
+                is_signer: account.is_signer,
+                is_writable: account.is_writable,
+            })
+            .collect(),
+        data: data.to_vec(),
+    };
+    invoke(&ix, accounts)
+}
+
+pub fn program_test() -> ProgramTest {
+    let mut program_test = ProgramTest::new("cred_x", cred_x::ID, processor!(entry));
+    program_test.add_program("mock_swap", mock_swap::ID, processor!(mock_swap_entry));
+    program_test.add_program("cpi_wrapper", CPI_WRAPPER_ID, processor!(cpi_wrapper_entry));
+    program_test.prefer_bpf(false);
+    program_test
+}
+
+pub fn protocol_params() -> InitializeProtocolParams {
+    InitializeProtocolParams {
+        credit_decimals: CREDIT_DECIMALS,
+        ltv_ratio_bps: 6_000,
+        liquidation_threshold_bps: 8_000,
+        max_price_age_secs: 300,
+        borrow_fee_bps: 0,
+        debt_ceiling: u64::MAX,
+        credit_transfer_mode: CreditTransferMode::Unrestricted,
+        credit_metadata: None,
+    }
+}
+
+pub fn collateral_params(oracle: Pubkey) -> CollateralConfigParams {
+    CollateralConfigParams {
+        supply_cap: u64::MAX,
+        pricing: PricingPolicy {
+            use_ema: false,
+            conf_multiplier_bps: 0,
+        },
+        oracle: OracleSources {
+            primary: oracle,
+            secondary: Pubkey::default(),
+            max_deviation_bps: 0,
+            fallback_max_age_secs: 0,
+            fallback_conf_multiplier_bps: 0,
+        },
+        staleness: StalenessPolicy {
+            max_age_secs: 0,
+            borrow_max_age_secs: 0,
+        },
+        max_price_move_bps: 0,
+    }
+}
+
+pub struct TestEnv {
+    pub ctx: ProgramTestContext,
+    pub admin: Keypair,
+    pub protocol: ProtocolContext,
+    pub collateral: CollateralContext,
+    pub oracle: Keypair,
+    nonce: u32,
+}
+
+impl TestEnv {
+    /// Protocol with a legacy SPL credit mint and one configured collateral
+    /// priced by a `SimplePriceOracle` at `INITIAL_PRICE`.
+    pub async fn new() -> Self {
+        Self::with_params(protocol_params(), spl_token::ID).await
+    }
+
+    pub async fn with_params(
+        params: InitializeProtocolParams,
+        credit_token_program: Pubkey,
+    ) -> Self {
+        let ctx = program_test().start_with_context().await;
+        let admin = Keypair::new();
+        let oracle = Keypair::new();
+        let mut env = Self {
+            protocol: ProtocolContext::new(admin.pubkey(), credit_token_program),
+            collateral: CollateralContext {
+                mint: Pubkey::default(),
+                token_program: spl_token::ID,
+                oracle: collateral_params(oracle.pubkey()).oracle,
+            },
+            ctx,
+            admin,
+            oracle,
+            nonce: 0,
+        };
+        env.airdrop(&env.admin.pubkey(), 100_000_000_000).await;
+
+        let admin = env.admin.insecure_clone();
+        env.process(
+            &[initialize_protocol(
+                admin.pubkey(),
+                credit_token_program,
+                params,
+            )],
+            &[&admin],
+        )
+        .await
+        .unwrap();
+
+        env.collateral.mint = env
+            .create_mint(&spl_token::ID, COLLATERAL_DECIMALS, &admin.pubkey())
+            .await;
+        let oracle = env.oracle.insecure_clone();
+        env.process(
+            &[create_simple_oracle(
+                admin.pubkey(),
+                oracle.pubkey(),
+                INITIAL_PRICE,
+                0,
+            )],
+            &[&admin, &oracle],
+        )
+        .await
+        .unwrap();
+        env.configure_collateral(collateral_params(oracle.pubkey()))
+            .await
+            .unwrap();
+        env
+    }
+
+    /// Sends `instructions` with the context payer. A compute budget
+    /// instruction with a per-call limit keeps otherwise identical
+    /// transactions from being deduplicated.
+    pub async fn process(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> std::result::Result<(), BanksClientError> {
+        self.nonce += 1;
+        let mut all = vec![ComputeBudgetInstruction::set_compute_unit_limit(
+            1_400_000 - self.nonce,
+        )];
+        all.extend_from_slice(instructions);
+
+        let mut keypairs: Vec<&Keypair> = vec![&self.ctx.payer];
+        keypairs.extend_from_slice(signers);
+        let blockhash = self.ctx.banks_client.get_latest_blockhash().await?;
+        let tx = Transaction::new_signed_with_payer(
+            &all,
+            Some(&self.ctx.payer.pubkey()),
+            &keypairs,
+            blockhash,
+        );
+        self.ctx.banks_client.process_transaction(tx).await
+    }
+
+    pub async fn airdrop(&mut self, to: &Pubkey, lamports: u64) {
+        let payer = self.ctx.payer.pubkey();
+        self.process(&[system_instruction::transfer(&payer, to, lamports)], &[])
+            .await
+            .unwrap();
+    }
+
+    pub async fn create_mint(
+        &mut self,
+        token_program: &Pubkey,
+        decimals: u8,
+        authority: &Pubkey,
+    ) -> Pubkey {
+        let mint = Keypair::new();
+        let rent = self.ctx.banks_client.get_rent().await.unwrap();
+        let payer = self.ctx.payer.pubkey();
+        self.process(
+            &[
+                system_instruction::create_account(
+                    &payer,
+                    &mint.pubkey(),
+                    rent.minimum_balance(spl_token::state::Mint::LEN),
+                    spl_token::state::Mint::LEN as u64,
+                    token_program,
+                ),
+                spl_token_2022::instruction::initialize_mint2(
+                    token_program,
+                    &mint.pubkey(),
+                    authority,
+                    None,
+                    decimals,
+                )
+                .unwrap(),
+            ],
+            &[&mint],
+        )
+        .await
+        .unwrap();
+        mint.pubkey()
+    }
+
+    /// Token-2022 mint that withholds `fee_bps` of every transfer, without
+    /// a cap.
+    pub async fn create_fee_mint(
+        &mut self,
+        decimals: u8,
+        fee_bps: u16,
+        authority: &Pubkey,
+    ) -> Pubkey {
+        let mint = Keypair::new();
+        let space =
+            ExtensionType::try_calculate_account_len::<Mint>(&[ExtensionType::TransferFeeConfig])
+                .unwrap();
+        let rent = self.ctx.banks_client.get_rent().await.unwrap();
+        let payer = self.ctx.payer.pubkey();
+        self.process(
+            &[
+                system_instruction::create_account(
+                    &payer,
+                    &mint.pubkey(),
+                    rent.minimum_balance(space),
+                    space as u64,
+                    &spl_token_2022::ID,
+                ),
+                transfer_fee::instruction::initialize_transfer_fee_config(
+                    &spl_token_2022::ID,
+                    &mint.pubkey(),
+                    Some(authority),
+                    Some(authority),
+                    fee_bps,
+                    u64::MAX,
+                )
+                .unwrap(),
+                spl_token_2022::instruction::initialize_mint2(
+                    &spl_token_2022::ID,
+                    &mint.pubkey(),
+                    authority,
+                    None,
+                    decimals,
+                )
+                .unwrap(),
+            ],
+            &[&mint],
+        )
+        .await
+        .unwrap();
+        mint.pubkey()
+    }
+
+    /// Creates `owner`'s associated account for `mint` if needed and mints
+    /// `amount` into it, signed by the admin as mint authority.
+    pub async fn mint_to_owner(
+        &mut self,
+        mint: &Pubkey,
+        token_program: &Pubkey,
+        owner: &Pubkey,
+        amount: u64,
+    ) -> Pubkey {
+        let ata = associated_token_address(owner, mint, token_program);
+        let payer = self.ctx.payer.pubkey();
+        self.process(
+            &[create_associated_token_account_idempotent(
+                &payer,
+                owner,
+                mint,
+                token_program,
+            )],
+            &[],
+        )
+        .await
+        .unwrap();
+        self.mint_to(mint, token_program, &ata, amount).await;
+        ata
+    }
+
+    pub async fn mint_to(
+        &mut self,
+        mint: &Pubkey,
+        token_program: &Pubkey,
+        account: &Pubkey,
+        amount: u64,
+    ) {
+        let admin = self.admin.insecure_clone();
+        self.process(
+            &[spl_token_2022::instruction::mint_to(
+                token_program,
+                mint,
+                account,
+                &admin.pubkey(),
+                &[],
+                amount,
+            )
+            .unwrap()],
+            &[&admin],
+        )
+        .await
+        .unwrap();
+    }
+
+    pub async fn token_balance(&mut self, account: &Pubkey) -> u64 {
+        let account = self
+            .ctx
+            .banks_client
+            .get_account(*account)
+            .await
+            .unwrap()
+            .expect("token account missing");
+        StateWithExtensions::<TokenAccount>::unpack(&account.data)
+            .unwrap()
+            .base
+            .amount
+    }
+
+    pub async fn mint_supply(&mut self, mint: &Pubkey) -> u64 {
+        let account = self
+            .ctx
+            .banks_client
+            .get_account(*mint)
+            .await
+            .unwrap()
+            .expect("mint missing");
+        StateWithExtensions::<Mint>::unpack(&account.data)
+            .unwrap()
+            .base
+            .supply
+    }
+
+    pub async fn account<T: AccountDeserialize>(&mut self, address: &Pubkey) -> T {
+        let account = self
+            .ctx
+            .banks_client
+            .get_account(*address)
+            .await
+            .unwrap()
+            .expect("account missing");
+        T::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    pub async fn account_exists(&mut self, address: &Pubkey) -> bool {
+        self.ctx
+            .banks_client
+            .get_account(*address)
+            .await
+            .unwrap()
+            .is_some()
+    }
+
+    /// Moves the clock forward, which ages every oracle reading.
+    pub async fn warp_seconds(&mut self, seconds: i64) {
+        let mut clock: Clock = self.ctx.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp += seconds;
+        self.ctx.set_sysvar(&clock);
+    }
+
+    pub async fn now(&mut self) -> i64 {
+        let clock: Clock = self.ctx.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp
+    }
+
+    pub async fn configure_collateral(
+        &mut self,
+        params: CollateralConfigParams,
+    ) -> std::result::Result<(), BanksClientError> {
+        self.collateral.oracle = params.oracle;
+        let admin = self.admin.insecure_clone();
+        let ix = configure_collateral(&self.protocol, self.collateral.mint, params);
+        self.process(&[ix], &[&admin]).await
+    }
+
+    pub async fn set_price(&mut self, price: u64, conf: u64) {
+        let admin = self.admin.insecure_clone();
+        let oracle = self.oracle.pubkey();
+        self.process(
+            &[update_simple_oracle(admin.pubkey(), oracle, price, conf)],
+            &[&admin],
+        )
+        .await
+        .unwrap();
+    }
+
+    /// Funds a new user with `amount` collateral and opens their loan.
+    pub async fn new_user(&mut self, amount: u64) -> Keypair {
+        let user = Keypair::new();
+        self.airdrop(&user.pubkey(), 10_000_000_000).await;
+        let (mint, token_program) = (self.collateral.mint, self.collateral.token_program);
+        self.mint_to_owner(&mint, &token_program, &user.pubkey(), amount)
+            .await;
+        let ix = initialize_loan(&self.protocol, user.pubkey(), &self.collateral);
+        self.process(&[ix], &[&user]).await.unwrap();
+        user
+    }
+
+    pub async fn deposit(
+        &mut self,
+        user: &Keypair,
+        amount: u64,
+    ) -> std::result::Result<(), BanksClientError> {
+        let ix = deposit_collateral(&self.protocol, user.pubkey(), &self.collateral, amount);
+        self.process(&[ix], &[user]).await
+    }
+
+    pub async fn borrow(&mut self, user: &Keypair) -> std::result::Result<(), BanksClientError> {
+        let admin = self.admin.insecure_clone();
+        let ix = lend_credit_token(&self.protocol, user.pubkey(), &self.collateral);
+        self.process(&[ix], &[user, &admin]).await
+    }
+
+    /// User with `amount` collateral deposited and the maximum borrowed.
+    pub async fn borrower(&mut self, amount: u64) -> Keypair {
+        let user = self.new_user(amount).await;
+        self.deposit(&user, amount).await.unwrap();
+        self.borrow(&user).await.unwrap();
+        user
+    }
+
+    /// Moves `amount` credit from `from` to `to`'s associated account,
+    /// creating it if needed. Only valid for transfers the hook does not
+    /// need to see.
+    pub async fn transfer_credit(&mut self, from: &Keypair, to: &Pubkey, amount: u64) {
+        let (mint, token_program) = (
+            self.protocol.credit_mint,
+            self.protocol.credit_token_program,
+        );
+        let payer = self.ctx.payer.pubkey();
+        let ix = spl_token_2022::instruction::transfer_checked(
+            &token_program,
+            &self.protocol.credit_ata(&from.pubkey()),
+            &mint,
+            &self.protocol.credit_ata(to),
+            &from.pubkey(),
+            &[],
+            amount,
+            CREDIT_DECIMALS,
+        )
+        .unwrap();
+        self.process(
+            &[
+                create_associated_token_account_idempotent(&payer, to, &mint, &token_program),
+                ix,
+            ],
+            &[from],
+        )
+        .await
+        .unwrap();
+    }
+
+    /// Simulates staking yield by minting extra collateral into the vault.
+    pub async fn accrue_yield(&mut self, user: &Pubkey, amount: u64) {
+        let vault_ata = self.collateral.vault_ata(user);
+        let (mint, token_program) = (self.collateral.mint, self.collateral.token_program);
+        self.mint_to(&mint, &token_program, &vault_ata, amount)
+            .await;
+    }
+
+    pub fn collateral_vault(user: &Pubkey) -> Pubkey {
+        collateral_vault_pda(user).0
+    }
+
+    /// Configures another SPL collateral mint, priced by its own
+    /// `SimplePriceOracle` whose key is returned with it.
+    pub async fn add_collateral(&mut self, price: u64) -> (CollateralContext, Pubkey) {
+        let admin = self.admin.insecure_clone();
+        let oracle = Keypair::new();
+        let mint = self
+            .create_mint(&spl_token::ID, COLLATERAL_DECIMALS, &admin.pubkey())
+            .await;
+        let params = collateral_params(oracle.pubkey());
+        let collateral = CollateralContext {
+            mint,
+            token_program: spl_token::ID,
+            oracle: params.oracle,
+        };
+        self.process(
+            &[
+                create_simple_oracle(admin.pubkey(), oracle.pubkey(), price, 0),
+                configure_collateral(&self.protocol, mint, params),
+            ],
+            &[&admin, &oracle],
+        )
+        .await
+        .unwrap();
+        (collateral, oracle.pubkey())
+    }
+
+    /// Creates a mock swap pool from `input` to `output` paying
+    /// `rate_num / rate_den`, with `reserve` output tokens to pay from. The
+    /// admin is the pool authority and must be the output mint's authority.
+    pub async fn create_mock_pool(
+        &mut self,
+        input: (Pubkey, Pubkey),
+        output: (Pubkey, Pubkey),
+        rate: (u64, u64),
+        reserve: u64,
+    ) {
+        let admin = self.admin.insecure_clone();
+        let pool = mock_pool(&input.0, &output.0);
+        let ix = Instruction {
+            program_id: mock_swap::ID,
+            accounts: mock_swap::accounts::InitializePool {
+                authority: admin.pubkey(),
+                input_mint: input.0,
+                output_mint: output.0,
+                pool,
+                input_reserve: associated_token_address(&pool, &input.0, &input.1),
+                output_reserve: associated_token_address(&pool, &output.0, &output.1),
+                input_token_program: input.1,
+                output_token_program: output.1,
+                associated_token_program: associated_token::ID,
+                system_program: system_program::ID,
+            }
+            .to_account_metas(None),
+            data: mock_swap::instruction::InitializePool {
+                rate_num: rate.0,
+                rate_den: rate.1,
+            }
+            .data(),
+        };
+        self.process(&[ix], &[&admin]).await.unwrap();
+        if reserve > 0 {
+            let output_reserve = associated_token_address(&pool, &output.0, &output.1);
+            self.mint_to(&output.0, &output.1, &output_reserve, reserve)
+                .await;
+        }
+    }
+}
+
+pub fn mock_pool(input_mint: &Pubkey, output_mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"pool", input_mint.as_ref(), output_mint.as_ref()],
+        &mock_swap::ID,
+    )
+    .0
+}
+
+/// The mock swap program's own accounts for a swap from `input` to `output`
+/// (mint, token program), passed after the swap adapter accounts.
+pub fn mock_swap_accounts(input: (Pubkey, Pubkey), output: (Pubkey, Pubkey)) -> Vec<AccountMeta> {
+    let pool = mock_pool(&input.0, &output.0);
+    vec![
+        AccountMeta::new_readonly(pool, false),
+        AccountMeta::new(associated_token_address(&pool, &input.0, &input.1), false),
+        AccountMeta::new(associated_token_address(&pool, &output.0, &output.1), false),
+    ]
+}
+
+/// Swaps every occurrence of `from` in the instruction's accounts for `to`.
+/// `ix` issued as a CPI from the forwarding program.
+pub fn through_cpi(ix: Instruction) -> Instruction {
+    let mut accounts = vec![AccountMeta::new_readonly(ix.program_id, false)];
+    accounts.extend(ix.accounts);
+    Instruction {
+        program_id: CPI_WRAPPER_ID,
+        accounts,
+        data: ix.data,
+    }
+}
+
+pub fn replace_account(mut ix: Instruction, from: &Pubkey, to: &Pubkey) -> Instruction {
+    for meta in ix.accounts.iter_mut().filter(|meta| meta.pubkey == *from) {
+        meta.pubkey = *to;
+    }
+    ix
+}
+
+pub fn custom_error_code(result: std::result::Result<(), BanksClientError>) -> u32 {
+    match result.expect_err("transaction should fail").unwrap() {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => code,
+        err => panic!("expected a custom program error, got {err:?}"),
+    }
+}
+
+/// Asserts the transaction failed with the given Anchor or `CredXError` code.
+pub fn assert_error(result: std::result::Result<(), BanksClientError>, expected: impl Into<u32>) {
+    let expected = expected.into();
+    let code = custom_error_code(result);
+    assert_eq!(code, expected, "expected error {expected}, got {code}");
+}
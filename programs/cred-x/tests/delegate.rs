@@ -0,0 +1,234 @@
+mod common;
+
+use anchor_lang::error::ErrorCode;
+use common::*;
+use cred_x::{CredXError, DelegatePermissions, LoanAccount, PositionDelegate, ProtocolState};
+use cred_x_client::{
+    deposit_collateral_by, lend_credit_token_by, loan_pda, position_delegate_pda, repay_debt,
+    revoke_position_delegate, set_position_delegate, withdraw_collateral_by,
+};
+use solana_sdk::signature::{Keypair, Signer};
+
+/// Debt of a full borrow against one unit of collateral at the initial price.
+const MAX_BORROW: u64 = 90_000_000_000_000_000;
+
+const DEPOSIT_AND_BORROW: DelegatePermissions = DelegatePermissions {
+    deposit: true,
+    repay: false,
+    borrow: true,
+    withdraw: false,
+};
+
+async fn manager(env: &mut TestEnv, collateral: u64) -> Keypair {
+    let manager = Keypair::new();
+    env.airdrop(&manager.pubkey(), 10_000_000_000).await;
+    let (mint, token_program) = (env.collateral.mint, env.collateral.token_program);
+    env.mint_to_owner(&mint, &token_program, &manager.pubkey(), collateral)
+        .await;
+    manager
+}
+
+async fn grant(
+    env: &mut TestEnv,
+    owner: &Keypair,
+    manager: &Keypair,
+    permissions: DelegatePermissions,
+    borrow_limit: u64,
+) {
+    let ix = set_position_delegate(owner.pubkey(), manager.pubkey(), permissions, borrow_limit);
+    env.process(&[ix], &[owner]).await.unwrap();
+}
+
+#[tokio::test]
+async fn delegate_deposits_and_borrows_within_limit() {
+    let mut env = TestEnv::new().await;
+    let owner = env.new_user(0).await;
+    let manager = manager(&mut env, ONE_COLLATERAL).await;
+    let admin = env.admin.insecure_clone();
+    grant(
+        &mut env,
+        &owner,
+        &manager,
+        DEPOSIT_AND_BORROW,
+        MAX_BORROW - 1,
+    )
+    .await;
+
+    // The manager funds the deposit from its own collateral
+    let ix = deposit_collateral_by(
+        &env.protocol,
+        manager.pubkey(),
+        owner.pubkey(),
+        &env.collateral,
+        ONE_COLLATERAL,
+    );
+    env.process(&[ix], &[&manager]).await.unwrap();
+    let loan: LoanAccount = env.account(&loan_pda(&owner.pubkey()).0).await;
+    assert_eq!(loan.collateral_amount, ONE_COLLATERAL);
+    let manager_ata = env.collateral.user_ata(&manager.pubkey());
+    assert_eq!(env.token_balance(&manager_ata).await, 0);
+
+    let ix = lend_credit_token_by(
+        &env.protocol,
+        manager.pubkey(),
+        owner.pubkey(),
+        &env.collateral,
+    );
+    assert_error(
+        env.process(std::slice::from_ref(&ix), &[&manager, &admin])
+            .await,
+        CredXError::DelegateBorrowLimitExceeded,
+    );
+
+    grant(&mut env, &owner, &manager, DEPOSIT_AND_BORROW, MAX_BORROW).await;
+    env.process(&[ix], &[&manager, &admin]).await.unwrap();
+
+    // Credit goes to the owner, the debt counts against the grant
+    let owner_credit = env.protocol.credit_ata(&owner.pubkey());
+    assert_eq!(env.token_balance(&owner_credit).await, MAX_BORROW);
+    let loan: LoanAccount = env.account(&loan_pda(&owner.pubkey()).0).await;
+    assert_eq!(loan.remaining_debt, MAX_BORROW);
+    let grant: PositionDelegate = env
+        .account(&position_delegate_pda(&owner.pubkey(), &manager.pubkey()).0)
+        .await;
+    assert_eq!(grant.borrowed, MAX_BORROW);
+}
+
+#[tokio::test]
+async fn delegate_permissions_are_scoped_and_revocable() {
+    let mut env = TestEnv::new().await;
+    let owner = env.borrower(ONE_COLLATERAL).await;
+    let manager = manager(&mut env, ONE_COLLATERAL).await;
+    let admin = env.admin.insecure_clone();
+    let deposit_only = DelegatePermissions {
+        deposit: true,
+        ..Default::default()
+    };
+    grant(&mut env, &owner, &manager, deposit_only, u64::MAX).await;
+
+    let ix = lend_credit_token_by(
+        &env.protocol,
+        manager.pubkey(),
+        owner.pubkey(),
+        &env.collateral,
+    );
+    assert_error(
+        env.process(&[ix], &[&manager, &admin]).await,
+        CredXError::DelegatePermissionDenied,
+    );
+    let ix = withdraw_collateral_by(
+        &env.protocol,
+        manager.pubkey(),
+        owner.pubkey(),
+        &env.collateral,
+    );
+    assert_error(
+        env.process(&[ix], &[&manager]).await,
+        CredXError::DelegatePermissionDenied,
+    );
+    env.transfer_credit(&owner, &manager.pubkey(), 1).await;
+    let ix = repay_debt(
+        &env.protocol,
+        manager.pubkey(),
+        owner.pubkey(),
+        &env.collateral,
+        1,
+    );
+    assert_error(
+        env.process(&[ix], &[&manager]).await,
+        CredXError::DelegatePermissionDenied,
+    );
+
+    // A grant from another owner does not carry over
+    let other = env.new_user(0).await;
+    grant(&mut env, &other, &manager, deposit_only, 0).await;
+    let ix = replace_account(
+        deposit_collateral_by(
+            &env.protocol,
+            manager.pubkey(),
+            owner.pubkey(),
+            &env.collateral,
+            1,
+        ),
+        &position_delegate_pda(&owner.pubkey(), &manager.pubkey()).0,
+        &position_delegate_pda(&other.pubkey(), &manager.pubkey()).0,
+    );
+    assert_error(
+        env.process(&[ix], &[&manager]).await,
+        CredXError::UnauthorizedUser,
+    );
+
+    let deposit = |env: &TestEnv| {
+        deposit_collateral_by(
+            &env.protocol,
+            manager.pubkey(),
+            owner.pubkey(),
+            &env.collateral,
+            1,
+        )
+    };
+    env.process(&[deposit(&env)], &[&manager]).await.unwrap();
+
+    let grant_address = position_delegate_pda(&owner.pubkey(), &manager.pubkey()).0;
+    let ix = revoke_position_delegate(owner.pubkey(), manager.pubkey());
+    env.process(&[ix], &[&owner]).await.unwrap();
+    assert!(!env.account_exists(&grant_address).await);
+    assert_error(
+        env.process(&[deposit(&env)], &[&manager]).await,
+        ErrorCode::AccountNotInitialized,
+    );
+
+    let ix = set_position_delegate(owner.pubkey(), owner.pubkey(), deposit_only, 0);
+    assert_error(
+        env.process(&[ix], &[&owner]).await,
+        CredXError::InvalidDelegate,
+    );
+}
+
+#[tokio::test]
+async fn delegate_repays_and_withdraws_to_owner() {
+    let mut env = TestEnv::new().await;
+    let owner = env.borrower(ONE_COLLATERAL).await;
+    let manager = manager(&mut env, 0).await;
+    let repay_and_withdraw = DelegatePermissions {
+        repay: true,
+        withdraw: true,
+        ..Default::default()
+    };
+    grant(&mut env, &owner, &manager, repay_and_withdraw, 0).await;
+
+    // The manager repays part of the debt with its own credit
+    let repaid = MAX_BORROW / 3;
+    env.transfer_credit(&owner, &manager.pubkey(), repaid).await;
+    let ix = repay_debt(
+        &env.protocol,
+        manager.pubkey(),
+        owner.pubkey(),
+        &env.collateral,
+        repaid,
+    );
+    env.process(&[ix], &[&manager]).await.unwrap();
+    let loan: LoanAccount = env.account(&loan_pda(&owner.pubkey()).0).await;
+    assert_eq!(loan.remaining_debt, MAX_BORROW - repaid);
+    let state: ProtocolState = env.account(&env.protocol.protocol()).await;
+    assert_eq!(state.total_debt, MAX_BORROW - repaid);
+    assert_eq!(state.stats.total_repaid_via_user, repaid);
+    let manager_credit = env.protocol.credit_ata(&manager.pubkey());
+    assert_eq!(env.token_balance(&manager_credit).await, 0);
+
+    let ix = withdraw_collateral_by(
+        &env.protocol,
+        manager.pubkey(),
+        owner.pubkey(),
+        &env.collateral,
+    );
+    env.process(&[ix], &[&manager]).await.unwrap();
+
+    let owner_collateral = env.collateral.user_ata(&owner.pubkey());
+    assert_eq!(env.token_balance(&owner_collateral).await, ONE_COLLATERAL);
+    let manager_collateral = env.collateral.user_ata(&manager.pubkey());
+    assert_eq!(env.token_balance(&manager_collateral).await, 0);
+    let loan: LoanAccount = env.account(&loan_pda(&owner.pubkey()).0).await;
+    assert_eq!(loan.remaining_debt, 0);
+    assert_eq!(loan.collateral_amount, 0);
+}
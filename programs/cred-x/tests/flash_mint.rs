@@ -0,0 +1,174 @@
+mod common;
+
+use anchor_spl::associated_token::spl_associated_token_account::instruction::create_associated_token_account_idempotent;
+use common::*;
+use cred_x::{CredXError, ProtocolState};
+use cred_x_client::{flash_mint, flash_repay, set_flash_fee};
+use solana_sdk::signature::{Keypair, Signer};
+
+const AMOUNT: u64 = 1_000_000_000;
+
+async fn with_flash_fee(fee_bps: u16) -> (TestEnv, Keypair) {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let ix = set_flash_fee(&env.protocol, fee_bps);
+    env.process(&[ix], &[&admin]).await.unwrap();
+    let user = env.borrower(ONE_COLLATERAL).await;
+    (env, user)
+}
+
+#[tokio::test]
+async fn flash_mint_is_repaid_with_fee_in_same_transaction() {
+    let (mut env, user) = with_flash_fee(50).await;
+    let credit_mint = env.protocol.credit_mint;
+    let user_ata = env.protocol.credit_ata(&user.pubkey());
+    let protocol_ata = env.protocol.protocol_credit_ata();
+    let balance = env.token_balance(&user_ata).await;
+    let supply = env.mint_supply(&credit_mint).await;
+    let protocol_balance = env.token_balance(&protocol_ata).await;
+    let debt = env
+        .account::<ProtocolState>(&env.protocol.protocol())
+        .await
+        .total_debt;
+
+    let ixs = [
+        flash_mint(&env.protocol, user_ata, AMOUNT),
+        flash_repay(&env.protocol, user.pubkey(), AMOUNT),
+    ];
+    env.process(&ixs, &[&user]).await.unwrap();
+
+    let fee = AMOUNT / 200;
+    assert_eq!(env.token_balance(&user_ata).await, balance - fee);
+    assert_eq!(
+        env.token_balance(&protocol_ata).await,
+        protocol_balance + fee
+    );
+    assert_eq!(env.mint_supply(&credit_mint).await, supply);
+    // Flash minted credit never becomes debt
+    let state: ProtocolState = env.account(&env.protocol.protocol()).await;
+    assert_eq!(state.total_debt, debt);
+}
+
+#[tokio::test]
+async fn flash_mint_requires_matching_repay() {
+    let (mut env, user) = with_flash_fee(0).await;
+    let user_ata = env.protocol.credit_ata(&user.pubkey());
+
+    let ix = flash_mint(&env.protocol, user_ata, AMOUNT);
+    assert_error(env.process(&[ix], &[]).await, CredXError::FlashRepayMissing);
+
+    let cases: Vec<(Vec<_>, CredXError)> = vec![
+        (
+            vec![
+                flash_mint(&env.protocol, user_ata, AMOUNT),
+                flash_repay(&env.protocol, user.pubkey(), AMOUNT - 1),
+            ],
+            CredXError::FlashRepayMissing,
+        ),
+        // One repay cannot settle two mints
+        (
+            vec![
+                flash_mint(&env.protocol, user_ata, AMOUNT),
+                flash_mint(&env.protocol, user_ata, AMOUNT),
+                flash_repay(&env.protocol, user.pubkey(), AMOUNT),
+            ],
+            CredXError::FlashRepayMissing,
+        ),
+        // The repay must come after the mint
+        (
+            vec![
+                flash_repay(&env.protocol, user.pubkey(), AMOUNT),
+                flash_mint(&env.protocol, user_ata, AMOUNT),
+            ],
+            CredXError::FlashMintMissing,
+        ),
+    ];
+    for (ixs, expected) in cases {
+        assert_error(env.process(&ixs, &[&user]).await, expected);
+    }
+
+    // Back-to-back pairs each settle on their own
+    let ixs = [
+        flash_mint(&env.protocol, user_ata, AMOUNT),
+        flash_repay(&env.protocol, user.pubkey(), AMOUNT),
+        flash_mint(&env.protocol, user_ata, 2 * AMOUNT),
+        flash_repay(&env.protocol, user.pubkey(), 2 * AMOUNT),
+    ];
+    env.process(&ixs, &[&user]).await.unwrap();
+}
+
+#[tokio::test]
+async fn flash_repay_needs_amount_plus_fee() {
+    let (mut env, _) = with_flash_fee(100).await;
+    // Holds nothing but the minted amount, so cannot cover the fee
+    let receiver = Keypair::new();
+    let (payer, mint, token_program) = (
+        env.ctx.payer.pubkey(),
+        env.protocol.credit_mint,
+        env.protocol.credit_token_program,
+    );
+    let ix = create_associated_token_account_idempotent(
+        &payer,
+        &receiver.pubkey(),
+        &mint,
+        &token_program,
+    );
+    env.process(&[ix], &[]).await.unwrap();
+    let receiver_ata = env.protocol.credit_ata(&receiver.pubkey());
+
+    let ixs = [
+        flash_mint(&env.protocol, receiver_ata, AMOUNT),
+        flash_repay(&env.protocol, receiver.pubkey(), AMOUNT),
+    ];
+    assert_error(
+        env.process(&ixs, &[&receiver]).await,
+        CredXError::InsufficientBalance,
+    );
+}
+
+#[tokio::test]
+async fn flash_mint_respects_pause_and_fee_bounds() {
+    let (mut env, user) = with_flash_fee(0).await;
+    let admin = env.admin.insecure_clone();
+    let user_ata = env.protocol.credit_ata(&user.pubkey());
+
+    let ix = set_flash_fee(&env.protocol, 1_001);
+    assert_error(env.process(&[ix], &[&admin]).await, CredXError::InvalidFee);
+
+    let ix = cred_x_client::set_protocol_locked(&env.protocol, true);
+    env.process(&[ix], &[&admin]).await.unwrap();
+    let ixs = [
+        flash_mint(&env.protocol, user_ata, AMOUNT),
+        flash_repay(&env.protocol, user.pubkey(), AMOUNT),
+    ];
+    assert_error(
+        env.process(&ixs, &[&user]).await,
+        CredXError::ProtocolLocked,
+    );
+}
+
+#[tokio::test]
+async fn flash_mint_refuses_cpi_callers() {
+    let (mut env, user) = with_flash_fee(0).await;
+    let user_ata = env.protocol.credit_ata(&user.pubkey());
+
+    let cases = [
+        vec![
+            through_cpi(flash_mint(&env.protocol, user_ata, AMOUNT)),
+            flash_repay(&env.protocol, user.pubkey(), AMOUNT),
+        ],
+        // A top-level mint would already miss a wrapped repay, so the repay
+        // is checked on its own
+        vec![through_cpi(flash_repay(
+            &env.protocol,
+            user.pubkey(),
+            AMOUNT,
+        ))],
+    ];
+    for ixs in cases {
+        assert_error(
+            env.process(&ixs, &[&user]).await,
+            CredXError::FlashMintCpiNotAllowed,
+        );
+    }
+}
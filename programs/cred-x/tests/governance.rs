@@ -0,0 +1,347 @@
+mod common;
+
+use anchor_lang::error::ErrorCode;
+use anchor_spl::{
+    token_2022::spl_token_2022::{
+        self,
+        extension::{BaseStateWithExtensions, StateWithExtensions},
+        state::Mint,
+    },
+    token_interface::spl_token_metadata_interface::state::TokenMetadata,
+};
+use common::*;
+use cred_x::{
+    CollateralConfig, CredXError, CreditMetadata, CreditTransferMode, LoanAccount,
+    MigrateProtocolParams, ParamChange, PendingParamChange, ProtocolState,
+};
+use cred_x_client::{
+    cancel_param_change, execute_param_change, initialize_credit_transfer_hook, loan_pda,
+    migrate_protocol, param_change_pda, queue_param_change, reset_circuit_breaker,
+    set_credit_transfer_mode, set_flash_fee, set_protocol_locked, set_swap_program,
+    trip_circuit_breaker, update_credit_metadata, ProtocolContext,
+};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+const DELAY_SECS: i64 = 3_600;
+
+impl TestEnv {
+    /// Queues `change` as the admin and returns its id.
+    async fn queue(
+        &mut self,
+        protocol: ProtocolContext,
+        admin: &Keypair,
+        change: ParamChange,
+    ) -> std::result::Result<u64, solana_program_test::BanksClientError> {
+        let state: ProtocolState = self.account(&protocol.protocol()).await;
+        let id = state.param_changes_queued;
+        let ix = queue_param_change(&protocol, id, change);
+        self.process(&[ix], &[admin]).await.map(|_| id)
+    }
+
+    /// Executes queued change `id` with the accounts its change needs.
+    async fn execute(
+        &mut self,
+        protocol: ProtocolContext,
+        admin: &Keypair,
+        id: u64,
+    ) -> std::result::Result<(), solana_program_test::BanksClientError> {
+        let pending: PendingParamChange = self
+            .account(&param_change_pda(&protocol.protocol(), id).0)
+            .await;
+        let ix = execute_param_change(&protocol, id, &pending.change);
+        self.process(&[ix], &[admin]).await
+    }
+
+    /// Turns on the timelock, which takes effect immediately while the delay
+    /// is still zero.
+    async fn enable_timelock(&mut self) {
+        let (protocol, admin) = (self.protocol, self.admin.insecure_clone());
+        let change = ParamChange::GovernanceDelay {
+            delay_secs: DELAY_SECS,
+        };
+        let id = self.queue(protocol, &admin, change).await.unwrap();
+        self.execute(protocol, &admin, id).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn param_changes_wait_for_the_governance_delay() {
+    let mut env = TestEnv::new().await;
+    let (protocol, admin) = (env.protocol, env.admin.insecure_clone());
+    env.enable_timelock().await;
+    assert_error(
+        env.process(&[set_flash_fee(&protocol, 50)], &[&admin])
+            .await,
+        CredXError::TimelockActive,
+    );
+    let mut params = collateral_params(env.oracle.pubkey());
+    assert_error(
+        env.configure_collateral(params.clone()).await,
+        CredXError::TimelockActive,
+    );
+
+    let id = env
+        .queue(protocol, &admin, ParamChange::FlashFee { fee_bps: 50 })
+        .await
+        .unwrap();
+    let pending: PendingParamChange = env
+        .account(&param_change_pda(&protocol.protocol(), id).0)
+        .await;
+    assert_eq!(pending.eta - pending.queued_at, DELAY_SECS);
+    assert_error(
+        env.execute(protocol, &admin, id).await,
+        CredXError::ParamChangeNotReady,
+    );
+    env.warp_seconds(DELAY_SECS).await;
+    env.execute(protocol, &admin, id).await.unwrap();
+    let state: ProtocolState = env.account(&protocol.protocol()).await;
+    assert_eq!(state.flash_fee_bps, 50);
+    assert!(
+        !env.account_exists(&param_change_pda(&protocol.protocol(), id).0)
+            .await
+    );
+
+    // Collateral changes carry the collateral's config
+    params.supply_cap = 7 * ONE_COLLATERAL;
+    let change = ParamChange::Collateral {
+        mint: env.collateral.mint,
+        params,
+    };
+    let id = env.queue(protocol, &admin, change).await.unwrap();
+    env.warp_seconds(DELAY_SECS).await;
+    // Built for a change without a collateral, so the config is left out
+    let ix = execute_param_change(&protocol, id, &ParamChange::FlashFee { fee_bps: 0 });
+    assert_error(
+        env.process(&[ix], &[&admin]).await,
+        CredXError::InvalidParamChangeCollateral,
+    );
+    env.execute(protocol, &admin, id).await.unwrap();
+    let config: CollateralConfig = env.account(&env.collateral.config(&protocol)).await;
+    assert_eq!(config.supply_cap, 7 * ONE_COLLATERAL);
+
+    let change = ParamChange::LoanRatios {
+        ltv_ratio_bps: 8_500,
+        liquidation_threshold_bps: 8_000,
+    };
+    assert_error(
+        env.queue(protocol, &admin, change).await.map(|_| ()),
+        CredXError::InvalidLiquidationThreshold,
+    );
+    let change = ParamChange::GovernanceDelay {
+        delay_secs: 31 * 24 * 60 * 60,
+    };
+    assert_error(
+        env.queue(protocol, &admin, change).await.map(|_| ()),
+        CredXError::InvalidGovernanceDelay,
+    );
+}
+
+#[tokio::test]
+async fn swap_program_and_breaker_resets_wait_for_the_governance_delay() {
+    let mut env = TestEnv::new().await;
+    let (protocol, admin) = (env.protocol, env.admin.insecure_clone());
+    let mut params = collateral_params(env.oracle.pubkey());
+    params.max_price_move_bps = 1_000;
+    env.configure_collateral(params).await.unwrap();
+    let user = env.borrower(ONE_COLLATERAL).await;
+    env.set_price(2 * INITIAL_PRICE, 0).await;
+    let ix = trip_circuit_breaker(&protocol, &env.collateral);
+    env.process(&[ix], &[]).await.unwrap();
+    env.enable_timelock().await;
+
+    let swap_program = Pubkey::new_unique();
+    assert_error(
+        env.process(&[set_swap_program(&protocol, swap_program)], &[&admin])
+            .await,
+        CredXError::TimelockActive,
+    );
+    assert_error(
+        env.process(
+            &[reset_circuit_breaker(&protocol, &env.collateral)],
+            &[&admin],
+        )
+        .await,
+        CredXError::TimelockActive,
+    );
+
+    let id = env
+        .queue(protocol, &admin, ParamChange::SwapProgram { swap_program })
+        .await
+        .unwrap();
+    let change = ParamChange::ResetCircuitBreaker {
+        mint: env.collateral.mint,
+    };
+    let reset_id = env.queue(protocol, &admin, change).await.unwrap();
+    env.warp_seconds(DELAY_SECS).await;
+    env.execute(protocol, &admin, id).await.unwrap();
+    env.execute(protocol, &admin, reset_id).await.unwrap();
+
+    let state: ProtocolState = env.account(&protocol.protocol()).await;
+    assert_eq!(state.swap_program, swap_program);
+    let config: CollateralConfig = env.account(&env.collateral.config(&protocol)).await;
+    assert!(!config.circuit_breaker.tripped);
+    assert_eq!(config.circuit_breaker.last_price, 0);
+
+    // The first price read after the reset becomes the reference
+    env.set_price(2 * INITIAL_PRICE, 0).await;
+    env.borrow(&user).await.unwrap();
+    let config: CollateralConfig = env.account(&env.collateral.config(&protocol)).await;
+    assert_eq!(config.circuit_breaker.last_price, 2 * INITIAL_PRICE);
+    let loan: LoanAccount = env.account(&loan_pda(&user.pubkey()).0).await;
+    assert!(loan.remaining_debt > 0);
+}
+
+#[tokio::test]
+async fn credit_token_changes_wait_for_the_governance_delay() {
+    let mut params = protocol_params();
+    params.credit_metadata = Some(CreditMetadata {
+        name: "Cred".to_string(),
+        symbol: "CRED".to_string(),
+        uri: "https://example.com/cred.json".to_string(),
+    });
+    params.credit_transfer_mode = CreditTransferMode::DebtFloor;
+    let mut env = TestEnv::with_params(params, spl_token_2022::ID).await;
+    let (protocol, admin) = (env.protocol, env.admin.insecure_clone());
+    let ix = initialize_credit_transfer_hook(&protocol);
+    env.process(&[ix], &[&admin]).await.unwrap();
+    env.enable_timelock().await;
+
+    let mode = CreditTransferMode::Allowlist;
+    assert_error(
+        env.process(&[set_credit_transfer_mode(&protocol, mode)], &[&admin])
+            .await,
+        CredXError::TimelockActive,
+    );
+    let metadata = CreditMetadata {
+        name: "Cred Dollar".to_string(),
+        symbol: "CRED".to_string(),
+        uri: "https://example.com/cred-dollar.json".to_string(),
+    };
+    assert_error(
+        env.process(
+            &[update_credit_metadata(&protocol, metadata.clone())],
+            &[&admin],
+        )
+        .await,
+        CredXError::TimelockActive,
+    );
+
+    let id = env
+        .queue(protocol, &admin, ParamChange::CreditTransferMode { mode })
+        .await
+        .unwrap();
+    let metadata_id = env
+        .queue(protocol, &admin, ParamChange::CreditMetadata { metadata })
+        .await
+        .unwrap();
+    env.warp_seconds(DELAY_SECS).await;
+    env.execute(protocol, &admin, id).await.unwrap();
+    env.execute(protocol, &admin, metadata_id).await.unwrap();
+
+    let state: ProtocolState = env.account(&protocol.protocol()).await;
+    assert_eq!(state.credit_transfer_mode, mode);
+    let mint = env
+        .ctx
+        .banks_client
+        .get_account(protocol.credit_mint)
+        .await
+        .unwrap()
+        .unwrap();
+    let mint = StateWithExtensions::<Mint>::unpack(&mint.data).unwrap();
+    let stored = mint.get_variable_len_extension::<TokenMetadata>().unwrap();
+    assert_eq!(stored.name, "Cred Dollar");
+    assert_eq!(stored.uri, "https://example.com/cred-dollar.json");
+}
+
+#[tokio::test]
+async fn cancelled_changes_cannot_be_executed() {
+    let mut env = TestEnv::new().await;
+    let (protocol, admin) = (env.protocol, env.admin.insecure_clone());
+    env.enable_timelock().await;
+    let change = ParamChange::DebtCeiling { debt_ceiling: 1 };
+    let id = env.queue(protocol, &admin, change.clone()).await.unwrap();
+
+    let stranger = Keypair::new();
+    env.airdrop(&stranger.pubkey(), 1_000_000_000).await;
+    let ix = cancel_param_change(&protocol.with_admin(stranger.pubkey()), id);
+    assert_error(
+        env.process(&[ix], &[&stranger]).await,
+        CredXError::UnauthorizedAdmin,
+    );
+    env.process(&[cancel_param_change(&protocol, id)], &[&admin])
+        .await
+        .unwrap();
+    env.warp_seconds(DELAY_SECS).await;
+    assert_error(
+        env.process(&[execute_param_change(&protocol, id, &change)], &[&admin])
+            .await,
+        ErrorCode::AccountNotInitialized,
+    );
+    let state: ProtocolState = env.account(&protocol.protocol()).await;
+    assert_ne!(state.debt_ceiling, 1);
+}
+
+#[tokio::test]
+async fn admin_rotation_keeps_the_protocol_address() {
+    let mut env = TestEnv::new().await;
+    let (protocol, admin) = (env.protocol, env.admin.insecure_clone());
+    let new_admin = Keypair::new();
+    env.airdrop(&new_admin.pubkey(), 10_000_000_000).await;
+    let change = ParamChange::Admin {
+        admin: new_admin.pubkey(),
+    };
+    let id = env.queue(protocol, &admin, change).await.unwrap();
+    env.execute(protocol, &admin, id).await.unwrap();
+
+    let state: ProtocolState = env.account(&protocol.protocol()).await;
+    assert_eq!(state.admin, new_admin.pubkey());
+    assert_eq!(state.seed_admin(), admin.pubkey());
+    assert_eq!(
+        ProtocolContext::from_state(&state, protocol.credit_token_program),
+        protocol.with_admin(new_admin.pubkey())
+    );
+
+    assert_error(
+        env.process(&[set_protocol_locked(&protocol, true)], &[&admin])
+            .await,
+        CredXError::UnauthorizedAdmin,
+    );
+    let rotated = protocol.with_admin(new_admin.pubkey());
+    env.process(&[set_protocol_locked(&rotated, true)], &[&new_admin])
+        .await
+        .unwrap();
+    env.process(&[set_protocol_locked(&rotated, false)], &[&new_admin])
+        .await
+        .unwrap();
+    // Migration finds it under its original address too
+    let ix = migrate_protocol(
+        &rotated,
+        MigrateProtocolParams {
+            liquidation_threshold_bps: 8_000,
+            max_price_age_secs: 300,
+            debt_ceiling: u64::MAX,
+            total_debt: 0,
+            open_loans: 0,
+            legacy_loans: 0,
+        },
+    );
+    assert_error(
+        env.process(&[ix], &[&new_admin]).await,
+        CredXError::AccountAlreadyMigrated,
+    );
+    // Loans still find the protocol under its original address
+    let user = env.new_user(ONE_COLLATERAL).await;
+    env.deposit(&user, ONE_COLLATERAL).await.unwrap();
+
+    let change = ParamChange::Admin {
+        admin: admin.pubkey(),
+    };
+    let id = env.queue(rotated, &new_admin, change).await.unwrap();
+    env.execute(rotated, &new_admin, id).await.unwrap();
+    let state: ProtocolState = env.account(&protocol.protocol()).await;
+    assert_eq!(state.admin, admin.pubkey());
+    assert_eq!(state.original_admin, admin.pubkey());
+}
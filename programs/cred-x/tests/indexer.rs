@@ -0,0 +1,229 @@
+//! The native program-test runtime prints `msg!` output to stdout instead of
+//! the transaction logs, so these tests feed the indexer logs laid out the way
+//! a validator records them.
+
+use anchor_lang::prelude::Pubkey;
+use cred_x_indexer::{decode_logs, CredXEvent, IndexedTransaction, RepaymentSource, Store};
+
+const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+
+/// Logs of a top-level cred-x instruction that calls the token program before
+/// logging `messages`.
+fn cred_x_logs(instruction: &str, messages: &[String]) -> Vec<String> {
+    let program = cred_x::ID.to_string();
+    let mut logs = vec![
+        format!("Program {program} invoke [1]"),
+        format!("Program log: Instruction: {instruction}"),
+        format!("Program {TOKEN_PROGRAM} invoke [2]"),
+        "Program log: Instruction: Burn".to_string(),
+        format!("Program {TOKEN_PROGRAM} consumed 4000 of 190000 compute units"),
+        format!("Program {TOKEN_PROGRAM} success"),
+    ];
+    logs.extend(
+        messages
+            .iter()
+            .map(|message| format!("Program log: {message}")),
+    );
+    logs.push(format!(
+        "Program {program} consumed 30000 of 200000 compute units"
+    ));
+    logs.push(format!("Program {program} success"));
+    logs
+}
+
+fn tx(slot: u64, logs: Vec<String>) -> IndexedTransaction {
+    IndexedTransaction {
+        signature: format!("sig{slot}"),
+        slot,
+        block_time: Some(1_700_000_000 + slot as i64),
+        failed: false,
+        logs,
+    }
+}
+
+#[test]
+fn indexer_builds_loan_statement_from_logs() {
+    let user = Pubkey::new_unique();
+    let oracle = Pubkey::new_unique();
+    let txs = vec![
+        tx(
+            1,
+            cred_x_logs(
+                "DepositCollateral",
+                &[format!("Deposited 1000 collateral tokens for user: {user}")],
+            ),
+        ),
+        tx(
+            2,
+            cred_x_logs(
+                "LendCreditToken",
+                &[format!(
+                    "Admin minted 590 credit tokens to user: {user}, Fee: 10, Total debt: 600"
+                )],
+            ),
+        ),
+        tx(
+            3,
+            cred_x_logs(
+                "UpdateSimpleOracle",
+                &[format!("Oracle {oracle} price set to 151000000, conf: 7")],
+            ),
+        ),
+        tx(
+            4,
+            cred_x_logs(
+                "CronRepayment",
+                &[format!(
+                    "Repaid 150 credit tokens for user: {user}, remaining debt: 450"
+                )],
+            ),
+        ),
+        tx(
+            5,
+            cred_x_logs(
+                "WithdrawCollateral",
+                &[
+                    "Checking withdrawal eligibility - Vault balance: 1002, Remaining debt: 450"
+                        .to_string(),
+                    "Repaid full debt: 450".to_string(),
+                    format!("Loan fully closed for user {user}. Collateral returned: 1002, Yield earned this withdrawal: 1, Total yield: 2"),
+                ],
+            ),
+        ),
+    ];
+
+    let mut store = Store::open_in_memory().unwrap();
+    for tx in &txs {
+        assert!(store.record(tx).unwrap());
+    }
+    // Overlapping polls are ignored
+    assert!(!store.record(&txs[1]).unwrap());
+    assert_eq!(store.latest_signature().unwrap().as_deref(), Some("sig5"));
+
+    let statement = store.statement(&user).unwrap();
+    let events: Vec<_> = statement.iter().map(|line| line.event.clone()).collect();
+    assert_eq!(
+        events,
+        vec![
+            CredXEvent::Deposit { user, amount: 1000 },
+            CredXEvent::Borrow {
+                user,
+                amount: 590,
+                fee: 10,
+                total_debt: 600,
+            },
+            CredXEvent::Repayment {
+                user,
+                amount: 150,
+                remaining_debt: 450,
+                source: RepaymentSource::Yield,
+            },
+            CredXEvent::Repayment {
+                user,
+                amount: 450,
+                remaining_debt: 0,
+                source: RepaymentSource::Withdrawal,
+            },
+            CredXEvent::Withdrawal {
+                user,
+                collateral_returned: 1002,
+                yield_earned: 1,
+                total_yield: 2,
+            },
+        ]
+    );
+    assert_eq!(statement[2].signature, "sig4");
+    assert_eq!(statement[2].block_time, Some(1_700_000_004));
+
+    let loan = store.loan(&user).unwrap().unwrap();
+    assert!(!loan.open);
+    assert_eq!(loan.remaining_debt, 0);
+    assert_eq!(loan.opened_signature, "sig1");
+    assert!(store.open_loans().unwrap().is_empty());
+    assert_eq!(
+        store.oracle_updates(&oracle).unwrap(),
+        vec![("sig3".to_string(), 3, 151_000_000, 7)]
+    );
+
+    // Depositing again opens a new loan
+    store
+        .record(&tx(
+            6,
+            cred_x_logs(
+                "DepositCollateral",
+                &[format!("Deposited 5 collateral tokens for user: {user}")],
+            ),
+        ))
+        .unwrap();
+    let loan = store.loan(&user).unwrap().unwrap();
+    assert!(loan.open);
+    assert_eq!(loan.opened_signature, "sig6");
+    assert_eq!(store.open_loans().unwrap(), vec![loan]);
+}
+
+#[test]
+fn indexer_ignores_failed_transactions_and_other_programs() {
+    let user = Pubkey::new_unique();
+    let deposit = format!("Deposited 1000 collateral tokens for user: {user}");
+
+    let mut failed = tx(1, cred_x_logs("DepositCollateral", std::slice::from_ref(&deposit)));
+    failed.failed = true;
+
+    // Another program logging a lookalike message, then calling cred-x
+    let program = cred_x::ID.to_string();
+    let other = Pubkey::new_unique().to_string();
+    let spoofed = tx(
+        2,
+        vec![
+            format!("Program {other} invoke [1]"),
+            format!("Program log: {deposit}"),
+            format!("Program {program} invoke [2]"),
+            "Program log: Deposited 7 collateral tokens for user: 11111111111111111111111111111111"
+                .to_string(),
+            format!("Program {program} success"),
+            format!("Program {other} success"),
+        ],
+    );
+
+    let events = decode_logs(&spoofed.logs);
+    assert_eq!(events.len(), 1);
+    assert_eq!(
+        events[0].events,
+        vec![CredXEvent::Deposit {
+            user: Pubkey::default(),
+            amount: 7,
+        }]
+    );
+
+    let mut store = Store::open_in_memory().unwrap();
+    assert!(store.record(&failed).unwrap());
+    assert!(store.record(&spoofed).unwrap());
+    assert!(store.statement(&user).unwrap().is_empty());
+    assert_eq!(store.loan(&user).unwrap(), None);
+    assert_eq!(store.latest_signature().unwrap().as_deref(), Some("sig2"));
+}
+
+#[test]
+fn indexer_tells_direct_repayments_from_yield() {
+    let user = Pubkey::new_unique();
+    let manager = Pubkey::new_unique();
+    let logs = cred_x_logs(
+        "RepayDebt",
+        &[format!(
+            "Repaid 30 credit tokens for user: {user}, remaining debt: 70, paid by: {manager}"
+        )],
+    );
+
+    let mut store = Store::open_in_memory().unwrap();
+    store.record(&tx(1, logs)).unwrap();
+    let statement = store.statement(&user).unwrap();
+    assert_eq!(
+        statement[0].event,
+        CredXEvent::Repayment {
+            user,
+            amount: 30,
+            remaining_debt: 70,
+            source: RepaymentSource::Direct,
+        }
+    );
+}
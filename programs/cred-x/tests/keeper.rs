@@ -0,0 +1,202 @@
+mod common;
+
+use std::{cell::Cell, time::Duration};
+
+use anchor_lang::{prelude::Pubkey, solana_program::instruction::Instruction};
+use common::*;
+use cred_x::LoanAccount;
+use cred_x_client::loan_pda;
+use cred_x_keeper::{Chain, ChainAccount, Keeper, KeeperConfig, RunSummary};
+use solana_program_test::BanksClient;
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    signature::{Keypair, Signer},
+    transaction::Transaction,
+};
+use tokio::runtime::Runtime;
+
+/// `Chain` over the in-process bank. Banks has no program-account index, so
+/// `program_accounts` filters a fixed list of watched addresses.
+struct BankChain<'a> {
+    runtime: &'a Runtime,
+    banks: BanksClient,
+    payer: Keypair,
+    watched: Vec<Pubkey>,
+    nonce: Cell<u32>,
+}
+
+impl BankChain<'_> {
+    fn transaction(&self, instruction: &Instruction) -> anyhow::Result<Transaction> {
+        // Keeps retries of the same instruction from being deduplicated
+        self.nonce.set(self.nonce.get() + 1);
+        let budget = ComputeBudgetInstruction::set_compute_unit_limit(1_000_000 - self.nonce.get());
+        let blockhash = self
+            .runtime
+            .block_on(self.banks.clone().get_latest_blockhash())?;
+        Ok(Transaction::new_signed_with_payer(
+            &[budget, instruction.clone()],
+            Some(&self.payer.pubkey()),
+            &[&self.payer],
+            blockhash,
+        ))
+    }
+}
+
+impl Chain for BankChain<'_> {
+    fn program_accounts(
+        &self,
+        discriminator: &[u8],
+    ) -> anyhow::Result<Vec<(Pubkey, ChainAccount)>> {
+        let mut accounts = vec![];
+        for address in &self.watched {
+            if let Some(account) = self.account(address)? {
+                if account.owner == cred_x::ID && account.data.starts_with(discriminator) {
+                    accounts.push((*address, account));
+                }
+            }
+        }
+        Ok(accounts)
+    }
+
+    fn account(&self, address: &Pubkey) -> anyhow::Result<Option<ChainAccount>> {
+        let account = self
+            .runtime
+            .block_on(self.banks.clone().get_account(*address))?;
+        Ok(account.map(|account| ChainAccount {
+            owner: account.owner,
+            data: account.data,
+        }))
+    }
+
+    fn simulate(&self, instruction: &Instruction) -> anyhow::Result<Vec<String>> {
+        let tx = self.transaction(instruction)?;
+        let simulation = self
+            .runtime
+            .block_on(self.banks.clone().simulate_transaction(tx))?;
+        if let Some(Err(err)) = simulation.result {
+            anyhow::bail!("simulation failed: {err}");
+        }
+        Ok(simulation
+            .simulation_details
+            .map(|details| details.logs)
+            .unwrap_or_default())
+    }
+
+    fn send(&self, instruction: &Instruction) -> anyhow::Result<String> {
+        let tx = self.transaction(instruction)?;
+        let signature = tx.signatures[0].to_string();
+        self.runtime
+            .block_on(self.banks.clone().process_transaction(tx))?;
+        Ok(signature)
+    }
+}
+
+fn keeper<'a>(
+    runtime: &'a Runtime,
+    env: &TestEnv,
+    watched: Vec<Pubkey>,
+    dry_run: bool,
+) -> Keeper<BankChain<'a>> {
+    let chain = BankChain {
+        runtime,
+        banks: env.ctx.banks_client.clone(),
+        payer: env.ctx.payer.insecure_clone(),
+        watched,
+        nonce: Cell::new(0),
+    };
+    let config = KeeperConfig {
+        dry_run,
+        max_retries: 1,
+        retry_delay: Duration::ZERO,
+    };
+    Keeper::new(chain, env.admin.pubkey(), config).unwrap()
+}
+
+#[test]
+fn keeper_repays_loans_with_yield() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let mut env = runtime.block_on(TestEnv::new());
+
+    let yield_amount = ONE_COLLATERAL / 1_000;
+    let (earning, idle, unborrowed) = runtime.block_on(async {
+        let earning = env.borrower(ONE_COLLATERAL).await;
+        env.accrue_yield(&earning.pubkey(), yield_amount).await;
+        let idle = env.borrower(ONE_COLLATERAL).await;
+        let unborrowed = env.new_user(ONE_COLLATERAL).await;
+        env.deposit(&unborrowed, ONE_COLLATERAL).await.unwrap();
+        (earning, idle, unborrowed)
+    });
+    let watched: Vec<Pubkey> = [&earning, &idle, &unborrowed]
+        .iter()
+        .map(|user| loan_pda(&user.pubkey()).0)
+        .collect();
+    let debt = (ONE_COLLATERAL as u128 * INITIAL_PRICE as u128 * 6_000 / 10_000) as u64;
+    let loan_debt = |env: &mut TestEnv| {
+        let loan: LoanAccount = runtime.block_on(env.account(&loan_pda(&earning.pubkey()).0));
+        loan.remaining_debt
+    };
+
+    let (loans_scanned, jobs) = keeper(&runtime, &env, watched.clone(), true)
+        .scan()
+        .unwrap();
+    assert_eq!(loans_scanned, 3);
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].user, earning.pubkey());
+    assert_eq!(jobs[0].yield_amount, yield_amount);
+    assert_eq!(
+        jobs[0].estimated_repayment,
+        Some(yield_amount * INITIAL_PRICE)
+    );
+
+    let summary = keeper(&runtime, &env, watched.clone(), true)
+        .run_once()
+        .unwrap();
+    assert_eq!(
+        summary,
+        RunSummary {
+            loans_scanned: 3,
+            jobs: 1,
+            repaid: 1,
+            failed: 0
+        }
+    );
+    assert_eq!(loan_debt(&mut env), debt);
+
+    let summary = keeper(&runtime, &env, watched.clone(), false)
+        .run_once()
+        .unwrap();
+    assert_eq!(summary.repaid, 1);
+    assert_eq!(loan_debt(&mut env), debt - yield_amount * INITIAL_PRICE);
+
+    // The harvested yield is not queued again
+    let summary = keeper(&runtime, &env, watched.clone(), false)
+        .run_once()
+        .unwrap();
+    assert_eq!(
+        summary,
+        RunSummary {
+            loans_scanned: 3,
+            jobs: 0,
+            repaid: 0,
+            failed: 0
+        }
+    );
+    assert_eq!(loan_debt(&mut env), debt - yield_amount * INITIAL_PRICE);
+
+    // A stale oracle fails the send, and the retry, without aborting the run.
+    runtime.block_on(env.accrue_yield(&earning.pubkey(), yield_amount));
+    runtime.block_on(env.warp_seconds(300));
+    let summary = keeper(&runtime, &env, watched, false).run_once().unwrap();
+    assert_eq!(
+        summary,
+        RunSummary {
+            loans_scanned: 3,
+            jobs: 1,
+            repaid: 0,
+            failed: 1
+        }
+    );
+}
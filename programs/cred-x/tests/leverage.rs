@@ -0,0 +1,252 @@
+mod common;
+
+use anchor_spl::token_2022::spl_token_2022;
+use common::*;
+use cred_x::{CredXError, CreditTransferMode, LoanAccount, ProtocolState};
+use cred_x_client::{
+    collateral_vault_pda, deleverage, initialize_credit_transfer_hook, leverage, loan_pda,
+    set_swap_program, withdraw_collateral,
+};
+use mock_swap::MockSwapError;
+use solana_sdk::{
+    instruction::Instruction,
+    signature::{Keypair, Signer},
+};
+
+/// Credit swapped for one unit of collateral at the initial price.
+const ONE_COLLATERAL_IN_CREDIT: u64 = ONE_COLLATERAL * INITIAL_PRICE;
+
+struct LeverageEnv {
+    env: TestEnv,
+    user: Keypair,
+}
+
+/// A user with one unit of collateral deposited and nothing borrowed, and
+/// pools between credit and collateral. Credit buys collateral at the oracle
+/// price divided by `discount`, collateral sells at the oracle price.
+async fn leverage_env(discount: u64) -> LeverageEnv {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let ix = set_swap_program(&env.protocol, mock_swap::ID);
+    env.process(&[ix], &[&admin]).await.unwrap();
+
+    let user = env.new_user(ONE_COLLATERAL).await;
+    env.deposit(&user, ONE_COLLATERAL).await.unwrap();
+
+    let credit = (env.protocol.credit_mint, env.protocol.credit_token_program);
+    let collateral = (env.collateral.mint, env.collateral.token_program);
+    env.create_mock_pool(
+        credit,
+        collateral,
+        (1, INITIAL_PRICE * discount),
+        10 * ONE_COLLATERAL,
+    )
+    .await;
+    env.create_mock_pool(collateral, credit, (INITIAL_PRICE, 1), 0)
+        .await;
+    // Another borrower funds the credit side of the pool
+    let lender = env.borrower(4 * ONE_COLLATERAL).await;
+    let pool = mock_pool(&collateral.0, &credit.0);
+    env.transfer_credit(&lender, &pool, 2 * ONE_COLLATERAL_IN_CREDIT)
+        .await;
+
+    LeverageEnv { env, user }
+}
+
+impl LeverageEnv {
+    fn leverage(&self, target_ltv_bps: u16, min_collateral_out: u64) -> Instruction {
+        let env = &self.env;
+        leverage(
+            &env.protocol,
+            self.user.pubkey(),
+            &env.collateral,
+            mock_swap::ID,
+            mock_swap_accounts(
+                (env.protocol.credit_mint, env.protocol.credit_token_program),
+                (env.collateral.mint, env.collateral.token_program),
+            ),
+            target_ltv_bps,
+            min_collateral_out,
+        )
+    }
+
+    fn deleverage(&self, target_ltv_bps: u16, min_credit_out: u64) -> Instruction {
+        let env = &self.env;
+        deleverage(
+            &env.protocol,
+            self.user.pubkey(),
+            &env.collateral,
+            mock_swap::ID,
+            mock_swap_accounts(
+                (env.collateral.mint, env.collateral.token_program),
+                (env.protocol.credit_mint, env.protocol.credit_token_program),
+            ),
+            target_ltv_bps,
+            min_credit_out,
+        )
+    }
+
+    async fn send(
+        &mut self,
+        ix: Instruction,
+        signers: &[&Keypair],
+    ) -> std::result::Result<(), solana_program_test::BanksClientError> {
+        self.env.process(&[ix], signers).await
+    }
+
+    async fn loan(&mut self) -> LoanAccount {
+        self.env.account(&loan_pda(&self.user.pubkey()).0).await
+    }
+}
+
+#[tokio::test]
+async fn leverage_borrows_and_redeposits_up_to_target_ltv() {
+    let mut s = leverage_env(1).await;
+    let (user, admin) = (s.user.insecure_clone(), s.env.admin.insecure_clone());
+
+    // At 50% the loop doubles the position: borrow its full value, buy one more unit
+    let ix = s.leverage(5_000, ONE_COLLATERAL);
+    s.send(ix, &[&user, &admin]).await.unwrap();
+
+    let loan = s.loan().await;
+    assert_eq!(loan.collateral_amount, 2 * ONE_COLLATERAL);
+    assert_eq!(loan.remaining_debt, ONE_COLLATERAL_IN_CREDIT);
+    let vault_ata = s.env.collateral.vault_ata(&user.pubkey());
+    assert_eq!(s.env.token_balance(&vault_ata).await, 2 * ONE_COLLATERAL);
+    // Borrowed credit only passes through the vault
+    let vault_credit = s
+        .env
+        .protocol
+        .credit_ata(&collateral_vault_pda(&user.pubkey()).0);
+    assert_eq!(s.env.token_balance(&vault_credit).await, 0);
+    let user_credit = s.env.protocol.credit_ata(&user.pubkey());
+    assert_eq!(s.env.token_balance(&user_credit).await, 0);
+
+    let state: ProtocolState = s.env.account(&s.env.protocol.protocol()).await;
+    assert_eq!(
+        state.total_debt,
+        ONE_COLLATERAL_IN_CREDIT + 4 * 90_000_000_000_000_000
+    );
+}
+
+#[tokio::test]
+async fn leverage_respects_ltv_and_slippage_bounds() {
+    let mut s = leverage_env(2).await;
+    let (user, admin) = (s.user.insecure_clone(), s.env.admin.insecure_clone());
+
+    let cases: Vec<(Instruction, u32)> = vec![
+        (s.leverage(6_001, 0), CredXError::InvalidTargetLtv.into()),
+        (
+            s.leverage(5_000, ONE_COLLATERAL),
+            MockSwapError::SlippageExceeded.into(),
+        ),
+        // Buying at half the oracle price leaves the loan above the LTV
+        (s.leverage(6_000, 0), CredXError::ExceedsMaxBorrow.into()),
+    ];
+    for (ix, expected) in cases {
+        assert_error(s.send(ix, &[&user, &admin]).await, expected);
+    }
+
+    let ix = s.leverage(4_000, ONE_COLLATERAL / 4);
+    s.send(ix, &[&user, &admin]).await.unwrap();
+    let loan = s.loan().await;
+    assert!(loan.remaining_debt > 0);
+    // Already above the target
+    assert_error(
+        s.send(s.leverage(1_000, 0), &[&user, &admin]).await,
+        CredXError::InvalidTargetLtv,
+    );
+}
+
+#[tokio::test]
+async fn deleverage_sells_collateral_to_repay_debt() {
+    let mut s = leverage_env(1).await;
+    let (user, admin) = (s.user.insecure_clone(), s.env.admin.insecure_clone());
+    let ix = s.leverage(5_000, 0);
+    s.send(ix, &[&user, &admin]).await.unwrap();
+
+    assert_error(
+        s.send(s.deleverage(6_000, 0), &[&user]).await,
+        CredXError::InvalidTargetLtv,
+    );
+    assert_error(
+        s.send(s.deleverage(2_500, ONE_COLLATERAL_IN_CREDIT), &[&user])
+            .await,
+        MockSwapError::SlippageExceeded,
+    );
+
+    // Two thirds of a unit, rounded up, brings 50% down to 25%
+    let sold = 666_666_667;
+    let ix = s.deleverage(2_500, 0);
+    s.send(ix, &[&user]).await.unwrap();
+    let loan = s.loan().await;
+    assert_eq!(loan.collateral_amount, 2 * ONE_COLLATERAL - sold);
+    let remaining = ONE_COLLATERAL_IN_CREDIT - sold * INITIAL_PRICE;
+    assert_eq!(loan.remaining_debt, remaining);
+
+    // Selling to zero repays everything and returns the rounding surplus
+    let state: ProtocolState = s.env.account(&s.env.protocol.protocol()).await;
+    let minted_before = state.stats.total_minted;
+    let ix = s.deleverage(0, 0);
+    s.send(ix, &[&user]).await.unwrap();
+    let loan = s.loan().await;
+    assert_eq!(loan.remaining_debt, 0);
+    let sold_again = remaining.div_ceil(INITIAL_PRICE);
+    assert_eq!(
+        loan.collateral_amount,
+        2 * ONE_COLLATERAL - sold - sold_again
+    );
+    let user_credit = s.env.protocol.credit_ata(&user.pubkey());
+    assert_eq!(
+        s.env.token_balance(&user_credit).await,
+        sold_again * INITIAL_PRICE - remaining
+    );
+    let state: ProtocolState = s.env.account(&s.env.protocol.protocol()).await;
+    assert_eq!(state.stats.open_loans, 1);
+    assert_eq!(state.stats.total_repaid_via_user, ONE_COLLATERAL_IN_CREDIT);
+    // The surplus is minted to the owner like a borrow would be
+    assert_eq!(
+        state.stats.total_minted,
+        minted_before + sold_again * INITIAL_PRICE - remaining
+    );
+
+    // With no debt left the rest of the collateral comes back
+    let remaining_collateral = loan.collateral_amount;
+    let ix = withdraw_collateral(&s.env.protocol, user.pubkey(), &s.env.collateral);
+    s.send(ix, &[&user]).await.unwrap();
+    let user_collateral = s.env.collateral.user_ata(&user.pubkey());
+    assert_eq!(
+        s.env.token_balance(&user_collateral).await,
+        remaining_collateral
+    );
+    let loan = s.loan().await;
+    assert_eq!(loan.collateral_amount, 0);
+    let state: ProtocolState = s.env.account(&s.env.protocol.protocol()).await;
+    assert_eq!(state.stats.open_loans, 1);
+}
+
+#[tokio::test]
+async fn leverage_needs_unrestricted_credit_transfers() {
+    let mut params = protocol_params();
+    params.credit_transfer_mode = CreditTransferMode::DebtFloor;
+    let mut env = TestEnv::with_params(params, spl_token_2022::ID).await;
+    let admin = env.admin.insecure_clone();
+    let ixs = [
+        initialize_credit_transfer_hook(&env.protocol),
+        set_swap_program(&env.protocol, mock_swap::ID),
+    ];
+    env.process(&ixs, &[&admin]).await.unwrap();
+    let user = env.new_user(ONE_COLLATERAL).await;
+    env.deposit(&user, ONE_COLLATERAL).await.unwrap();
+    let mut s = LeverageEnv { env, user };
+    let user = s.user.insecure_clone();
+
+    assert_error(
+        s.send(s.leverage(5_000, 0), &[&user, &admin]).await,
+        CredXError::CreditTransfersRestricted,
+    );
+    assert_error(
+        s.send(s.deleverage(0, 0), &[&user]).await,
+        CredXError::CreditTransfersRestricted,
+    );
+}
@@ -0,0 +1,402 @@
+//! Property tests for the loan math in `cred_x::math` and the stability
+//! pool's accounting. Every function is checked against a reference computed
+//! in u128 without any narrowing, so a wrapped cast shows up as a mismatch
+//! rather than passing silently.
+
+use anchor_lang::error::Error;
+use cred_x::{
+    borrow_limit, borrow_quote, bps_of, collateral_value, covers_debt, debt_collateral,
+    deleverage_collateral_amount, harvestable_yield, leverage_borrow_amount, mul_div_floor,
+    redemption_payout, yield_repayment, CredXError, StabilityDeposit, StabilityPool, MAX_SCALES,
+    PRODUCT_PRECISION, SCALE_FACTOR,
+};
+use proptest::prelude::*;
+
+fn error_code(err: Error) -> u32 {
+    match err {
+        Error::AnchorError(err) => err.error_code_number,
+        Error::ProgramError(err) => panic!("unexpected program error {err:?}"),
+    }
+}
+
+fn assert_error<T: std::fmt::Debug>(result: anchor_lang::Result<T>, expected: CredXError) {
+    let code = error_code(result.expect_err("expected an error"));
+    assert_eq!(code, u32::from(expected), "expected {expected:?}");
+}
+
+/// LTVs the protocol accepts, plus the out-of-range tail.
+fn ltv_bps() -> impl Strategy<Value = u16> {
+    prop_oneof![4 => 1..=9_000u16, 1 => any::<u16>()]
+}
+
+/// Prices and amounts spread across every magnitude, not just large values.
+fn magnitude() -> impl Strategy<Value = u64> {
+    prop_oneof![
+        any::<u64>(),
+        (0u32..64).prop_flat_map(|bits| 0..=(u64::MAX >> bits)),
+        Just(0),
+        Just(u64::MAX),
+    ]
+}
+
+fn empty_pool() -> StabilityPool {
+    StabilityPool {
+        protocol: Default::default(),
+        collateral_mint: Default::default(),
+        total_deposits: 0,
+        total_collateral: 0,
+        total_debt_offset: 0,
+        product: PRODUCT_PRECISION,
+        scale: 0,
+        scale_sums: Default::default(),
+        bump: 0,
+    }
+}
+
+/// Adds `amount` to the pool as a new deposit.
+fn deposit(pool: &mut StabilityPool, amount: u64) -> StabilityDeposit {
+    let mut deposit = StabilityDeposit {
+        pool: Default::default(),
+        owner: Default::default(),
+        amount: 0,
+        product_snapshot: 0,
+        sum_snapshot: 0,
+        scale_snapshot: 0,
+        bump: 0,
+    };
+    pool.total_deposits += amount;
+    pool.snapshot(&mut deposit, amount);
+    deposit
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig {
+        cases: 4_096,
+        // Integration tests have no lib.rs for proptest to anchor regression
+        // files to; failures print the minimal input instead.
+        failure_persistence: None,
+        ..ProptestConfig::default()
+    })]
+
+    #[test]
+    fn collateral_value_is_exact(amount in magnitude(), price in magnitude()) {
+        let value = collateral_value(amount, price);
+        if price > 0 {
+            prop_assert_eq!(value % u128::from(price), 0);
+            prop_assert_eq!(value / u128::from(price), u128::from(amount));
+        } else {
+            prop_assert_eq!(value, 0);
+        }
+    }
+
+    #[test]
+    fn borrow_limit_never_exceeds_value_at_sane_ltv(value in any::<u128>(), ltv in 0..=10_000u16) {
+        match borrow_limit(value, ltv) {
+            Ok(limit) => prop_assert!(limit <= value),
+            Err(err) => {
+                prop_assert_eq!(error_code(err), u32::from(CredXError::MathOverflow));
+                prop_assert!(value.checked_mul(u128::from(ltv)).is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn bps_of_never_wraps(amount in magnitude(), bps in any::<u16>()) {
+        let exact = u128::from(amount) * u128::from(bps) / 10_000;
+        match bps_of(amount, bps) {
+            Ok(share) => prop_assert_eq!(u128::from(share), exact),
+            Err(_) => prop_assert!(exact > u128::from(u64::MAX)),
+        }
+    }
+
+    #[test]
+    fn borrow_stays_within_the_limit(
+        collateral in magnitude(),
+        price in magnitude(),
+        ltv in ltv_bps(),
+        debt in magnitude(),
+        fee_bps in 0..=10_000u16,
+    ) {
+        let Some(limit) = collateral_value(collateral, price).checked_mul(u128::from(ltv)) else {
+            assert_error(
+                borrow_quote(collateral, price, ltv, debt, fee_bps),
+                CredXError::MathOverflow,
+            );
+            return Ok(());
+        };
+        let limit = limit / 10_000;
+        match borrow_quote(collateral, price, ltv, debt, fee_bps) {
+            Ok(quote) => {
+                // Debt after origination never exceeds the borrow limit, and
+                // the amount minted is exactly the headroom.
+                prop_assert_eq!(u128::from(debt) + u128::from(quote.borrow_amount), limit);
+                prop_assert_eq!(quote.user_amount + quote.fee_amount, quote.borrow_amount);
+                prop_assert!(quote.fee_amount <= quote.borrow_amount);
+                prop_assert!(quote.borrow_amount > 0);
+            }
+            Err(err) => {
+                let expected = if limit < u128::from(debt) {
+                    CredXError::ExceedsMaxBorrow
+                } else if limit - u128::from(debt) > u128::from(u64::MAX) {
+                    CredXError::MathOverflow
+                } else if limit == 0 {
+                    CredXError::ZeroBorrowAmount
+                } else {
+                    CredXError::MaxBorrowLimitReached
+                };
+                prop_assert_eq!(error_code(err), u32::from(expected));
+            }
+        }
+    }
+
+    #[test]
+    fn yield_is_the_balance_above_the_deposit(balance in magnitude(), collateral in magnitude()) {
+        match harvestable_yield(balance, collateral) {
+            Ok(amount) => prop_assert_eq!(amount + collateral, balance),
+            Err(err) => {
+                prop_assert!(balance < collateral);
+                prop_assert_eq!(error_code(err), u32::from(CredXError::NegativeYield));
+            }
+        }
+    }
+
+    #[test]
+    fn repayment_never_exceeds_debt(yield_amount in magnitude(), price in magnitude(), debt in magnitude()) {
+        let value = collateral_value(yield_amount, price);
+        match yield_repayment(yield_amount, price, debt) {
+            Ok(repayment) => {
+                prop_assert!(repayment <= debt);
+                prop_assert!(u128::from(repayment) <= value);
+                // No truncation: either the whole value or the whole debt.
+                prop_assert_eq!(u128::from(repayment), value.min(u128::from(debt)));
+            }
+            Err(err) => {
+                prop_assert_eq!(value, 0);
+                prop_assert_eq!(error_code(err), u32::from(CredXError::ZeroRepaymentValue));
+            }
+        }
+    }
+
+    #[test]
+    fn withdrawal_check_matches_exact_value(balance in magnitude(), price in magnitude(), debt in magnitude()) {
+        prop_assert_eq!(
+            covers_debt(balance, price, debt),
+            u128::from(balance) * u128::from(price) >= u128::from(debt)
+        );
+    }
+
+    /// Borrowing the full limit and then harvesting yield keeps the loan
+    /// within its limit at the origination price.
+    #[test]
+    fn borrow_then_repay_keeps_debt_bounded(
+        collateral in 1..=u64::MAX >> 20,
+        price in 1..=1_000_000_000_000u64,
+        ltv in 1..=9_000u16,
+        yield_amount in 0..=u64::MAX >> 20,
+    ) {
+        let Ok(quote) = borrow_quote(collateral, price, ltv, 0, 0) else {
+            return Ok(());
+        };
+        let limit = collateral_value(collateral, price) * u128::from(ltv) / 10_000;
+        prop_assert!(u128::from(quote.borrow_amount) <= limit);
+
+        if yield_amount > 0 {
+            let repayment = yield_repayment(yield_amount, price, quote.borrow_amount).unwrap();
+            let remaining = quote.borrow_amount - repayment;
+            prop_assert!(remaining <= quote.borrow_amount);
+            if remaining > 0 {
+                // A later borrow tops the loan back up to exactly the limit.
+                let top_up = borrow_quote(collateral, price, ltv, remaining, 0).unwrap();
+                prop_assert_eq!(u128::from(remaining) + u128::from(top_up.borrow_amount), limit);
+            }
+        }
+    }
+
+    /// Swapping what `leverage` borrows, net of the fee, at the oracle price
+    /// never leaves the loan above the target.
+    #[test]
+    fn leverage_never_overshoots_target(
+        collateral in 1..=u64::MAX >> 20,
+        price in 1..=1_000_000_000_000u64,
+        debt in magnitude(),
+        ltv in 1..=9_000u16,
+        fee_bps in 0..=1_000u16,
+    ) {
+        let value = collateral_value(collateral, price);
+        match leverage_borrow_amount(value, debt, ltv, fee_bps) {
+            Ok(borrowed) => {
+                let bought = u128::from(borrowed) * u128::from(10_000 - fee_bps);
+                let debt_after = u128::from(debt) + u128::from(borrowed);
+                prop_assert!(
+                    debt_after * 100_000_000 <= u128::from(ltv) * (value * 10_000 + bought)
+                );
+            }
+            Err(err) => {
+                let code = error_code(err);
+                if code == u32::from(CredXError::InvalidTargetLtv) {
+                    prop_assert!(value * u128::from(ltv) / 10_000 <= u128::from(debt));
+                } else {
+                    prop_assert_eq!(code, u32::from(CredXError::MathOverflow));
+                }
+            }
+        }
+    }
+
+    /// Repaying with what `deleverage` sells at the oracle price brings the
+    /// loan down to the target, unless it has to sell everything.
+    #[test]
+    fn deleverage_reaches_target(
+        collateral in 1..=u64::MAX >> 20,
+        price in 1..=1_000_000_000_000u64,
+        debt in magnitude(),
+        ltv in 0..=9_000u16,
+    ) {
+        let value = collateral_value(collateral, price);
+        match deleverage_collateral_amount(collateral, price, debt, ltv) {
+            Ok(sold) => {
+                prop_assert!(sold > 0 && sold <= collateral);
+                if sold < collateral {
+                    let remaining = u128::from(debt).saturating_sub(collateral_value(sold, price));
+                    let kept = collateral_value(collateral - sold, price);
+                    prop_assert!(remaining * 10_000 <= kept * u128::from(ltv));
+                }
+            }
+            Err(err) => {
+                prop_assert_eq!(error_code(err), u32::from(CredXError::InvalidTargetLtv));
+                prop_assert!(u128::from(debt) <= value * u128::from(ltv) / 10_000);
+            }
+        }
+    }
+
+    /// A redemption never pays out more than the redeemed credit is worth
+    /// after the fee, and never short by a whole unit of collateral.
+    #[test]
+    fn redemption_payout_matches_reference(
+        credit in magnitude(),
+        price in 1..=1_000_000_000_000u64,
+        fee_bps in 0..=1_000u16,
+    ) {
+        let paid = redemption_payout(credit, price, fee_bps).unwrap();
+        let net = u128::from(credit) - u128::from(credit) * u128::from(fee_bps) / 10_000;
+        prop_assert!(collateral_value(paid, price) <= net);
+        prop_assert!(collateral_value(paid + 1, price) > net);
+    }
+
+    /// Debt converted to collateral is always enough to cover it, and never
+    /// by more than a whole unit of collateral.
+    #[test]
+    fn debt_collateral_matches_reference(
+        debt in magnitude(),
+        price in 1..=1_000_000_000_000u64,
+    ) {
+        let collateral = debt_collateral(debt, price).unwrap();
+        prop_assert!(collateral_value(collateral, price) >= u128::from(debt));
+        if collateral > 0 {
+            prop_assert!(collateral_value(collateral - 1, price) < u128::from(debt));
+        }
+    }
+
+    #[test]
+    fn mul_div_floor_matches_reference(
+        value in any::<u64>(),
+        shift in 0u32..64,
+        multiplier in magnitude(),
+        divisor in 1..=u64::MAX,
+    ) {
+        let value = u128::from(value) << shift;
+        let result = mul_div_floor(value, multiplier, u128::from(divisor));
+        match value.checked_mul(u128::from(multiplier)) {
+            Some(product) => prop_assert_eq!(result.unwrap(), product / u128::from(divisor)),
+            None => {
+                if let Ok(result) = result {
+                    prop_assert!(result >= value / u128::from(divisor) * u128::from(multiplier));
+                }
+            }
+        }
+    }
+
+    /// However many liquidations a pool absorbs, its depositors are never
+    /// owed more credit or collateral than it holds.
+    #[test]
+    fn stability_pool_never_owes_more_than_it_holds(
+        deposits in (1..=1_000_000_000_000_000u64, 1..=1_000_000_000_000_000u64),
+        liquidations in prop::collection::vec((1..=10_000u64, magnitude()), 1..12),
+    ) {
+        let mut pool = empty_pool();
+        let accounts = [deposits.0, deposits.1].map(|amount| deposit(&mut pool, amount));
+
+        for (share_bps, collateral) in liquidations {
+            let capacity = pool.offset_capacity(1_000_000);
+            let debt = capacity / 10_000 * share_bps;
+            if debt == 0 || collateral > u64::MAX - pool.total_collateral {
+                continue;
+            }
+            if pool.offset(debt, collateral).is_err() {
+                break;
+            }
+        }
+
+        let mut owed = 0u128;
+        let mut gains = 0u128;
+        for deposit in &accounts {
+            owed += u128::from(pool.compounded_deposit(deposit).unwrap());
+            gains += u128::from(pool.collateral_gain(deposit).unwrap());
+        }
+        prop_assert!(owed <= u128::from(pool.total_deposits));
+        prop_assert!(gains <= u128::from(pool.total_collateral));
+    }
+}
+
+#[test]
+fn extreme_inputs_fail_instead_of_wrapping() {
+    // The old i64 cron math wrapped here; the value now stays exact.
+    let repayment = yield_repayment(u64::MAX, u64::MAX, u64::MAX).unwrap();
+    assert_eq!(repayment, u64::MAX);
+    assert_eq!(yield_repayment(1 << 40, 1 << 40, 5).unwrap(), 5);
+
+    // The smallest LTV that overflows at the largest value
+    for ltv in [2, 9_000] {
+        assert_error(
+            borrow_quote(u64::MAX, u64::MAX, ltv, 0, 0),
+            CredXError::MathOverflow,
+        );
+    }
+    assert_error(borrow_limit(u128::MAX, 2), CredXError::MathOverflow);
+    assert_error(bps_of(u64::MAX, 10_001), CredXError::MathOverflow);
+}
+
+#[test]
+fn stability_pool_keeps_offsetting_after_its_scales_wrap() {
+    const REFILL: u64 = 1_000_000_000_000_000;
+    const SEIZED: u64 = 1_000_000_000_000;
+    let mut pool = empty_pool();
+    let first = deposit(&mut pool, REFILL);
+
+    // Drained to its floor and refilled, the pool rescales every round or two
+    while pool.scale < 3 * MAX_SCALES as u64 {
+        pool.offset(pool.offset_capacity(0), SEIZED).unwrap();
+        deposit(&mut pool, REFILL);
+    }
+    let before = pool.total_deposits;
+    let late = deposit(&mut pool, REFILL);
+    pool.offset(pool.offset_capacity(0), SEIZED).unwrap();
+
+    let expected = u128::from(SEIZED) * u128::from(REFILL) / u128::from(before + REFILL);
+    let gain = u128::from(pool.collateral_gain(&late).unwrap());
+    assert!(gain <= expected && gain >= expected - expected / 1_000);
+    assert_eq!(pool.compounded_deposit(&first).unwrap(), 0);
+    // Its sums were recycled long ago
+    assert_eq!(pool.collateral_gain(&first).unwrap(), 0);
+}
+
+#[test]
+fn stability_pool_offset_past_its_capacity_is_exhausted() {
+    let mut pool = empty_pool();
+    pool.total_deposits = u64::MAX;
+    pool.product = SCALE_FACTOR;
+    assert!(u64::MAX - 1 > pool.offset_capacity(0));
+    assert_error(
+        pool.offset(u64::MAX - 1, 0),
+        CredXError::StabilityPoolExhausted,
+    );
+}
@@ -0,0 +1,496 @@
+mod common;
+
+use anchor_lang::{error::ErrorCode, prelude::Pubkey, Discriminator, Space};
+use anchor_spl::{
+    token::spl_token::{
+        self,
+        solana_program::{program_option::COption, program_pack::Pack},
+    },
+    token_2022::spl_token_2022,
+};
+use common::*;
+use cred_x::{
+    CollateralConfig, CollateralVault, CredXError, LoanAccount, MigrateProtocolParams,
+    ProtocolState, ProtocolStateV0, SimplePriceOracle,
+};
+use cred_x_client::{
+    bind_loan, collateral_vault_pda, configure_collateral, cron_repayment, initialize_protocol,
+    loan_pda, migrate_loan, migrate_protocol, migrate_simple_oracle, program_authority_pda,
+    set_debt_ceiling, update_simple_oracle, ProtocolContext,
+};
+use solana_sdk::{
+    account::Account,
+    rent::Rent,
+    signature::{Keypair, Signer},
+};
+
+// Bytes from the version on
+const PROTOCOL_TAIL: usize = 1 + 128;
+const ORACLE_TAIL: usize = 1 + 32;
+// The oracle's EMA, confidence and authority came after its price and timestamp
+const ORACLE_ADDED: usize = 8 + 8 + 32 + ORACLE_TAIL;
+
+async fn raw_account(env: &mut TestEnv, address: &Pubkey) -> Account {
+    env.ctx
+        .banks_client
+        .get_account(*address)
+        .await
+        .unwrap()
+        .expect("account missing")
+}
+
+/// Rewrites the account as it was laid out before versioning, funded only for
+/// that size.
+async fn make_legacy(env: &mut TestEnv, address: &Pubkey, tail: usize) {
+    let mut account = raw_account(env, address).await;
+    let len = account.data.len() - tail;
+    account.data.truncate(len);
+    account.lamports = Rent::default().minimum_balance(len);
+    env.ctx.set_account(address, &account.into());
+}
+
+async fn set_version(env: &mut TestEnv, address: &Pubkey, tail: usize, version: u8) {
+    let mut account = raw_account(env, address).await;
+    let offset = account.data.len() - tail;
+    account.data[offset] = version;
+    env.ctx.set_account(address, &account.into());
+}
+
+async fn assert_current_size(env: &mut TestEnv, address: &Pubkey, space: usize) {
+    let account = raw_account(env, address).await;
+    assert_eq!(account.data.len(), 8 + space);
+    assert!(account.lamports >= Rent::default().minimum_balance(8 + space));
+}
+
+/// Rewrites the protocol byte for byte as the program first laid it out:
+/// admin, LTV, credit mint, lock flag and bump.
+async fn make_legacy_protocol(env: &mut TestEnv) -> ProtocolState {
+    let address = env.protocol.protocol();
+    let state: ProtocolState = env.account(&address).await;
+    let mut data = ProtocolState::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(state.admin.as_ref());
+    data.extend_from_slice(&state.ltv_ratio_bps.to_le_bytes());
+    data.extend_from_slice(state.credit_mint.as_ref());
+    data.push(u8::from(state.is_locked));
+    data.push(state.bump);
+
+    let mut account = raw_account(env, &address).await;
+    account.lamports = Rent::default().minimum_balance(data.len());
+    account.data = data;
+    env.ctx.set_account(&address, &account.into());
+    state
+}
+
+fn migrate_params(state: &ProtocolState) -> MigrateProtocolParams {
+    MigrateProtocolParams {
+        liquidation_threshold_bps: 8_500,
+        max_price_age_secs: 120,
+        debt_ceiling: u64::MAX,
+        total_debt: state.total_debt,
+        open_loans: state.stats.open_loans,
+        legacy_loans: 0,
+    }
+}
+
+/// Writes `user`'s vault and loan in the layouts the program first created
+/// them with, holding `collateral` priced by the test oracle and owing `debt`.
+async fn write_legacy_loan(env: &mut TestEnv, user: &Pubkey, collateral: u64, debt: u64) {
+    let (vault, vault_bump) = collateral_vault_pda(user);
+    let (loan, loan_bump) = loan_pda(user);
+    let mut data = CollateralVault::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(env.collateral.mint.as_ref());
+    data.push(vault_bump);
+    write_program_account(env, &vault, data);
+
+    let mut data = LoanAccount::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(user.as_ref());
+    data.extend_from_slice(&collateral.to_le_bytes());
+    data.extend_from_slice(&debt.to_le_bytes());
+    data.extend_from_slice(&0u64.to_le_bytes());
+    data.push(loan_bump);
+    data.extend_from_slice(env.oracle.pubkey().as_ref());
+    write_program_account(env, &loan, data);
+
+    // Deposits approved the program to move the vault's tokens
+    let (mint, token_program) = (env.collateral.mint, env.collateral.token_program);
+    let ata = env
+        .mint_to_owner(&mint, &token_program, &vault, collateral)
+        .await;
+    let mut account = raw_account(env, &ata).await;
+    let mut state = spl_token::state::Account::unpack(&account.data).unwrap();
+    state.delegate = COption::Some(program_authority_pda().0);
+    state.delegated_amount = collateral;
+    state.pack_into_slice(&mut account.data);
+    env.ctx.set_account(&ata, &account.into());
+}
+
+fn write_program_account(env: &mut TestEnv, address: &Pubkey, data: Vec<u8>) {
+    let account = Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: cred_x::ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    env.ctx.set_account(address, &account.into());
+}
+
+#[tokio::test]
+async fn migrate_protocol_decodes_the_original_layout() {
+    let mut env = TestEnv::new().await;
+    let user = env.borrower(ONE_COLLATERAL).await;
+    let admin = env.admin.insecure_clone();
+    let protocol = env.protocol.protocol();
+    let before = make_legacy_protocol(&mut env).await;
+    assert_eq!(
+        raw_account(&mut env, &protocol).await.data.len(),
+        8 + ProtocolStateV0::INIT_SPACE
+    );
+
+    let ix = set_debt_ceiling(&env.protocol, 1_000);
+    assert_error(
+        env.process(&[ix], &[&admin]).await,
+        ErrorCode::AccountDidNotDeserialize,
+    );
+
+    // Only the admin recorded in the account may migrate it
+    let stranger = Keypair::new();
+    env.airdrop(&stranger.pubkey(), 1_000_000_000).await;
+    let ix = migrate_protocol(
+        &env.protocol.with_admin(stranger.pubkey()),
+        migrate_params(&before),
+    );
+    assert_error(
+        env.process(&[ix], &[&stranger]).await,
+        CredXError::UnauthorizedAdmin,
+    );
+    let mut params = migrate_params(&before);
+    params.liquidation_threshold_bps = before.ltv_ratio_bps;
+    let ix = migrate_protocol(&env.protocol, params);
+    assert_error(
+        env.process(&[ix], &[&admin]).await,
+        CredXError::InvalidLiquidationThreshold,
+    );
+
+    let ix = migrate_protocol(&env.protocol, migrate_params(&before));
+    env.process(&[ix], &[&admin]).await.unwrap();
+    assert_current_size(&mut env, &protocol, ProtocolState::INIT_SPACE).await;
+    let state: ProtocolState = env.account(&protocol).await;
+    assert_eq!(state.version, 1);
+    assert_eq!(state.admin, admin.pubkey());
+    assert_eq!(state.ltv_ratio_bps, 6_000);
+    assert_eq!(state.liquidation_threshold_bps, 8_500);
+    assert_eq!(state.credit_mint, env.protocol.credit_mint);
+    assert_eq!(state.credit_decimals, CREDIT_DECIMALS);
+    assert_eq!(state.max_price_age_secs, 120);
+    assert_eq!(state.debt_ceiling, u64::MAX);
+    assert_eq!(state.total_debt, before.total_debt);
+    assert_eq!(state.stats.open_loans, 1);
+    assert!(!state.is_locked);
+    assert_eq!(state.bump, before.bump);
+    assert_eq!(state.original_admin, Pubkey::default());
+
+    let ix = migrate_protocol(&env.protocol, migrate_params(&before));
+    assert_error(
+        env.process(&[ix], &[&admin]).await,
+        CredXError::AccountAlreadyMigrated,
+    );
+    let ix = set_debt_ceiling(&env.protocol, u64::MAX - 1);
+    env.process(&[ix], &[&admin]).await.unwrap();
+
+    // Repayments draw down the reconciled total
+    env.accrue_yield(&user.pubkey(), ONE_COLLATERAL / 100).await;
+    let ix = cron_repayment(&env.protocol, user.pubkey(), &env.collateral);
+    env.process(&[ix], &[]).await.unwrap();
+    let state: ProtocolState = env.account(&protocol).await;
+    assert!(state.total_debt < before.total_debt);
+}
+
+#[tokio::test]
+async fn migrate_loan_is_permissionless_and_preserves_debt() {
+    let mut env = TestEnv::new().await;
+    let donor = env.borrower(ONE_COLLATERAL).await;
+    let debt = env
+        .account::<LoanAccount>(&loan_pda(&donor.pubkey()).0)
+        .await
+        .remaining_debt
+        / 2;
+    let user = Keypair::new();
+    env.airdrop(&user.pubkey(), 1_000_000_000).await;
+    env.transfer_credit(&donor, &user.pubkey(), debt).await;
+    // Borrowing approved the program to burn the credit for repayments
+    let ix = spl_token_2022::instruction::approve(
+        &env.protocol.credit_token_program,
+        &env.protocol.credit_ata(&user.pubkey()),
+        &program_authority_pda().0,
+        &user.pubkey(),
+        &[],
+        debt,
+    )
+    .unwrap();
+    env.process(&[ix], &[&user]).await.unwrap();
+    let vault = TestEnv::collateral_vault(&user.pubkey());
+    let loan = loan_pda(&user.pubkey()).0;
+    write_legacy_loan(&mut env, &user.pubkey(), ONE_COLLATERAL, debt).await;
+
+    // The protocol declares the legacy loan and its debt when migrated
+    let admin = env.admin.insecure_clone();
+    let before = make_legacy_protocol(&mut env).await;
+    let mut params = migrate_params(&before);
+    params.total_debt += debt;
+    params.open_loans += 1;
+    params.legacy_loans = 1;
+    let ix = migrate_protocol(&env.protocol, params);
+    env.process(&[ix], &[&admin]).await.unwrap();
+
+    env.accrue_yield(&user.pubkey(), ONE_COLLATERAL / 100).await;
+    let ix = cron_repayment(&env.protocol, user.pubkey(), &env.collateral);
+    assert_error(
+        env.process(&[ix], &[]).await,
+        ErrorCode::AccountDidNotDeserialize,
+    );
+
+    let payer = env.ctx.payer.pubkey();
+    env.process(&[migrate_loan(payer, user.pubkey())], &[])
+        .await
+        .unwrap();
+    assert_current_size(&mut env, &vault, CollateralVault::INIT_SPACE).await;
+    assert_current_size(&mut env, &loan, LoanAccount::INIT_SPACE).await;
+    let state: LoanAccount = env.account(&loan).await;
+    assert_eq!(state.version, 1);
+    assert_eq!(state.user, user.pubkey());
+    assert_eq!(state.remaining_debt, debt);
+    assert_eq!(state.collateral_amount, ONE_COLLATERAL);
+    assert_eq!(state.oracle_price_account, env.oracle.pubkey());
+    let state: CollateralVault = env.account(&vault).await;
+    assert_eq!(state.version, 1);
+    assert_eq!(state.mint, env.collateral.mint);
+
+    assert_error(
+        env.process(&[migrate_loan(payer, user.pubkey())], &[])
+            .await,
+        CredXError::AccountAlreadyMigrated,
+    );
+    // The legacy loan never recorded its protocol
+    assert_eq!(
+        env.account::<LoanAccount>(&loan).await.protocol,
+        Pubkey::default()
+    );
+    let ix = cron_repayment(&env.protocol, user.pubkey(), &env.collateral);
+    assert_error(
+        env.process(&[ix], &[]).await,
+        CredXError::LoanProtocolMismatch,
+    );
+
+    // Only the admin can vouch for it
+    let stranger = Keypair::new();
+    env.airdrop(&stranger.pubkey(), 10_000_000_000).await;
+    let ix = bind_loan(
+        &env.protocol.with_admin(stranger.pubkey()),
+        user.pubkey(),
+        &env.collateral,
+    );
+    assert_error(
+        env.process(&[ix], &[&stranger]).await,
+        CredXError::UnauthorizedAdmin,
+    );
+    // and a protocol created since migrated no legacy loans to bind
+    let ix = initialize_protocol(stranger.pubkey(), spl_token::ID, protocol_params());
+    env.process(&[ix], &[&stranger]).await.unwrap();
+    let foreign = ProtocolContext::new(stranger.pubkey(), spl_token::ID);
+    let ix = configure_collateral(
+        &foreign,
+        env.collateral.mint,
+        collateral_params(env.oracle.pubkey()),
+    );
+    env.process(&[ix], &[&stranger]).await.unwrap();
+    let ix = bind_loan(&foreign, user.pubkey(), &env.collateral);
+    assert_error(
+        env.process(&[ix], &[&stranger]).await,
+        CredXError::LegacyLoanNotCovered,
+    );
+
+    let config = env.collateral.config(&env.protocol);
+    let collateral_before = env
+        .account::<CollateralConfig>(&config)
+        .await
+        .total_collateral;
+    let ix = bind_loan(&env.protocol, user.pubkey(), &env.collateral);
+    env.process(&[ix], &[&admin]).await.unwrap();
+    assert_eq!(
+        env.account::<LoanAccount>(&loan).await.protocol,
+        env.protocol.protocol()
+    );
+    let state: ProtocolState = env.account(&env.protocol.protocol()).await;
+    assert_eq!(state.unbound_legacy_loans, 0);
+    assert_eq!(state.unbound_legacy_debt, before.total_debt);
+    let state: CollateralConfig = env.account(&config).await;
+    assert_eq!(state.total_collateral, collateral_before + ONE_COLLATERAL);
+    let ix = bind_loan(&env.protocol, user.pubkey(), &env.collateral);
+    assert_error(
+        env.process(&[ix], &[&admin]).await,
+        CredXError::AccountAlreadyMigrated,
+    );
+
+    let ix = cron_repayment(&env.protocol, user.pubkey(), &env.collateral);
+    env.process(&[ix], &[]).await.unwrap();
+    assert!(env.account::<LoanAccount>(&loan).await.remaining_debt < debt);
+}
+
+#[tokio::test]
+async fn bind_loan_stays_within_the_declared_legacy_loans() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let debt = 1_000;
+    let users: Vec<Keypair> = (0..4).map(|_| Keypair::new()).collect();
+    let payer = env.ctx.payer.pubkey();
+    for (i, user) in users.iter().enumerate() {
+        let owed = if i < 2 { debt } else { 0 };
+        write_legacy_loan(&mut env, &user.pubkey(), ONE_COLLATERAL, owed).await;
+        env.process(&[migrate_loan(payer, user.pubkey())], &[])
+            .await
+            .unwrap();
+    }
+    let bind = |user: &Keypair| bind_loan(&env.protocol, user.pubkey(), &env.collateral);
+    let binds: Vec<_> = users.iter().map(bind).collect();
+
+    // Two legacy loans declared, with one unit of debt short of the two owing
+    let before = make_legacy_protocol(&mut env).await;
+    let mut params = migrate_params(&before);
+    params.total_debt += 2 * debt - 1;
+    params.legacy_loans = 2;
+    let ix = migrate_protocol(&env.protocol, params);
+    env.process(&[ix], &[&admin]).await.unwrap();
+    let mut collateral = collateral_params(env.oracle.pubkey());
+    collateral.supply_cap = ONE_COLLATERAL;
+    env.configure_collateral(collateral.clone()).await.unwrap();
+
+    env.process(&[binds[0].clone()], &[&admin]).await.unwrap();
+    assert_error(
+        env.process(&[binds[1].clone()], &[&admin]).await,
+        CredXError::LegacyLoanNotCovered,
+    );
+    // The bound collateral counts against the supply cap
+    assert_error(
+        env.process(&[binds[2].clone()], &[&admin]).await,
+        CredXError::CollateralSupplyCapExceeded,
+    );
+    collateral.supply_cap = 4 * ONE_COLLATERAL;
+    env.configure_collateral(collateral).await.unwrap();
+    env.process(&[binds[2].clone()], &[&admin]).await.unwrap();
+    assert_error(
+        env.process(&[binds[3].clone()], &[&admin]).await,
+        CredXError::LegacyLoanNotCovered,
+    );
+
+    let state: ProtocolState = env.account(&env.protocol.protocol()).await;
+    assert_eq!(state.unbound_legacy_loans, 0);
+    assert_eq!(state.unbound_legacy_debt, before.total_debt + debt - 1);
+    let config: CollateralConfig = env.account(&env.collateral.config(&env.protocol)).await;
+    assert_eq!(config.total_collateral, 2 * ONE_COLLATERAL);
+}
+
+#[tokio::test]
+async fn migrate_simple_oracle_hands_updates_to_the_named_authority() {
+    let mut env = TestEnv::new().await;
+    let oracle = env.oracle.pubkey();
+    let user = env.new_user(ONE_COLLATERAL).await;
+    env.deposit(&user, ONE_COLLATERAL).await.unwrap();
+    make_legacy(&mut env, &oracle, ORACLE_ADDED).await;
+    assert_eq!(raw_account(&mut env, &oracle).await.data.len(), 8 + 8 + 8);
+
+    assert_error(env.borrow(&user).await, ErrorCode::AccountDidNotDeserialize);
+
+    // The original layout has no authority, so only the admin may assign one
+    let publisher = Keypair::new();
+    env.airdrop(&publisher.pubkey(), 1_000_000_000).await;
+    let ix = migrate_simple_oracle(
+        &env.protocol.with_admin(publisher.pubkey()),
+        env.collateral.mint,
+        oracle,
+        publisher.pubkey(),
+    );
+    assert_error(
+        env.process(&[ix], &[&publisher]).await,
+        CredXError::UnauthorizedAdmin,
+    );
+    // and only for an oracle one of its collaterals reads
+    let ix = initialize_protocol(publisher.pubkey(), spl_token::ID, protocol_params());
+    env.process(&[ix], &[&publisher]).await.unwrap();
+    let foreign = ProtocolContext::new(publisher.pubkey(), spl_token::ID);
+    let ix = configure_collateral(
+        &foreign,
+        env.collateral.mint,
+        collateral_params(Keypair::new().pubkey()),
+    );
+    env.process(&[ix], &[&publisher]).await.unwrap();
+    let ix = migrate_simple_oracle(&foreign, env.collateral.mint, oracle, publisher.pubkey());
+    assert_error(
+        env.process(&[ix], &[&publisher]).await,
+        CredXError::InvalidOracleAccount,
+    );
+
+    let admin = env.admin.insecure_clone();
+    let ix = migrate_simple_oracle(
+        &env.protocol,
+        env.collateral.mint,
+        oracle,
+        publisher.pubkey(),
+    );
+    env.process(&[ix], &[&admin]).await.unwrap();
+    assert_current_size(&mut env, &oracle, SimplePriceOracle::INIT_SPACE).await;
+    let state: SimplePriceOracle = env.account(&oracle).await;
+    assert_eq!(state.version, 1);
+    assert_eq!(state.price, INITIAL_PRICE);
+    assert_eq!(state.authority, publisher.pubkey());
+    let ix = migrate_simple_oracle(
+        &env.protocol,
+        env.collateral.mint,
+        oracle,
+        publisher.pubkey(),
+    );
+    assert_error(
+        env.process(&[ix], &[&admin]).await,
+        CredXError::AccountAlreadyMigrated,
+    );
+
+    let ix = update_simple_oracle(admin.pubkey(), oracle, INITIAL_PRICE, 0);
+    assert_error(
+        env.process(&[ix], &[&admin]).await,
+        CredXError::UnauthorizedOracleAuthority,
+    );
+    let ix = update_simple_oracle(publisher.pubkey(), oracle, INITIAL_PRICE, 0);
+    env.process(&[ix], &[&publisher]).await.unwrap();
+
+    env.borrow(&user).await.unwrap();
+}
+
+#[tokio::test]
+async fn newer_account_versions_are_rejected() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let protocol = env.protocol.protocol();
+    set_version(&mut env, &protocol, PROTOCOL_TAIL, 2).await;
+
+    let ix = set_debt_ceiling(&env.protocol, 1_000);
+    assert_error(
+        env.process(&[ix], &[&admin]).await,
+        CredXError::UnsupportedAccountVersion,
+    );
+    let state: ProtocolState = env.account(&protocol).await;
+    let ix = migrate_protocol(&env.protocol, migrate_params(&state));
+    assert_error(
+        env.process(&[ix], &[&admin]).await,
+        CredXError::UnsupportedAccountVersion,
+    );
+
+    let mut env = TestEnv::new().await;
+    let oracle = env.oracle.pubkey();
+    let user = env.new_user(ONE_COLLATERAL).await;
+    env.deposit(&user, ONE_COLLATERAL).await.unwrap();
+    set_version(&mut env, &oracle, ORACLE_TAIL, 2).await;
+    assert_error(
+        env.borrow(&user).await,
+        CredXError::UnsupportedAccountVersion,
+    );
+}
@@ -0,0 +1,222 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use common::*;
+use cred_x::{CredXError, LoanAccount, SimplePriceOracle};
+use cred_x_client::{create_simple_oracle, loan_pda, update_simple_oracle, CollateralContext};
+use pyth_sdk_solana::state::{AccountType, PriceStatus, SolanaPriceAccount, MAGIC, VERSION_2};
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+};
+
+/// Pyth publishes with eight decimals; the program normalizes to six.
+const PYTH_EXPO: i32 = -8;
+
+fn borrow_limit(collateral: u64, price: u64) -> u64 {
+    (collateral as u128 * price as u128 * 6_000 / 10_000) as u64
+}
+
+fn pyth_account(price: i64, status: PriceStatus, timestamp: i64) -> Account {
+    let mut feed: SolanaPriceAccount = bytemuck::Zeroable::zeroed();
+    feed.magic = MAGIC;
+    feed.ver = VERSION_2;
+    feed.atype = AccountType::Price as u32;
+    feed.expo = PYTH_EXPO;
+    feed.agg.price = price;
+    feed.agg.status = status;
+    feed.ema_price.val = price;
+    feed.timestamp = timestamp;
+    Account {
+        lamports: 1_000_000_000,
+        data: bytemuck::bytes_of(&feed).to_vec(),
+        owner: Pubkey::new_unique(),
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+/// Points the collateral at a Pyth feed at `address` and opens a loan
+/// with one unit of collateral deposited.
+async fn pyth_env(address: Pubkey, account: Account) -> (TestEnv, Keypair) {
+    let mut env = TestEnv::new().await;
+    env.ctx.set_account(&address, &account.into());
+    env.configure_collateral(collateral_params(address))
+        .await
+        .unwrap();
+    let user = env.new_user(ONE_COLLATERAL).await;
+    env.deposit(&user, ONE_COLLATERAL).await.unwrap();
+    (env, user)
+}
+
+/// Adds a second `SimplePriceOracle` as the collateral's secondary source.
+async fn with_secondary(env: &mut TestEnv, price: u64) -> Keypair {
+    let admin = env.admin.insecure_clone();
+    let secondary = Keypair::new();
+    env.process(
+        &[create_simple_oracle(
+            admin.pubkey(),
+            secondary.pubkey(),
+            price,
+            0,
+        )],
+        &[&admin, &secondary],
+    )
+    .await
+    .unwrap();
+
+    let mut params = collateral_params(env.oracle.pubkey());
+    params.oracle.secondary = secondary.pubkey();
+    params.oracle.max_deviation_bps = 500;
+    params.oracle.fallback_max_age_secs = 60;
+    params.oracle.fallback_conf_multiplier_bps = 20_000;
+    env.configure_collateral(params).await.unwrap();
+    secondary
+}
+
+#[tokio::test]
+async fn simple_oracle_is_created_and_updated_by_its_authority() {
+    let mut env = TestEnv::new().await;
+    let oracle: SimplePriceOracle = env.account(&env.oracle.pubkey()).await;
+    assert_eq!(oracle.price, INITIAL_PRICE);
+    assert_eq!(oracle.authority, env.admin.pubkey());
+
+    env.warp_seconds(10).await;
+    env.set_price(INITIAL_PRICE + 1, 5).await;
+    let now = env.now().await;
+    let oracle: SimplePriceOracle = env.account(&env.oracle.pubkey()).await;
+    assert_eq!(oracle.price, INITIAL_PRICE + 1);
+    assert_eq!(oracle.conf, 5);
+    assert_eq!(oracle.timestamp, now);
+}
+
+#[tokio::test]
+async fn simple_oracle_rejects_other_signers() {
+    let mut env = TestEnv::new().await;
+    let intruder = Keypair::new();
+    env.airdrop(&intruder.pubkey(), 1_000_000_000).await;
+
+    let ix = update_simple_oracle(intruder.pubkey(), env.oracle.pubkey(), 1, 0);
+    assert_error(
+        env.process(&[ix], &[&intruder]).await,
+        CredXError::UnauthorizedOracleAuthority,
+    );
+}
+
+#[tokio::test]
+async fn lend_reads_a_pyth_feed() {
+    let feed = Pubkey::new_unique();
+    let mut env = TestEnv::new().await;
+    let now = env.now().await;
+    env.ctx.set_account(
+        &feed,
+        &pyth_account(15_000_000_000, PriceStatus::Trading, now).into(),
+    );
+    env.configure_collateral(collateral_params(feed))
+        .await
+        .unwrap();
+    let user = env.borrower(ONE_COLLATERAL).await;
+
+    let loan: LoanAccount = env.account(&loan_pda(&user.pubkey()).0).await;
+    assert_eq!(
+        loan.remaining_debt,
+        borrow_limit(ONE_COLLATERAL, INITIAL_PRICE)
+    );
+}
+
+#[tokio::test]
+async fn lend_rejects_unusable_pyth_feeds() {
+    let feed = Pubkey::new_unique();
+    let (mut env, user) =
+        pyth_env(feed, pyth_account(15_000_000_000, PriceStatus::Halted, 0)).await;
+    assert_error(env.borrow(&user).await, CredXError::InvalidPriceStatus);
+
+    let mut garbage = pyth_account(15_000_000_000, PriceStatus::Trading, 0);
+    garbage.data[0] ^= 0xff;
+    env.ctx.set_account(&feed, &garbage.into());
+    assert_error(
+        env.borrow(&user).await,
+        CredXError::FailedToLoadPriceAccount,
+    );
+
+    let mut empty = pyth_account(0, PriceStatus::Trading, 0);
+    empty.data.clear();
+    env.ctx.set_account(&feed, &empty.into());
+    assert_error(env.borrow(&user).await, CredXError::EmptyOracleAccount);
+
+    let now = env.now().await;
+    env.ctx
+        .set_account(&feed, &pyth_account(-1, PriceStatus::Trading, now).into());
+    assert_error(env.borrow(&user).await, CredXError::InvalidPrice);
+
+    env.ctx.set_account(
+        &feed,
+        &pyth_account(15_000_000_000, PriceStatus::Trading, now - 300).into(),
+    );
+    assert_error(env.borrow(&user).await, CredXError::StalePrice);
+}
+
+#[tokio::test]
+async fn lend_requires_the_configured_secondary() {
+    let mut env = TestEnv::new().await;
+    with_secondary(&mut env, INITIAL_PRICE).await;
+    let user = env.new_user(ONE_COLLATERAL).await;
+    env.deposit(&user, ONE_COLLATERAL).await.unwrap();
+
+    let configured = env.collateral;
+    let mut oracle = configured.oracle;
+    oracle.secondary = Pubkey::default();
+    env.collateral = CollateralContext {
+        oracle,
+        ..configured
+    };
+    assert_error(env.borrow(&user).await, CredXError::MissingSecondaryOracle);
+
+    env.collateral = configured;
+    env.borrow(&user).await.unwrap();
+}
+
+#[tokio::test]
+async fn lend_rejects_diverging_sources() {
+    let mut env = TestEnv::new().await;
+    with_secondary(&mut env, INITIAL_PRICE * 110 / 100).await;
+    let user = env.new_user(ONE_COLLATERAL).await;
+    env.deposit(&user, ONE_COLLATERAL).await.unwrap();
+    assert_error(env.borrow(&user).await, CredXError::OracleDeviationTooLarge);
+}
+
+#[tokio::test]
+async fn lend_falls_back_to_the_fresh_source() {
+    let mut env = TestEnv::new().await;
+    let secondary = with_secondary(&mut env, INITIAL_PRICE).await;
+    let user = env.new_user(ONE_COLLATERAL).await;
+    env.deposit(&user, ONE_COLLATERAL).await.unwrap();
+
+    // The primary goes stale while the secondary keeps publishing.
+    env.warp_seconds(300).await;
+    let fallback_price = INITIAL_PRICE * 99 / 100;
+    let admin = env.admin.insecure_clone();
+    env.process(
+        &[update_simple_oracle(
+            admin.pubkey(),
+            secondary.pubkey(),
+            fallback_price,
+            0,
+        )],
+        &[&admin],
+    )
+    .await
+    .unwrap();
+    env.borrow(&user).await.unwrap();
+    let loan: LoanAccount = env.account(&loan_pda(&user.pubkey()).0).await;
+    assert_eq!(
+        loan.remaining_debt,
+        borrow_limit(ONE_COLLATERAL, fallback_price)
+    );
+
+    // The survivor is held to the shorter fallback window.
+    env.warp_seconds(61).await;
+    let user = env.new_user(ONE_COLLATERAL).await;
+    env.deposit(&user, ONE_COLLATERAL).await.unwrap();
+    assert_error(env.borrow(&user).await, CredXError::StalePrice);
+}
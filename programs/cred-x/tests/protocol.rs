@@ -0,0 +1,439 @@
+mod common;
+
+use anchor_lang::prelude::Pubkey;
+use anchor_spl::{
+    associated_token::spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+    token::spl_token,
+    token_2022::spl_token_2022::{
+        self,
+        extension::{transfer_hook, BaseStateWithExtensions, ExtensionType, StateWithExtensions},
+        state::Mint,
+    },
+    token_interface::spl_token_metadata_interface::state::TokenMetadata,
+};
+use common::*;
+use cred_x::{CredXError, CreditMetadata, CreditTransferMode, ProtocolState, ProtocolStats};
+use cred_x_client::{
+    associated_token_address, credit_mint_pda, cron_repayment, initialize_protocol,
+    initialize_protocol_with_mint, program_authority_pda, set_credit_allowlist,
+    set_credit_transfer_mode, set_debt_ceiling, set_protocol_locked, update_credit_metadata,
+    withdraw_collateral, ProtocolContext,
+};
+use solana_sdk::{
+    instruction::Instruction,
+    signature::{Keypair, Signer},
+};
+use solana_system_interface::instruction as system_instruction;
+
+type Tweak = Box<dyn Fn(&mut cred_x::InitializeProtocolParams)>;
+
+fn metadata(name: &str) -> CreditMetadata {
+    CreditMetadata {
+        name: name.to_string(),
+        symbol: "CRED".to_string(),
+        uri: "https://example.com/cred.json".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn initialize_protocol_creates_state_and_credit_mint() {
+    let mut env = TestEnv::new().await;
+
+    let state: ProtocolState = env.account(&env.protocol.protocol()).await;
+    assert_eq!(state.admin, env.admin.pubkey());
+    assert_eq!(state.credit_mint, credit_mint_pda(&env.admin.pubkey()).0);
+    assert_eq!(state.ltv_ratio_bps, 6_000);
+    assert_eq!(state.liquidation_threshold_bps, 8_000);
+    assert_eq!(state.max_price_age_secs, 300);
+    assert_eq!(state.total_debt, 0);
+    assert_eq!(state.stats, ProtocolStats::default());
+
+    let credit_mint = env.protocol.credit_mint;
+    assert_eq!(env.mint_supply(&credit_mint).await, 0);
+    let protocol_credit_ata = env.protocol.protocol_credit_ata();
+    assert!(env.account_exists(&protocol_credit_ata).await);
+}
+
+#[tokio::test]
+async fn initialize_protocol_rejects_invalid_params() {
+    let mut env = TestEnv::new().await;
+
+    let cases: Vec<(Tweak, Pubkey, CredXError)> = vec![
+        (
+            Box::new(|p| p.ltv_ratio_bps = 0),
+            spl_token::ID,
+            CredXError::InvalidLtvRatio,
+        ),
+        (
+            Box::new(|p| p.ltv_ratio_bps = 9_500),
+            spl_token::ID,
+            CredXError::InvalidLtvRatio,
+        ),
+        (
+            Box::new(|p| p.liquidation_threshold_bps = p.ltv_ratio_bps),
+            spl_token::ID,
+            CredXError::InvalidLiquidationThreshold,
+        ),
+        (
+            Box::new(|p| p.credit_decimals = 10),
+            spl_token::ID,
+            CredXError::InvalidCreditDecimals,
+        ),
+        (
+            Box::new(|p| p.max_price_age_secs = 0),
+            spl_token::ID,
+            CredXError::InvalidStalenessWindow,
+        ),
+        (
+            Box::new(|p| p.borrow_fee_bps = 1_001),
+            spl_token::ID,
+            CredXError::InvalidFee,
+        ),
+        (
+            Box::new(|p| p.credit_metadata = Some(metadata(&"x".repeat(33)))),
+            spl_token_2022::ID,
+            CredXError::InvalidCreditMetadata,
+        ),
+        (
+            Box::new(|p| p.credit_transfer_mode = CreditTransferMode::DebtFloor),
+            spl_token::ID,
+            CredXError::TransferHookRequiresToken2022,
+        ),
+        (
+            Box::new(|p| p.credit_metadata = Some(metadata("Cred"))),
+            spl_token::ID,
+            CredXError::CreditMetadataNotSupported,
+        ),
+    ];
+
+    for (tweak, token_program, expected) in cases {
+        let admin = Keypair::new();
+        env.airdrop(&admin.pubkey(), 10_000_000_000).await;
+        let mut params = protocol_params();
+        tweak(&mut params);
+        let result = env
+            .process(
+                &[initialize_protocol(admin.pubkey(), token_program, params)],
+                &[&admin],
+            )
+            .await;
+        assert_error(result, expected);
+    }
+}
+
+#[tokio::test]
+async fn initialize_protocol_with_token_2022_metadata_and_update_it() {
+    let mut params = protocol_params();
+    params.credit_metadata = Some(metadata("Cred"));
+    let mut env = TestEnv::with_params(params, spl_token_2022::ID).await;
+
+    let admin = env.admin.insecure_clone();
+    let ix = update_credit_metadata(&env.protocol, metadata("Cred Dollar"));
+    env.process(&[ix], &[&admin]).await.unwrap();
+
+    let mint = env
+        .ctx
+        .banks_client
+        .get_account(env.protocol.credit_mint)
+        .await
+        .unwrap()
+        .unwrap();
+    let mint = StateWithExtensions::<Mint>::unpack(&mint.data).unwrap();
+    let stored = mint.get_variable_len_extension::<TokenMetadata>().unwrap();
+    assert_eq!(stored.name, "Cred Dollar");
+    assert_eq!(stored.symbol, "CRED");
+    assert_eq!(
+        mint.base.mint_authority,
+        Some(program_authority_pda().0).into()
+    );
+}
+
+#[tokio::test]
+async fn update_credit_metadata_requires_token_2022() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let ix = update_credit_metadata(&env.protocol, metadata("Cred"));
+    assert_error(
+        env.process(&[ix], &[&admin]).await,
+        CredXError::CreditMetadataNotSupported,
+    );
+}
+
+#[tokio::test]
+async fn initialize_protocol_with_mint_adopts_existing_mint() {
+    let mut env = TestEnv::new().await;
+    let admin = Keypair::new();
+    let mint_authority = Keypair::new();
+    env.airdrop(&admin.pubkey(), 10_000_000_000).await;
+    let credit_mint = env
+        .create_mint(&spl_token::ID, 6, &mint_authority.pubkey())
+        .await;
+    let protocol = ProtocolContext {
+        admin: admin.pubkey(),
+        original_admin: admin.pubkey(),
+        credit_mint,
+        credit_token_program: spl_token::ID,
+    };
+
+    let mut wrong_decimals = protocol_params();
+    wrong_decimals.credit_decimals = 9;
+    let ix = initialize_protocol_with_mint(&protocol, mint_authority.pubkey(), wrong_decimals);
+    assert_error(
+        env.process(&[ix], &[&admin, &mint_authority]).await,
+        CredXError::InvalidCreditDecimals,
+    );
+
+    let mut hooked = protocol_params();
+    hooked.credit_transfer_mode = CreditTransferMode::Allowlist;
+    let ix = initialize_protocol_with_mint(&protocol, mint_authority.pubkey(), hooked);
+    assert_error(
+        env.process(&[ix], &[&admin, &mint_authority]).await,
+        CredXError::TransferHookNotEnabled,
+    );
+
+    let ix = initialize_protocol_with_mint(&protocol, mint_authority.pubkey(), protocol_params());
+    env.process(&[ix], &[&admin, &mint_authority])
+        .await
+        .unwrap();
+
+    let state: ProtocolState = env.account(&protocol.protocol()).await;
+    assert_eq!(state.credit_mint, credit_mint);
+    let mint = env
+        .ctx
+        .banks_client
+        .get_account(credit_mint)
+        .await
+        .unwrap()
+        .unwrap();
+    let mint = StateWithExtensions::<Mint>::unpack(&mint.data).unwrap();
+    assert_eq!(
+        mint.base.mint_authority,
+        Some(program_authority_pda().0).into()
+    );
+}
+
+/// Token-2022 mint with `extensions`, set up by `init` before the mint
+/// itself is initialized.
+async fn create_token_2022_mint(
+    env: &mut TestEnv,
+    authority: &Pubkey,
+    freeze_authority: Option<&Pubkey>,
+    extensions: &[ExtensionType],
+    init: impl Fn(&Pubkey) -> Vec<Instruction>,
+) -> Pubkey {
+    let mint = Keypair::new();
+    let space = ExtensionType::try_calculate_account_len::<Mint>(extensions).unwrap();
+    let rent = env.ctx.banks_client.get_rent().await.unwrap();
+    let payer = env.ctx.payer.pubkey();
+    let mut ixs = vec![system_instruction::create_account(
+        &payer,
+        &mint.pubkey(),
+        rent.minimum_balance(space),
+        space as u64,
+        &spl_token_2022::ID,
+    )];
+    ixs.extend(init(&mint.pubkey()));
+    ixs.push(
+        spl_token_2022::instruction::initialize_mint2(
+            &spl_token_2022::ID,
+            &mint.pubkey(),
+            authority,
+            freeze_authority,
+            6,
+        )
+        .unwrap(),
+    );
+    env.process(&ixs, &[&mint]).await.unwrap();
+    mint.pubkey()
+}
+
+#[tokio::test]
+async fn initialize_protocol_with_mint_refuses_mints_it_would_not_control() {
+    let mut env = TestEnv::new().await;
+    let admin = Keypair::new();
+    let mint_authority = Keypair::new();
+    env.airdrop(&admin.pubkey(), 10_000_000_000).await;
+    let authority = mint_authority.pubkey();
+    let hooked = |program: Pubkey, hook_authority: Pubkey| {
+        move |mint: &Pubkey| {
+            vec![transfer_hook::instruction::initialize(
+                &spl_token_2022::ID,
+                mint,
+                Some(hook_authority),
+                Some(program),
+            )
+            .unwrap()]
+        }
+    };
+
+    let frozen =
+        create_token_2022_mint(&mut env, &authority, Some(&authority), &[], |_| vec![]).await;
+    let with_supply = create_token_2022_mint(&mut env, &authority, None, &[], |_| vec![]).await;
+    let holder = env.ctx.payer.pubkey();
+    let ata = associated_token_address(&holder, &with_supply, &spl_token_2022::ID);
+    let ixs = [
+        create_associated_token_account_idempotent(
+            &holder,
+            &holder,
+            &with_supply,
+            &spl_token_2022::ID,
+        ),
+        spl_token_2022::instruction::mint_to(
+            &spl_token_2022::ID,
+            &with_supply,
+            &ata,
+            &authority,
+            &[],
+            1,
+        )
+        .unwrap(),
+    ];
+    env.process(&ixs, &[&mint_authority]).await.unwrap();
+    let with_fee = env.create_fee_mint(6, 100, &authority).await;
+    let foreign_hook = create_token_2022_mint(
+        &mut env,
+        &authority,
+        None,
+        &[ExtensionType::TransferHook],
+        hooked(Keypair::new().pubkey(), program_authority_pda().0),
+    )
+    .await;
+    let repointable_hook = create_token_2022_mint(
+        &mut env,
+        &authority,
+        None,
+        &[ExtensionType::TransferHook],
+        hooked(cred_x::ID, authority),
+    )
+    .await;
+
+    for credit_mint in [
+        frozen,
+        with_supply,
+        with_fee,
+        foreign_hook,
+        repointable_hook,
+    ] {
+        let protocol = ProtocolContext {
+            admin: admin.pubkey(),
+            original_admin: admin.pubkey(),
+            credit_mint,
+            credit_token_program: spl_token_2022::ID,
+        };
+        let ix = initialize_protocol_with_mint(&protocol, authority, protocol_params());
+        assert_error(
+            env.process(&[ix], &[&admin, &mint_authority]).await,
+            CredXError::CreditMintNotAdoptable,
+        );
+    }
+
+    // A hook into this program that only the protocol can repoint is kept
+    let credit_mint = create_token_2022_mint(
+        &mut env,
+        &authority,
+        None,
+        &[ExtensionType::TransferHook],
+        hooked(cred_x::ID, program_authority_pda().0),
+    )
+    .await;
+    let protocol = ProtocolContext {
+        admin: admin.pubkey(),
+        original_admin: admin.pubkey(),
+        credit_mint,
+        credit_token_program: spl_token_2022::ID,
+    };
+    let mut params = protocol_params();
+    params.credit_transfer_mode = CreditTransferMode::Allowlist;
+    let ix = initialize_protocol_with_mint(&protocol, authority, params);
+    env.process(&[ix], &[&admin, &mint_authority])
+        .await
+        .unwrap();
+    let state: ProtocolState = env.account(&protocol.protocol()).await;
+    assert_eq!(state.credit_mint, credit_mint);
+}
+
+#[tokio::test]
+async fn set_debt_ceiling_is_admin_only() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+
+    env.process(&[set_debt_ceiling(&env.protocol, 1_000)], &[&admin])
+        .await
+        .unwrap();
+    let state: ProtocolState = env.account(&env.protocol.protocol()).await;
+    assert_eq!(state.debt_ceiling, 1_000);
+
+    let intruder = Keypair::new();
+    env.airdrop(&intruder.pubkey(), 1_000_000_000).await;
+    let ix = replace_account(
+        set_debt_ceiling(&env.protocol, u64::MAX),
+        &admin.pubkey(),
+        &intruder.pubkey(),
+    );
+    assert_error(
+        env.process(&[ix], &[&intruder]).await,
+        CredXError::UnauthorizedAdmin,
+    );
+}
+
+#[tokio::test]
+async fn locking_the_protocol_pauses_user_instructions() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let user = env.borrower(ONE_COLLATERAL).await;
+    env.accrue_yield(&user.pubkey(), 1_000).await;
+
+    env.process(&[set_protocol_locked(&env.protocol, true)], &[&admin])
+        .await
+        .unwrap();
+    let state: ProtocolState = env.account(&env.protocol.protocol()).await;
+    assert!(state.is_locked);
+
+    assert_error(env.deposit(&user, 1).await, CredXError::ProtocolLocked);
+    assert_error(env.borrow(&user).await, CredXError::ProtocolLocked);
+    let ix = cron_repayment(&env.protocol, user.pubkey(), &env.collateral);
+    assert_error(env.process(&[ix], &[]).await, CredXError::ProtocolLocked);
+    let ix = withdraw_collateral(&env.protocol, user.pubkey(), &env.collateral);
+    assert_error(
+        env.process(&[ix], &[&user]).await,
+        CredXError::ProtocolLocked,
+    );
+
+    let intruder = Keypair::new();
+    env.airdrop(&intruder.pubkey(), 1_000_000_000).await;
+    let ix = replace_account(
+        set_protocol_locked(&env.protocol, false),
+        &admin.pubkey(),
+        &intruder.pubkey(),
+    );
+    assert_error(
+        env.process(&[ix], &[&intruder]).await,
+        CredXError::UnauthorizedAdmin,
+    );
+
+    env.process(&[set_protocol_locked(&env.protocol, false)], &[&admin])
+        .await
+        .unwrap();
+    let ix = cron_repayment(&env.protocol, user.pubkey(), &env.collateral);
+    env.process(&[ix], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn transfer_restrictions_need_a_hooked_mint() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+
+    let ix = set_credit_transfer_mode(&env.protocol, CreditTransferMode::DebtFloor);
+    assert_error(
+        env.process(&[ix], &[&admin]).await,
+        CredXError::TransferHookNotEnabled,
+    );
+
+    // Unrestricted is always allowed, and allow-list entries can be kept
+    // ahead of switching modes.
+    let ix = set_credit_transfer_mode(&env.protocol, CreditTransferMode::Unrestricted);
+    env.process(&[ix], &[&admin]).await.unwrap();
+    let owner = Pubkey::new_unique();
+    let ix = set_credit_allowlist(&env.protocol, owner, true);
+    env.process(&[ix], &[&admin]).await.unwrap();
+}
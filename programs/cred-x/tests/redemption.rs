@@ -0,0 +1,260 @@
+mod common;
+
+use common::*;
+use cred_x::{CollateralConfig, CredXError, LoanAccount, ProtocolState};
+use cred_x_client::{
+    loan_pda, redeem_credit, redemption_order, repay_debt, set_redemption_fee, withdraw_collateral,
+};
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+/// Debt of a full borrow against one unit of collateral at the initial price.
+const MAX_BORROW: u64 = 90_000_000_000_000_000;
+
+struct RedemptionEnv {
+    env: TestEnv,
+    // Borrowed the maximum against one unit, 60% LTV
+    risky: Keypair,
+    // Borrowed against one unit, then deposited a second, 30% LTV
+    safe: Keypair,
+    redeemer: Keypair,
+}
+
+async fn redemption_env(redemption_fee_bps: u16) -> RedemptionEnv {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.insecure_clone();
+    let ix = set_redemption_fee(&env.protocol, redemption_fee_bps);
+    env.process(&[ix], &[&admin]).await.unwrap();
+
+    let risky = env.borrower(ONE_COLLATERAL).await;
+    let safe = env.borrower(ONE_COLLATERAL).await;
+    let (mint, token_program) = (env.collateral.mint, env.collateral.token_program);
+    env.mint_to_owner(&mint, &token_program, &safe.pubkey(), ONE_COLLATERAL)
+        .await;
+    env.deposit(&safe, ONE_COLLATERAL).await.unwrap();
+    let redeemer = env.borrower(4 * ONE_COLLATERAL).await;
+
+    RedemptionEnv {
+        env,
+        risky,
+        safe,
+        redeemer,
+    }
+}
+
+impl RedemptionEnv {
+    fn redeem(&self, borrowers: &[Pubkey], amount: u64, min_collateral_out: u64) -> Instruction {
+        redeem_credit(
+            &self.env.protocol,
+            self.redeemer.pubkey(),
+            &self.env.collateral,
+            borrowers,
+            amount,
+            min_collateral_out,
+        )
+    }
+
+    async fn send(
+        &mut self,
+        ix: Instruction,
+    ) -> std::result::Result<(), solana_program_test::BanksClientError> {
+        let redeemer = self.redeemer.insecure_clone();
+        self.env.process(&[ix], &[&redeemer]).await
+    }
+
+    async fn loan(&mut self, user: &Keypair) -> LoanAccount {
+        self.env.account(&loan_pda(&user.pubkey()).0).await
+    }
+}
+
+#[tokio::test]
+async fn redeem_credit_takes_collateral_from_riskiest_loans_first() {
+    let mut s = redemption_env(50).await;
+    let (risky, safe) = (s.risky.pubkey(), s.safe.pubkey());
+    let loans = vec![
+        (safe, s.loan(&s.safe.insecure_clone()).await),
+        (risky, s.loan(&s.risky.insecure_clone()).await),
+    ];
+    let order = redemption_order(&loans, INITIAL_PRICE);
+    assert_eq!(order, vec![risky, safe]);
+
+    // Clears the risky loan and a third of the safe one
+    let amount = MAX_BORROW + MAX_BORROW / 3;
+    let ix = s.redeem(&order, amount, 0);
+    s.send(ix).await.unwrap();
+
+    // 0.5% of the value stays behind with each borrower
+    let risky_paid = MAX_BORROW / 10_000 * 9_950 / INITIAL_PRICE;
+    let safe_paid = MAX_BORROW / 3 / 10_000 * 9_950 / INITIAL_PRICE;
+    let loan = s.loan(&s.risky.insecure_clone()).await;
+    assert_eq!(loan.remaining_debt, 0);
+    assert_eq!(loan.collateral_amount, ONE_COLLATERAL - risky_paid);
+    let loan = s.loan(&s.safe.insecure_clone()).await;
+    assert_eq!(loan.remaining_debt, MAX_BORROW - MAX_BORROW / 3);
+    assert_eq!(loan.collateral_amount, 2 * ONE_COLLATERAL - safe_paid);
+
+    let risky_vault = s.env.collateral.vault_ata(&risky);
+    assert_eq!(
+        s.env.token_balance(&risky_vault).await,
+        ONE_COLLATERAL - risky_paid
+    );
+    let redeemer = s.redeemer.pubkey();
+    let redeemer_collateral = s.env.collateral.user_ata(&redeemer);
+    assert_eq!(
+        s.env.token_balance(&redeemer_collateral).await,
+        risky_paid + safe_paid
+    );
+    let redeemer_credit = s.env.protocol.credit_ata(&redeemer);
+    assert_eq!(
+        s.env.token_balance(&redeemer_credit).await,
+        4 * MAX_BORROW - amount
+    );
+
+    let state: ProtocolState = s.env.account(&s.env.protocol.protocol()).await;
+    assert_eq!(state.total_redeemed, amount);
+    assert_eq!(state.total_debt, 6 * MAX_BORROW - amount);
+    assert_eq!(state.stats.open_loans, 2);
+    let config: CollateralConfig = s
+        .env
+        .account(&s.env.collateral.config(&s.env.protocol))
+        .await;
+    assert_eq!(
+        config.total_collateral,
+        7 * ONE_COLLATERAL - risky_paid - safe_paid
+    );
+}
+
+#[tokio::test]
+async fn redeem_credit_enforces_order_and_bounds() {
+    let mut s = redemption_env(0).await;
+    let (risky, safe) = (s.risky.pubkey(), s.safe.pubkey());
+    let admin = s.env.admin.insecure_clone();
+
+    assert_error(
+        s.env
+            .process(&[set_redemption_fee(&s.env.protocol, 1_001)], &[&admin])
+            .await,
+        CredXError::InvalidFee,
+    );
+
+    let cases: Vec<(Instruction, u32)> = vec![
+        (
+            s.redeem(&[], MAX_BORROW, 0),
+            CredXError::InvalidRedemptionLoans.into(),
+        ),
+        (
+            s.redeem(&[safe, risky], MAX_BORROW * 2, 0),
+            CredXError::InvalidRedemptionLoans.into(),
+        ),
+        // Leaving out the riskiest loan
+        (
+            s.redeem(&[safe], MAX_BORROW / 2, 0),
+            CredXError::InvalidRedemptionLoans.into(),
+        ),
+        (
+            s.redeem(&[risky], MAX_BORROW, ONE_COLLATERAL),
+            CredXError::SlippageExceeded.into(),
+        ),
+        (
+            s.redeem(&[risky], 5 * MAX_BORROW, 0),
+            CredXError::InsufficientCreditTokens.into(),
+        ),
+    ];
+    for (ix, expected) in cases {
+        assert_error(s.send(ix).await, expected);
+    }
+
+    // Below 90 the risky loan's collateral no longer covers its debt, so it
+    // can be skipped
+    s.env.set_price(INITIAL_PRICE / 2, 0).await;
+    assert_error(
+        s.send(s.redeem(&[risky, safe], MAX_BORROW, 0)).await,
+        CredXError::UnderwaterLoan,
+    );
+    let loans = vec![
+        (risky, s.loan(&s.risky.insecure_clone()).await),
+        (safe, s.loan(&s.safe.insecure_clone()).await),
+    ];
+    assert_eq!(redemption_order(&loans, INITIAL_PRICE / 2), vec![safe]);
+
+    // Without a fee the full value is paid out
+    s.send(s.redeem(&[safe], MAX_BORROW / 2, 0)).await.unwrap();
+    let loan = s.loan(&s.safe.insecure_clone()).await;
+    assert_eq!(
+        loan.collateral_amount,
+        2 * ONE_COLLATERAL - MAX_BORROW / 2 / (INITIAL_PRICE / 2)
+    );
+    assert_eq!(loan.remaining_debt, MAX_BORROW / 2);
+}
+
+#[tokio::test]
+async fn redeem_credit_pays_out_at_the_upper_price_bound() {
+    let mut s = redemption_env(0).await;
+    let risky = s.risky.pubkey();
+    let mut params = collateral_params(s.env.oracle.pubkey());
+    params.pricing.conf_multiplier_bps = 10_000;
+    s.env.configure_collateral(params).await.unwrap();
+    // A 1% confidence interval around the initial price
+    let conf = INITIAL_PRICE / 100;
+    s.env.set_price(INITIAL_PRICE, conf).await;
+
+    s.send(s.redeem(&[risky], MAX_BORROW / 2, 0)).await.unwrap();
+    let paid = MAX_BORROW / 2 / (INITIAL_PRICE + conf);
+    let loan = s.loan(&s.risky.insecure_clone()).await;
+    assert_eq!(loan.collateral_amount, ONE_COLLATERAL - paid);
+    let redeemer_collateral = s.env.collateral.user_ata(&s.redeemer.pubkey());
+    assert_eq!(s.env.token_balance(&redeemer_collateral).await, paid);
+}
+
+#[tokio::test]
+async fn redemption_mark_follows_the_riskiest_loan() {
+    let mut s = redemption_env(0).await;
+    let (risky, safe) = (s.risky.pubkey(), s.safe.pubkey());
+    let config_address = s.env.collateral.config(&s.env.protocol);
+    let config: CollateralConfig = s.env.account(&config_address).await;
+    assert_eq!(config.redemption_mark.loan, loan_pda(&risky).0);
+    assert_eq!(config.redemption_mark.debt, MAX_BORROW);
+    assert_eq!(config.redemption_mark.collateral, ONE_COLLATERAL);
+
+    // Repaying the riskiest loan takes the mark down with it
+    let ix = repay_debt(
+        &s.env.protocol,
+        risky,
+        risky,
+        &s.env.collateral,
+        MAX_BORROW / 2,
+    );
+    let borrower = s.risky.insecure_clone();
+    s.env.process(&[ix], &[&borrower]).await.unwrap();
+    let config: CollateralConfig = s.env.account(&config_address).await;
+    assert_eq!(config.redemption_mark.debt, MAX_BORROW / 2);
+
+    s.send(s.redeem(&[safe], MAX_BORROW / 2, 0)).await.unwrap();
+    let loan = s.loan(&s.safe.insecure_clone()).await;
+    assert_eq!(loan.remaining_debt, MAX_BORROW / 2);
+}
+
+#[tokio::test]
+async fn fully_redeemed_borrowers_withdraw_the_surplus() {
+    let mut s = redemption_env(50).await;
+    let risky = s.risky.pubkey();
+    s.send(s.redeem(&[risky], MAX_BORROW, 0)).await.unwrap();
+    let loan = s.loan(&s.risky.insecure_clone()).await;
+    assert_eq!(loan.remaining_debt, 0);
+    let surplus = loan.collateral_amount;
+    assert!(surplus > 0);
+
+    let ix = withdraw_collateral(&s.env.protocol, risky, &s.env.collateral);
+    let borrower = s.risky.insecure_clone();
+    s.env.process(&[ix], &[&borrower]).await.unwrap();
+    let risky_collateral = s.env.collateral.user_ata(&risky);
+    assert_eq!(s.env.token_balance(&risky_collateral).await, surplus);
+    // The borrower keeps the credit they borrowed
+    let risky_credit = s.env.protocol.credit_ata(&risky);
+    assert_eq!(s.env.token_balance(&risky_credit).await, MAX_BORROW);
+    let state: ProtocolState = s.env.account(&s.env.protocol.protocol()).await;
+    assert_eq!(state.stats.open_loans, 2);
+}
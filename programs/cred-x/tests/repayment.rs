@@ -0,0 +1,412 @@
+mod common;
+
+use anchor_lang::{error::ErrorCode, AccountSerialize};
+use anchor_spl::{
+    associated_token::spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+    token::spl_token,
+};
+use common::*;
+use cred_x::{CollateralConfig, CredXError, LoanAccount, ProtocolState};
+use cred_x_client::{
+    collateral_vault_pda, configure_collateral, cron_repayment, initialize_protocol, loan_pda,
+    program_authority_pda, repay_debt, withdraw_collateral, ProtocolContext,
+};
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signature::Signer};
+
+fn debt_for(collateral: u64) -> u64 {
+    (collateral as u128 * INITIAL_PRICE as u128 * 6_000 / 10_000) as u64
+}
+
+async fn crank(
+    env: &mut TestEnv,
+    user: &Pubkey,
+) -> Result<(), solana_program_test::BanksClientError> {
+    let ix = cron_repayment(&env.protocol, *user, &env.collateral);
+    env.process(&[ix], &[]).await
+}
+
+async fn withdraw(
+    env: &mut TestEnv,
+    user: &Keypair,
+) -> Result<(), solana_program_test::BanksClientError> {
+    let ix = withdraw_collateral(&env.protocol, user.pubkey(), &env.collateral);
+    env.process(&[ix], &[user]).await
+}
+
+#[tokio::test]
+async fn cron_repays_debt_from_yield() {
+    let mut env = TestEnv::new().await;
+    let user = env.borrower(ONE_COLLATERAL).await;
+    let debt = debt_for(ONE_COLLATERAL);
+    let yield_amount = ONE_COLLATERAL / 1_000;
+    let repaid = yield_amount * INITIAL_PRICE;
+
+    env.accrue_yield(&user.pubkey(), yield_amount).await;
+    crank(&mut env, &user.pubkey()).await.unwrap();
+
+    let loan: LoanAccount = env.account(&loan_pda(&user.pubkey()).0).await;
+    assert_eq!(loan.remaining_debt, debt - repaid);
+    assert_eq!(loan.yield_earned, yield_amount);
+    let credit_ata = env.protocol.credit_ata(&user.pubkey());
+    assert_eq!(env.token_balance(&credit_ata).await, debt - repaid);
+
+    let state: ProtocolState = env.account(&env.protocol.protocol()).await;
+    assert_eq!(state.total_debt, debt - repaid);
+    assert_eq!(state.stats.total_repaid_via_yield, repaid);
+    assert_eq!(state.stats.total_yield_harvested, yield_amount);
+    assert_eq!(state.stats.open_loans, 1);
+
+    // The yield now counts as collateral and repays nothing more
+    assert_eq!(loan.collateral_amount, ONE_COLLATERAL + yield_amount);
+    let config: CollateralConfig = env.account(&env.collateral.config(&env.protocol)).await;
+    assert_eq!(config.total_collateral, ONE_COLLATERAL + yield_amount);
+    crank(&mut env, &user.pubkey()).await.unwrap();
+    let loan: LoanAccount = env.account(&loan_pda(&user.pubkey()).0).await;
+    assert_eq!(loan.remaining_debt, debt - repaid);
+    assert_eq!(env.token_balance(&credit_ata).await, debt - repaid);
+}
+
+#[tokio::test]
+async fn cron_without_yield_is_a_no_op() {
+    let mut env = TestEnv::new().await;
+    let user = env.borrower(ONE_COLLATERAL).await;
+
+    crank(&mut env, &user.pubkey()).await.unwrap();
+
+    let loan: LoanAccount = env.account(&loan_pda(&user.pubkey()).0).await;
+    assert_eq!(loan.remaining_debt, debt_for(ONE_COLLATERAL));
+    assert_eq!(loan.yield_earned, 0);
+}
+
+#[tokio::test]
+async fn cron_caps_repayment_at_the_debt() {
+    let mut env = TestEnv::new().await;
+    let user = env.borrower(ONE_COLLATERAL).await;
+
+    env.accrue_yield(&user.pubkey(), ONE_COLLATERAL).await;
+    crank(&mut env, &user.pubkey()).await.unwrap();
+
+    let loan: LoanAccount = env.account(&loan_pda(&user.pubkey()).0).await;
+    assert_eq!(loan.remaining_debt, 0);
+    let credit_ata = env.protocol.credit_ata(&user.pubkey());
+    assert_eq!(env.token_balance(&credit_ata).await, 0);
+    let state: ProtocolState = env.account(&env.protocol.protocol()).await;
+    assert_eq!(state.total_debt, 0);
+    assert_eq!(state.stats.total_repaid_via_yield, debt_for(ONE_COLLATERAL));
+    assert_eq!(state.stats.open_loans, 0);
+    // Only the yield worth the debt was used to repay it
+    let used = debt_for(ONE_COLLATERAL) / INITIAL_PRICE;
+    assert_eq!(loan.yield_earned, used);
+    assert_eq!(state.stats.total_yield_harvested, used);
+
+    assert_error(
+        crank(&mut env, &user.pubkey()).await,
+        CredXError::NoOutstandingDebt,
+    );
+}
+
+#[tokio::test]
+async fn cron_needs_credit_to_burn() {
+    let mut env = TestEnv::new().await;
+    let user = env.borrower(ONE_COLLATERAL).await;
+    let debt = debt_for(ONE_COLLATERAL);
+    let elsewhere = Pubkey::new_unique();
+    env.accrue_yield(&user.pubkey(), ONE_COLLATERAL / 1_000)
+        .await;
+
+    env.transfer_credit(&user, &elsewhere, debt - 1).await;
+    assert_error(
+        crank(&mut env, &user.pubkey()).await,
+        CredXError::InsufficientCreditTokens,
+    );
+
+    env.transfer_credit(&user, &elsewhere, 1).await;
+    assert_error(
+        crank(&mut env, &user.pubkey()).await,
+        CredXError::NoTokensToBurn,
+    );
+}
+
+#[tokio::test]
+async fn cron_keeps_repaying_while_the_breaker_is_tripped() {
+    let mut env = TestEnv::new().await;
+    let mut params = collateral_params(env.oracle.pubkey());
+    params.max_price_move_bps = 1_000;
+    env.configure_collateral(params).await.unwrap();
+    let user = env.borrower(ONE_COLLATERAL).await;
+    let yield_amount = ONE_COLLATERAL / 1_000;
+
+    env.set_price(INITIAL_PRICE / 2, 0).await;
+    env.accrue_yield(&user.pubkey(), yield_amount).await;
+    crank(&mut env, &user.pubkey()).await.unwrap();
+
+    let config: CollateralConfig = env.account(&env.collateral.config(&env.protocol)).await;
+    assert!(config.circuit_breaker.tripped);
+    let loan: LoanAccount = env.account(&loan_pda(&user.pubkey()).0).await;
+    assert_eq!(
+        loan.remaining_debt,
+        debt_for(ONE_COLLATERAL) - yield_amount * (INITIAL_PRICE / 2)
+    );
+    assert_error(
+        withdraw(&mut env, &user).await,
+        CredXError::CircuitBreakerTripped,
+    );
+}
+
+#[tokio::test]
+async fn withdraw_repays_and_returns_collateral_with_yield() {
+    let mut env = TestEnv::new().await;
+    let user = env.borrower(ONE_COLLATERAL).await;
+    let debt = debt_for(ONE_COLLATERAL);
+    let yield_amount = 1_000;
+    env.accrue_yield(&user.pubkey(), yield_amount).await;
+
+    withdraw(&mut env, &user).await.unwrap();
+
+    let user_ata = env.collateral.user_ata(&user.pubkey());
+    assert_eq!(
+        env.token_balance(&user_ata).await,
+        ONE_COLLATERAL + yield_amount
+    );
+    let vault_ata = env.collateral.vault_ata(&user.pubkey());
+    assert!(!env.account_exists(&vault_ata).await);
+    let credit_ata = env.protocol.credit_ata(&user.pubkey());
+    assert_eq!(env.token_balance(&credit_ata).await, 0);
+
+    let loan: LoanAccount = env.account(&loan_pda(&user.pubkey()).0).await;
+    assert_eq!(loan.collateral_amount, 0);
+    assert_eq!(loan.remaining_debt, 0);
+    assert_eq!(loan.yield_earned, yield_amount);
+
+    let state: ProtocolState = env.account(&env.protocol.protocol()).await;
+    assert_eq!(state.total_debt, 0);
+    assert_eq!(state.stats.total_repaid_via_user, debt);
+    assert_eq!(state.stats.total_yield_harvested, yield_amount);
+    assert_eq!(state.stats.open_loans, 0);
+    let config: CollateralConfig = env.account(&env.collateral.config(&env.protocol)).await;
+    assert_eq!(config.total_collateral, 0);
+}
+
+#[tokio::test]
+async fn withdraw_returns_collateral_that_was_never_borrowed_against() {
+    let mut env = TestEnv::new().await;
+    let user = env.new_user(ONE_COLLATERAL).await;
+    env.deposit(&user, ONE_COLLATERAL).await.unwrap();
+    withdraw(&mut env, &user).await.unwrap();
+
+    let user_collateral = env.collateral.user_ata(&user.pubkey());
+    assert_eq!(env.token_balance(&user_collateral).await, ONE_COLLATERAL);
+    let state: ProtocolState = env.account(&env.protocol.protocol()).await;
+    assert_eq!(state.stats.open_loans, 0);
+    // The vault was closed with the loan
+    assert_error(
+        withdraw(&mut env, &user).await,
+        ErrorCode::AccountNotInitialized,
+    );
+}
+
+#[tokio::test]
+async fn withdraw_after_repaying_in_full() {
+    let mut env = TestEnv::new().await;
+    let user = env.borrower(ONE_COLLATERAL).await;
+    let debt = debt_for(ONE_COLLATERAL);
+    let ix = repay_debt(
+        &env.protocol,
+        user.pubkey(),
+        user.pubkey(),
+        &env.collateral,
+        u64::MAX,
+    );
+    env.process(&[ix], &[&user]).await.unwrap();
+    let state: ProtocolState = env.account(&env.protocol.protocol()).await;
+    assert_eq!(state.stats.open_loans, 0);
+
+    // No price is needed once the debt is gone
+    env.warp_seconds(300).await;
+    withdraw(&mut env, &user).await.unwrap();
+
+    let user_collateral = env.collateral.user_ata(&user.pubkey());
+    assert_eq!(env.token_balance(&user_collateral).await, ONE_COLLATERAL);
+    let vault_ata = env.collateral.vault_ata(&user.pubkey());
+    assert!(!env.account_exists(&vault_ata).await);
+    let loan: LoanAccount = env.account(&loan_pda(&user.pubkey()).0).await;
+    assert_eq!(loan.collateral_amount, 0);
+    assert_eq!(loan.remaining_debt, 0);
+
+    let state: ProtocolState = env.account(&env.protocol.protocol()).await;
+    assert_eq!(state.total_debt, 0);
+    assert_eq!(state.stats.total_repaid_via_user, debt);
+    assert_eq!(state.stats.open_loans, 0);
+    let config: CollateralConfig = env.account(&env.collateral.config(&env.protocol)).await;
+    assert_eq!(config.total_collateral, 0);
+}
+
+#[tokio::test]
+async fn repay_debt_rejects_a_loan_from_another_protocol() {
+    let mut env = TestEnv::new().await;
+    let user = env.borrower(ONE_COLLATERAL).await;
+
+    // Anyone can create a protocol with a credit mint of their own and list
+    // the same collateral
+    let admin = Keypair::new();
+    env.airdrop(&admin.pubkey(), 10_000_000_000).await;
+    let ix = initialize_protocol(admin.pubkey(), spl_token::ID, protocol_params());
+    env.process(&[ix], &[&admin]).await.unwrap();
+    let foreign = ProtocolContext::new(admin.pubkey(), spl_token::ID);
+    let ix = configure_collateral(
+        &foreign,
+        env.collateral.mint,
+        collateral_params(env.oracle.pubkey()),
+    );
+    env.process(&[ix], &[&admin]).await.unwrap();
+    let payer = env.ctx.payer.pubkey();
+    let ix = create_associated_token_account_idempotent(
+        &payer,
+        &user.pubkey(),
+        &foreign.credit_mint,
+        &spl_token::ID,
+    );
+    env.process(&[ix], &[]).await.unwrap();
+
+    let ix = repay_debt(&foreign, user.pubkey(), user.pubkey(), &env.collateral, 1);
+    assert_error(
+        env.process(&[ix], &[&user]).await,
+        CredXError::LoanProtocolMismatch,
+    );
+    let loan: LoanAccount = env.account(&loan_pda(&user.pubkey()).0).await;
+    assert_eq!(loan.remaining_debt, debt_for(ONE_COLLATERAL));
+}
+
+#[tokio::test]
+async fn withdraw_rejects_underwater_loans() {
+    let mut env = TestEnv::new().await;
+    let user = env.borrower(ONE_COLLATERAL).await;
+    env.set_price(INITIAL_PRICE / 2, 0).await;
+    assert_error(
+        withdraw(&mut env, &user).await,
+        CredXError::InsufficientCollateralValue,
+    );
+}
+
+#[tokio::test]
+async fn withdraw_requires_credit_for_the_full_debt() {
+    let mut env = TestEnv::new().await;
+    let user = env.borrower(ONE_COLLATERAL).await;
+    env.transfer_credit(&user, &Pubkey::new_unique(), 1).await;
+    assert_error(
+        withdraw(&mut env, &user).await,
+        CredXError::InsufficientCreditTokens,
+    );
+}
+
+#[tokio::test]
+async fn withdraw_rejects_stale_prices() {
+    let mut env = TestEnv::new().await;
+    let user = env.borrower(ONE_COLLATERAL).await;
+    env.warp_seconds(300).await;
+    assert_error(withdraw(&mut env, &user).await, CredXError::StalePrice);
+}
+
+#[tokio::test]
+async fn repay_debt_needs_the_credit_mint_and_an_open_loan() {
+    let mut env = TestEnv::new().await;
+    let user = env.borrower(ONE_COLLATERAL).await;
+    let other_mint = env
+        .create_mint(&spl_token::ID, CREDIT_DECIMALS, &program_authority_pda().0)
+        .await;
+    let ix = replace_account(
+        repay_debt(
+            &env.protocol,
+            user.pubkey(),
+            user.pubkey(),
+            &env.collateral,
+            1,
+        ),
+        &env.protocol.credit_mint,
+        &other_mint,
+    );
+    assert_error(
+        env.process(&[ix], &[&user]).await,
+        CredXError::InvalidCreditMint,
+    );
+
+    // Deposited but never borrowed
+    let saver = env.new_user(ONE_COLLATERAL).await;
+    env.deposit(&saver, ONE_COLLATERAL).await.unwrap();
+    let payer = env.ctx.payer.pubkey();
+    let ix = create_associated_token_account_idempotent(
+        &payer,
+        &saver.pubkey(),
+        &env.protocol.credit_mint,
+        &env.protocol.credit_token_program,
+    );
+    env.process(&[ix], &[]).await.unwrap();
+    let ix = repay_debt(
+        &env.protocol,
+        saver.pubkey(),
+        saver.pubkey(),
+        &env.collateral,
+        1,
+    );
+    assert_error(
+        env.process(&[ix], &[&saver]).await,
+        CredXError::NoActiveLoan,
+    );
+}
+
+#[tokio::test]
+async fn withdraw_needs_debt_or_collateral() {
+    let mut env = TestEnv::new().await;
+    let user = env.new_user(ONE_COLLATERAL).await;
+    // Anyone can open the vault's token account before the first deposit
+    let payer = env.ctx.payer.pubkey();
+    let ixs = [
+        create_associated_token_account_idempotent(
+            &payer,
+            &collateral_vault_pda(&user.pubkey()).0,
+            &env.collateral.mint,
+            &env.collateral.token_program,
+        ),
+        create_associated_token_account_idempotent(
+            &payer,
+            &user.pubkey(),
+            &env.protocol.credit_mint,
+            &env.protocol.credit_token_program,
+        ),
+    ];
+    env.process(&ixs, &[]).await.unwrap();
+    assert_error(withdraw(&mut env, &user).await, CredXError::NoActiveLoan);
+}
+
+#[tokio::test]
+async fn withdraw_and_cron_refuse_a_vault_short_of_the_loan() {
+    let mut env = TestEnv::new().await;
+    let user = env.borrower(ONE_COLLATERAL).await;
+    env.accrue_yield(&user.pubkey(), ONE_COLLATERAL / 100).await;
+
+    // Vault tokens can leave without the program, e.g. through a Token-2022
+    // permanent delegate, so the loan records more than the vault holds
+    let loan_address = loan_pda(&user.pubkey()).0;
+    let mut loan: LoanAccount = env.account(&loan_address).await;
+    loan.collateral_amount = 2 * ONE_COLLATERAL;
+    let mut account = env
+        .ctx
+        .banks_client
+        .get_account(loan_address)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut data = Vec::new();
+    loan.try_serialize(&mut data).unwrap();
+    account.data[..data.len()].copy_from_slice(&data);
+    env.ctx.set_account(&loan_address, &account.into());
+
+    assert_error(
+        withdraw(&mut env, &user).await,
+        CredXError::InsufficientCollateral,
+    );
+    assert_error(
+        crank(&mut env, &user.pubkey()).await,
+        CredXError::InsufficientCollateral,
+    );
+}
@@ -0,0 +1,232 @@
+mod common;
+
+use anchor_spl::token_2022::spl_token_2022;
+use common::*;
+use cred_x::{CredXError, LoanAccount, ProtocolState, StabilityPool};
+use cred_x_client::{
+    claim_stability_gains, deposit_to_stability_pool, initialize_stability_pool, liquidate,
+    loan_pda, stability_pool_pda, withdraw_from_stability_pool,
+};
+use solana_sdk::{
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+/// Collateral of the loan that gets liquidated, a hundredth of a unit.
+const SMALL_COLLATERAL: u64 = ONE_COLLATERAL / 100;
+/// Full borrow against `SMALL_COLLATERAL` at the initial price.
+const SMALL_DEBT: u64 = 900_000_000_000_000;
+/// Puts a loan at the maximum LTV above the 80% liquidation threshold.
+const CRASH_PRICE: u64 = 100_000_000;
+
+struct PoolEnv {
+    env: TestEnv,
+    user: Keypair,
+}
+
+/// An open stability pool, and a small loan at the maximum LTV.
+async fn pool_env() -> PoolEnv {
+    open_pool(TestEnv::new().await).await
+}
+
+async fn open_pool(mut env: TestEnv) -> PoolEnv {
+    let user = env.borrower(SMALL_COLLATERAL).await;
+    let admin = env.admin.insecure_clone();
+    let ix = initialize_stability_pool(&env.protocol, &env.collateral);
+    env.process(&[ix], &[&admin]).await.unwrap();
+    PoolEnv { env, user }
+}
+
+impl PoolEnv {
+    /// A borrower who puts `amount` of their credit in the pool.
+    async fn depositor(&mut self, amount: u64) -> Keypair {
+        let depositor = self.env.borrower(ONE_COLLATERAL).await;
+        let ix = deposit_to_stability_pool(
+            &self.env.protocol,
+            depositor.pubkey(),
+            &self.env.collateral,
+            amount,
+        );
+        self.env.process(&[ix], &[&depositor]).await.unwrap();
+        depositor
+    }
+
+    async fn liquidate(
+        &mut self,
+    ) -> std::result::Result<(), solana_program_test::BanksClientError> {
+        let ix = liquidate(&self.env.protocol, self.user.pubkey(), &self.env.collateral);
+        self.env.process(&[ix], &[]).await
+    }
+
+    async fn pool(&mut self) -> StabilityPool {
+        let address = self.pool_address();
+        self.env.account(&address).await
+    }
+
+    fn pool_address(&self) -> Pubkey {
+        stability_pool_pda(&self.env.protocol.protocol(), &self.env.collateral.mint).0
+    }
+}
+
+#[tokio::test]
+async fn liquidation_shares_collateral_among_depositors() {
+    let mut s = pool_env().await;
+    assert_error(s.liquidate().await, CredXError::LoanHealthy);
+    s.env.set_price(CRASH_PRICE, 0).await;
+    assert_error(s.liquidate().await, CredXError::StabilityPoolEmpty);
+
+    let first = s.depositor(2 * SMALL_DEBT).await;
+    let second = s.depositor(SMALL_DEBT).await;
+    let state: ProtocolState = s.env.account(&s.env.protocol.protocol()).await;
+    let total_debt = state.total_debt;
+    s.liquidate().await.unwrap();
+
+    let loan: LoanAccount = s.env.account(&loan_pda(&s.user.pubkey()).0).await;
+    assert_eq!(loan.remaining_debt, 0);
+    assert_eq!(loan.collateral_amount, 0);
+    let state: ProtocolState = s.env.account(&s.env.protocol.protocol()).await;
+    assert_eq!(state.total_debt, total_debt - SMALL_DEBT);
+    let pool = s.pool().await;
+    assert_eq!(pool.total_deposits, 2 * SMALL_DEBT);
+    assert_eq!(pool.total_collateral, SMALL_COLLATERAL);
+    assert_eq!(pool.total_debt_offset, SMALL_DEBT);
+    let pool_address = s.pool_address();
+    let pool_credit = s.env.protocol.credit_ata(&pool_address);
+    assert_eq!(s.env.token_balance(&pool_credit).await, 2 * SMALL_DEBT);
+    assert_error(s.liquidate().await, CredXError::NoOutstandingDebt);
+
+    // Two thirds of the collateral to the first depositor, a third to the
+    // second, each rounded down
+    let ix = claim_stability_gains(&s.env.protocol, first.pubkey(), &s.env.collateral);
+    s.env.process(&[ix], &[&first]).await.unwrap();
+    let first_collateral = s.env.collateral.user_ata(&first.pubkey());
+    assert_eq!(s.env.token_balance(&first_collateral).await, 6_666_666);
+    let ix = claim_stability_gains(&s.env.protocol, first.pubkey(), &s.env.collateral);
+    assert_error(
+        s.env.process(&[ix], &[&first]).await,
+        CredXError::NoStabilityGains,
+    );
+
+    let second_credit = s.env.protocol.credit_ata(&second.pubkey());
+    let credit_before = s.env.token_balance(&second_credit).await;
+    let ix = withdraw_from_stability_pool(
+        &s.env.protocol,
+        second.pubkey(),
+        &s.env.collateral,
+        u64::MAX,
+    );
+    s.env.process(&[ix], &[&second]).await.unwrap();
+    // Compounded deposits round down, leaving dust in the pool
+    let withdrawn = SMALL_DEBT * 2 / 3 - 1;
+    assert_eq!(
+        s.env.token_balance(&second_credit).await - credit_before,
+        withdrawn
+    );
+    let second_collateral = s.env.collateral.user_ata(&second.pubkey());
+    assert_eq!(s.env.token_balance(&second_collateral).await, 3_333_333);
+    let ix = withdraw_from_stability_pool(&s.env.protocol, second.pubkey(), &s.env.collateral, 1);
+    assert_error(
+        s.env.process(&[ix], &[&second]).await,
+        CredXError::NoStabilityDeposit,
+    );
+
+    let pool = s.pool().await;
+    assert_eq!(pool.total_deposits, 2 * SMALL_DEBT - withdrawn);
+    assert_eq!(pool.total_collateral, 1);
+}
+
+#[tokio::test]
+async fn liquidation_is_partial_when_the_pool_runs_short() {
+    let mut s = pool_env().await;
+    let deposit = SMALL_DEBT / 2;
+    let depositor = s.depositor(deposit).await;
+    s.env.set_price(CRASH_PRICE, 0).await;
+    s.liquidate().await.unwrap();
+
+    // The pool keeps one whole credit token
+    let one_credit = 10u64.pow(u32::from(CREDIT_DECIMALS));
+    let offset = deposit - one_credit;
+    let seized = SMALL_COLLATERAL * (offset / 1_000_000) / (SMALL_DEBT / 1_000_000);
+    let loan: LoanAccount = s.env.account(&loan_pda(&s.user.pubkey()).0).await;
+    assert_eq!(loan.remaining_debt, SMALL_DEBT - offset);
+    assert_eq!(loan.collateral_amount, SMALL_COLLATERAL - seized);
+    let pool = s.pool().await;
+    assert_eq!(pool.total_deposits, one_credit);
+    assert_eq!(pool.total_collateral, seized);
+    assert_error(s.liquidate().await, CredXError::StabilityPoolEmpty);
+
+    let depositor_credit = s.env.protocol.credit_ata(&depositor.pubkey());
+    let credit_before = s.env.token_balance(&depositor_credit).await;
+    let ix = withdraw_from_stability_pool(
+        &s.env.protocol,
+        depositor.pubkey(),
+        &s.env.collateral,
+        u64::MAX,
+    );
+    s.env.process(&[ix], &[&depositor]).await.unwrap();
+    // Both the deposit and the gain round down
+    assert_eq!(
+        s.env.token_balance(&depositor_credit).await - credit_before,
+        one_credit - 1
+    );
+    let depositor_collateral = s.env.collateral.user_ata(&depositor.pubkey());
+    assert_eq!(s.env.token_balance(&depositor_collateral).await, seized - 1);
+}
+
+#[tokio::test]
+async fn partial_liquidation_of_an_underwater_loan_covers_the_offset() {
+    let mut s = pool_env().await;
+    let deposit = SMALL_DEBT / 2;
+    s.depositor(deposit).await;
+    // Collateral worth little over half the debt
+    let price = CRASH_PRICE / 2;
+    s.env.set_price(price, 0).await;
+    s.liquidate().await.unwrap();
+
+    // The pro-rata share would be worth under half the offset, so the pool
+    // takes the offset's worth instead
+    let one_credit = 10u64.pow(u32::from(CREDIT_DECIMALS));
+    let offset = deposit - one_credit;
+    let seized = offset.div_ceil(price);
+    assert!(seized > SMALL_COLLATERAL * (offset / 1_000_000) / (SMALL_DEBT / 1_000_000));
+    let loan: LoanAccount = s.env.account(&loan_pda(&s.user.pubkey()).0).await;
+    assert_eq!(loan.remaining_debt, SMALL_DEBT - offset);
+    assert_eq!(loan.collateral_amount, SMALL_COLLATERAL - seized);
+    let pool = s.pool().await;
+    assert_eq!(pool.total_collateral, seized);
+}
+
+#[tokio::test]
+async fn liquidation_credits_the_pool_net_of_transfer_fees() {
+    let mut env = TestEnv::new().await;
+    let admin = env.admin.pubkey();
+    env.collateral.mint = env.create_fee_mint(COLLATERAL_DECIMALS, 100, &admin).await;
+    env.collateral.token_program = spl_token_2022::ID;
+    let oracle = env.oracle.pubkey();
+    env.configure_collateral(collateral_params(oracle))
+        .await
+        .unwrap();
+    let mut s = open_pool(env).await;
+    let depositor = s.depositor(2 * SMALL_DEBT).await;
+    s.env.set_price(CRASH_PRICE, 0).await;
+    let loan: LoanAccount = s.env.account(&loan_pda(&s.user.pubkey()).0).await;
+    s.liquidate().await.unwrap();
+
+    // 1% of the seized collateral is withheld on the way into the pool
+    let pool_collateral = s.env.collateral.user_ata(&s.pool_address());
+    let received = s.env.token_balance(&pool_collateral).await;
+    assert_eq!(
+        received,
+        loan.collateral_amount - loan.collateral_amount / 100
+    );
+    let pool = s.pool().await;
+    assert_eq!(pool.total_collateral, received);
+
+    let ix = claim_stability_gains(&s.env.protocol, depositor.pubkey(), &s.env.collateral);
+    s.env.process(&[ix], &[&depositor]).await.unwrap();
+    let pool = s.pool().await;
+    assert_eq!(
+        s.env.token_balance(&pool_collateral).await,
+        pool.total_collateral
+    );
+}
@@ -0,0 +1,161 @@
+mod common;
+
+use common::*;
+use cred_x::{CollateralConfig, CollateralVault, CredXError, LoanAccount};
+use cred_x_client::{
+    collateral_vault_pda, loan_pda, set_swap_program, swap_collateral, update_simple_oracle,
+    CollateralContext,
+};
+use mock_swap::MockSwapError;
+use solana_sdk::{
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+};
+
+/// Debt of a full borrow against one unit of collateral at the initial price.
+const MAX_BORROW: u64 = 90_000_000_000_000_000;
+
+struct SwapEnv {
+    env: TestEnv,
+    user: Keypair,
+    target: CollateralContext,
+    target_oracle: Pubkey,
+}
+
+/// A borrower at the maximum LTV, a second collateral at `target_price` and a
+/// one-to-one pool into it.
+async fn swap_env(target_price: u64) -> SwapEnv {
+    let mut env = TestEnv::new().await;
+    let user = env.borrower(ONE_COLLATERAL).await;
+    let (target, target_oracle) = env.add_collateral(target_price).await;
+    let source = &env.collateral;
+    env.create_mock_pool(
+        (source.mint, source.token_program),
+        (target.mint, target.token_program),
+        (1, 1),
+        10 * ONE_COLLATERAL,
+    )
+    .await;
+    SwapEnv {
+        env,
+        user,
+        target,
+        target_oracle,
+    }
+}
+
+impl SwapEnv {
+    fn swap(&self, min_amount_out: u64) -> Instruction {
+        let source = &self.env.collateral;
+        swap_collateral(
+            &self.env.protocol,
+            self.user.pubkey(),
+            source,
+            &self.target,
+            mock_swap::ID,
+            mock_swap_accounts(
+                (source.mint, source.token_program),
+                (self.target.mint, self.target.token_program),
+            ),
+            min_amount_out,
+        )
+    }
+
+    async fn enable_swaps(&mut self) {
+        let admin = self.env.admin.insecure_clone();
+        let ix = set_swap_program(&self.env.protocol, mock_swap::ID);
+        self.env.process(&[ix], &[&admin]).await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn swap_collateral_moves_position_to_new_mint() {
+    let mut s = swap_env(INITIAL_PRICE).await;
+    s.enable_swaps().await;
+    let yield_amount = ONE_COLLATERAL / 10;
+    s.env.accrue_yield(&s.user.pubkey(), yield_amount).await;
+
+    let ix = s.swap(ONE_COLLATERAL + yield_amount);
+    let user = s.user.insecure_clone();
+    s.env.process(&[ix], &[&user]).await.unwrap();
+
+    let swapped = ONE_COLLATERAL + yield_amount;
+    let vault: CollateralVault = s.env.account(&collateral_vault_pda(&user.pubkey()).0).await;
+    assert_eq!(vault.mint, s.target.mint);
+    let loan: LoanAccount = s.env.account(&loan_pda(&user.pubkey()).0).await;
+    assert_eq!(loan.collateral_amount, swapped);
+    assert_eq!(loan.remaining_debt, MAX_BORROW);
+    assert_eq!(loan.yield_earned, yield_amount);
+    assert_eq!(loan.oracle_price_account, s.target_oracle);
+
+    let old_vault = s.env.collateral.vault_ata(&user.pubkey());
+    assert_eq!(s.env.token_balance(&old_vault).await, 0);
+    let new_vault = s.target.vault_ata(&user.pubkey());
+    assert_eq!(s.env.token_balance(&new_vault).await, swapped);
+
+    let source_config: CollateralConfig = s
+        .env
+        .account(&s.env.collateral.config(&s.env.protocol))
+        .await;
+    assert_eq!(source_config.total_collateral, 0);
+    let target_config: CollateralConfig = s.env.account(&s.target.config(&s.env.protocol)).await;
+    assert_eq!(target_config.total_collateral, swapped);
+}
+
+#[tokio::test]
+async fn swap_collateral_requires_the_configured_swap_program() {
+    let mut s = swap_env(INITIAL_PRICE).await;
+    let user = s.user.insecure_clone();
+
+    assert_error(
+        s.env.process(&[s.swap(0)], &[&user]).await,
+        CredXError::SwapProgramNotSet,
+    );
+
+    s.enable_swaps().await;
+    let ix = replace_account(s.swap(0), &mock_swap::ID, &anchor_spl::token::ID);
+    assert_error(
+        s.env.process(&[ix], &[&user]).await,
+        CredXError::InvalidSwapProgram,
+    );
+}
+
+#[tokio::test]
+async fn swap_collateral_rejects_slippage_and_loans_over_the_ltv() {
+    let mut s = swap_env(INITIAL_PRICE).await;
+    s.enable_swaps().await;
+    let user = s.user.insecure_clone();
+    let admin = s.env.admin.insecure_clone();
+
+    assert_error(
+        s.env.process(&[s.swap(ONE_COLLATERAL + 1)], &[&user]).await,
+        MockSwapError::SlippageExceeded,
+    );
+
+    // Three percent less value is within the loss bound but over the LTV
+    let ix = update_simple_oracle(admin.pubkey(), s.target_oracle, INITIAL_PRICE * 97 / 100, 0);
+    s.env.process(&[ix], &[&admin]).await.unwrap();
+    assert_error(
+        s.env.process(&[s.swap(ONE_COLLATERAL)], &[&user]).await,
+        CredXError::ExceedsMaxBorrow,
+    );
+
+    // A minimum worth two thirds of the old collateral gives up too much
+    let ix = update_simple_oracle(admin.pubkey(), s.target_oracle, INITIAL_PRICE * 2 / 3, 0);
+    s.env.process(&[ix], &[&admin]).await.unwrap();
+    assert_error(
+        s.env.process(&[s.swap(ONE_COLLATERAL)], &[&user]).await,
+        CredXError::SwapValueLossTooHigh,
+    );
+
+    let ix = update_simple_oracle(admin.pubkey(), s.target_oracle, INITIAL_PRICE, 0);
+    s.env.process(&[ix], &[&admin]).await.unwrap();
+    s.env
+        .process(&[s.swap(ONE_COLLATERAL)], &[&user])
+        .await
+        .unwrap();
+    let loan: LoanAccount = s.env.account(&loan_pda(&user.pubkey()).0).await;
+    assert_eq!(loan.collateral_amount, ONE_COLLATERAL);
+    assert_eq!(loan.remaining_debt, MAX_BORROW);
+}
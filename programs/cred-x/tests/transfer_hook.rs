@@ -0,0 +1,212 @@
+mod common;
+
+use anchor_lang::{prelude::Pubkey, solana_program::instruction::AccountMeta};
+use anchor_spl::{
+    associated_token::spl_associated_token_account::instruction::create_associated_token_account_idempotent,
+    token_2022::spl_token_2022,
+};
+use common::*;
+use cred_x::{CredXError, CreditTransferMode, InitializeProtocolParams};
+use cred_x_client::{
+    collateral_vault_pda, credit_allowlist_pda, credit_transfer_hook, deposit_to_stability_pool,
+    extra_account_metas_pda, fund_insurance, initialize_credit_transfer_hook,
+    initialize_stability_pool, insurance_fund_pda, loan_pda, set_credit_allowlist,
+    set_credit_transfer_mode, stability_pool_pda, withdraw_from_stability_pool,
+};
+use solana_program_test::BanksClientError;
+use solana_sdk::signature::{Keypair, Signer};
+
+/// Token-2022 credit mint with the hook installed and `mode` active.
+async fn hooked_env(mode: CreditTransferMode) -> TestEnv {
+    let mut params = protocol_params();
+    params.credit_transfer_mode = mode;
+    hooked_env_with(params).await
+}
+
+async fn hooked_env_with(params: InitializeProtocolParams) -> TestEnv {
+    let mut env = TestEnv::with_params(params, spl_token_2022::ID).await;
+    let admin = env.admin.insecure_clone();
+    let ix = initialize_credit_transfer_hook(&env.protocol);
+    env.process(&[ix], &[&admin]).await.unwrap();
+    env
+}
+
+async fn set_mode(env: &mut TestEnv, mode: CreditTransferMode) {
+    let admin = env.admin.insecure_clone();
+    let ix = set_credit_transfer_mode(&env.protocol, mode);
+    env.process(&[ix], &[&admin]).await.unwrap();
+}
+
+async fn set_allowed(env: &mut TestEnv, owner: &Pubkey, allowed: bool) {
+    let admin = env.admin.insecure_clone();
+    let ix = set_credit_allowlist(&env.protocol, *owner, allowed);
+    env.process(&[ix], &[&admin]).await.unwrap();
+}
+
+/// `transfer_checked` with the accounts the hook's extra-account-metas
+/// resolve to, as a wallet would build it.
+async fn transfer(
+    env: &mut TestEnv,
+    from: &Keypair,
+    to: &Pubkey,
+    amount: u64,
+) -> Result<(), BanksClientError> {
+    let mint = env.protocol.credit_mint;
+    let protocol = env.protocol.protocol();
+    let source_vault = collateral_vault_pda(&from.pubkey()).0;
+    let mut ix = spl_token_2022::instruction::transfer_checked(
+        &spl_token_2022::ID,
+        &env.protocol.credit_ata(&from.pubkey()),
+        &mint,
+        &env.protocol.credit_ata(to),
+        &from.pubkey(),
+        &[],
+        amount,
+        CREDIT_DECIMALS,
+    )
+    .unwrap();
+    ix.accounts.extend([
+        AccountMeta::new_readonly(protocol, false),
+        AccountMeta::new_readonly(source_vault, false),
+        AccountMeta::new_readonly(loan_pda(&from.pubkey()).0, false),
+        AccountMeta::new_readonly(credit_allowlist_pda(&protocol, to).0, false),
+        AccountMeta::new_readonly(cred_x::ID, false),
+        AccountMeta::new_readonly(extra_account_metas_pda(&mint).0, false),
+    ]);
+
+    let payer = env.ctx.payer.pubkey();
+    let create = create_associated_token_account_idempotent(&payer, to, &mint, &spl_token_2022::ID);
+    env.process(&[create, ix], &[from]).await
+}
+
+#[tokio::test]
+async fn initialize_hook_writes_extra_account_metas() {
+    let mut env = hooked_env(CreditTransferMode::DebtFloor).await;
+    let metas = extra_account_metas_pda(&env.protocol.credit_mint).0;
+    assert!(env.account_exists(&metas).await);
+}
+
+#[tokio::test]
+async fn debt_floor_keeps_borrowers_above_their_debt() {
+    let mut env = hooked_env(CreditTransferMode::DebtFloor).await;
+    let borrower = env.borrower(ONE_COLLATERAL).await;
+    let holder = Keypair::new();
+    env.airdrop(&holder.pubkey(), 1_000_000_000).await;
+
+    assert_error(
+        transfer(&mut env, &borrower, &holder.pubkey(), 1).await,
+        CredXError::CreditBelowOutstandingDebt,
+    );
+
+    // Unrestricted lets the borrower move credit below the floor.
+    set_mode(&mut env, CreditTransferMode::Unrestricted).await;
+    transfer(&mut env, &borrower, &holder.pubkey(), 1_000)
+        .await
+        .unwrap();
+    set_mode(&mut env, CreditTransferMode::DebtFloor).await;
+
+    // Accounts without a loan have no floor.
+    transfer(&mut env, &holder, &borrower.pubkey(), 400)
+        .await
+        .unwrap();
+    let holder_ata = env.protocol.credit_ata(&holder.pubkey());
+    assert_eq!(env.token_balance(&holder_ata).await, 600);
+    assert_error(
+        transfer(&mut env, &borrower, &holder.pubkey(), 1).await,
+        CredXError::CreditBelowOutstandingDebt,
+    );
+}
+
+#[tokio::test]
+async fn allowlist_only_admits_listed_destinations() {
+    let mut env = hooked_env(CreditTransferMode::Allowlist).await;
+    let borrower = env.borrower(ONE_COLLATERAL).await;
+    let destination = Keypair::new().pubkey();
+
+    assert_error(
+        transfer(&mut env, &borrower, &destination, 1).await,
+        CredXError::DestinationNotAllowlisted,
+    );
+
+    set_allowed(&mut env, &destination, true).await;
+    transfer(&mut env, &borrower, &destination, 1)
+        .await
+        .unwrap();
+    let destination_ata = env.protocol.credit_ata(&destination);
+    assert_eq!(env.token_balance(&destination_ata).await, 1);
+
+    set_allowed(&mut env, &destination, false).await;
+    assert_error(
+        transfer(&mut env, &borrower, &destination, 1).await,
+        CredXError::DestinationNotAllowlisted,
+    );
+}
+
+#[tokio::test]
+async fn hook_rejects_direct_calls() {
+    let mut env = hooked_env(CreditTransferMode::DebtFloor).await;
+    let borrower = env.borrower(ONE_COLLATERAL).await;
+    let destination = Keypair::new();
+    env.airdrop(&destination.pubkey(), 1_000_000_000).await;
+    set_mode(&mut env, CreditTransferMode::Unrestricted).await;
+    transfer(&mut env, &borrower, &destination.pubkey(), 1)
+        .await
+        .unwrap();
+
+    let ix = credit_transfer_hook(
+        &env.protocol,
+        borrower.pubkey(),
+        destination.pubkey(),
+        borrower.pubkey(),
+        1,
+    );
+    assert_error(env.process(&[ix], &[]).await, CredXError::NotTransferring);
+}
+
+#[tokio::test]
+async fn stability_pool_takes_hooked_credit_without_transfers() {
+    // Nothing is listed, so any transfer through the hook would fail
+    let mut env = hooked_env(CreditTransferMode::Allowlist).await;
+    let borrower = env.borrower(ONE_COLLATERAL).await;
+    let admin = env.admin.insecure_clone();
+    let ix = initialize_stability_pool(&env.protocol, &env.collateral);
+    env.process(&[ix], &[&admin]).await.unwrap();
+    let borrower_ata = env.protocol.credit_ata(&borrower.pubkey());
+    let balance = env.token_balance(&borrower_ata).await;
+    let pool = stability_pool_pda(&env.protocol.protocol(), &env.collateral.mint).0;
+    let pool_ata = env.protocol.credit_ata(&pool);
+
+    let ix = deposit_to_stability_pool(&env.protocol, borrower.pubkey(), &env.collateral, 1_000);
+    env.process(&[ix], &[&borrower]).await.unwrap();
+    assert_eq!(env.token_balance(&pool_ata).await, 1_000);
+    assert_eq!(env.token_balance(&borrower_ata).await, balance - 1_000);
+
+    let ix =
+        withdraw_from_stability_pool(&env.protocol, borrower.pubkey(), &env.collateral, u64::MAX);
+    env.process(&[ix], &[&borrower]).await.unwrap();
+    assert_eq!(env.token_balance(&pool_ata).await, 0);
+    assert_eq!(env.token_balance(&borrower_ata).await, balance);
+}
+
+#[tokio::test]
+async fn insurance_fund_takes_hooked_credit_without_transfers() {
+    let mut params = protocol_params();
+    params.credit_transfer_mode = CreditTransferMode::Allowlist;
+    params.borrow_fee_bps = 100;
+    let mut env = hooked_env_with(params).await;
+    env.borrower(ONE_COLLATERAL).await;
+    let admin = env.admin.insecure_clone();
+
+    let fees = env.token_balance(&env.protocol.protocol_credit_ata()).await;
+    assert!(fees > 0);
+    let ix = fund_insurance(&env.protocol, fees);
+    env.process(&[ix], &[&admin]).await.unwrap();
+    let fund_ata = env
+        .protocol
+        .credit_ata(&insurance_fund_pda(&env.protocol.protocol()).0);
+    assert_eq!(env.token_balance(&fund_ata).await, fees);
+    assert_eq!(
+        env.token_balance(&env.protocol.protocol_credit_ata()).await,
+        0
+    );
+}
@@ -0,0 +1,216 @@
+//! Fixed-rate swap program that implements the cred-x swap adapter interface.
+//! Each pool converts one mint into another at `rate_num / rate_den`, paying
+//! out of reserves held by the pool PDA. Only meant for tests and localnet.
+
+#![allow(deprecated)]
+use anchor_lang::prelude::*;
+use anchor_spl::{
+    associated_token::AssociatedToken,
+    token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked},
+};
+
+declare_id!("9AsD5ZMV3ZkMMbcb53VUrFbNmVa5nWnpZKTaJwnWKHzq");
+
+#[program]
+pub mod mock_swap {
+    use super::*;
+
+    pub fn initialize_pool(
+        ctx: Context<InitializePool>,
+        rate_num: u64,
+        rate_den: u64,
+    ) -> Result<()> {
+        require!(rate_den > 0, MockSwapError::InvalidRate);
+        ctx.accounts.pool.set_inner(Pool {
+            authority: ctx.accounts.authority.key(),
+            input_mint: ctx.accounts.input_mint.key(),
+            output_mint: ctx.accounts.output_mint.key(),
+            rate_num,
+            rate_den,
+            bump: ctx.bumps.pool,
+        });
+        Ok(())
+    }
+
+    pub fn set_rate(ctx: Context<SetRate>, rate_num: u64, rate_den: u64) -> Result<()> {
+        require!(rate_den > 0, MockSwapError::InvalidRate);
+        ctx.accounts.pool.rate_num = rate_num;
+        ctx.accounts.pool.rate_den = rate_den;
+        Ok(())
+    }
+
+    /// Takes `amount_in` from `source` and pays `amount_in * rate` from the
+    /// pool's output reserve into `destination`.
+    pub fn swap(ctx: Context<Swap>, amount_in: u64, min_amount_out: u64) -> Result<()> {
+        let accounts = ctx.accounts;
+        let amount_out = accounts.pool.quote(amount_in)?;
+        require!(
+            amount_out >= min_amount_out,
+            MockSwapError::SlippageExceeded
+        );
+
+        transfer_checked(
+            CpiContext::new(
+                accounts.input_token_program.to_account_info(),
+                TransferChecked {
+                    from: accounts.source.to_account_info(),
+                    mint: accounts.input_mint.to_account_info(),
+                    to: accounts.input_reserve.to_account_info(),
+                    authority: accounts.authority.to_account_info(),
+                },
+            ),
+            amount_in,
+            accounts.input_mint.decimals,
+        )?;
+
+        let input_mint = accounts.input_mint.key();
+        let output_mint = accounts.output_mint.key();
+        let seeds = &[
+            b"pool".as_ref(),
+            input_mint.as_ref(),
+            output_mint.as_ref(),
+            &[accounts.pool.bump],
+        ];
+        transfer_checked(
+            CpiContext::new_with_signer(
+                accounts.output_token_program.to_account_info(),
+                TransferChecked {
+                    from: accounts.output_reserve.to_account_info(),
+                    mint: accounts.output_mint.to_account_info(),
+                    to: accounts.destination.to_account_info(),
+                    authority: accounts.pool.to_account_info(),
+                },
+                &[&seeds[..]],
+            ),
+            amount_out,
+            accounts.output_mint.decimals,
+        )?;
+
+        msg!("Swapped {} for {}", amount_in, amount_out);
+        Ok(())
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Pool {
+    pub authority: Pubkey,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub rate_num: u64,
+    pub rate_den: u64,
+    pub bump: u8,
+}
+
+impl Pool {
+    pub fn quote(&self, amount_in: u64) -> Result<u64> {
+        let amount_out =
+            u128::from(amount_in) * u128::from(self.rate_num) / u128::from(self.rate_den);
+        Ok(u64::try_from(amount_out).map_err(|_| MockSwapError::InvalidRate)?)
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializePool<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub input_mint: InterfaceAccount<'info, Mint>,
+    pub output_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Pool::INIT_SPACE,
+        seeds = [b"pool", input_mint.key().as_ref(), output_mint.key().as_ref()],
+        bump
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+        init,
+        payer = authority,
+        associated_token::mint = input_mint,
+        associated_token::authority = pool,
+        associated_token::token_program = input_token_program
+    )]
+    pub input_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = authority,
+        associated_token::mint = output_mint,
+        associated_token::authority = pool,
+        associated_token::token_program = output_token_program
+    )]
+    pub output_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    pub input_token_program: Interface<'info, TokenInterface>,
+    pub output_token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetRate<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(mut, has_one = authority)]
+    pub pool: Account<'info, Pool>,
+}
+
+/// The account prefix cred-x passes to every swap adapter, followed by the
+/// adapter's own accounts.
+#[derive(Accounts)]
+pub struct Swap<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        token::mint = input_mint,
+        token::token_program = input_token_program
+    )]
+    pub source: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = output_mint,
+        token::token_program = output_token_program
+    )]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+
+    pub input_mint: InterfaceAccount<'info, Mint>,
+    pub output_mint: InterfaceAccount<'info, Mint>,
+    pub input_token_program: Interface<'info, TokenInterface>,
+    pub output_token_program: Interface<'info, TokenInterface>,
+
+    #[account(
+        seeds = [b"pool", input_mint.key().as_ref(), output_mint.key().as_ref()],
+        bump = pool.bump
+    )]
+    pub pool: Account<'info, Pool>,
+
+    #[account(
+        mut,
+        associated_token::mint = input_mint,
+        associated_token::authority = pool,
+        associated_token::token_program = input_token_program
+    )]
+    pub input_reserve: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        associated_token::mint = output_mint,
+        associated_token::authority = pool,
+        associated_token::token_program = output_token_program
+    )]
+    pub output_reserve: InterfaceAccount<'info, TokenAccount>,
+}
+
+#[error_code]
+pub enum MockSwapError {
+    #[msg("Swap output is below the minimum")]
+    SlippageExceeded,
+    #[msg("Invalid pool rate")]
+    InvalidRate,
+}
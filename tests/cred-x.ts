@@ -39,6 +39,7 @@ describe('CredX Protocol - Complete Integration Test', () => {
   let collateralVaultPda: PublicKey;
   let loanAccountPda: PublicKey;
   let oraclePriceAccount: PublicKey;
+  let collateralConfigPda: PublicKey;
 
   let stakedTokenMint: PublicKey;
   let stakedTokenAuthority: Keypair;
@@ -151,7 +152,8 @@ describe('CredX Protocol - Complete Integration Test', () => {
       try {
         await program.methods
           .createSimpleOracle(
-            new anchor.BN(Math.floor(initialPrice * 1_000_000))
+            new anchor.BN(Math.floor(initialPrice * 1_000_000)),
+            new anchor.BN(0)
           )
           .accounts({
             authority: payer.publicKey,
@@ -174,7 +176,10 @@ describe('CredX Protocol - Complete Integration Test', () => {
 
       try {
         await program.methods
-          .updateSimpleOracle(new anchor.BN(Math.floor(newPrice * 1_000_000)))
+          .updateSimpleOracle(
+            new anchor.BN(Math.floor(newPrice * 1_000_000)),
+            new anchor.BN(0)
+          )
           .accounts({
             authority: payer.publicKey,
             priceAccount: this.priceKeypair.publicKey,
@@ -276,6 +281,15 @@ describe('CredX Protocol - Complete Integration Test', () => {
       program.programId
     );
 
+    [collateralConfigPda] = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from('collateral_config'),
+        protocolPda.toBuffer(),
+        collateralMint.toBuffer(),
+      ],
+      program.programId
+    );
+
     oraclePriceAccount = mockOracleManager.getPriceAccount();
 
     console.log('✅ Test environment setup complete!\n');
@@ -312,12 +326,29 @@ describe('CredX Protocol - Complete Integration Test', () => {
       }
 
       try {
+        const protocolCreditAta = await getAssociatedTokenAddress(
+          creditMint,
+          programAuthorityPda,
+          true
+        );
         const tx = await program.methods
-          .initializeProtocol()
+          .initializeProtocol({
+            creditDecimals: 6,
+            ltvRatioBps: 6000,
+            liquidationThresholdBps: 8000,
+            maxPriceAgeSecs: new anchor.BN(300),
+            borrowFeeBps: 0,
+            debtCeiling: new anchor.BN('18446744073709551615'),
+            creditTransferMode: { unrestricted: {} },
+            creditMetadata: null,
+          })
           .accounts({
             admin: admin.publicKey,
+            programAuthority: programAuthorityPda,
             creditMint,
             protocol: protocolPda,
+            protocolCreditAta,
+            associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
             tokenProgram: TOKEN_PROGRAM_ID,
             systemProgram: SystemProgram.programId,
           } as any)
@@ -354,6 +385,33 @@ describe('CredX Protocol - Complete Integration Test', () => {
       const mintInfo = await connection.getAccountInfo(stakedTokenMint);
       expect(mintInfo).to.not.be.null;
 
+      await program.methods
+        .configureCollateral({
+          supplyCap: new anchor.BN('18446744073709551615'),
+          pricing: { useEma: false, confMultiplierBps: 10_000 },
+          oracle: {
+            primary: oraclePriceAccount,
+            secondary: PublicKey.default,
+            maxDeviationBps: 0,
+            fallbackMaxAgeSecs: new anchor.BN(0),
+            fallbackConfMultiplierBps: 0,
+          },
+          staleness: {
+            maxAgeSecs: new anchor.BN(0),
+            borrowMaxAgeSecs: new anchor.BN(120),
+          },
+          maxPriceMoveBps: 2_000,
+        })
+        .accounts({
+          admin: admin.publicKey,
+          protocol: protocolPda,
+          collateralMint: stakedTokenMint,
+          collateralConfig: collateralConfigPda,
+          systemProgram: SystemProgram.programId,
+        } as any)
+        .signers([admin])
+        .rpc();
+
       console.log(`✅ Mock staked token mint: ${stakedTokenMint.toString()}`);
       console.log(`✅ Oracle price account: ${oraclePriceAccount.toString()}`);
     });
@@ -463,6 +521,7 @@ describe('CredX Protocol - Complete Integration Test', () => {
           user: user.publicKey,
           protocol: protocolPda,
           collateralMint: stakedTokenMint,
+          collateralConfig: collateralConfigPda,
           userCollateralAta: userStakedAta,
           collateralVault: collateralVaultPda,
           collateralVaultAta: collateralVaultAta,
@@ -508,9 +567,16 @@ describe('CredX Protocol - Complete Integration Test', () => {
           protocol: protocolPda,
           creditMint: creditMint,
           userCreditAta: userCreditAta,
+          protocolCreditAta: await getAssociatedTokenAddress(
+            creditMint,
+            programAuthorityPda,
+            true
+          ),
           collateralVault: collateralVaultPda,
+          collateralConfig: collateralConfigPda,
           loanAccount: loanAccountPda,
           oraclePriceAccount: oraclePriceAccount,
+          secondaryOracleAccount: null,
           associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
           tokenProgram: TOKEN_PROGRAM_ID,
           systemProgram: SystemProgram.programId,
@@ -596,13 +662,16 @@ describe('CredX Protocol - Complete Integration Test', () => {
           authority: user.publicKey,
           protocol: protocolPda,
           collateralVault: collateralVaultPda,
+          collateralConfig: collateralConfigPda,
           collateralVaultAta: collateralVaultAta,
           loanAccount: loanAccountPda,
           creditMint: creditMint,
           programAuthority: programAuthorityPda,
           userCreditAta: userCreditAta,
           oraclePriceAccount: oraclePriceAccount,
-          tokenProgram: TOKEN_PROGRAM_ID,
+          secondaryOracleAccount: null,
+          creditTokenProgram: TOKEN_PROGRAM_ID,
+          collateralTokenProgram: TOKEN_PROGRAM_ID,
         } as any)
         .rpc();
 
@@ -682,13 +751,16 @@ describe('CredX Protocol - Complete Integration Test', () => {
             authority: admin.publicKey,
             protocol: protocolPda,
             collateralVault: collateralVaultPda,
+            collateralConfig: collateralConfigPda,
             collateralVaultAta: collateralVaultAta,
             loanAccount: loanAccountPda,
             creditMint: creditMint,
             programAuthority: programAuthorityPda,
             userCreditAta: userCreditAta,
             oraclePriceAccount: oraclePriceAccount,
-            tokenProgram: TOKEN_PROGRAM_ID,
+            secondaryOracleAccount: null,
+            creditTokenProgram: TOKEN_PROGRAM_ID,
+            collateralTokenProgram: TOKEN_PROGRAM_ID,
           } as any)
           .rpc();
 
@@ -756,13 +828,17 @@ describe('CredX Protocol - Complete Integration Test', () => {
           creditMint: creditMint,
           programAuthority: programAuthorityPda,
           collateralVault: collateralVaultPda,
+          collateralMint: stakedTokenMint,
+          collateralConfig: collateralConfigPda,
           collateralVaultAta: collateralVaultAta,
           userCollateralAta: userStakedAta,
           loanAccount: loanAccountPda,
           userCreditAta: userCreditAta,
           oraclePriceAccount: oraclePriceAccount,
+          secondaryOracleAccount: null,
           associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
-          tokenProgram: TOKEN_PROGRAM_ID,
+          creditTokenProgram: TOKEN_PROGRAM_ID,
+          collateralTokenProgram: TOKEN_PROGRAM_ID,
           systemProgram: SystemProgram.programId,
         } as any)
         .signers([user])